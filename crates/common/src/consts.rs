@@ -1,7 +1,7 @@
 //! Repeated constants used around pathfinder
 
-use crate::macro_prelude::block_hash;
-use crate::BlockHash;
+use crate::macro_prelude::{block_hash, public_key};
+use crate::{BlockHash, PublicKey};
 
 /// Vergen string
 pub const VERGEN_GIT_DESCRIBE: &str = env!("VERGEN_GIT_DESCRIBE");
@@ -23,3 +23,7 @@ pub const SEPOLIA_TESTNET_GENESIS_HASH: BlockHash =
 
 pub const SEPOLIA_INTEGRATION_GENESIS_HASH: BlockHash =
     block_hash!("19f675d3fb226821493a6ab9a1955e384bba80f130de625621a418e9a7c0ca3");
+
+/// The public key of the mainnet sequencer, used to verify block signatures.
+pub const MAINNET_SEQUENCER_PUBLIC_KEY: PublicKey =
+    public_key!("0x48253ff2c3bed7af18bde0b611b083b39445959102d4947c51c4db6aa4f4e58");
@@ -23,7 +23,7 @@ pub mod test_utils;
 pub mod transaction;
 pub mod trie;
 
-pub use signature::BlockCommitmentSignature;
+pub use signature::{BlockCommitmentSignature, SignatureStatus};
 pub use state_update::StateUpdate;
 
 pub use header::{BlockHeader, BlockHeaderBuilder, SignedBlockHeader};
@@ -480,6 +480,7 @@ macros::felt_newtypes!(
         L1ToL2MessagePayloadElem,
         L2ToL1MessagePayloadElem,
         PaymasterDataElem,
+        PublicKey,
         SequencerAddress,
         StateCommitment,
         StateDiffCommitment,
@@ -31,6 +31,33 @@ pub struct L2ToL1Message {
     pub to_address: EthereumAddress,
 }
 
+impl L2ToL1Message {
+    /// The hash under which the Starknet core contract stores the message, and which is emitted
+    /// by its `ConsumedMessageToL1` event once the message is consumed on L1.
+    pub fn calculate_message_hash(&self) -> primitive_types::H256 {
+        use sha3::{Digest, Keccak256};
+
+        let mut hash = Keccak256::new();
+
+        hash.update(self.from_address.0.as_be_bytes());
+        // Pad the Ethereum address to 32 bytes to match a felt.
+        hash.update([0u8; 12]);
+        hash.update(self.to_address.0.as_bytes());
+
+        // Pad the u64 to 32 bytes to match a felt.
+        hash.update([0u8; 24]);
+        hash.update((self.payload.len() as u64).to_be_bytes());
+
+        for elem in &self.payload {
+            hash.update(elem.0.as_be_bytes());
+        }
+
+        let hash = <[u8; 32]>::from(hash.finalize());
+
+        hash.into()
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ExecutionResources {
     pub builtin_instance_counter: BuiltinCounters,
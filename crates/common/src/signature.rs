@@ -1,9 +1,81 @@
 use fake::Dummy;
+use pathfinder_crypto::signature::SignatureError;
 
-use crate::BlockCommitmentSignatureElem;
+use crate::{BlockCommitmentSignatureElem, BlockHash, PublicKey, StateDiffCommitment};
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Dummy)]
 pub struct BlockCommitmentSignature {
     pub r: BlockCommitmentSignatureElem,
     pub s: BlockCommitmentSignatureElem,
 }
+
+impl BlockCommitmentSignature {
+    /// Verifies that the sequencer holding `public_key` signed the block hash and state diff
+    /// commitment of a block.
+    ///
+    /// The signed message is the Poseidon hash of the block hash and the state diff commitment.
+    pub fn verify(
+        &self,
+        public_key: PublicKey,
+        block_hash: BlockHash,
+        state_diff_commitment: StateDiffCommitment,
+    ) -> Result<(), SignatureError> {
+        let message = pathfinder_crypto::hash::poseidon_hash_many(&[
+            block_hash.0.into(),
+            state_diff_commitment.0.into(),
+        ]);
+        pathfinder_crypto::signature::ecdsa_verify_partial(
+            public_key.0,
+            message.into(),
+            self.r.0,
+            self.s.0,
+        )
+    }
+}
+
+/// The outcome of verifying a block's [BlockCommitmentSignature] during sync.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// The signature was not verified, as no sequencer public key was available.
+    #[default]
+    Unverified,
+    Valid,
+    Invalid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macro_prelude::*;
+
+    #[test]
+    fn verify() {
+        // Mainnet block 350000, signed by the mainnet sequencer.
+        let block_hash =
+            block_hash!("0x6f7342a680d7f99bdfdd859f587c75299e7ffabe62c071ded3a6d8a34cb132c");
+        let state_diff_commitment = state_diff_commitment!(
+            "0x432e8e2ad833548e1c1077fc298991b055ba1e6f7a17dd332db98f4f428c56c"
+        );
+        let signature = BlockCommitmentSignature {
+            r: block_commitment_signature_elem!(
+                "0x95e98f5b91d39ae2b1bf77447a4fc01725352ae8b0b2c0a3fe09d43d1d9e57"
+            ),
+            s: block_commitment_signature_elem!(
+                "0x541b2db8dae6d5ae24b34e427d251edc2e94dcffddd85f207e1b51f2f4bb1ef"
+            ),
+        };
+        let public_key = crate::consts::MAINNET_SEQUENCER_PUBLIC_KEY;
+
+        signature
+            .verify(public_key, block_hash, state_diff_commitment)
+            .unwrap();
+        assert_eq!(
+            signature.verify(public_key, block_hash!("0x1234"), state_diff_commitment),
+            Err(SignatureError::Signature)
+        );
+        assert_eq!(
+            signature.verify(public_key, block_hash, state_diff_commitment!("0x1234")),
+            Err(SignatureError::Signature)
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! An [EthereumApi] over multiple Ethereum endpoints, which fails over to the next endpoint on
+//! errors and can require several endpoints to agree on the Starknet state.
+
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::EthereumChain;
+use primitive_types::{H160, H256};
+
+use crate::{
+    EthereumApi, EthereumClient, EthereumStateUpdate, L1ToL2MessageLog, L2ToL1MessageLog,
+    StateUpdateLog,
+};
+
+const METRIC_REQUESTS: &str = "ethereum_requests_total";
+const METRIC_FAILED_REQUESTS: &str = "ethereum_failed_requests_total";
+const METRIC_ENDPOINT_HEALTHY: &str = "ethereum_endpoint_healthy";
+
+/// Sends requests to one endpoint at a time, starting with the endpoint which succeeded most
+/// recently and failing over to the next one on errors.
+///
+/// With a quorum above one, the Starknet state, logs and block hashes are requested from all
+/// endpoints instead, and only returned if at least `quorum` endpoints agree on them. The
+/// finalized block is then the highest block which at least `quorum` endpoints have finalized.
+/// Endpoints which lag behind may briefly disagree, in which case the request fails and should be
+/// retried.
+///
+/// Each request is recorded per endpoint in the `ethereum_requests_total` and
+/// `ethereum_failed_requests_total` counters, and the `ethereum_endpoint_healthy` gauge holds
+/// whether the latest request to an endpoint succeeded.
+pub struct FailoverClient<T = EthereumClient> {
+    endpoints: Arc<[Endpoint<T>]>,
+    quorum: NonZeroUsize,
+    preferred: Arc<AtomicUsize>,
+}
+
+struct Endpoint<T> {
+    client: T,
+    /// Identifies the endpoint in logs and metrics.
+    label: String,
+}
+
+impl<T> Endpoint<T> {
+    fn record(&self, method: &'static str, result: &anyhow::Result<impl Sized>) {
+        metrics::increment_counter!(METRIC_REQUESTS, "endpoint" => self.label.clone(), "method" => method);
+
+        match result {
+            Ok(_) => {
+                metrics::gauge!(METRIC_ENDPOINT_HEALTHY, 1.0, "endpoint" => self.label.clone());
+            }
+            Err(error) => {
+                metrics::increment_counter!(METRIC_FAILED_REQUESTS, "endpoint" => self.label.clone(), "method" => method);
+                metrics::gauge!(METRIC_ENDPOINT_HEALTHY, 0.0, "endpoint" => self.label.clone());
+                tracing::debug!(endpoint=%self.label, method, %error, "Ethereum request failed");
+            }
+        }
+    }
+}
+
+impl<T> Clone for FailoverClient<T> {
+    fn clone(&self) -> Self {
+        Self {
+            endpoints: self.endpoints.clone(),
+            quorum: self.quorum,
+            preferred: self.preferred.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for FailoverClient<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let labels = self.endpoints.iter().map(|x| &x.label).collect::<Vec<_>>();
+        f.debug_struct("FailoverClient")
+            .field("endpoints", &labels)
+            .field("quorum", &self.quorum)
+            .finish()
+    }
+}
+
+impl<T: EthereumApi + Send + Sync> FailoverClient<T> {
+    /// Creates a client over the given `(label, client)` endpoints, which are initially tried in
+    /// order.
+    pub fn new(endpoints: Vec<(String, T)>, quorum: NonZeroUsize) -> anyhow::Result<Self> {
+        anyhow::ensure!(!endpoints.is_empty(), "No Ethereum endpoints configured");
+        anyhow::ensure!(
+            quorum.get() <= endpoints.len(),
+            "Quorum of {quorum} exceeds the number of Ethereum endpoints ({})",
+            endpoints.len()
+        );
+
+        let endpoints = endpoints
+            .into_iter()
+            .map(|(label, client)| Endpoint { client, label })
+            .collect();
+
+        Ok(Self {
+            endpoints,
+            quorum,
+            preferred: Default::default(),
+        })
+    }
+
+    /// Tries each endpoint in turn until one succeeds, starting with the preferred endpoint.
+    async fn failover<'a, R, F, Fut>(&'a self, method: &'static str, f: F) -> anyhow::Result<R>
+    where
+        F: Fn(&'a T) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for i in 0..self.endpoints.len() {
+            let index = (preferred + i) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+
+            let result = f(&endpoint.client).await;
+            endpoint.record(method, &result);
+            match result {
+                Ok(result) => {
+                    if index != preferred {
+                        tracing::info!(endpoint=%endpoint.label, "Failed over to Ethereum endpoint");
+                        self.preferred.store(index, Ordering::Relaxed);
+                    }
+                    return Ok(result);
+                }
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.expect("There is at least one endpoint"))
+            .with_context(|| format!("All Ethereum endpoints failed {method}"))
+    }
+
+    /// Sends the request to all endpoints at once.
+    async fn all<'a, R, F, Fut>(&'a self, method: &'static str, f: F) -> Vec<anyhow::Result<R>>
+    where
+        F: Fn(&'a T) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        futures::future::join_all(self.endpoints.iter().map(|endpoint| {
+            let result = f(&endpoint.client);
+            async move {
+                let result = result.await;
+                endpoint.record(method, &result);
+                result
+            }
+        }))
+        .await
+    }
+
+    /// Sends the request to all endpoints and returns the result at least `quorum` of them agree
+    /// on, failing over instead if no more than one endpoint is required to agree.
+    async fn agree<'a, R, F, Fut>(&'a self, method: &'static str, f: F) -> anyhow::Result<R>
+    where
+        R: PartialEq,
+        F: Fn(&'a T) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        if self.quorum.get() == 1 {
+            return self.failover(method, f).await;
+        }
+
+        let results = self.all(method, f).await;
+
+        let mut votes: Vec<(R, usize)> = Vec::new();
+        for result in results.into_iter().flatten() {
+            match votes.iter_mut().find(|(x, _)| *x == result) {
+                Some((_, count)) => *count += 1,
+                None => votes.push((result, 1)),
+            }
+        }
+
+        let (result, count) = votes
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .with_context(|| format!("All Ethereum endpoints failed {method}"))?;
+        anyhow::ensure!(
+            count >= self.quorum.get(),
+            "Only {count} Ethereum endpoints agree on {method}, but {} are required",
+            self.quorum
+        );
+
+        Ok(result)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: EthereumApi + Send + Sync> EthereumApi for FailoverClient<T> {
+    async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate> {
+        self.agree("get_starknet_state", |client| {
+            client.get_starknet_state(address)
+        })
+        .await
+    }
+
+    async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+        self.failover("get_chain", |client| client.get_chain())
+            .await
+    }
+
+    /// Requires at least `quorum` endpoints to have finalized the returned block, so that a single
+    /// endpoint cannot advance L1 sync on its own.
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+        const METHOD: &str = "get_finalized_block_number";
+
+        if self.quorum.get() == 1 {
+            return self
+                .failover(METHOD, |client| client.get_finalized_block_number())
+                .await;
+        }
+
+        let mut numbers = self
+            .all(METHOD, |client| client.get_finalized_block_number())
+            .await
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        anyhow::ensure!(
+            numbers.len() >= self.quorum.get(),
+            "Only {} Ethereum endpoints returned {METHOD}, but {} are required",
+            numbers.len(),
+            self.quorum
+        );
+
+        // The highest block which at least `quorum` endpoints have finalized.
+        numbers.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(numbers[self.quorum.get() - 1])
+    }
+
+    /// Requires at least `quorum` endpoints to agree on the hash, as it is trusted when verifying
+    /// [proofs](EthereumApi::get_proven_starknet_state).
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<H256> {
+        self.agree("get_block_hash", |client| client.get_block_hash(number))
+            .await
+    }
+
+    /// Fails over instead of requiring a quorum, as the proofs are verified against the trusted
+    /// `block_hash`.
+    async fn get_proven_starknet_state(
+        &self,
+        address: &H160,
+        block_hash: H256,
+    ) -> anyhow::Result<EthereumStateUpdate> {
+        self.failover("get_proven_starknet_state", |client| {
+            client.get_proven_starknet_state(address, block_hash)
+        })
+        .await
+    }
+
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>> {
+        self.agree("get_state_update_logs", |client| {
+            client.get_state_update_logs(address, from_block, to_block)
+        })
+        .await
+    }
+
+    async fn get_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+        self.agree("get_message_logs", |client| {
+            client.get_message_logs(address, from_block, to_block)
+        })
+        .await
+    }
+
+    async fn get_consumed_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L2ToL1MessageLog>> {
+        self.agree("get_consumed_message_logs", |client| {
+            client.get_consumed_message_logs(address, from_block, to_block)
+        })
+        .await
+    }
+
+    async fn wait_for_new_block(&self, address: &H160, timeout: Duration) {
+        let waits = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.client.wait_for_new_block(address, timeout));
+        futures::future::select_all(waits).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::BlockNumber;
+
+    use super::*;
+
+    /// Returns `state` from [EthereumApi::get_starknet_state] and the chain from
+    /// [EthereumApi::get_chain], or fails if it is marked as down. The hash of each block and the
+    /// finalized block number are derived from the Starknet block number of `state`, so that
+    /// endpoints with different states disagree on them, and only `state` can be proven against
+    /// the hash.
+    #[derive(Default)]
+    struct MockEndpoint {
+        state: EthereumStateUpdate,
+        down: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    impl MockEndpoint {
+        fn request<R>(&self, result: R) -> anyhow::Result<R> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            anyhow::ensure!(!self.down.load(Ordering::Relaxed), "Endpoint is down");
+            Ok(result)
+        }
+
+        fn block_hash(&self) -> H256 {
+            H256::from_low_u64_be(self.state.block_number.get())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EthereumApi for MockEndpoint {
+        async fn get_starknet_state(&self, _: &H160) -> anyhow::Result<EthereumStateUpdate> {
+            self.request(self.state.clone())
+        }
+
+        async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+            self.request(EthereumChain::Mainnet)
+        }
+
+        async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+            self.request(self.state.block_number.get())
+        }
+
+        async fn get_block_hash(&self, _: u64) -> anyhow::Result<H256> {
+            self.request(self.block_hash())
+        }
+
+        async fn get_proven_starknet_state(
+            &self,
+            _: &H160,
+            block_hash: H256,
+        ) -> anyhow::Result<EthereumStateUpdate> {
+            self.request(())?;
+            anyhow::ensure!(
+                block_hash == self.block_hash(),
+                "Block header does not match"
+            );
+            Ok(self.state.clone())
+        }
+
+        async fn get_state_update_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<StateUpdateLog>> {
+            self.request(vec![])
+        }
+
+        async fn get_message_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+            self.request(vec![])
+        }
+
+        async fn get_consumed_message_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<L2ToL1MessageLog>> {
+            self.request(vec![])
+        }
+
+        async fn wait_for_new_block(&self, _: &H160, timeout: Duration) {
+            tokio::time::sleep(timeout).await
+        }
+    }
+
+    fn state(block: u64) -> EthereumStateUpdate {
+        EthereumStateUpdate {
+            state_root: state_commitment!("0x1"),
+            block_number: BlockNumber::new_or_panic(block),
+            block_hash: block_hash!("0x2"),
+        }
+    }
+
+    fn client(states: &[u64], quorum: usize) -> FailoverClient<MockEndpoint> {
+        let endpoints = states
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let endpoint = MockEndpoint {
+                    state: state(*block),
+                    ..Default::default()
+                };
+                (format!("endpoint {i}"), endpoint)
+            })
+            .collect();
+
+        FailoverClient::new(endpoints, NonZeroUsize::new(quorum).unwrap()).unwrap()
+    }
+
+    fn calls(client: &FailoverClient<MockEndpoint>) -> Vec<usize> {
+        client
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.client.calls.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn failover() {
+        let client = client(&[1, 1], 1);
+        client.endpoints[0]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+
+        client.get_chain().await.unwrap();
+        assert_eq!(calls(&client), vec![1, 1]);
+
+        // The endpoint which succeeded is tried first from now on.
+        client.endpoints[0]
+            .client
+            .down
+            .store(false, Ordering::Relaxed);
+        client.get_chain().await.unwrap();
+        assert_eq!(calls(&client), vec![1, 2]);
+
+        client.endpoints[1]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        client.get_chain().await.unwrap();
+        assert_eq!(calls(&client), vec![2, 3]);
+
+        client.endpoints[0]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        client.get_chain().await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn quorum() {
+        let address = H160::zero();
+
+        let client = client(&[1, 2, 1], 2);
+        let result = client.get_starknet_state(&address).await.unwrap();
+        assert_eq!(result, state(1));
+        assert_eq!(calls(&client), vec![1, 1, 1]);
+
+        client.endpoints[2]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        client.get_starknet_state(&address).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn logs_by_quorum() {
+        let address = H160::zero();
+
+        let client = client(&[1, 1, 1], 2);
+        client.get_state_update_logs(&address, 0, 1).await.unwrap();
+        client.get_message_logs(&address, 0, 1).await.unwrap();
+        client
+            .get_consumed_message_logs(&address, 0, 1)
+            .await
+            .unwrap();
+        assert_eq!(calls(&client), vec![3, 3, 3]);
+
+        client.endpoints[1]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        client.endpoints[2]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        client.get_message_logs(&address, 0, 1).await.unwrap_err();
+        client
+            .get_consumed_message_logs(&address, 0, 1)
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn finalized_block_by_quorum() {
+        let client = client(&[3, 1, 2], 2);
+        assert_eq!(client.get_finalized_block_number().await.unwrap(), 2);
+        assert_eq!(calls(&client), vec![1, 1, 1]);
+
+        client.endpoints[2]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        assert_eq!(client.get_finalized_block_number().await.unwrap(), 1);
+
+        client.endpoints[1]
+            .client
+            .down
+            .store(true, Ordering::Relaxed);
+        client.get_finalized_block_number().await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn proof_verification() {
+        let address = H160::zero();
+
+        // The preferred endpoint reports a different block hash and state than the others, so
+        // its proof does not match the agreed hash and the next endpoint is used.
+        let agreed = client(&[2, 1, 1], 2);
+        let block_hash = agreed.get_block_hash(0).await.unwrap();
+        let result = agreed
+            .get_proven_starknet_state(&address, block_hash)
+            .await
+            .unwrap();
+        assert_eq!(result, state(1));
+
+        let disagreed = client(&[1, 2], 2);
+        disagreed.get_block_hash(0).await.unwrap_err();
+    }
+
+    #[test]
+    fn quorum_exceeds_endpoints() {
+        let endpoints = vec![("endpoint".to_owned(), MockEndpoint::default())];
+        FailoverClient::new(endpoints, NonZeroUsize::new(2).unwrap()).unwrap_err();
+    }
+}
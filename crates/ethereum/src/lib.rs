@@ -1,7 +1,17 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
 use pathfinder_common::{BlockHash, BlockNumber, EthereumChain, StateCommitment};
 use pathfinder_crypto::Felt;
 use primitive_types::{H160, H256, U256};
 
+mod failover;
+mod proof;
+mod websocket;
+
+pub use failover::FailoverClient;
+
 pub mod core_addr {
     use const_decoder::Decoder;
 
@@ -16,6 +26,16 @@ pub mod core_addr {
         Decoder::Hex.decode(b"4737c0c1B4D5b1A687B42610DdabEE781152359c");
 }
 
+/// Ethereum blocks at or shortly before the deployment of each network's core contract. The
+/// contracts have no logs before these blocks, so scanning for logs can start from them.
+pub mod core_deployment_block {
+    pub const MAINNET: u64 = 13_500_000;
+    pub const GOERLI_TESTNET: u64 = 4_500_000;
+    pub const GOERLI_INTEGRATION: u64 = 4_500_000;
+    pub const SEPOLIA_TESTNET: u64 = 3_000_000;
+    pub const SEPOLIA_INTEGRATION: u64 = 3_000_000;
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct EthereumStateUpdate {
     pub state_root: StateCommitment,
@@ -23,16 +43,136 @@ pub struct EthereumStateUpdate {
     pub block_hash: BlockHash,
 }
 
+/// A `LogStateUpdate` event emitted by the Starknet core contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateUpdateLog {
+    pub state_root: StateCommitment,
+    pub block_number: BlockNumber,
+    /// Only included by the core contract since Starknet v0.11, and therefore `None` for older
+    /// state updates.
+    pub block_hash: Option<BlockHash>,
+    /// The Ethereum block containing the event.
+    pub l1_block_number: u64,
+    /// The Ethereum transaction which emitted the event.
+    pub l1_transaction_hash: H256,
+}
+
+impl StateUpdateLog {
+    /// The [EthereumStateUpdate] this log represents, if it includes the block hash.
+    pub fn state_update(&self) -> Option<EthereumStateUpdate> {
+        Some(EthereumStateUpdate {
+            state_root: self.state_root,
+            block_number: self.block_number,
+            block_hash: self.block_hash?,
+        })
+    }
+}
+
+/// A `LogMessageToL2` event emitted by the Starknet core contract when an L1 contract sends a
+/// message to L2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1ToL2MessageLog {
+    /// Matches the message hash of the L1 handler transaction which consumes the message on L2.
+    pub message_hash: H256,
+    /// The Ethereum block containing the event.
+    pub l1_block_number: u64,
+    /// The Ethereum transaction which sent the message.
+    pub l1_transaction_hash: H256,
+}
+
+/// A `ConsumedMessageToL1` event emitted by the Starknet core contract when an L1 contract
+/// consumes a message sent from L2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L2ToL1MessageLog {
+    /// Matches the message hash of the L2 to L1 message in the receipt of the L2 transaction which
+    /// sent it. Identical messages have the same hash.
+    pub message_hash: H256,
+    /// The Ethereum block containing the event.
+    pub l1_block_number: u64,
+    /// The position of the event within its Ethereum block.
+    pub l1_log_index: u64,
+    /// The Ethereum transaction which consumed the message.
+    pub l1_transaction_hash: H256,
+}
+
 #[async_trait::async_trait]
 pub trait EthereumApi {
     async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate>;
     async fn get_chain(&self) -> anyhow::Result<EthereumChain>;
+    /// Returns the number of the latest finalized Ethereum block.
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64>;
+    /// Returns the hash of the Ethereum block with the given number.
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<H256>;
+    /// Reads the Starknet state from the storage of the core contract at `address` in the Ethereum
+    /// block with hash `block_hash`, verifying `eth_getProof` storage proofs against the block's
+    /// header instead of trusting `eth_call` results.
+    ///
+    /// The header is checked against `block_hash`, which must therefore come from a trusted source.
+    async fn get_proven_starknet_state(
+        &self,
+        address: &H160,
+        block_hash: H256,
+    ) -> anyhow::Result<EthereumStateUpdate>;
+    /// Returns the `LogStateUpdate` events emitted by the core contract at `address` within the
+    /// given range of Ethereum blocks, in the order they were emitted.
+    ///
+    /// Fails if `to_block` is not finalized yet.
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>>;
+    /// Returns the `LogMessageToL2` events emitted by the core contract at `address` within the
+    /// given range of Ethereum blocks, in the order they were emitted.
+    ///
+    /// Fails if `to_block` is not finalized yet.
+    async fn get_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>>;
+    /// Returns the `ConsumedMessageToL1` events emitted by the core contract at `address` within
+    /// the given range of Ethereum blocks, in the order they were emitted.
+    ///
+    /// Fails if `to_block` is not finalized yet.
+    async fn get_consumed_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L2ToL1MessageLog>>;
+    /// Waits until a new Ethereum block or a log emitted by the contract at `address` is observed,
+    /// or at most `timeout`.
+    ///
+    /// Clients which are not notified of new blocks always wait for the full timeout.
+    async fn wait_for_new_block(&self, address: &H160, timeout: Duration);
 }
 
+/// An Ethereum JSON-RPC client, connecting over websocket for `ws` and `wss` URLs and over HTTP
+/// otherwise.
+///
+/// Over websocket, the client subscribes to new blocks and to the logs of the contract passed to
+/// [EthereumApi::wait_for_new_block]. The connection is re-established whenever it is lost.
+/// Meanwhile, requests are sent over HTTP(S) to the same URL instead.
 #[derive(Clone, Debug)]
 pub struct EthereumClient {
-    http: reqwest::Client,
-    url: reqwest::Url,
+    transport: Transport,
+}
+
+#[derive(Clone, Debug)]
+enum Transport {
+    Http {
+        client: reqwest::Client,
+        url: reqwest::Url,
+    },
+    Websocket {
+        websocket: Arc<websocket::WebsocketTransport>,
+        /// Used while the websocket is disconnected.
+        client: reqwest::Client,
+        http_url: reqwest::Url,
+    },
 }
 
 const HTTP_OK: u16 = 200;
@@ -44,14 +184,37 @@ impl EthereumClient {
         Self::new(url)
     }
 
+    /// Creates a new client. Websocket URLs must be used within a Tokio runtime, as the
+    /// connection is managed by a background task.
     pub fn new(url: reqwest::Url) -> anyhow::Result<Self> {
-        Ok(Self {
-            http: reqwest::ClientBuilder::new().build()?,
-            url,
-        })
+        let transport = match url.scheme() {
+            "ws" | "wss" => {
+                let mut http_url = url.clone();
+                let scheme = if url.scheme() == "wss" {
+                    "https"
+                } else {
+                    "http"
+                };
+                http_url
+                    .set_scheme(scheme)
+                    .map_err(|_| anyhow::anyhow!("Deriving HTTP URL from {url}"))?;
+
+                Transport::Websocket {
+                    websocket: Arc::new(websocket::WebsocketTransport::spawn(url)),
+                    client: reqwest::ClientBuilder::new().build()?,
+                    http_url,
+                }
+            }
+            _ => Transport::Http {
+                client: reqwest::ClientBuilder::new().build()?,
+                url,
+            },
+        };
+
+        Ok(Self { transport })
     }
 
-    async fn get_finalized_block_hash(&self) -> anyhow::Result<H256> {
+    async fn get_finalized_block(&self) -> anyhow::Result<serde_json::Value> {
         self.call_ethereum(serde_json::json!({
             "jsonrpc": "2.0",
             "method": "eth_getBlockByNumber",
@@ -62,7 +225,47 @@ impl EthereumClient {
             "id": 0
         }))
         .await
-        .and_then(|value| get_h256(&value["hash"]))
+    }
+
+    async fn get_logs(
+        &self,
+        address: &H160,
+        topics: &[H256],
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        // An endpoint which lags behind returns no logs for the blocks it has not seen yet, which
+        // cannot be told apart from blocks without logs.
+        let finalized = self.get_finalized_block_number().await?;
+        anyhow::ensure!(
+            to_block <= finalized,
+            "Logs up to Ethereum block {to_block} were requested, but only block {finalized} is finalized"
+        );
+
+        let topics = topics
+            .iter()
+            .map(|topic| format!("0x{}", hex::encode(topic.as_bytes())))
+            .collect::<Vec<_>>();
+        let logs = self
+            .call_ethereum(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getLogs",
+                "params": [
+                    {
+                        "address": format!("0x{}", hex::encode(address.as_bytes())),
+                        "fromBlock": format!("{from_block:#x}"),
+                        "toBlock": format!("{to_block:#x}"),
+                        "topics": [topics]
+                    }
+                ],
+                "id": 0
+            }))
+            .await?;
+
+        match logs {
+            serde_json::Value::Array(logs) => Ok(logs),
+            other => anyhow::bail!("Unexpected eth_getLogs result: {other}"),
+        }
     }
 
     async fn call_starknet_contract(
@@ -89,7 +292,22 @@ impl EthereumClient {
     }
 
     async fn call_ethereum(&self, value: serde_json::Value) -> anyhow::Result<serde_json::Value> {
-        let res = self.http.post(self.url.clone()).json(&value).send().await?;
+        let (client, url) = match &self.transport {
+            Transport::Websocket {
+                websocket,
+                client,
+                http_url,
+            } => {
+                if websocket.is_connected() {
+                    return websocket.call(value).await;
+                }
+                tracing::trace!("Websocket disconnected, falling back to HTTP");
+                (client, http_url)
+            }
+            Transport::Http { client, url } => (client, url),
+        };
+
+        let res = client.post(url.clone()).json(&value).send().await?;
 
         let status = res.status();
         let (code, message) = (status.as_u16(), status.as_str());
@@ -106,10 +324,11 @@ impl EthereumClient {
 #[async_trait::async_trait]
 impl EthereumApi for EthereumClient {
     async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate> {
-        let hash = self.get_finalized_block_hash().await?;
+        let block = self.get_finalized_block().await?;
+        let hash = get_h256(&block["hash"])?;
         let hash = format!("0x{}", hex::encode(hash.as_bytes()));
         let addr = format!("0x{}", hex::encode(address.as_bytes()));
-        Ok(EthereumStateUpdate {
+        let state = EthereumStateUpdate {
             state_root: self
                 .call_starknet_contract(&hash, &addr, "stateRoot()")
                 .await
@@ -127,9 +346,165 @@ impl EthereumApi for EthereumClient {
                 .await
                 .and_then(|value| get_u256(&value))
                 .and_then(get_number)?,
+        };
+
+        Ok(state)
+    }
+
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+        self.call_ethereum(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_getBlockByNumber",
+            "params": [
+                "finalized",
+                false
+            ],
+            "id": 0
+        }))
+        .await
+        .and_then(|value| get_u256(&value["number"]))
+        .and_then(get_u64)
+    }
+
+    async fn get_block_hash(&self, number: u64) -> anyhow::Result<H256> {
+        self.call_ethereum(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_getBlockByNumber",
+            "params": [
+                format!("{number:#x}"),
+                false
+            ],
+            "id": 0
+        }))
+        .await
+        .and_then(|value| get_h256(&value["hash"]))
+    }
+
+    async fn get_proven_starknet_state(
+        &self,
+        address: &H160,
+        block_hash: H256,
+    ) -> anyhow::Result<EthereumStateUpdate> {
+        let hash = format!("0x{}", hex::encode(block_hash.as_bytes()));
+        let block = self
+            .call_ethereum(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getBlockByHash",
+                "params": [
+                    &hash,
+                    false
+                ],
+                "id": 0
+            }))
+            .await?;
+        let state_root =
+            proof::verify_header(&block, block_hash).context("Verifying block header")?;
+
+        let slots = proof::state_slots();
+        let keys = slots
+            .iter()
+            .map(|slot| format!("0x{}", hex::encode(slot.as_bytes())))
+            .collect::<Vec<_>>();
+        let response = self
+            .call_ethereum(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getProof",
+                "params": [
+                    format!("0x{}", hex::encode(address.as_bytes())),
+                    keys,
+                    {"blockHash": hash}
+                ],
+                "id": 0
+            }))
+            .await?;
+        let values = proof::verify_storage_proof(state_root, address, &slots, &response)
+            .context("Verifying storage proof")?;
+
+        let [state_root, block_number, block_hash] = values[..] else {
+            anyhow::bail!("Expected a value for each state slot");
+        };
+        let to_felt = |value: U256| {
+            let mut bytes = H256::zero();
+            value.to_big_endian(bytes.as_bytes_mut());
+            get_felt(bytes)
+        };
+
+        Ok(EthereumStateUpdate {
+            state_root: to_felt(state_root).map(StateCommitment)?,
+            block_number: get_number(block_number)?,
+            block_hash: to_felt(block_hash).map(BlockHash)?,
         })
     }
 
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>> {
+        let topics = [
+            event_topic(LOG_STATE_UPDATE),
+            event_topic(LEGACY_LOG_STATE_UPDATE),
+        ];
+        let logs = self
+            .get_logs(address, &topics, from_block, to_block)
+            .await?;
+
+        logs.iter()
+            // Logs are only removed by reorgs, which cannot affect finalized blocks. Skip them
+            // regardless in case a non-finalized range was requested.
+            .filter(|log| log["removed"].as_bool() != Some(true))
+            .map(|log| parse_state_update_log(log, &topics))
+            .collect()
+    }
+
+    async fn get_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+        let topics = [
+            event_topic(LOG_MESSAGE_TO_L2),
+            event_topic(LEGACY_LOG_MESSAGE_TO_L2),
+        ];
+        let logs = self
+            .get_logs(address, &topics, from_block, to_block)
+            .await?;
+
+        logs.iter()
+            .filter(|log| log["removed"].as_bool() != Some(true))
+            .map(|log| parse_message_log(log, &topics))
+            .collect()
+    }
+
+    async fn get_consumed_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L2ToL1MessageLog>> {
+        let topic = event_topic(CONSUMED_MESSAGE_TO_L1);
+        let logs = self
+            .get_logs(address, &[topic], from_block, to_block)
+            .await?;
+
+        logs.iter()
+            .filter(|log| log["removed"].as_bool() != Some(true))
+            .map(|log| parse_consumed_message_log(log, topic))
+            .collect()
+    }
+
+    async fn wait_for_new_block(&self, address: &H160, timeout: Duration) {
+        match &self.transport {
+            Transport::Websocket { websocket, .. } => {
+                websocket.watch_logs(*address);
+                let _ = tokio::time::timeout(timeout, websocket.notification()).await;
+            }
+            Transport::Http { .. } => tokio::time::sleep(timeout).await,
+        }
+    }
+
     async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
         let id = self
             .call_ethereum(serde_json::json!({
@@ -149,6 +524,188 @@ impl EthereumApi for EthereumClient {
     }
 }
 
+/// The `LogStateUpdate` event signature since Starknet v0.11.
+const LOG_STATE_UPDATE: &str = "LogStateUpdate(uint256,int256,uint256)";
+/// The `LogStateUpdate` event signature before Starknet v0.11, which lacks the block hash.
+const LEGACY_LOG_STATE_UPDATE: &str = "LogStateUpdate(uint256,int256)";
+
+fn event_topic(signature: &str) -> H256 {
+    let mut output = H256::zero();
+    keccak_hash::keccak_256(signature.as_bytes(), output.as_bytes_mut());
+    output
+}
+
+/// Parses a `LogStateUpdate` event, where `topics` holds the current and legacy event topics.
+fn parse_state_update_log(
+    log: &serde_json::Value,
+    topics: &[H256; 2],
+) -> anyhow::Result<StateUpdateLog> {
+    let topic = get_h256(&log["topics"][0]).context("Parsing log topic")?;
+    let data = log["data"].as_str().context("Log data missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Parsing log data")?;
+
+    let words = match topic {
+        topic if topic == topics[0] => 3,
+        topic if topic == topics[1] => 2,
+        other => anyhow::bail!("Unexpected log topic {other:?}"),
+    };
+    anyhow::ensure!(
+        data.len() == words * 32,
+        "Unexpected log data length {}",
+        data.len()
+    );
+    let word = |i: usize| H256::from_slice(&data[i * 32..(i + 1) * 32]);
+
+    let state_root = get_felt(word(0)).map(StateCommitment)?;
+    let block_number = get_number(U256::from_big_endian(word(1).as_bytes()))?;
+    let block_hash = match words {
+        3 => Some(get_felt(word(2)).map(BlockHash)?),
+        _ => None,
+    };
+
+    let l1_block_number = get_u256(&log["blockNumber"]).and_then(get_u64)?;
+    let l1_transaction_hash = get_h256(&log["transactionHash"])?;
+
+    Ok(StateUpdateLog {
+        state_root,
+        block_number,
+        block_hash,
+        l1_block_number,
+        l1_transaction_hash,
+    })
+}
+
+/// The `LogMessageToL2` event signature since Starknet v0.11.
+const LOG_MESSAGE_TO_L2: &str = "LogMessageToL2(address,uint256,uint256,uint256[],uint256,uint256)";
+/// The `LogMessageToL2` event signature before Starknet v0.11, which lacks the message fee.
+const LEGACY_LOG_MESSAGE_TO_L2: &str = "LogMessageToL2(address,uint256,uint256,uint256[],uint256)";
+
+/// Parses a `LogMessageToL2` event, where `topics` holds the current and legacy event topics.
+///
+/// The sender, recipient and selector are indexed and therefore part of the log topics, while the
+/// data holds the ABI encoded payload, nonce and fee.
+fn parse_message_log(
+    log: &serde_json::Value,
+    topics: &[H256; 2],
+) -> anyhow::Result<L1ToL2MessageLog> {
+    let topic = |i: usize| get_h256(&log["topics"][i]).context("Parsing log topic");
+    let data = log["data"].as_str().context("Log data missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Parsing log data")?;
+
+    match topic(0)? {
+        topic if topic == topics[0] || topic == topics[1] => {}
+        other => anyhow::bail!("Unexpected log topic {other:?}"),
+    }
+    let (from_address, to_address, selector) = (topic(1)?, topic(2)?, topic(3)?);
+
+    let word = |i: usize| {
+        data.get(i * 32..(i + 1) * 32)
+            .map(U256::from_big_endian)
+            .with_context(|| format!("Unexpected log data length {}", data.len()))
+    };
+    let nonce = word(1)?;
+    // The payload is a dynamic array, so its position is given as a byte offset.
+    let offset = get_u64(word(0)?)? as usize;
+    anyhow::ensure!(
+        offset % 32 == 0 && offset < data.len(),
+        "Invalid payload offset {offset}"
+    );
+    let payload_start = offset / 32;
+    let payload_len = get_u64(word(payload_start)?)? as usize;
+    anyhow::ensure!(
+        payload_len <= data.len() / 32,
+        "Invalid payload length {payload_len}"
+    );
+    let payload = data
+        .get((payload_start + 1) * 32..(payload_start + 1 + payload_len) * 32)
+        .with_context(|| format!("Unexpected log data length {}", data.len()))?;
+
+    // Mirrors the message hash computed by the core contract, which is keccak256 of the packed
+    // sender, recipient, nonce, selector, payload length and payload.
+    let mut message = vec![0u8; 5 * 32];
+    message[..32].copy_from_slice(from_address.as_bytes());
+    message[32..64].copy_from_slice(to_address.as_bytes());
+    nonce.to_big_endian(&mut message[64..96]);
+    message[96..128].copy_from_slice(selector.as_bytes());
+    U256::from(payload_len as u64).to_big_endian(&mut message[128..160]);
+    message.extend_from_slice(payload);
+    let mut message_hash = H256::zero();
+    keccak_hash::keccak_256(&message, message_hash.as_bytes_mut());
+
+    let l1_block_number = get_u256(&log["blockNumber"]).and_then(get_u64)?;
+    let l1_transaction_hash = get_h256(&log["transactionHash"])?;
+
+    Ok(L1ToL2MessageLog {
+        message_hash,
+        l1_block_number,
+        l1_transaction_hash,
+    })
+}
+
+/// The `ConsumedMessageToL1` event signature.
+const CONSUMED_MESSAGE_TO_L1: &str = "ConsumedMessageToL1(uint256,address,uint256[])";
+
+/// Parses a `ConsumedMessageToL1` event.
+///
+/// The sender and recipient are indexed and therefore part of the log topics, while the data holds
+/// the ABI encoded payload.
+fn parse_consumed_message_log(
+    log: &serde_json::Value,
+    expected_topic: H256,
+) -> anyhow::Result<L2ToL1MessageLog> {
+    let topic = |i: usize| get_h256(&log["topics"][i]).context("Parsing log topic");
+    let data = log["data"].as_str().context("Log data missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Parsing log data")?;
+
+    match topic(0)? {
+        topic if topic == expected_topic => {}
+        other => anyhow::bail!("Unexpected log topic {other:?}"),
+    }
+    let (from_address, to_address) = (topic(1)?, topic(2)?);
+
+    let word = |i: usize| {
+        data.get(i * 32..(i + 1) * 32)
+            .map(U256::from_big_endian)
+            .with_context(|| format!("Unexpected log data length {}", data.len()))
+    };
+    // The payload is a dynamic array, so its position is given as a byte offset.
+    let offset = get_u64(word(0)?)? as usize;
+    anyhow::ensure!(
+        offset % 32 == 0 && offset < data.len(),
+        "Invalid payload offset {offset}"
+    );
+    let payload_start = offset / 32;
+    let payload_len = get_u64(word(payload_start)?)? as usize;
+    anyhow::ensure!(
+        payload_len <= data.len() / 32,
+        "Invalid payload length {payload_len}"
+    );
+    let payload = data
+        .get((payload_start + 1) * 32..(payload_start + 1 + payload_len) * 32)
+        .with_context(|| format!("Unexpected log data length {}", data.len()))?;
+
+    // Mirrors the message hash computed by the core contract, which is keccak256 of the packed
+    // sender, recipient, payload length and payload.
+    let mut message = vec![0u8; 3 * 32];
+    message[..32].copy_from_slice(from_address.as_bytes());
+    message[32..64].copy_from_slice(to_address.as_bytes());
+    U256::from(payload_len as u64).to_big_endian(&mut message[64..96]);
+    message.extend_from_slice(payload);
+    let mut message_hash = H256::zero();
+    keccak_hash::keccak_256(&message, message_hash.as_bytes_mut());
+
+    let l1_block_number = get_u256(&log["blockNumber"]).and_then(get_u64)?;
+    let l1_log_index = get_u256(&log["logIndex"]).and_then(get_u64)?;
+    let l1_transaction_hash = get_h256(&log["transactionHash"])?;
+
+    Ok(L2ToL1MessageLog {
+        message_hash,
+        l1_block_number,
+        l1_log_index,
+        l1_transaction_hash,
+    })
+}
+
 fn encode_ethereum_call_data(signature: &[u8]) -> String {
     let mut output: [u8; 32] = Default::default();
     keccak_hash::keccak_256(signature, &mut output[..]);
@@ -179,10 +736,18 @@ fn get_felt(value: H256) -> anyhow::Result<Felt> {
 }
 
 fn get_number(value: U256) -> anyhow::Result<BlockNumber> {
-    let value = value.as_u64();
+    let value = get_u64(value)?;
     BlockNumber::new(value).ok_or(anyhow::anyhow!("Failed to read u64 from U256"))
 }
 
+fn get_u64(value: U256) -> anyhow::Result<u64> {
+    anyhow::ensure!(
+        value <= U256::from(u64::MAX),
+        "Failed to read u64 from U256"
+    );
+    Ok(value.as_u64())
+}
+
 fn lpad64(value: &str) -> String {
     let input = value.strip_prefix("0x").unwrap_or(value);
     let prefix = if value.starts_with("0x") { "0x" } else { "" };
@@ -242,6 +807,27 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn websocket_falls_back_to_http() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+
+        let mock = server.mock(|when, then| {
+            when.path("/").method(POST);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(r#"{"jsonrpc":"2.0","id":0,"result":"0x1"}"#);
+        });
+
+        // The server does not accept websocket connections.
+        let url = Url::parse(&server.url("/").replacen("http", "ws", 1))?;
+        let eth = EthereumClient::new(url)?;
+        let chain_id = eth.get_chain().await?;
+
+        mock.assert();
+        assert_eq!(chain_id, EthereumChain::Mainnet);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_get_starknet_state() -> anyhow::Result<()> {
         let server = MockServer::start_async().await;
@@ -311,6 +897,380 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_get_block_hash() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .header("Content-type", "application/json")
+                .body(r#"{"id":0,"jsonrpc":"2.0","method":"eth_getBlockByNumber","params":["0x1048e0e",false]}"#);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(r#"{"jsonrpc":"2.0","id":0,"result":{"number":"0x1048e0e","hash":"0x9921984fd976f261e0d70618b51e3db3724b9f4d28d0534c3483dd2162f13fff"}}"#);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+        let hash = eth.get_block_hash(0x1048e0e).await?;
+
+        mock.assert();
+        assert_eq!(
+            hash,
+            H256::from_str("0x9921984fd976f261e0d70618b51e3db3724b9f4d28d0534c3483dd2162f13fff")?
+        );
+        Ok(())
+    }
+
+    /// Serves `number` as the finalized block, which log requests are checked against.
+    fn serve_finalized_block(server: &MockServer, number: u64) {
+        server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .body_contains("eth_getBlockByNumber")
+                .body_contains("finalized");
+            then.status(200).json_body(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": {"number": format!("{number:#x}")}
+            }));
+        });
+    }
+
+    /// Serves the block and `eth_getProof` response of the proof fixture for any block hash, and
+    /// returns the fixture's block hash.
+    async fn serve_proven_state(server: &MockServer) -> H256 {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/starknet_state_proof.json")).unwrap();
+        let responses = [
+            ("eth_getBlockByHash", fixture["block"].clone()),
+            ("eth_getProof", fixture["proof"].clone()),
+        ];
+
+        for (request, result) in responses {
+            server.mock(|when, then| {
+                when.path("/").method(POST).body_contains(request);
+                then.status(200)
+                    .json_body(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": result}));
+            });
+        }
+
+        get_h256(&fixture["block"]["hash"]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_proven_starknet_state() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        let block_hash = serve_proven_state(&server).await;
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let state = eth.get_proven_starknet_state(&addr, block_hash).await?;
+
+        let expected = EthereumStateUpdate {
+            state_root: StateCommitment(Felt::from_hex_str(
+                "0x02a4651c1ba5151c48ebeb4477216b04d7a65058a5b99e5fbc602507ae933d2f",
+            )?),
+            block_number: BlockNumber::new_or_panic(0x7eeb),
+            block_hash: BlockHash(Felt::from_hex_str(
+                "0x0602ee9a6e1e1b3a9eb6e9bcb1a1d3b4f4e7e5b1bfa9d1c9e3a0f1b2c3d4e5f6",
+            )?),
+        };
+        assert_eq!(state, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_proven_starknet_state_untrusted_block() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        serve_proven_state(&server).await;
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+
+        // The endpoint serves a valid block and proof, but not for the requested block hash.
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        eth.get_proven_starknet_state(&addr, H256::repeat_byte(1))
+            .await
+            .unwrap_err();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_state_update_logs() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        serve_finalized_block(&server, 0x20);
+
+        let topic = format!("0x{}", hex::encode(event_topic(LOG_STATE_UPDATE)));
+        let legacy_topic = format!("0x{}", hex::encode(event_topic(LEGACY_LOG_STATE_UPDATE)));
+        let request = format!(
+            r#"{{"id":0,"jsonrpc":"2.0","method":"eth_getLogs","params":[{{"address":"0xc662c410c0ecf747543f5ba90660f6abebd9c8c4","fromBlock":"0x10","toBlock":"0x20","topics":[["{topic}","{legacy_topic}"]]}}]}}"#
+        );
+        let word = |value: &str| format!("{value:0>64}");
+        let response = format!(
+            r#"{{"jsonrpc":"2.0","id":0,"result":[
+                {{"topics":["{legacy_topic}"],"data":"0x{}{}","blockNumber":"0x11","transactionHash":"0x{}","removed":false}},
+                {{"topics":["{topic}"],"data":"0x{}{}{}","blockNumber":"0x1f","transactionHash":"0x{}","removed":false}},
+                {{"topics":["{topic}"],"data":"0x{}{}{}","blockNumber":"0x20","transactionHash":"0x{}","removed":true}}
+            ]}}"#,
+            word("aa"),
+            word("7"),
+            word("1"),
+            word("bb"),
+            word("8"),
+            word("cc"),
+            word("2"),
+            word("dd"),
+            word("9"),
+            word("ee"),
+            word("3"),
+        );
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .header("Content-type", "application/json")
+                .body(request);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(response);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let logs = eth.get_state_update_logs(&addr, 0x10, 0x20).await?;
+
+        mock.assert();
+        assert_eq!(
+            logs,
+            vec![
+                StateUpdateLog {
+                    state_root: StateCommitment(Felt::from_hex_str("0xaa")?),
+                    block_number: BlockNumber::new_or_panic(7),
+                    block_hash: None,
+                    l1_block_number: 0x11,
+                    l1_transaction_hash: H256::from_low_u64_be(1),
+                },
+                StateUpdateLog {
+                    state_root: StateCommitment(Felt::from_hex_str("0xbb")?),
+                    block_number: BlockNumber::new_or_panic(8),
+                    block_hash: Some(BlockHash(Felt::from_hex_str("0xcc")?)),
+                    l1_block_number: 0x1f,
+                    l1_transaction_hash: H256::from_low_u64_be(2),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_message_logs() -> anyhow::Result<()> {
+        use pathfinder_common::transaction::L1HandlerTransaction;
+        use pathfinder_common::{CallParam, ContractAddress, EntryPoint, TransactionNonce};
+
+        let server = MockServer::start_async().await;
+        serve_finalized_block(&server, 0x20);
+
+        let topic = format!("0x{}", hex::encode(event_topic(LOG_MESSAGE_TO_L2)));
+        let legacy_topic = format!("0x{}", hex::encode(event_topic(LEGACY_LOG_MESSAGE_TO_L2)));
+        let request = format!(
+            r#"{{"id":0,"jsonrpc":"2.0","method":"eth_getLogs","params":[{{"address":"0xc662c410c0ecf747543f5ba90660f6abebd9c8c4","fromBlock":"0x10","toBlock":"0x20","topics":[["{topic}","{legacy_topic}"]]}}]}}"#
+        );
+        let word = |value: &str| format!("{value:0>64}");
+        let (from, to, selector) = (word("aa"), word("bb"), word("cc"));
+        // The legacy log holds the payload offset and nonce, followed by the payload [1, 2]. The
+        // current log additionally holds the fee before its payload [3].
+        let response = format!(
+            r#"{{"jsonrpc":"2.0","id":0,"result":[
+                {{"topics":["{legacy_topic}","0x{from}","0x{to}","0x{selector}"],"data":"0x{}{}{}{}{}","blockNumber":"0x11","transactionHash":"0x{}","removed":false}},
+                {{"topics":["{topic}","0x{from}","0x{to}","0x{selector}"],"data":"0x{}{}{}{}{}","blockNumber":"0x1f","transactionHash":"0x{}","removed":false}},
+                {{"topics":["{topic}","0x{from}","0x{to}","0x{selector}"],"data":"0x{}{}{}{}{}","blockNumber":"0x20","transactionHash":"0x{}","removed":true}}
+            ]}}"#,
+            word("40"),
+            word("5"),
+            word("2"),
+            word("1"),
+            word("2"),
+            word("1"),
+            word("60"),
+            word("6"),
+            word("99"),
+            word("1"),
+            word("3"),
+            word("2"),
+            word("60"),
+            word("7"),
+            word("99"),
+            word("1"),
+            word("4"),
+            word("3"),
+        );
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .header("Content-type", "application/json")
+                .body(request);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(response);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let logs = eth.get_message_logs(&addr, 0x10, 0x20).await?;
+
+        // The hashes must match those of the L1 handler transactions consuming the messages.
+        let message_hash = |nonce: u64, payload: &[u64]| {
+            let calldata = std::iter::once(0xaa)
+                .chain(payload.iter().copied())
+                .map(|x| CallParam(Felt::from_u64(x)))
+                .collect();
+            L1HandlerTransaction {
+                contract_address: ContractAddress(Felt::from_u64(0xbb)),
+                entry_point_selector: EntryPoint(Felt::from_u64(0xcc)),
+                nonce: TransactionNonce(Felt::from_u64(nonce)),
+                calldata,
+            }
+            .calculate_message_hash()
+        };
+
+        mock.assert();
+        assert_eq!(
+            logs,
+            vec![
+                L1ToL2MessageLog {
+                    message_hash: message_hash(5, &[1, 2]),
+                    l1_block_number: 0x11,
+                    l1_transaction_hash: H256::from_low_u64_be(1),
+                },
+                L1ToL2MessageLog {
+                    message_hash: message_hash(6, &[3]),
+                    l1_block_number: 0x1f,
+                    l1_transaction_hash: H256::from_low_u64_be(2),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_consumed_message_logs() -> anyhow::Result<()> {
+        use pathfinder_common::receipt::L2ToL1Message;
+        use pathfinder_common::{ContractAddress, EthereumAddress, L2ToL1MessagePayloadElem};
+
+        let server = MockServer::start_async().await;
+        serve_finalized_block(&server, 0x20);
+
+        let topic = format!("0x{}", hex::encode(event_topic(CONSUMED_MESSAGE_TO_L1)));
+        let request = format!(
+            r#"{{"id":0,"jsonrpc":"2.0","method":"eth_getLogs","params":[{{"address":"0xc662c410c0ecf747543f5ba90660f6abebd9c8c4","fromBlock":"0x10","toBlock":"0x20","topics":[["{topic}"]]}}]}}"#
+        );
+        let word = |value: &str| format!("{value:0>64}");
+        let (from, to) = (word("aa"), word("bb"));
+        // Each log holds the payload offset followed by the payload, [1, 2] and [3] respectively.
+        let response = format!(
+            r#"{{"jsonrpc":"2.0","id":0,"result":[
+                {{"topics":["{topic}","0x{from}","0x{to}"],"data":"0x{}{}{}{}","blockNumber":"0x11","logIndex":"0x3","transactionHash":"0x{}","removed":false}},
+                {{"topics":["{topic}","0x{from}","0x{to}"],"data":"0x{}{}{}","blockNumber":"0x1f","logIndex":"0x0","transactionHash":"0x{}","removed":false}},
+                {{"topics":["{topic}","0x{from}","0x{to}"],"data":"0x{}{}{}","blockNumber":"0x20","logIndex":"0x0","transactionHash":"0x{}","removed":true}}
+            ]}}"#,
+            word("20"),
+            word("2"),
+            word("1"),
+            word("2"),
+            word("1"),
+            word("20"),
+            word("1"),
+            word("3"),
+            word("2"),
+            word("20"),
+            word("1"),
+            word("4"),
+            word("3"),
+        );
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .header("Content-type", "application/json")
+                .body(request);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(response);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let logs = eth.get_consumed_message_logs(&addr, 0x10, 0x20).await?;
+
+        // The hashes must match those of the messages in the receipts of the sending transactions.
+        let message_hash = |payload: &[u64]| {
+            L2ToL1Message {
+                from_address: ContractAddress(Felt::from_u64(0xaa)),
+                to_address: EthereumAddress(H160::from_low_u64_be(0xbb)),
+                payload: payload
+                    .iter()
+                    .map(|x| L2ToL1MessagePayloadElem(Felt::from_u64(*x)))
+                    .collect(),
+            }
+            .calculate_message_hash()
+        };
+
+        mock.assert();
+        assert_eq!(
+            logs,
+            vec![
+                L2ToL1MessageLog {
+                    message_hash: message_hash(&[1, 2]),
+                    l1_block_number: 0x11,
+                    l1_log_index: 3,
+                    l1_transaction_hash: H256::from_low_u64_be(1),
+                },
+                L2ToL1MessageLog {
+                    message_hash: message_hash(&[3]),
+                    l1_block_number: 0x1f,
+                    l1_log_index: 0,
+                    l1_transaction_hash: H256::from_low_u64_be(2),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn logs_beyond_the_finalized_block_are_rejected() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        serve_finalized_block(&server, 0x1f);
+        let logs = server.mock(|when, then| {
+            when.path("/").method(POST).body_contains("eth_getLogs");
+            then.status(200)
+                .json_body(serde_json::json!({"jsonrpc": "2.0", "id": 0, "result": []}));
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+
+        // The endpoint has not seen block 0x20 yet, so it would return no logs for it.
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        eth.get_state_update_logs(&addr, 0x10, 0x20)
+            .await
+            .unwrap_err();
+        eth.get_state_update_logs(&addr, 0x10, 0x1f).await?;
+
+        logs.assert_hits(1);
+        Ok(())
+    }
+
     #[test]
     fn test_h256() {
         assert!(H256::from_str(
@@ -0,0 +1,510 @@
+//! Verification of the Starknet state stored in the core contract using `eth_getProof` storage
+//! proofs, which are checked against the state root of a block header with a trusted hash.
+
+use anyhow::Context;
+use primitive_types::{H160, H256, U256};
+
+/// The core contract stores its `StarknetState.State` struct, consisting of the state root, block
+/// number and block hash, in consecutive slots starting at the hash of this tag.
+const STATE_STRUCT_TAG: &str = "STARKNET_1.0_INIT_STARKNET_STATE_STRUCT";
+
+/// The storage slots of the state root, block number and block hash in the core contract.
+pub(crate) fn state_slots() -> [H256; 3] {
+    let base = U256::from_big_endian(keccak(STATE_STRUCT_TAG.as_bytes()).as_bytes());
+    [0u32, 1, 2].map(|offset| {
+        let mut slot = H256::zero();
+        (base + offset).to_big_endian(slot.as_bytes_mut());
+        slot
+    })
+}
+
+/// Whether a header field is a byte string or a quantity, which is encoded without leading zeros.
+#[derive(Copy, Clone)]
+enum Field {
+    Bytes,
+    Quantity,
+}
+
+/// The header fields of `eth_getBlockByHash` in the order in which they are hashed. Fields added
+/// by later forks are only present in blocks since that fork.
+const HEADER_FIELDS: &[(&str, Field)] = &[
+    ("parentHash", Field::Bytes),
+    ("sha3Uncles", Field::Bytes),
+    ("miner", Field::Bytes),
+    ("stateRoot", Field::Bytes),
+    ("transactionsRoot", Field::Bytes),
+    ("receiptsRoot", Field::Bytes),
+    ("logsBloom", Field::Bytes),
+    ("difficulty", Field::Quantity),
+    ("number", Field::Quantity),
+    ("gasLimit", Field::Quantity),
+    ("gasUsed", Field::Quantity),
+    ("timestamp", Field::Quantity),
+    ("extraData", Field::Bytes),
+    ("mixHash", Field::Bytes),
+    ("nonce", Field::Bytes),
+    // London
+    ("baseFeePerGas", Field::Quantity),
+    // Shanghai
+    ("withdrawalsRoot", Field::Bytes),
+    // Cancun
+    ("blobGasUsed", Field::Quantity),
+    ("excessBlobGas", Field::Quantity),
+    ("parentBeaconBlockRoot", Field::Bytes),
+    // Prague
+    ("requestsHash", Field::Bytes),
+];
+
+/// Checks that the header fields of a block returned by `eth_getBlockByHash` hash to `hash`, and
+/// returns the state root of the block.
+///
+/// The `hash` reported by the endpoint itself is ignored, as it is only as trustworthy as the rest
+/// of the response.
+pub(crate) fn verify_header(block: &serde_json::Value, hash: H256) -> anyhow::Result<H256> {
+    let mut fields = Vec::new();
+    let mut missing = None;
+    for (name, kind) in HEADER_FIELDS {
+        let value = &block[name];
+        if value.is_null() {
+            missing.get_or_insert(name);
+            continue;
+        }
+        if let Some(missing) = missing {
+            anyhow::bail!("Block header has {name} but lacks {missing}");
+        }
+
+        let bytes = parse_bytes(value).with_context(|| format!("Parsing header field {name}"))?;
+        let bytes = match kind {
+            Field::Bytes => bytes,
+            Field::Quantity => strip_leading_zeros(&bytes).to_vec(),
+        };
+        fields.push(bytes);
+    }
+
+    let mut header = Vec::new();
+    encode_list(&fields, &mut header);
+    let computed = keccak(&header);
+    anyhow::ensure!(
+        computed == hash,
+        "Block header hashes to {computed:?} instead of {hash:?}"
+    );
+
+    let state_root = parse_bytes(&block["stateRoot"]).context("Parsing state root")?;
+    anyhow::ensure!(state_root.len() == 32, "Invalid state root length");
+    Ok(H256::from_slice(&state_root))
+}
+
+/// Verifies an `eth_getProof` response for `address` and `slots` against the state root of the
+/// block it was requested for, and returns the proven values of the slots.
+pub(crate) fn verify_storage_proof(
+    state_root: H256,
+    address: &H160,
+    slots: &[H256],
+    proof: &serde_json::Value,
+) -> anyhow::Result<Vec<U256>> {
+    let account_proof = parse_proof(&proof["accountProof"]).context("Parsing account proof")?;
+    let account = verify_proof(state_root, keccak(address.as_bytes()), &account_proof)
+        .context("Verifying account proof")?
+        .context("Account does not exist")?;
+
+    // Accounts are encoded as [nonce, balance, storage root, code hash].
+    let account = decode_list(&account).context("Decoding account")?;
+    let storage_root = match account.as_slice() {
+        [_, _, Item::Bytes(root), _] if root.len() == 32 => H256::from_slice(root),
+        _ => anyhow::bail!("Invalid account encoding"),
+    };
+
+    let storage_proofs = proof["storageProof"]
+        .as_array()
+        .context("Missing storage proofs")?;
+    anyhow::ensure!(
+        storage_proofs.len() == slots.len(),
+        "Expected {} storage proofs but got {}",
+        slots.len(),
+        storage_proofs.len()
+    );
+
+    slots
+        .iter()
+        .zip(storage_proofs)
+        .map(|(slot, storage_proof)| {
+            let key = parse_bytes(&storage_proof["key"]).context("Parsing storage key")?;
+            anyhow::ensure!(
+                U256::from_big_endian(&key) == U256::from_big_endian(slot.as_bytes()),
+                "Storage proof is for a different slot"
+            );
+
+            let nodes = parse_proof(&storage_proof["proof"]).context("Parsing storage proof")?;
+            let value = verify_proof(storage_root, keccak(slot.as_bytes()), &nodes)
+                .with_context(|| format!("Verifying storage proof of slot {slot:?}"))?;
+
+            // Storage values are RLP encoded integers, and slots which are not in the trie are zero.
+            match value {
+                Some(value) => match decode_item(&value)? {
+                    (Item::Bytes(bytes), []) if bytes.len() <= 32 => {
+                        Ok(U256::from_big_endian(bytes))
+                    }
+                    _ => anyhow::bail!("Invalid storage value encoding"),
+                },
+                None => Ok(U256::zero()),
+            }
+        })
+        .collect()
+}
+
+/// A reference from a trie node to its child.
+enum Child<'a> {
+    Hash(H256),
+    /// Nodes shorter than a hash are embedded in their parent.
+    Inline(&'a [u8]),
+}
+
+/// Walks the Merkle-Patricia trie with the given root along `key` using the proof nodes, and
+/// returns the value stored at the key or `None` if the proof shows that the key is absent.
+fn verify_proof(root: H256, key: H256, proof: &[Vec<u8>]) -> anyhow::Result<Option<Vec<u8>>> {
+    let path = nibbles(key.as_bytes());
+    let mut path = path.as_slice();
+    let mut proof = proof.iter();
+    let mut next = Child::Hash(root);
+
+    loop {
+        let node = match next {
+            Child::Hash(hash) => {
+                let node = proof.next().context("Proof is incomplete")?;
+                anyhow::ensure!(keccak(node) == hash, "Proof node does not match its hash");
+                node.as_slice()
+            }
+            Child::Inline(node) => node,
+        };
+
+        let items = decode_list(node).context("Decoding proof node")?;
+        match items.as_slice() {
+            // Branch node
+            [children @ .., value] if children.len() == 16 => {
+                let Some((nibble, rest)) = path.split_first() else {
+                    return Ok(value.non_empty_bytes()?.map(<[u8]>::to_vec));
+                };
+                match children[usize::from(*nibble)].child()? {
+                    Some(child) => next = child,
+                    None => return Ok(None),
+                }
+                path = rest;
+            }
+            // Leaf or extension node
+            [Item::Bytes(encoded_path), item] => {
+                let (is_leaf, partial) = decode_hex_prefix(encoded_path)?;
+                if is_leaf {
+                    return match path == partial.as_slice() {
+                        true => Ok(item.non_empty_bytes()?.map(<[u8]>::to_vec)),
+                        false => Ok(None),
+                    };
+                }
+
+                let Some(rest) = path.strip_prefix(partial.as_slice()) else {
+                    return Ok(None);
+                };
+                next = item.child()?.context("Extension node without child")?;
+                path = rest;
+            }
+            _ => anyhow::bail!("Invalid proof node"),
+        }
+    }
+}
+
+/// Decodes the hex-prefix encoded path of a leaf or extension node into its nibbles, returning
+/// whether the node is a leaf.
+fn decode_hex_prefix(encoded: &[u8]) -> anyhow::Result<(bool, Vec<u8>)> {
+    let (first, rest) = encoded.split_first().context("Empty node path")?;
+    let flag = first >> 4;
+    anyhow::ensure!(flag <= 3, "Invalid node path flag");
+
+    let mut path = Vec::with_capacity(2 * encoded.len());
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(nibbles(rest));
+
+    Ok((flag & 2 == 2, path))
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// A decoded RLP item.
+enum Item<'a> {
+    Bytes(&'a [u8]),
+    /// The list is kept encoded, as proof nodes embed short child nodes as lists.
+    List {
+        encoded: &'a [u8],
+    },
+}
+
+impl<'a> Item<'a> {
+    fn non_empty_bytes(&self) -> anyhow::Result<Option<&'a [u8]>> {
+        match *self {
+            Item::Bytes([]) => Ok(None),
+            Item::Bytes(bytes) => Ok(Some(bytes)),
+            Item::List { .. } => anyhow::bail!("Expected bytes but found a list"),
+        }
+    }
+
+    fn child(&self) -> anyhow::Result<Option<Child<'a>>> {
+        match *self {
+            Item::Bytes([]) => Ok(None),
+            Item::Bytes(hash) if hash.len() == 32 => Ok(Some(Child::Hash(H256::from_slice(hash)))),
+            Item::Bytes(_) => anyhow::bail!("Invalid child reference"),
+            Item::List { encoded } => Ok(Some(Child::Inline(encoded))),
+        }
+    }
+}
+
+/// Decodes the first RLP item of `data`, returning it and the remaining data.
+fn decode_item(data: &[u8]) -> anyhow::Result<(Item<'_>, &[u8])> {
+    let (&prefix, rest) = data.split_first().context("Unexpected end of RLP data")?;
+
+    let (header_len, payload_len, is_list) = match prefix {
+        0x00..=0x7f => return Ok((Item::Bytes(&data[..1]), rest)),
+        0x80..=0xb7 => (1, usize::from(prefix - 0x80), false),
+        0xb8..=0xbf => {
+            let len_of_len = usize::from(prefix - 0xb7);
+            (1 + len_of_len, decode_length(rest, len_of_len)?, false)
+        }
+        0xc0..=0xf7 => (1, usize::from(prefix - 0xc0), true),
+        0xf8..=0xff => {
+            let len_of_len = usize::from(prefix - 0xf7);
+            (1 + len_of_len, decode_length(rest, len_of_len)?, true)
+        }
+    };
+
+    let end = header_len
+        .checked_add(payload_len)
+        .filter(|end| *end <= data.len())
+        .context("RLP item exceeds data")?;
+    let item = match is_list {
+        true => Item::List {
+            encoded: &data[..end],
+        },
+        false => Item::Bytes(&data[header_len..end]),
+    };
+
+    Ok((item, &data[end..]))
+}
+
+fn decode_length(data: &[u8], len_of_len: usize) -> anyhow::Result<usize> {
+    let bytes = data.get(..len_of_len).context("RLP length exceeds data")?;
+    anyhow::ensure!(
+        len_of_len <= std::mem::size_of::<usize>(),
+        "RLP length too large"
+    );
+    Ok(bytes
+        .iter()
+        .fold(0, |len, byte| (len << 8) | usize::from(*byte)))
+}
+
+/// Decodes `data`, which must consist of a single RLP list, into its items.
+fn decode_list(data: &[u8]) -> anyhow::Result<Vec<Item<'_>>> {
+    let (Item::List { encoded }, []) = decode_item(data)? else {
+        anyhow::bail!("Expected a single RLP list");
+    };
+
+    // Skip the list header.
+    let (_, mut payload) = match encoded[0] {
+        0xc0..=0xf7 => encoded.split_at(1),
+        prefix => encoded.split_at(1 + usize::from(prefix - 0xf7)),
+    };
+
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let (item, rest) = decode_item(payload)?;
+        items.push(item);
+        payload = rest;
+    }
+    Ok(items)
+}
+
+fn encode_bytes(bytes: &[u8], output: &mut Vec<u8>) {
+    match bytes {
+        [byte] if *byte < 0x80 => output.push(*byte),
+        _ => {
+            encode_length(bytes.len(), 0x80, output);
+            output.extend_from_slice(bytes);
+        }
+    }
+}
+
+fn encode_list(items: &[Vec<u8>], output: &mut Vec<u8>) {
+    let mut payload = Vec::new();
+    for item in items {
+        encode_bytes(item, &mut payload);
+    }
+    encode_length(payload.len(), 0xc0, output);
+    output.extend(payload);
+}
+
+fn encode_length(len: usize, offset: u8, output: &mut Vec<u8>) {
+    match u8::try_from(len) {
+        Ok(len) if len <= 55 => output.push(offset + len),
+        _ => {
+            let len = len.to_be_bytes();
+            let len = strip_leading_zeros(&len);
+            // The length of the length is at most the size of usize.
+            output.push(offset + 55 + len.len() as u8);
+            output.extend_from_slice(len);
+        }
+    }
+}
+
+fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn parse_bytes(value: &serde_json::Value) -> anyhow::Result<Vec<u8>> {
+    let value = value.as_str().context("Expected a hex string")?;
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    // Quantities are returned without leading zeros.
+    let bytes = match value.len() % 2 {
+        0 => hex::decode(value),
+        _ => hex::decode(format!("0{value}")),
+    };
+    bytes.context("Decoding hex string")
+}
+
+fn parse_proof(value: &serde_json::Value) -> anyhow::Result<Vec<Vec<u8>>> {
+    value
+        .as_array()
+        .context("Expected a list of proof nodes")?
+        .iter()
+        .map(parse_bytes)
+        .collect()
+}
+
+fn keccak(data: &[u8]) -> H256 {
+    let mut output = H256::zero();
+    keccak_hash::keccak_256(data, output.as_bytes_mut());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A finalized block and the `eth_getProof` response for the state slots of the mainnet core
+    /// contract at that block.
+    const FIXTURE: &str = include_str!("../fixtures/starknet_state_proof.json");
+
+    fn fixture() -> (serde_json::Value, serde_json::Value) {
+        let fixture: serde_json::Value = serde_json::from_str(FIXTURE).unwrap();
+        (fixture["block"].clone(), fixture["proof"].clone())
+    }
+
+    fn block_hash(block: &serde_json::Value) -> H256 {
+        H256::from_slice(&parse_bytes(&block["hash"]).unwrap())
+    }
+
+    fn core_address() -> H160 {
+        H160::from_slice(&crate::core_addr::MAINNET)
+    }
+
+    #[test]
+    fn header() {
+        let (block, _) = fixture();
+
+        let state_root = verify_header(&block, block_hash(&block)).unwrap();
+
+        assert_eq!(
+            serde_json::json!(format!("{state_root:?}")),
+            block["stateRoot"]
+        );
+    }
+
+    #[test]
+    fn tampered_header() {
+        let (mut block, _) = fixture();
+        let hash = block_hash(&block);
+        block["stateRoot"] = serde_json::json!(format!("{:?}", H256::repeat_byte(1)));
+
+        verify_header(&block, hash).unwrap_err();
+    }
+
+    #[test]
+    fn untrusted_hash() {
+        let (block, _) = fixture();
+
+        // The block is consistent with its own hash, but not with the trusted one.
+        verify_header(&block, H256::repeat_byte(1)).unwrap_err();
+    }
+
+    #[test]
+    fn storage_proof() {
+        let (block, proof) = fixture();
+        let state_root = verify_header(&block, block_hash(&block)).unwrap();
+
+        let values =
+            verify_storage_proof(state_root, &core_address(), &state_slots(), &proof).unwrap();
+
+        let expected = proof["storageProof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|storage_proof| U256::from_str_radix(storage_proof["value"].as_str().unwrap(), 16))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn tampered_storage_proof() {
+        let (block, mut proof) = fixture();
+        let state_root = verify_header(&block, block_hash(&block)).unwrap();
+
+        let leaf = proof["storageProof"][0]["proof"]
+            .as_array_mut()
+            .unwrap()
+            .last_mut()
+            .unwrap();
+        let mut bytes = parse_bytes(leaf).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        *leaf = serde_json::json!(format!("0x{}", hex::encode(bytes)));
+
+        verify_storage_proof(state_root, &core_address(), &state_slots(), &proof).unwrap_err();
+    }
+
+    #[test]
+    fn wrong_state_root() {
+        let (_, proof) = fixture();
+
+        verify_storage_proof(H256::zero(), &core_address(), &state_slots(), &proof).unwrap_err();
+    }
+
+    #[test]
+    fn rlp_roundtrip() {
+        let items = vec![
+            vec![],
+            vec![0x7f],
+            vec![0x80],
+            vec![0xab; 56],
+            vec![0xcd; 1024],
+        ];
+        let mut encoded = Vec::new();
+        encode_list(&items, &mut encoded);
+
+        let decoded = decode_list(&encoded)
+            .unwrap()
+            .into_iter()
+            .map(|item| match item {
+                Item::Bytes(bytes) => bytes.to_vec(),
+                Item::List { .. } => panic!("Unexpected list"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(decoded, items);
+    }
+}
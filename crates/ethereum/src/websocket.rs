@@ -0,0 +1,418 @@
+//! An Ethereum JSON-RPC transport over websocket, which is notified of new blocks and of the logs
+//! of a watched contract using `eth_subscribe` subscriptions to `newHeads` and `logs`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::{SinkExt, StreamExt};
+use primitive_types::H160;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, watch, Notify};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// The delay before reconnecting, which is doubled after each failed attempt up to
+/// [MAX_RECONNECT_DELAY].
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+/// How long a request waits for the connection to be (re-)established before giving up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests which are not answered in time fail, so that they can be retried.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The request ids of the `newHeads` and `logs` subscriptions. Other requests are numbered from 2.
+const NEW_HEADS_SUBSCRIPTION_ID: u64 = 0;
+const LOGS_SUBSCRIPTION_ID: u64 = 1;
+
+type Reply = oneshot::Sender<anyhow::Result<serde_json::Value>>;
+
+#[derive(Debug)]
+pub(crate) struct WebsocketTransport {
+    requests: mpsc::Sender<(serde_json::Value, Reply)>,
+    connected: watch::Receiver<bool>,
+    /// The contract whose logs are subscribed to.
+    watched: watch::Sender<Option<H160>>,
+    notifications: Arc<Notify>,
+}
+
+impl WebsocketTransport {
+    /// Connects to `url` in a background task, which reconnects whenever the connection is lost.
+    /// The task stops once the transport is dropped.
+    ///
+    /// Credentials included in the URL are sent using basic authentication.
+    pub(crate) fn spawn(url: reqwest::Url) -> Self {
+        let (requests, requests_rx) = mpsc::channel(64);
+        let (connected_tx, connected) = watch::channel(false);
+        let (watched, watched_rx) = watch::channel(None);
+        let notifications = Arc::new(Notify::new());
+
+        tokio::spawn(run(
+            url,
+            requests_rx,
+            connected_tx,
+            watched_rx,
+            notifications.clone(),
+        ));
+
+        Self {
+            requests,
+            connected,
+            watched,
+            notifications,
+        }
+    }
+
+    /// Subscribes to the logs of the contract at `address`, replacing any previously watched
+    /// contract. The subscription is renewed whenever the connection is re-established.
+    pub(crate) fn watch_logs(&self, address: H160) {
+        self.watched.send_if_modified(|watched| {
+            let modified = *watched != Some(address);
+            *watched = Some(address);
+            modified
+        });
+    }
+
+    /// Whether the connection is currently established.
+    pub(crate) fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
+    /// Sends a JSON-RPC request and returns its `result`.
+    ///
+    /// Fails if the connection is not established within [CONNECT_TIMEOUT].
+    pub(crate) async fn call(
+        &self,
+        request: serde_json::Value,
+    ) -> anyhow::Result<serde_json::Value> {
+        let mut connected = self.connected.clone();
+        tokio::time::timeout(CONNECT_TIMEOUT, connected.wait_for(|connected| *connected))
+            .await
+            .context("Websocket is not connected")?
+            .context("Websocket task stopped")?;
+
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send((request, reply))
+            .await
+            .context("Websocket task stopped")?;
+
+        tokio::time::timeout(REQUEST_TIMEOUT, response)
+            .await
+            .context("Websocket request timed out")?
+            .context("Websocket connection lost")?
+    }
+
+    /// Waits for a `newHeads` or `logs` notification received since the previous call returned.
+    /// Never completes while disconnected.
+    ///
+    /// Only a single waiter is woken per notification.
+    pub(crate) async fn notification(&self) {
+        self.notifications.notified().await
+    }
+}
+
+async fn run(
+    url: reqwest::Url,
+    mut requests: mpsc::Receiver<(serde_json::Value, Reply)>,
+    connected: watch::Sender<bool>,
+    mut watched: watch::Receiver<Option<H160>>,
+    notifications: Arc<Notify>,
+) {
+    let mut delay = RECONNECT_DELAY;
+
+    loop {
+        match connect(&url).await {
+            Ok(stream) => {
+                tracing::debug!("Connected to Ethereum websocket");
+                delay = RECONNECT_DELAY;
+                connected.send_replace(true);
+
+                let result = serve(stream, &mut requests, &mut watched, &notifications).await;
+                connected.send_replace(false);
+
+                match result {
+                    Ok(()) => return,
+                    Err(error) => tracing::warn!(%error, "Ethereum websocket connection lost"),
+                }
+            }
+            Err(error) => tracing::warn!(%error, "Connecting to Ethereum websocket failed"),
+        }
+
+        if connected.is_closed() {
+            return;
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+async fn connect(url: &reqwest::Url) -> anyhow::Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+    let mut without_credentials = url.clone();
+    let _ = without_credentials.set_username("");
+    let _ = without_credentials.set_password(None);
+
+    let mut request = without_credentials
+        .as_str()
+        .into_client_request()
+        .context("Creating websocket request")?;
+    if !url.username().is_empty() || url.password().is_some() {
+        let credentials = format!("{}:{}", url.username(), url.password().unwrap_or_default());
+        let header = format!("Basic {}", base64::encode(credentials))
+            .parse()
+            .context("Creating authorization header")?;
+        request.headers_mut().insert(AUTHORIZATION, header);
+    }
+
+    let (stream, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .context("Opening websocket")?;
+
+    Ok(stream)
+}
+
+/// Serves requests and notifications over an established connection.
+///
+/// Returns `Ok` once the transport was dropped, and an error if the connection was lost.
+async fn serve(
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    requests: &mut mpsc::Receiver<(serde_json::Value, Reply)>,
+    watched: &mut watch::Receiver<Option<H160>>,
+    notifications: &Notify,
+) -> anyhow::Result<()> {
+    let (mut sink, mut stream) = stream.split();
+    // Pending requests fail once this is dropped, as their reply channel is closed.
+    let mut pending = HashMap::new();
+    let mut next_id = LOGS_SUBSCRIPTION_ID + 1;
+
+    let subscribe = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_subscribe",
+        "params": ["newHeads"],
+        "id": NEW_HEADS_SUBSCRIPTION_ID
+    });
+    sink.send(Message::Text(subscribe.to_string()))
+        .await
+        .context("Subscribing to new heads")?;
+
+    // Subscribes to the logs of the watched contract on connecting, and whenever it changes. A
+    // replaced subscription keeps notifying until the connection is re-established, which only
+    // causes spurious wake-ups.
+    let mut address = *watched.borrow_and_update();
+    loop {
+        if let Some(address) = address.take() {
+            let subscribe = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscribe",
+                "params": ["logs", {"address": address}],
+                "id": LOGS_SUBSCRIPTION_ID
+            });
+            sink.send(Message::Text(subscribe.to_string()))
+                .await
+                .context("Subscribing to logs")?;
+        }
+
+        tokio::select! {
+            changed = watched.changed() => {
+                // The transport was dropped if this fails, which is noticed by `requests`.
+                if changed.is_ok() {
+                    address = *watched.borrow_and_update();
+                }
+            }
+            request = requests.recv() => {
+                let Some((mut request, reply)) = request else {
+                    return Ok(());
+                };
+
+                request["id"] = next_id.into();
+                pending.insert(next_id, reply);
+                next_id += 1;
+
+                sink.send(Message::Text(request.to_string()))
+                    .await
+                    .context("Sending request")?;
+            }
+            message = stream.next() => {
+                let message = message
+                    .context("Connection closed")?
+                    .context("Receiving message")?;
+
+                match message {
+                    Message::Text(text) => {
+                        let message = serde_json::from_str(&text).context("Parsing message")?;
+                        handle_message(message, &mut pending, notifications)?;
+                    }
+                    Message::Close(frame) => anyhow::bail!("Connection closed by server: {frame:?}"),
+                    // Pings are answered by tungstenite.
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn handle_message(
+    message: serde_json::Value,
+    pending: &mut HashMap<u64, Reply>,
+    notifications: &Notify,
+) -> anyhow::Result<()> {
+    if message["method"] == "eth_subscription" {
+        let result = &message["params"]["result"];
+        if result.get("topics").is_some() {
+            tracing::trace!(block=%result["blockNumber"], "New log of the watched contract");
+        } else {
+            tracing::trace!(number=%result["number"], "New Ethereum block");
+        }
+        // Stores a permit if nobody is waiting, so that a notification is not missed while the
+        // waiter is busy.
+        notifications.notify_one();
+        return Ok(());
+    }
+
+    let id = message["id"]
+        .as_u64()
+        .with_context(|| format!("Unexpected message: {message}"))?;
+    let error = message.get("error").filter(|error| !error.is_null());
+
+    if id == NEW_HEADS_SUBSCRIPTION_ID || id == LOGS_SUBSCRIPTION_ID {
+        if let Some(error) = error {
+            anyhow::bail!("Subscribing to notifications failed: {error}");
+        }
+        return Ok(());
+    }
+
+    if let Some(reply) = pending.remove(&id) {
+        let result = match error {
+            Some(error) => Err(anyhow::anyhow!("Ethereum call failed: {error}")),
+            None => Ok(message["result"].clone()),
+        };
+        // The caller may have timed out in the meantime.
+        let _ = reply.send(result);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn next_request(server: &mut WebSocketStream<TcpStream>) -> serde_json::Value {
+        loop {
+            if let Message::Text(text) = server.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    fn text(value: serde_json::Value) -> Message {
+        Message::Text(value.to_string())
+    }
+
+    #[tokio::test]
+    async fn requests_and_notifications() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = reqwest::Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            let subscribe = next_request(&mut server).await;
+            assert_eq!(subscribe["method"], "eth_subscribe");
+            assert_eq!(subscribe["params"], serde_json::json!(["newHeads"]));
+            let response =
+                serde_json::json!({"jsonrpc": "2.0", "id": subscribe["id"], "result": "0xabc"});
+            server.send(text(response)).await.unwrap();
+
+            let subscribe = next_request(&mut server).await;
+            assert_eq!(subscribe["method"], "eth_subscribe");
+            assert_eq!(
+                subscribe["params"],
+                serde_json::json!(["logs", {"address": H160::from_low_u64_be(1)}])
+            );
+            let response =
+                serde_json::json!({"jsonrpc": "2.0", "id": subscribe["id"], "result": "0xdef"});
+            server.send(text(response)).await.unwrap();
+
+            let request = next_request(&mut server).await;
+            assert_eq!(request["method"], "eth_blockNumber");
+            let response =
+                serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": "0x10"});
+            server.send(text(response)).await.unwrap();
+
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": {"subscription": "0xabc", "result": {"number": "0x11"}}
+            });
+            server.send(text(notification)).await.unwrap();
+
+            // Wait for the head to be observed before sending the log.
+            let request = next_request(&mut server).await;
+            assert_eq!(request["method"], "eth_blockNumber");
+            let response =
+                serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": "0x11"});
+            server.send(text(response)).await.unwrap();
+
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": {"subscription": "0xdef", "result": {"blockNumber": "0x11", "topics": []}}
+            });
+            server.send(text(notification)).await.unwrap();
+
+            let request = next_request(&mut server).await;
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32601, "message": "Method not found"}
+            });
+            server.send(text(response)).await.unwrap();
+
+            // Keep the connection open until the test completes.
+            server
+        });
+
+        let transport = WebsocketTransport::spawn(url);
+        transport.watch_logs(H160::from_low_u64_be(1));
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "method": "eth_blockNumber", "params": [], "id": 0});
+        let result = transport.call(request.clone()).await.unwrap();
+        assert_eq!(result, "0x10");
+
+        tokio::time::timeout(Duration::from_secs(5), transport.notification())
+            .await
+            .expect("New head notification");
+
+        let result = transport.call(request).await.unwrap();
+        assert_eq!(result, "0x11");
+
+        tokio::time::timeout(Duration::from_secs(5), transport.notification())
+            .await
+            .expect("Log notification");
+
+        let request =
+            serde_json::json!({"jsonrpc": "2.0", "method": "eth_unknown", "params": [], "id": 0});
+        transport.call(request).await.unwrap_err();
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn not_connected() {
+        // Nothing is listening on this port once the listener is dropped.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = reqwest::Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+
+        let transport = WebsocketTransport::spawn(url);
+
+        let request = serde_json::json!({"jsonrpc": "2.0", "method": "eth_blockNumber", "params": [], "id": 0});
+        transport.call(request).await.unwrap_err();
+    }
+}
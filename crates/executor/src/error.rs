@@ -5,6 +5,8 @@ use blockifier::{
     state::errors::StateError,
     transaction::errors::TransactionExecutionError as BlockifierTransactionExecutionError,
 };
+use pathfinder_common::ContractAddress;
+use pathfinder_crypto::Felt;
 
 #[derive(Debug)]
 pub enum CallError {
@@ -102,30 +104,165 @@ impl From<anyhow::Error> for TransactionExecutionError {
     }
 }
 
+/// Replaying the leading transactions of a block for a
+/// [mid-block state](crate::ExecutionState::mid_block) failed.
+///
+/// This is returned as the source of [TransactionExecutionError::Internal], since the state could
+/// not be set up rather than the executed transactions failing.
+#[derive(Debug)]
+pub struct PrefixReplayError {
+    pub transaction_index: usize,
+    pub error: String,
+}
+
+impl std::fmt::Display for PrefixReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Replaying transaction {} of the block failed: {}",
+            self.transaction_index, self.error
+        )
+    }
+}
+
+impl std::error::Error for PrefixReplayError {}
+
 impl TransactionExecutionError {
+    /// Returns the prefix replay failure this error was caused by, if any.
+    pub fn prefix_replay_error(&self) -> Option<&PrefixReplayError> {
+        match self {
+            Self::Internal(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+
     pub fn new(transaction_index: usize, error: BlockifierTransactionExecutionError) -> Self {
         Self::ExecutionError {
             transaction_index,
-            error: match &error {
-                // Some variants don't propagate their child's error so we do this manually until it is
-                // fixed in the blockifier. We have a test to ensure we don't miss fix.
-                BlockifierTransactionExecutionError::ContractConstructorExecutionFailed(x) => {
-                    format!("{error} {x}")
-                }
-                BlockifierTransactionExecutionError::ExecutionError(x) => format!("{error} {x}"),
-                BlockifierTransactionExecutionError::ValidateTransactionError(x) => {
-                    format!("{error} {x}")
-                }
-                other => other.to_string(),
-            },
+            error: describe(&error),
+        }
+    }
+}
+
+pub(crate) fn describe(error: &BlockifierTransactionExecutionError) -> String {
+    match error {
+        // Some variants don't propagate their child's error so we do this manually until it is
+        // fixed in the blockifier. We have a test to ensure we don't miss fix.
+        BlockifierTransactionExecutionError::ContractConstructorExecutionFailed(x) => {
+            format!("{error} {x}")
         }
+        BlockifierTransactionExecutionError::ExecutionError(x) => format!("{error} {x}"),
+        BlockifierTransactionExecutionError::ValidateTransactionError(x) => {
+            format!("{error} {x}")
+        }
+        other => other.to_string(),
+    }
+}
+
+/// The contracts on the failing call stack of `error`, outermost first.
+///
+/// The blockifier only keeps the call stack of Cairo VM failures, as the error stack trace of
+/// [BlockifierEntryPointExecutionError::VirtualMachineExecutionErrorWithTrace]. Other failures
+/// have no call path.
+pub(crate) fn call_path(error: &BlockifierTransactionExecutionError) -> Vec<ContractAddress> {
+    use BlockifierTransactionExecutionError::*;
+    match error {
+        ContractConstructorExecutionFailed(e)
+        | EntryPointExecutionError(e)
+        | ExecutionError(e)
+        | ValidateTransactionError(e) => match e {
+            BlockifierEntryPointExecutionError::VirtualMachineExecutionErrorWithTrace {
+                trace,
+                ..
+            } => trace_call_path(trace),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
     }
 }
 
+/// Extracts the contracts from the frames of a blockifier error stack trace.
+///
+/// The blockifier only exposes this trace as text, in which each frame starts with a line
+/// `Error in the called contract ({address}):`, outermost call first. Depending on the blockifier
+/// version this line is prefixed with the index of the frame as `{index}: `, so both forms are
+/// accepted. The `estimate_fee_each` tests of the RPC crate pin the format of the blockifier
+/// version in use.
+///
+/// The revert error of a reverted transaction is only available as this trace.
+pub(crate) fn trace_call_path(trace: &str) -> Vec<ContractAddress> {
+    trace
+        .lines()
+        .filter_map(|line| {
+            let frame = match line.split_once(": ") {
+                Some((index, frame)) if index.parse::<usize>().is_ok() => frame,
+                _ => line,
+            };
+            let address = frame
+                .strip_prefix("Error in the called contract (")?
+                .strip_suffix("):")?;
+            let address = Felt::from_hex_str(address).ok()?;
+            ContractAddress::new(address)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn call_path_is_extracted_from_error_trace() {
+        let trace = "0: Error in the called contract (0x0123abc):
+Error at pc=0:4835:
+Cairo traceback (most recent call last):
+Unknown location (pc=0:67)
+1: Error in the called contract (0x0456def):
+Execution failed. Failure reason: 0x496e76616c6964 ('Invalid').";
+
+        let path = trace_call_path(trace);
+
+        assert_eq!(
+            path,
+            vec![
+                ContractAddress::new_or_panic(Felt::from_hex_str("0x123abc").unwrap()),
+                ContractAddress::new_or_panic(Felt::from_hex_str("0x456def").unwrap()),
+            ]
+        );
+        assert!(trace_call_path("Invalid transaction nonce").is_empty());
+    }
+
+    #[test]
+    fn call_path_is_extracted_from_error_trace_without_frame_indices() {
+        let trace = "Error in the called contract (0x0000000000000000000000000000000000000000000000000000000000123abc):
+Error at pc=0:4835:
+Got an exception while executing a hint.
+Cairo traceback (most recent call last):
+Unknown location (pc=0:67)
+
+Error in the called contract (0x0000000000000000000000000000000000000000000000000000000000456def):
+Entry point EntryPointSelector(StarkFelt(\"0x0000000000000000000000000000000000000000000000000000000000000001\")) not found in contract.";
+
+        let path = trace_call_path(trace);
+
+        assert_eq!(
+            path,
+            vec![
+                ContractAddress::new_or_panic(Felt::from_hex_str("0x123abc").unwrap()),
+                ContractAddress::new_or_panic(Felt::from_hex_str("0x456def").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_without_trace_have_no_call_path() {
+        let error = BlockifierTransactionExecutionError::ExecutionError(
+            BlockifierEntryPointExecutionError::RecursionDepthExceeded,
+        );
+
+        assert!(call_path(&error).is_empty());
+    }
+
     mod transaction_errors_are_mapped_correctly {
         //! Some variants in the blockifier are opaque and omit the inner error's data. We've patched this manually
         //! and this tests ensures we don't accidentally stutter once the blockifier fixes this.
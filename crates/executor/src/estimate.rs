@@ -1,10 +1,15 @@
-use crate::types::PriceUnit;
+use crate::types::{
+    ExecutionResources, FailureStage, PriceUnit, ResourcesBreakdown, TransactionEstimate,
+    TransactionFailure,
+};
 
 use super::{
     error::TransactionExecutionError, execution_state::ExecutionState, types::FeeEstimate,
 };
 
 use blockifier::{
+    state::cached_state::CachedState,
+    transaction::errors::TransactionExecutionError as BlockifierTransactionExecutionError,
     transaction::transaction_execution::Transaction,
     transaction::transactions::ExecutableTransaction,
 };
@@ -72,3 +77,112 @@ pub fn estimate(
     }
     Ok(fees)
 }
+
+/// Like [estimate] but a failing transaction does not abort the batch.
+///
+/// Each transaction gets its own result. A transaction which fails validation leaves no trace in the
+/// state seen by the transactions after it, while a reverted transaction still has its nonce bumped as it
+/// would on chain.
+pub fn estimate_each(
+    mut execution_state: ExecutionState<'_>,
+    transactions: Vec<Transaction>,
+    skip_validate: bool,
+) -> Result<Vec<TransactionEstimate>, TransactionExecutionError> {
+    let block_number = execution_state.header.number;
+
+    let (mut state, block_context) = execution_state.starknet_state()?;
+
+    let mut estimates = Vec::with_capacity(transactions.len());
+    for (transaction_idx, transaction) in transactions.into_iter().enumerate() {
+        let _span = tracing::debug_span!("estimate_each", transaction_hash=%super::transaction::transaction_hash(&transaction), %block_number, %transaction_idx).entered();
+
+        let fee_type = &super::transaction::fee_type(&transaction);
+        let gas_price: U256 = block_context.gas_prices.get_by_fee_type(fee_type).into();
+        let unit = match fee_type {
+            blockifier::transaction::objects::FeeType::Strk => PriceUnit::Fri,
+            blockifier::transaction::objects::FeeType::Eth => PriceUnit::Wei,
+        };
+
+        let mut tx_state = CachedState::<_>::create_transactional(&mut state);
+        let tx_info = transaction
+            .execute(&mut tx_state, &block_context, false, !skip_validate)
+            .and_then(|mut tx_info| {
+                if tx_info.actual_fee.0 == 0 {
+                    tx_info.actual_fee = blockifier::fee::fee_utils::calculate_tx_fee(
+                        &tx_info.actual_resources,
+                        &block_context,
+                        fee_type,
+                    )?;
+                }
+
+                Ok(tx_info)
+            });
+
+        let tx_info = match tx_info {
+            Ok(tx_info) => {
+                tx_state.commit();
+                tx_info
+            }
+            Err(error) => {
+                tx_state.abort();
+                tracing::debug!(%error, "Transaction estimation failed");
+
+                let stage = match &error {
+                    BlockifierTransactionExecutionError::ValidateTransactionError(_) => {
+                        FailureStage::Validate
+                    }
+                    BlockifierTransactionExecutionError::ExecutionError(_)
+                    | BlockifierTransactionExecutionError::ContractConstructorExecutionFailed(_) => {
+                        FailureStage::Execute
+                    }
+                    _ => FailureStage::Other,
+                };
+                estimates.push(TransactionEstimate::Failure(TransactionFailure {
+                    stage,
+                    call_path: crate::error::call_path(&error),
+                    error: crate::error::describe(&error),
+                }));
+                continue;
+            }
+        };
+
+        if let Some(revert_error) = tx_info.revert_error {
+            tracing::debug!(%revert_error, "Transaction reverted");
+            estimates.push(TransactionEstimate::Failure(TransactionFailure {
+                stage: FailureStage::Execute,
+                call_path: crate::error::trace_call_path(&revert_error),
+                error: revert_error,
+            }));
+            continue;
+        }
+
+        let validate = tx_info
+            .validate_call_info
+            .as_ref()
+            .map(ExecutionResources::from);
+        let execute = tx_info
+            .execute_call_info
+            .as_ref()
+            .map(ExecutionResources::from);
+        // Fees are not charged during estimation, so there is no fee transfer.
+        let mut calls = ExecutionResources::default();
+        for resources in [&validate, &execute].into_iter().flatten() {
+            calls += resources;
+        }
+
+        estimates.push(TransactionEstimate::Success {
+            fee_estimate: FeeEstimate {
+                gas_consumed: U256::from(tx_info.actual_fee.0) / gas_price.max(1.into()),
+                gas_price,
+                overall_fee: tx_info.actual_fee.0.into(),
+                unit,
+            },
+            resources: Box::new(ResourcesBreakdown {
+                validate,
+                execute,
+                calls,
+            }),
+        });
+    }
+    Ok(estimates)
+}
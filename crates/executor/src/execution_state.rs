@@ -10,6 +10,7 @@ use blockifier::{
         cached_state::{CachedState, GlobalContractCache},
         state_api::State,
     },
+    transaction::{transaction_execution::Transaction, transactions::ExecutableTransaction},
 };
 use pathfinder_common::{BlockHeader, ChainId, StateUpdate};
 
@@ -17,8 +18,10 @@ pub struct ExecutionState<'tx> {
     transaction: &'tx pathfinder_storage::Transaction<'tx>,
     pub chain_id: ChainId,
     pub header: BlockHeader,
-    execute_on_parent_state: bool,
-    pending_state: Option<Arc<StateUpdate>>,
+    pub(crate) execute_on_parent_state: bool,
+    pub(crate) pending_state: Option<Arc<StateUpdate>>,
+    /// Transactions of this block which are replayed before anything else is executed.
+    prefix: Vec<Transaction>,
 }
 
 impl<'tx> ExecutionState<'tx> {
@@ -70,9 +73,49 @@ impl<'tx> ExecutionState<'tx> {
             )
         }
 
+        for (transaction_idx, transaction) in
+            std::mem::take(&mut self.prefix).into_iter().enumerate()
+        {
+            let _span =
+                tracing::debug_span!("replay", block_number=%self.header.number, %transaction_idx)
+                    .entered();
+
+            transaction
+                .execute(&mut cached_state, &block_context, true, true)
+                .map_err(|error| crate::error::PrefixReplayError {
+                    transaction_index: transaction_idx,
+                    error: crate::error::describe(&error),
+                })?;
+        }
+
         Ok((cached_state, block_context))
     }
 
+    /// Creates an execution state with explicitly specified parameters.
+    ///
+    /// The parallel executor uses this to recreate the same state on top of the
+    /// database transactions of its workers.
+    pub(crate) fn new<'a>(
+        transaction: &'a pathfinder_storage::Transaction<'a>,
+        chain_id: ChainId,
+        header: BlockHeader,
+        pending_state: Option<Arc<StateUpdate>>,
+        execute_on_parent_state: bool,
+    ) -> ExecutionState<'a> {
+        ExecutionState {
+            transaction,
+            chain_id,
+            header,
+            execute_on_parent_state,
+            pending_state,
+            prefix: Vec::new(),
+        }
+    }
+
+    pub(crate) fn database(&self) -> &'tx pathfinder_storage::Transaction<'tx> {
+        self.transaction
+    }
+
     pub fn trace(
         transaction: &'tx pathfinder_storage::Transaction<'tx>,
         chain_id: ChainId,
@@ -85,6 +128,7 @@ impl<'tx> ExecutionState<'tx> {
             header,
             pending_state,
             execute_on_parent_state: true,
+            prefix: Vec::new(),
         }
     }
 
@@ -100,6 +144,28 @@ impl<'tx> ExecutionState<'tx> {
             header,
             pending_state,
             execute_on_parent_state: false,
+            prefix: Vec::new(),
+        }
+    }
+
+    /// Execution state right after the first `prefix.len()` transactions of the block.
+    ///
+    /// `prefix` must be the leading transactions of the block, in order. They are replayed on top of the
+    /// parent block's state, including fee charges, before anything else is executed. A failed replay
+    /// is reported as a [PrefixReplayError](crate::PrefixReplayError).
+    pub fn mid_block(
+        transaction: &'tx pathfinder_storage::Transaction<'tx>,
+        chain_id: ChainId,
+        header: BlockHeader,
+        prefix: Vec<Transaction>,
+    ) -> Self {
+        Self {
+            transaction,
+            chain_id,
+            header,
+            pending_state: None,
+            execute_on_parent_state: true,
+            prefix,
         }
     }
 }
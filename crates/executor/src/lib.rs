@@ -6,6 +6,7 @@ pub(crate) mod estimate;
 pub(crate) mod execution_state;
 pub(crate) mod felt;
 pub(crate) mod lru_cache;
+pub(crate) mod parallel;
 pub(crate) mod pending;
 pub(crate) mod simulate;
 pub(crate) mod state_reader;
@@ -15,10 +16,11 @@ pub mod types;
 pub use block_context::ETH_FEE_TOKEN_ADDRESS;
 pub use call::call;
 pub use class::{parse_casm_definition, parse_deprecated_class_definition};
-pub use error::{CallError, TransactionExecutionError};
-pub use estimate::estimate;
+pub use error::{CallError, PrefixReplayError, TransactionExecutionError};
+pub use estimate::{estimate, estimate_each};
 pub use execution_state::ExecutionState;
 pub use felt::{IntoFelt, IntoStarkFelt};
+pub use parallel::{trace_parallel, ParallelTrace};
 pub use simulate::{simulate, trace, TraceCache};
 
 // re-export blockifier transaction type since it's exposed on our API
@@ -0,0 +1,741 @@
+//! Parallel block tracing with optimistic concurrency control.
+//!
+//! Transactions are first executed speculatively and in parallel, each on top of the block's
+//! initial state. While doing so we record every state item a transaction reads. The speculative
+//! results are then validated in block order: a transaction which read the same values as it
+//! would read from the sequential state would have behaved identically when executed sequentially,
+//! so its result is accepted and its writes are applied to the sequential state. All other
+//! transactions are re-executed on top of the sequential state, exactly as [trace](crate::trace)
+//! would have done.
+//!
+//! Workers read the initial state through their own database transactions, which may see a
+//! different snapshot of the database than the sequential state. Validating the values read,
+//! rather than only which items were written, makes such differences result in a re-execution
+//! instead of a wrong trace.
+//!
+//! Every account transaction pays its fee to the sequencer, which means that the sequencer's
+//! fee token balance is written by almost every transaction in a block. This write is commutative
+//! so we do not treat it as a conflict as long as only the fee transfer itself touched the balance.
+//! Instead the balance is rebased onto the sequential state.
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use blockifier::block_context::BlockContext;
+use blockifier::execution::call_info::CallInfo;
+use blockifier::state::cached_state::{CachedState, GlobalContractCache};
+use blockifier::state::state_api::{State, StateReader, StateResult};
+use blockifier::transaction::objects::{FeeType, TransactionExecutionInfo};
+use blockifier::transaction::transaction_execution::Transaction;
+use blockifier::transaction::transactions::ExecutableTransaction;
+use pathfinder_common::{
+    BlockHash, ClassHash, ContractAddress, StorageAddress, StorageValue, TransactionHash,
+};
+use pathfinder_crypto::Felt;
+use primitive_types::U256;
+use starknet_api::core::PatriciaKey;
+use starknet_api::hash::StarkFelt;
+
+use crate::simulate::{
+    to_state_diff, to_trace, transaction_declared_deprecated_class, transaction_type, CacheLookup,
+    TransactionType,
+};
+use crate::transaction::{fee_type, transaction_hash};
+use crate::types::{StateDiff, TransactionTrace};
+use crate::{IntoFelt, IntoStarkFelt, TraceCache, TransactionExecutionError};
+
+use super::execution_state::ExecutionState;
+
+/// Identifies a single item of state which can be read or written by a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum StateKey {
+    Storage(ContractAddress, StorageAddress),
+    Nonce(ContractAddress),
+    ClassHash(ContractAddress),
+    CompiledClass(ClassHash),
+}
+
+/// The outcome of executing a transaction on top of the block's initial state.
+struct Speculation {
+    hash: TransactionHash,
+    transaction_type: TransactionType,
+    fee_type: FeeType,
+    /// Values of all state items read by the transaction.
+    reads: HashMap<StateKey, Felt>,
+    /// [None] if the execution failed.
+    outcome: Option<(TransactionExecutionInfo, StateDiff)>,
+}
+
+/// The result of [trace_parallel].
+#[derive(Debug)]
+pub struct ParallelTrace {
+    pub traces: Vec<(TransactionHash, TransactionTrace)>,
+    /// The number of transactions whose speculative result was discarded and which had to be
+    /// re-executed sequentially.
+    pub reexecuted: usize,
+}
+
+/// Traces all transactions of a block like [trace](crate::trace), but executes them in parallel
+/// using up to `concurrency` workers.
+///
+/// Each worker opens its own connection from `storage` and uses `compose` to turn the raw
+/// transactions into executable ones. Workers which fail to get a database connection
+/// simply leave their transactions to the sequential re-execution, so the result is always
+/// identical to the one produced by [trace](crate::trace).
+#[allow(clippy::too_many_arguments)]
+pub fn trace_parallel<T, F>(
+    mut execution_state: ExecutionState<'_>,
+    storage: pathfinder_storage::Storage,
+    concurrency: NonZeroUsize,
+    cache: TraceCache,
+    block_hash: BlockHash,
+    transactions: &[T],
+    compose: F,
+    charge_fee: bool,
+    validate: bool,
+) -> Result<ParallelTrace, TransactionExecutionError>
+where
+    T: Sync,
+    F: Fn(&T, &pathfinder_storage::Transaction<'_>) -> anyhow::Result<Transaction> + Sync,
+{
+    let sender = match cache.get_or_reserve(block_hash)? {
+        CacheLookup::Hit(traces) => {
+            return Ok(ParallelTrace {
+                traces,
+                reexecuted: 0,
+            })
+        }
+        CacheLookup::Miss(sender) => sender,
+    };
+
+    let speculations = speculate(
+        &execution_state,
+        &storage,
+        concurrency,
+        transactions,
+        &compose,
+        charge_fee,
+        validate,
+    );
+
+    let db = execution_state.database();
+    let (mut state, block_context) = execution_state.starknet_state()?;
+
+    let mut written = HashSet::new();
+    let mut traces = Vec::with_capacity(transactions.len());
+    let mut reexecuted = 0usize;
+    for (transaction_idx, (input, speculation)) in transactions.iter().zip(speculations).enumerate()
+    {
+        let accepted = match speculation {
+            Some(speculation) => {
+                let _span = tracing::debug_span!("validate", transaction_hash=%speculation.hash, %transaction_idx).entered();
+                accept(&mut state, &block_context, &written, speculation)?
+            }
+            None => None,
+        };
+
+        let (hash, tx_type, tx_info, state_diff) = match accepted {
+            Some(accepted) => accepted,
+            None => {
+                reexecuted += 1;
+
+                let tx = compose(input, db)?;
+                let hash = transaction_hash(&tx);
+                let _span =
+                    tracing::debug_span!("simulate", transaction_hash=%hash, %transaction_idx)
+                        .entered();
+
+                let tx_type = transaction_type(&tx);
+                let tx_declared_deprecated_class_hash = transaction_declared_deprecated_class(&tx);
+
+                let mut tx_state = CachedState::<_>::create_transactional(&mut state);
+                let tx_info = tx
+                    .execute(&mut tx_state, &block_context, charge_fee, validate)
+                    .map_err(|e| TransactionExecutionError::ExecutionError {
+                        transaction_index: transaction_idx,
+                        error: e.to_string(),
+                    })?;
+                let state_diff = to_state_diff(&mut tx_state, tx_declared_deprecated_class_hash)?;
+                tx_state.commit();
+
+                (hash, tx_type, tx_info, state_diff)
+            }
+        };
+
+        written.extend(written_keys(&state_diff));
+        traces.push((hash, to_trace(tx_type, tx_info, state_diff)));
+    }
+
+    tracing::debug!(block=%block_hash, transactions=%traces.len(), %reexecuted, "Parallel trace finished");
+
+    cache.complete(block_hash, sender, &traces);
+    Ok(ParallelTrace { traces, reexecuted })
+}
+
+/// Executes all transactions on top of the initial state of the block using a pool of workers.
+///
+/// Returns [None] for transactions which could not be executed speculatively.
+fn speculate<T, F>(
+    execution_state: &ExecutionState<'_>,
+    storage: &pathfinder_storage::Storage,
+    concurrency: NonZeroUsize,
+    transactions: &[T],
+    compose: &F,
+    charge_fee: bool,
+    validate: bool,
+) -> Vec<Option<Speculation>>
+where
+    T: Sync,
+    F: Fn(&T, &pathfinder_storage::Transaction<'_>) -> anyhow::Result<Transaction> + Sync,
+{
+    let results = transactions
+        .iter()
+        .map(|_| Mutex::new(None))
+        .collect::<Vec<_>>();
+    let next = AtomicUsize::new(0);
+
+    let chain_id = execution_state.chain_id;
+    let header = &execution_state.header;
+    let pending_state = &execution_state.pending_state;
+    let execute_on_parent_state = execution_state.execute_on_parent_state;
+
+    let worker = || -> anyhow::Result<()> {
+        let mut connection = storage.connection()?;
+        let db = connection.transaction()?;
+        let mut execution_state = ExecutionState::new(
+            &db,
+            chain_id,
+            header.clone(),
+            pending_state.clone(),
+            execute_on_parent_state,
+        );
+        // The base state is only ever read from, so that it serves as a cache of the block's
+        // initial state for all transactions executed by this worker.
+        let (mut base_state, block_context) = execution_state.starknet_state()?;
+
+        loop {
+            let transaction_idx = next.fetch_add(1, Ordering::Relaxed);
+            let Some(input) = transactions.get(transaction_idx) else {
+                return Ok(());
+            };
+
+            let transaction = compose(input, &db)?;
+            let _span = tracing::debug_span!("speculate", transaction_hash=%transaction_hash(&transaction), %transaction_idx).entered();
+
+            // Declarations add classes to the state which cannot be transferred to another
+            // state, so these are always executed sequentially.
+            if matches!(transaction_type(&transaction), TransactionType::Declare) {
+                continue;
+            }
+
+            let speculation = execute_speculatively(
+                &mut base_state,
+                &block_context,
+                transaction,
+                charge_fee,
+                validate,
+            );
+            *results[transaction_idx].lock().unwrap() = Some(speculation);
+        }
+    };
+
+    let worker = &worker;
+    let workers = concurrency.get().min(transactions.len());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let span = tracing::Span::current();
+            scope.spawn(move || {
+                let _g = span.enter();
+                if let Err(error) = worker() {
+                    tracing::debug!(%error, "Speculative execution worker failed");
+                }
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap())
+        .collect()
+}
+
+fn execute_speculatively<S: StateReader>(
+    base_state: &mut S,
+    block_context: &BlockContext,
+    transaction: Transaction,
+    charge_fee: bool,
+    validate: bool,
+) -> Speculation {
+    let hash = transaction_hash(&transaction);
+    let transaction_type = transaction_type(&transaction);
+    let fee_type = fee_type(&transaction);
+    let declared_deprecated_class_hash = transaction_declared_deprecated_class(&transaction);
+
+    let mut tx_state = CachedState::new(
+        RecordingStateReader::new(base_state),
+        GlobalContractCache::default(),
+    );
+    let outcome = match transaction.execute(&mut tx_state, block_context, charge_fee, validate) {
+        Ok(tx_info) => to_state_diff(&mut tx_state, declared_deprecated_class_hash)
+            .ok()
+            .map(|state_diff| (tx_info, state_diff)),
+        Err(error) => {
+            tracing::trace!(%error, "Speculative execution failed");
+            None
+        }
+    };
+
+    Speculation {
+        hash,
+        transaction_type,
+        fee_type,
+        reads: std::mem::take(&mut tx_state.state.reads),
+        outcome,
+    }
+}
+
+/// Validates a speculative execution against the sequential state, which includes the writes of
+/// all preceding transactions.
+///
+/// If the speculation is valid its writes are applied to `state` and its (possibly rebased)
+/// result is returned. Returns [None] if the transaction has to be re-executed.
+fn accept<S: StateReader>(
+    state: &mut CachedState<S>,
+    block_context: &BlockContext,
+    written: &HashSet<StateKey>,
+    speculation: Speculation,
+) -> Result<
+    Option<(
+        TransactionHash,
+        TransactionType,
+        TransactionExecutionInfo,
+        StateDiff,
+    )>,
+    TransactionExecutionError,
+> {
+    let Speculation {
+        hash,
+        transaction_type,
+        fee_type,
+        reads,
+        outcome,
+    } = speculation;
+
+    let Some((tx_info, mut state_diff)) = outcome else {
+        return Ok(None);
+    };
+
+    let fee_token_address = match fee_type {
+        FeeType::Eth => block_context.fee_token_addresses.eth_fee_token_address,
+        FeeType::Strk => block_context.fee_token_addresses.strk_fee_token_address,
+    };
+    let fee_token_address = ContractAddress::new_or_panic(fee_token_address.0.key().into_felt());
+    let sequencer_address =
+        ContractAddress::new_or_panic(block_context.sequencer_address.0.key().into_felt());
+    let balance_low = StorageAddress::from_map_name_and_key(b"ERC20_balances", sequencer_address.0);
+    let balance_high = StorageAddress::new_or_panic(balance_low.0 + Felt::from(1u64));
+    let balance_keys = [
+        StateKey::Storage(fee_token_address, balance_low),
+        StateKey::Storage(fee_token_address, balance_high),
+    ];
+
+    let mut rebase_fee_balance = false;
+    for (key, value) in &reads {
+        let conflicts = match current_value(state, key)? {
+            Some(current) => current != *value,
+            // Classes are only ever added to the state, and only by declarations. These are
+            // never accepted speculatively, so a preceding one must have added the class.
+            None => written.contains(key),
+        };
+        if !conflicts {
+            continue;
+        }
+        if !balance_keys.contains(key) {
+            tracing::trace!(
+                ?key,
+                "Speculative execution conflicts with a preceding transaction"
+            );
+            return Ok(None);
+        }
+        rebase_fee_balance = true;
+    }
+
+    if rebase_fee_balance {
+        let fee_transfer_only = only_fee_transfer_accessed(
+            &tx_info,
+            fee_token_address,
+            sequencer_address,
+            [balance_low, balance_high],
+        );
+        if !fee_transfer_only {
+            tracing::trace!("Sequencer balance was accessed outside of the fee transfer");
+            return Ok(None);
+        }
+
+        let read = |key: StorageAddress| {
+            reads
+                .get(&StateKey::Storage(fee_token_address, key))
+                .copied()
+                .unwrap_or_default()
+        };
+        let current = |state: &mut CachedState<S>, key: StorageAddress| -> StateResult<Felt> {
+            Ok(state
+                .get_storage_at(
+                    starknet_api::core::ContractAddress(PatriciaKey::try_from(
+                        fee_token_address.0.into_starkfelt(),
+                    )?),
+                    starknet_api::state::StorageKey(PatriciaKey::try_from(key.0.into_starkfelt())?),
+                )?
+                .into_felt())
+        };
+
+        let speculative_read = to_u256(read(balance_low), read(balance_high));
+        let actual_read = to_u256(current(state, balance_low)?, current(state, balance_high)?);
+
+        let diffs = state_diff
+            .storage_diffs
+            .get_mut(&fee_token_address)
+            .map(|diffs| diffs.as_mut_slice())
+            .unwrap_or_default();
+        let written_value = |key: StorageAddress, default: Felt| {
+            diffs
+                .iter()
+                .find(|diff| diff.key == key)
+                .map(|diff| diff.value.0)
+                .unwrap_or(default)
+        };
+        let speculative_written = to_u256(
+            written_value(balance_low, read(balance_low)),
+            written_value(balance_high, read(balance_high)),
+        );
+
+        let Some(fee) = speculative_written.checked_sub(speculative_read) else {
+            return Ok(None);
+        };
+        let Some(actual_written) = actual_read.checked_add(fee) else {
+            return Ok(None);
+        };
+
+        // The cost of the addition depends on whether the low word overflows, so the rebased
+        // result is only valid if the carry is the same as during the speculative execution.
+        let speculative_carry = high(speculative_written) - high(speculative_read);
+        let actual_carry = high(actual_written) - high(actual_read);
+        if speculative_carry != actual_carry {
+            tracing::trace!("Sequencer balance carry differs from speculative execution");
+            return Ok(None);
+        }
+
+        for diff in diffs.iter_mut() {
+            if diff.key == balance_low {
+                diff.value = StorageValue(Felt::from(actual_written.low_u128()));
+            } else if diff.key == balance_high {
+                diff.value = StorageValue(Felt::from(high(actual_written)));
+            }
+        }
+    }
+
+    // Nonces can only be incremented, so make sure that the write is exactly that.
+    for (address, nonce) in &state_diff.nonces {
+        let Some(read) = reads.get(&StateKey::Nonce(*address)) else {
+            return Ok(None);
+        };
+        if nonce.0 != *read + Felt::from(1u64) {
+            return Ok(None);
+        }
+    }
+
+    if !state_diff.declared_classes.is_empty() || !state_diff.deprecated_declared_classes.is_empty()
+    {
+        return Ok(None);
+    }
+
+    apply(state, &state_diff)?;
+
+    Ok(Some((hash, transaction_type, tx_info, state_diff)))
+}
+
+/// Reads the value of a storage item, nonce or class hash from `state`.
+///
+/// Returns [None] for compiled classes, which have no value to compare.
+fn current_value<S: StateReader>(
+    state: &mut CachedState<S>,
+    key: &StateKey,
+) -> StateResult<Option<Felt>> {
+    let contract_address = |address: &ContractAddress| {
+        PatriciaKey::try_from(address.0.into_starkfelt()).map(starknet_api::core::ContractAddress)
+    };
+
+    let value = match key {
+        StateKey::Storage(address, key) => state
+            .get_storage_at(
+                contract_address(address)?,
+                starknet_api::state::StorageKey(PatriciaKey::try_from(key.0.into_starkfelt())?),
+            )?
+            .into_felt(),
+        StateKey::Nonce(address) => state
+            .get_nonce_at(contract_address(address)?)?
+            .0
+            .into_felt(),
+        StateKey::ClassHash(address) => state
+            .get_class_hash_at(contract_address(address)?)?
+            .0
+            .into_felt(),
+        StateKey::CompiledClass(_) => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+/// Returns true if the sequencer's fee token balance was accessed by no call other than the
+/// fee transfer from an account other than the sequencer.
+fn only_fee_transfer_accessed(
+    tx_info: &TransactionExecutionInfo,
+    fee_token_address: ContractAddress,
+    sequencer_address: ContractAddress,
+    balance_keys: [StorageAddress; 2],
+) -> bool {
+    let Some(fee_transfer) = &tx_info.fee_transfer_call_info else {
+        return false;
+    };
+    if fee_transfer.call.caller_address.0.key().into_felt() == sequencer_address.0 {
+        return false;
+    }
+
+    let accesses_balance = |call_info: &CallInfo| {
+        call_info.into_iter().any(|call| {
+            call.call.storage_address.0.key().into_felt() == fee_token_address.0
+                && call.accessed_storage_keys.iter().any(|key| {
+                    balance_keys
+                        .iter()
+                        .any(|balance_key| key.0.key().into_felt() == balance_key.0)
+                })
+        })
+    };
+
+    !tx_info.validate_call_info.iter().any(accesses_balance)
+        && !tx_info.execute_call_info.iter().any(accesses_balance)
+}
+
+fn apply<S: StateReader>(
+    state: &mut CachedState<S>,
+    state_diff: &StateDiff,
+) -> Result<(), TransactionExecutionError> {
+    let contract_address = |address: ContractAddress| {
+        PatriciaKey::try_from(address.0.into_starkfelt()).map(starknet_api::core::ContractAddress)
+    };
+
+    for (address, diffs) in &state_diff.storage_diffs {
+        for diff in diffs {
+            state.set_storage_at(
+                contract_address(*address)?,
+                starknet_api::state::StorageKey(PatriciaKey::try_from(
+                    diff.key.0.into_starkfelt(),
+                )?),
+                diff.value.0.into_starkfelt(),
+            );
+        }
+    }
+
+    for address in state_diff.nonces.keys() {
+        state.increment_nonce(contract_address(*address)?)?;
+    }
+
+    let class_updates = state_diff
+        .deployed_contracts
+        .iter()
+        .map(|deployed| (deployed.address, deployed.class_hash))
+        .chain(
+            state_diff
+                .replaced_classes
+                .iter()
+                .map(|replaced| (replaced.contract_address, replaced.class_hash)),
+        );
+    for (address, class_hash) in class_updates {
+        state.set_class_hash_at(
+            contract_address(address)?,
+            starknet_api::core::ClassHash(class_hash.0.into_starkfelt()),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn written_keys(state_diff: &StateDiff) -> impl Iterator<Item = StateKey> + '_ {
+    let storage = state_diff
+        .storage_diffs
+        .iter()
+        .flat_map(|(address, diffs)| {
+            diffs
+                .iter()
+                .map(|diff| StateKey::Storage(*address, diff.key))
+        });
+    let nonces = state_diff
+        .nonces
+        .keys()
+        .map(|address| StateKey::Nonce(*address));
+    let deployed = state_diff
+        .deployed_contracts
+        .iter()
+        .map(|deployed| StateKey::ClassHash(deployed.address));
+    let replaced = state_diff
+        .replaced_classes
+        .iter()
+        .map(|replaced| StateKey::ClassHash(replaced.contract_address));
+    let declared = state_diff
+        .declared_classes
+        .iter()
+        .map(|declared| StateKey::CompiledClass(ClassHash(declared.class_hash.0)))
+        .chain(
+            state_diff
+                .deprecated_declared_classes
+                .iter()
+                .map(|class_hash| StateKey::CompiledClass(*class_hash)),
+        );
+
+    storage
+        .chain(nonces)
+        .chain(deployed)
+        .chain(replaced)
+        .chain(declared)
+}
+
+fn to_u256(low: Felt, high: Felt) -> U256 {
+    (U256::from_big_endian(high.as_be_bytes()) << 128) + U256::from_big_endian(low.as_be_bytes())
+}
+
+fn high(value: U256) -> u128 {
+    (value >> 128).low_u128()
+}
+
+/// A [StateReader] which records all state items read through it.
+struct RecordingStateReader<'a, S: StateReader> {
+    state: &'a mut S,
+    reads: HashMap<StateKey, Felt>,
+}
+
+impl<'a, S: StateReader> RecordingStateReader<'a, S> {
+    fn new(state: &'a mut S) -> Self {
+        Self {
+            state,
+            reads: Default::default(),
+        }
+    }
+}
+
+impl<S: StateReader> StateReader for RecordingStateReader<'_, S> {
+    fn get_storage_at(
+        &mut self,
+        contract_address: starknet_api::core::ContractAddress,
+        key: starknet_api::state::StorageKey,
+    ) -> StateResult<StarkFelt> {
+        let value = self.state.get_storage_at(contract_address, key)?;
+        self.reads.insert(
+            StateKey::Storage(
+                ContractAddress::new_or_panic(contract_address.0.key().into_felt()),
+                StorageAddress::new_or_panic(key.0.key().into_felt()),
+            ),
+            value.into_felt(),
+        );
+        Ok(value)
+    }
+
+    fn get_nonce_at(
+        &mut self,
+        contract_address: starknet_api::core::ContractAddress,
+    ) -> StateResult<starknet_api::core::Nonce> {
+        let nonce = self.state.get_nonce_at(contract_address)?;
+        self.reads.insert(
+            StateKey::Nonce(ContractAddress::new_or_panic(
+                contract_address.0.key().into_felt(),
+            )),
+            nonce.0.into_felt(),
+        );
+        Ok(nonce)
+    }
+
+    fn get_class_hash_at(
+        &mut self,
+        contract_address: starknet_api::core::ContractAddress,
+    ) -> StateResult<starknet_api::core::ClassHash> {
+        let class_hash = self.state.get_class_hash_at(contract_address)?;
+        self.reads.insert(
+            StateKey::ClassHash(ContractAddress::new_or_panic(
+                contract_address.0.key().into_felt(),
+            )),
+            class_hash.0.into_felt(),
+        );
+        Ok(class_hash)
+    }
+
+    fn get_compiled_contract_class(
+        &mut self,
+        class_hash: &starknet_api::core::ClassHash,
+    ) -> StateResult<blockifier::execution::contract_class::ContractClass> {
+        // Record the attempt even if it fails: the class might be declared by a preceding
+        // transaction of the block.
+        self.reads.insert(
+            StateKey::CompiledClass(ClassHash(class_hash.0.into_felt())),
+            Felt::ZERO,
+        );
+        self.state.get_compiled_contract_class(class_hash)
+    }
+
+    fn get_compiled_class_hash(
+        &mut self,
+        class_hash: starknet_api::core::ClassHash,
+    ) -> StateResult<starknet_api::core::CompiledClassHash> {
+        self.reads.insert(
+            StateKey::CompiledClass(ClassHash(class_hash.0.into_felt())),
+            Felt::ZERO,
+        );
+        self.state.get_compiled_class_hash(class_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DeployedContract, StorageDiff};
+    use pathfinder_common::{class_hash, contract_address, contract_nonce, felt, storage_address};
+
+    #[test]
+    fn written_keys_cover_the_whole_state_diff() {
+        let state_diff = StateDiff {
+            storage_diffs: [(
+                contract_address!("0x1"),
+                vec![StorageDiff {
+                    key: storage_address!("0x2"),
+                    value: StorageValue(felt!("0x3")),
+                }],
+            )]
+            .into(),
+            deployed_contracts: vec![DeployedContract {
+                address: contract_address!("0x4"),
+                class_hash: class_hash!("0x5"),
+            }],
+            deprecated_declared_classes: [class_hash!("0x6")].into(),
+            declared_classes: vec![],
+            nonces: [(contract_address!("0x7"), contract_nonce!("0x8"))].into(),
+            replaced_classes: vec![],
+        };
+
+        let keys = written_keys(&state_diff).collect::<HashSet<_>>();
+        let expected = [
+            StateKey::Storage(contract_address!("0x1"), storage_address!("0x2")),
+            StateKey::ClassHash(contract_address!("0x4")),
+            StateKey::CompiledClass(class_hash!("0x6")),
+            StateKey::Nonce(contract_address!("0x7")),
+        ]
+        .into();
+
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn u256_from_felts() {
+        let value = to_u256(Felt::from(u128::MAX), Felt::from(2u64));
+        assert_eq!(value.low_u128(), u128::MAX);
+        assert_eq!(high(value), 2);
+    }
+}
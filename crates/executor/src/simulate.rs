@@ -41,7 +41,7 @@ enum CacheItem {
 #[derive(Debug, Clone)]
 pub struct TraceCache(Arc<Mutex<SizedCache<BlockHash, CacheItem>>>);
 
-type Traces = Vec<(TransactionHash, TransactionTrace)>;
+pub(crate) type Traces = Vec<(TransactionHash, TransactionTrace)>;
 
 impl Default for TraceCache {
     fn default() -> Self {
@@ -133,28 +133,9 @@ pub fn trace(
 ) -> Result<Vec<(TransactionHash, TransactionTrace)>, TransactionExecutionError> {
     let (mut state, block_context) = execution_state.starknet_state()?;
 
-    let sender = {
-        let mut cache = cache.0.lock().unwrap();
-        match cache.cache_get(&block_hash) {
-            Some(CacheItem::Cached(cached)) => {
-                tracing::trace!(block=%block_hash, "trace cache hit");
-                return Ok(cached.clone());
-            }
-            Some(CacheItem::Inflight(receiver)) => {
-                tracing::trace!(block=%block_hash, "trace already inflight");
-                let mut receiver = receiver.resubscribe();
-                drop(cache);
-
-                let trace = receiver.blocking_recv().context("Trace error")?;
-                return Ok(trace);
-            }
-            None => {
-                tracing::trace!(block=%block_hash, "trace cache miss");
-                let (sender, receiver) = tokio::sync::broadcast::channel(1);
-                cache.cache_set(block_hash, CacheItem::Inflight(receiver));
-                sender
-            }
-        }
+    let sender = match cache.get_or_reserve(block_hash)? {
+        CacheLookup::Hit(traces) => return Ok(traces),
+        CacheLookup::Miss(sender) => sender,
     };
 
     tracing::trace!(block=%block_hash, "trace cache miss");
@@ -180,21 +161,68 @@ pub fn trace(
         traces.push((hash, trace));
     }
 
-    // Lock the cache before sending to avoid race conditions between senders and receivers.
-    let mut cache = cache.0.lock().unwrap();
-    let _ = sender.send(traces.clone());
-    cache.cache_set(block_hash, CacheItem::Cached(traces.clone()));
+    cache.complete(block_hash, sender, &traces);
     Ok(traces)
 }
 
-enum TransactionType {
+pub(crate) enum CacheLookup {
+    Hit(Traces),
+    Miss(tokio::sync::broadcast::Sender<Traces>),
+}
+
+impl TraceCache {
+    /// Returns the cached traces of the block, waiting for them if they are being computed
+    /// by someone else. Otherwise marks the block as inflight and returns the sender which
+    /// must be passed to [TraceCache::complete] once the traces are available.
+    pub(crate) fn get_or_reserve(
+        &self,
+        block_hash: BlockHash,
+    ) -> Result<CacheLookup, TransactionExecutionError> {
+        let mut cache = self.0.lock().unwrap();
+        match cache.cache_get(&block_hash) {
+            Some(CacheItem::Cached(cached)) => {
+                tracing::trace!(block=%block_hash, "trace cache hit");
+                Ok(CacheLookup::Hit(cached.clone()))
+            }
+            Some(CacheItem::Inflight(receiver)) => {
+                tracing::trace!(block=%block_hash, "trace already inflight");
+                let mut receiver = receiver.resubscribe();
+                drop(cache);
+
+                let trace = receiver.blocking_recv().context("Trace error")?;
+                Ok(CacheLookup::Hit(trace))
+            }
+            None => {
+                tracing::trace!(block=%block_hash, "trace cache miss");
+                let (sender, receiver) = tokio::sync::broadcast::channel(1);
+                cache.cache_set(block_hash, CacheItem::Inflight(receiver));
+                Ok(CacheLookup::Miss(sender))
+            }
+        }
+    }
+
+    pub(crate) fn complete(
+        &self,
+        block_hash: BlockHash,
+        sender: tokio::sync::broadcast::Sender<Traces>,
+        traces: &Traces,
+    ) {
+        // Lock the cache before sending to avoid race conditions between senders and receivers.
+        let mut cache = self.0.lock().unwrap();
+        let _ = sender.send(traces.clone());
+        cache.cache_set(block_hash, CacheItem::Cached(traces.clone()));
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum TransactionType {
     Declare,
     DeployAccount,
     Invoke,
     L1Handler,
 }
 
-fn transaction_type(transaction: &Transaction) -> TransactionType {
+pub(crate) fn transaction_type(transaction: &Transaction) -> TransactionType {
     match transaction {
         Transaction::AccountTransaction(tx) => match tx {
             blockifier::transaction::account_transaction::AccountTransaction::Declare(_) => {
@@ -211,7 +239,9 @@ fn transaction_type(transaction: &Transaction) -> TransactionType {
     }
 }
 
-fn transaction_declared_deprecated_class(transaction: &Transaction) -> Option<ClassHash> {
+pub(crate) fn transaction_declared_deprecated_class(
+    transaction: &Transaction,
+) -> Option<ClassHash> {
     match transaction {
         Transaction::AccountTransaction(
             blockifier::transaction::account_transaction::AccountTransaction::Declare(tx),
@@ -227,7 +257,7 @@ fn transaction_declared_deprecated_class(transaction: &Transaction) -> Option<Cl
     }
 }
 
-fn to_state_diff<S: blockifier::state::state_api::StateReader>(
+pub(crate) fn to_state_diff<S: blockifier::state::state_api::StateReader>(
     state: &mut blockifier::state::cached_state::CachedState<S>,
     old_declared_contract: Option<ClassHash>,
 ) -> Result<StateDiff, StateError> {
@@ -303,7 +333,7 @@ fn to_state_diff<S: blockifier::state::state_api::StateReader>(
     })
 }
 
-fn to_trace(
+pub(crate) fn to_trace(
     transaction_type: TransactionType,
     execution_info: blockifier::transaction::objects::TransactionExecutionInfo,
     state_diff: StateDiff,
@@ -167,7 +167,43 @@ pub struct ReplacedClass {
     pub class_hash: ClassHash,
 }
 
+/// The outcome of estimating a single transaction with [`crate::estimate_each`].
+#[derive(Debug)]
+pub enum TransactionEstimate {
+    Success {
+        fee_estimate: FeeEstimate,
+        resources: Box<ResourcesBreakdown>,
+    },
+    Failure(TransactionFailure),
+}
+
+/// Execution resources used by each stage of a transaction.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ResourcesBreakdown {
+    pub validate: Option<ExecutionResources>,
+    pub execute: Option<ExecutionResources>,
+    /// The resources of the validate and execute calls combined. This excludes the resources
+    /// the OS spends on the transaction itself, which are only reflected in the fee estimate.
+    pub calls: ExecutionResources,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TransactionFailure {
+    pub stage: FailureStage,
+    pub error: String,
+    /// The contracts on the failing call stack, outermost first.
+    pub call_path: Vec<ContractAddress>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FailureStage {
+    Validate,
+    Execute,
+    /// Checks which happen outside of the account's code, e.g. nonce or balance checks.
+    Other,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct ExecutionResources {
     pub steps: usize,
     pub memory_holes: usize,
@@ -181,6 +217,28 @@ pub struct ExecutionResources {
     pub segment_arena_builtin: usize,
 }
 
+impl std::ops::AddAssign<&ExecutionResources> for ExecutionResources {
+    fn add_assign(&mut self, rhs: &ExecutionResources) {
+        self.steps += rhs.steps;
+        self.memory_holes += rhs.memory_holes;
+        self.range_check_builtin_applications += rhs.range_check_builtin_applications;
+        self.pedersen_builtin_applications += rhs.pedersen_builtin_applications;
+        self.poseidon_builtin_applications += rhs.poseidon_builtin_applications;
+        self.ec_op_builtin_applications += rhs.ec_op_builtin_applications;
+        self.ecdsa_builtin_applications += rhs.ecdsa_builtin_applications;
+        self.bitwise_builtin_applications += rhs.bitwise_builtin_applications;
+        self.keccak_builtin_applications += rhs.keccak_builtin_applications;
+        self.segment_arena_builtin += rhs.segment_arena_builtin;
+    }
+}
+
+impl From<&blockifier::execution::call_info::CallInfo> for ExecutionResources {
+    /// The resources of the call, which already include those of its inner calls.
+    fn from(call_info: &blockifier::execution::call_info::CallInfo) -> Self {
+        call_info.vm_resources.clone().into()
+    }
+}
+
 impl From<blockifier::execution::call_info::CallInfo> for FunctionInvocation {
     fn from(call_info: blockifier::execution::call_info::CallInfo) -> Self {
         let messages = ordered_l2_to_l1_messages(&call_info);
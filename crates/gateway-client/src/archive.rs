@@ -0,0 +1,319 @@
+//! Recording of feeder gateway traffic into an archive, and replaying it from there.
+//!
+//! The archive is a file with one JSON [Entry] per line, holding a feeder gateway response
+//! along with the request it answered and when it was received. Replaying an archive serves
+//! the responses to each request in their recorded order and with their original timing, which
+//! allows reproducing sync issues such as reorgs or stale pending data deterministically.
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use reqwest::Url;
+use starknet_gateway_types::error::SequencerError;
+use tokio::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Entry {
+    /// Milliseconds since the recording started.
+    pub elapsed_ms: u64,
+    /// The gateway method and its query, e.g. `get_block?blockNumber=pending`.
+    pub request: String,
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum Archive {
+    Record(Recorder),
+    Replay(Replay),
+}
+
+impl Archive {
+    /// Sends `request` to `url`, recording its response or replaying it from the archive instead.
+    pub async fn send(
+        archive: Option<&Self>,
+        url: &Url,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, SequencerError> {
+        match archive {
+            None => Ok(request.send().await?),
+            Some(Archive::Record(recorder)) => {
+                let response = request.send().await?;
+                recorder.record(url, response).await
+            }
+            Some(Archive::Replay(replay)) => Ok(replay.respond(url).await),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    start: Instant,
+    /// Entries are written by a dedicated thread, as file IO would otherwise block the runtime.
+    entries: Option<std::sync::mpsc::Sender<Entry>>,
+    writer: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// Fails if a file already exists at `path`, so that an earlier recording is not lost.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .with_context(|| format!("Creating gateway archive at {}", path.display()))?;
+
+        let (entries, rx) = std::sync::mpsc::channel();
+        let writer = std::thread::Builder::new()
+            .name("gateway-archive".to_owned())
+            .spawn(move || write_entries(std::io::BufWriter::new(file), rx))
+            .context("Spawning gateway archive writer")?;
+
+        Ok(Self {
+            start: Instant::now(),
+            entries: Some(entries),
+            writer: Some(writer),
+        })
+    }
+
+    /// Appends the response to the archive and returns an equivalent one, as its body has to be
+    /// consumed for this.
+    async fn record(
+        &self,
+        url: &Url,
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, SequencerError> {
+        let status = response.status();
+        let body = response.bytes().await?;
+
+        match std::str::from_utf8(&body) {
+            Ok(text) => {
+                let entry = Entry {
+                    elapsed_ms: self.start.elapsed().as_millis() as u64,
+                    request: request_key(url),
+                    status: status.as_u16(),
+                    body: text.to_owned(),
+                };
+                let sent = self
+                    .entries
+                    .as_ref()
+                    .map(|entries| entries.send(entry).is_ok())
+                    .unwrap_or_default();
+                if !sent {
+                    tracing::warn!(%url, "Gateway archive writer has stopped, not recording response");
+                }
+            }
+            Err(_) => {
+                tracing::warn!(%url, "Not recording gateway response which is not valid UTF-8")
+            }
+        }
+
+        Ok(response_from_parts(status, body))
+    }
+}
+
+impl Drop for Recorder {
+    /// Waits for the pending entries to be written, so that the archive is complete once the
+    /// client is gone.
+    fn drop(&mut self) {
+        // Closing the channel stops the writer once it has drained it.
+        self.entries.take();
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                tracing::warn!("Gateway archive writer panicked");
+            }
+        }
+    }
+}
+
+fn write_entries(
+    mut file: std::io::BufWriter<std::fs::File>,
+    entries: std::sync::mpsc::Receiver<Entry>,
+) {
+    let mut write = |entry: &Entry| -> anyhow::Result<()> {
+        serde_json::to_writer(&mut file, entry).context("Serializing entry")?;
+        file.write_all(b"\n").context("Writing entry")?;
+        // Flush each entry so that the archive is complete even if the node is killed.
+        file.flush().context("Flushing archive")
+    };
+
+    for entry in entries {
+        if let Err(e) = write(&entry) {
+            tracing::warn!(error=%e, request=%entry.request, "Failed to record gateway response");
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Replay {
+    start: Instant,
+    /// The responses which have not been served yet, in recorded order per request.
+    responses: Mutex<HashMap<String, VecDeque<Entry>>>,
+    /// The last response served for each request, which is repeated once its responses run out.
+    last: Mutex<HashMap<String, Entry>>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Opening gateway archive at {}", path.display()))?;
+
+        let mut responses = HashMap::<_, VecDeque<_>>::new();
+        for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line.context("Reading gateway archive")?;
+            let entry = serde_json::from_str::<Entry>(&line)
+                .with_context(|| format!("Parsing gateway archive line {}", i + 1))?;
+            responses
+                .entry(entry.request.clone())
+                .or_default()
+                .push_back(entry);
+        }
+
+        Ok(Self {
+            start: Instant::now(),
+            responses: Mutex::new(responses),
+            last: Default::default(),
+        })
+    }
+
+    /// Returns the next recorded response for the request, once as much time has passed since
+    /// the replay started as had passed since the recording started when it was received.
+    ///
+    /// Requests which were never recorded are answered with `404 Not Found`.
+    async fn respond(&self, url: &Url) -> reqwest::Response {
+        let request = request_key(url);
+
+        let next = self
+            .responses
+            .lock()
+            .unwrap()
+            .get_mut(&request)
+            .and_then(VecDeque::pop_front);
+
+        let entry = match next {
+            Some(entry) => {
+                tokio::time::sleep_until(self.start + Duration::from_millis(entry.elapsed_ms))
+                    .await;
+                self.last
+                    .lock()
+                    .unwrap()
+                    .insert(request.clone(), entry.clone());
+                entry
+            }
+            None => match self.last.lock().unwrap().get(&request) {
+                Some(entry) => entry.clone(),
+                None => {
+                    tracing::warn!(%request, "Request not found in gateway archive");
+                    return response_from_parts(
+                        reqwest::StatusCode::NOT_FOUND,
+                        bytes::Bytes::new(),
+                    );
+                }
+            },
+        };
+
+        let status = reqwest::StatusCode::from_u16(entry.status)
+            .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+        response_from_parts(status, entry.body.into())
+    }
+}
+
+/// Identifies a request independently of the feeder gateway it was sent to.
+fn request_key(url: &Url) -> String {
+    let method = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+
+    match url.query() {
+        Some(query) => format!("{method}?{query}"),
+        None => method.to_owned(),
+    }
+}
+
+fn response_from_parts(status: reqwest::StatusCode, body: bytes::Bytes) -> reqwest::Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    response.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(elapsed_ms: u64, request: &str, body: &str) -> Entry {
+        Entry {
+            elapsed_ms,
+            request: request.to_owned(),
+            status: 200,
+            body: body.to_owned(),
+        }
+    }
+
+    fn archive(dir: &tempfile::TempDir, entries: &[Entry]) -> std::path::PathBuf {
+        let path = dir.path().join("archive.jsonl");
+
+        let lines = entries
+            .iter()
+            .map(|e| serde_json::to_string(e).unwrap() + "\n")
+            .collect::<String>();
+        std::fs::write(&path, lines).unwrap();
+        path
+    }
+
+    async fn text(replay: &Replay, url: &str) -> (reqwest::StatusCode, String) {
+        let response = replay.respond(&Url::parse(url).unwrap()).await;
+        (response.status(), response.text().await.unwrap())
+    }
+
+    #[test]
+    fn recorder_refuses_to_overwrite_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = archive(&dir, &[entry(0, "get_block?blockNumber=pending", "first")]);
+        let before = std::fs::read(&path).unwrap();
+
+        Recorder::create(&path).unwrap_err();
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+    }
+
+    #[test]
+    fn request_key_ignores_gateway() {
+        let a = Url::parse("https://a/feeder_gateway/get_block?blockNumber=pending").unwrap();
+        let b = Url::parse("http://b:9545/feeder_gateway/get_block?blockNumber=pending").unwrap();
+
+        assert_eq!(request_key(&a), "get_block?blockNumber=pending");
+        assert_eq!(request_key(&a), request_key(&b));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replay_order_and_timing() {
+        const PENDING: &str = "http://gateway/feeder_gateway/get_block?blockNumber=pending";
+        const LATEST: &str = "http://gateway/feeder_gateway/get_block?blockNumber=latest";
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = archive(
+            &dir,
+            &[
+                entry(0, "get_block?blockNumber=pending", "first"),
+                entry(10, "get_block?blockNumber=latest", "latest"),
+                entry(2000, "get_block?blockNumber=pending", "second"),
+            ],
+        );
+        let replay = Replay::open(&path).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(text(&replay, PENDING).await.1, "first");
+        assert_eq!(text(&replay, LATEST).await.1, "latest");
+        assert_eq!(text(&replay, PENDING).await.1, "second");
+        assert_eq!(start.elapsed(), Duration::from_millis(2000));
+
+        // The last response is repeated without delay once they run out.
+        assert_eq!(text(&replay, PENDING).await.1, "second");
+        assert_eq!(start.elapsed(), Duration::from_millis(2000));
+
+        let (status, _) = text(&replay, "http://gateway/feeder_gateway/get_state_update").await;
+        assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+    }
+}
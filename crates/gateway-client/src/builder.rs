@@ -9,8 +9,10 @@
 //!   2. [Method](stage::Method) where you select the REST API method.
 //!   3. [Params](stage::Params) where you select the retry behavior.
 //!   4. [Final](stage::Final) where you select the REST operation type, which is then executed.
+use crate::archive::Archive;
 use crate::metrics::{with_metrics, BlockTag, RequestMetadata};
-use pathfinder_common::{BlockId, ClassHash, TransactionHash};
+use crate::pool::FeederGatewayPool;
+use pathfinder_common::{BlockId, BlockNumber, ClassHash, TransactionHash};
 use starknet_gateway_types::error::SequencerError;
 
 const X_THROTTLING_BYPASS: &str = "X-Throttling-Bypass";
@@ -18,9 +20,19 @@ const X_THROTTLING_BYPASS: &str = "X-Throttling-Bypass";
 /// A Sequencer Request builder.
 pub struct Request<'a, S: RequestState> {
     state: S,
-    url: reqwest::Url,
+    /// The request is sent to one of these URLs, failing over to the next on errors.
+    urls: Vec<reqwest::Url>,
+    /// The index of the URL which is tried first.
+    first: usize,
+    /// Set for requests of data which a gateway that lags behind may not have yet. These also
+    /// fail over on Starknet errors saying that the block or class does not exist.
+    fail_over_if_missing: bool,
+    /// Is told which URL answered, so that it can fail over for subsequent requests.
+    pool: Option<&'a FeederGatewayPool>,
     api_key: Option<String>,
     client: &'a reqwest::Client,
+    /// Records the responses, or replays them instead of sending the request.
+    archive: Option<&'a Archive>,
 }
 
 pub mod stage {
@@ -73,9 +85,35 @@ impl<'a> Request<'a, stage::Init> {
         api_key: Option<String>,
     ) -> Request<'a, stage::Method> {
         Request {
-            url,
+            urls: vec![url],
+            first: 0,
+            fail_over_if_missing: false,
+            pool: None,
             client,
             api_key,
+            archive: None,
+            state: stage::Method,
+        }
+    }
+
+    /// Initialize a [Request] builder which is sent to the primary gateway of `pool`, and fails
+    /// over to the following gateways on errors which are not Starknet specific.
+    ///
+    /// Requests for a block by number are sent to the gateway which serves that block instead,
+    /// see [FeederGatewayPool::for_block].
+    pub(crate) fn with_failover(
+        client: &'a reqwest::Client,
+        pool: &'a FeederGatewayPool,
+        api_key: Option<String>,
+    ) -> Request<'a, stage::Method> {
+        Request {
+            urls: pool.urls().to_vec(),
+            first: pool.primary(),
+            fail_over_if_missing: false,
+            pool: Some(pool),
+            client,
+            api_key,
+            archive: None,
             state: stage::Method,
         }
     }
@@ -142,17 +180,28 @@ impl<'a> Request<'a, stage::Method> {
         get_signature,
     );
 
+    /// Records the responses to the request in `archive`, or replays them from it.
+    pub(crate) fn with_archive(mut self, archive: Option<&'a Archive>) -> Self {
+        self.archive = archive;
+        self
+    }
+
     /// Appends the given method to the request url.
     fn with_method(mut self, method: &'static str) -> Request<'a, stage::Params> {
-        self.url
-            .path_segments_mut()
-            .expect("Base URL is valid")
-            .push(method);
+        for url in &mut self.urls {
+            url.path_segments_mut()
+                .expect("Base URL is valid")
+                .push(method);
+        }
 
         Request {
-            url: self.url,
+            urls: self.urls,
+            first: self.first,
+            fail_over_if_missing: self.fail_over_if_missing,
+            pool: self.pool,
             client: self.client,
             api_key: self.api_key,
+            archive: self.archive,
             state: stage::Params {
                 meta: RequestMetadata::new(method),
             },
@@ -177,11 +226,32 @@ impl<'a> Request<'a, stage::Params> {
             BlockId::Pending => ("blockNumber", Cow::from("pending"), BlockTag::Pending),
         };
 
-        self.update_tag(tag).add_param(name, &value)
+        let request = match block {
+            BlockId::Number(number) => self.for_block(number),
+            BlockId::Hash(_) => self.fail_over_if_missing(),
+            BlockId::Latest | BlockId::Pending => self,
+        };
+        request.update_tag(tag).add_param(name, &value)
     }
 
+    /// Classes are requested from the primary, like the pending block which may declare them.
     pub fn with_class_hash(self, class_hash: ClassHash) -> Self {
-        self.add_param("classHash", &class_hash.0.to_hex_str())
+        self.fail_over_if_missing()
+            .add_param("classHash", &class_hash.0.to_hex_str())
+    }
+
+    /// Sends the request to the gateway of the pool which serves block `number`, rather than to
+    /// its primary.
+    fn for_block(mut self, number: BlockNumber) -> Self {
+        if let Some(pool) = self.pool {
+            self.first = pool.for_block(number);
+        }
+        self.fail_over_if_missing()
+    }
+
+    fn fail_over_if_missing(mut self) -> Self {
+        self.fail_over_if_missing = true;
+        self
     }
 
     pub fn with_optional_token(self, token: Option<&str>) -> Self {
@@ -196,7 +266,9 @@ impl<'a> Request<'a, stage::Params> {
     }
 
     pub fn add_param(mut self, name: &str, value: &str) -> Self {
-        self.url.query_pairs_mut().append_pair(name, value);
+        for url in &mut self.urls {
+            url.query_pairs_mut().append_pair(name, value);
+        }
         self
     }
 
@@ -208,9 +280,13 @@ impl<'a> Request<'a, stage::Params> {
     /// Sets the request retry behavior.
     pub fn with_retry(self, retry: bool) -> Request<'a, stage::Final> {
         Request {
-            url: self.url,
+            urls: self.urls,
+            first: self.first,
+            fail_over_if_missing: self.fail_over_if_missing,
+            pool: self.pool,
             client: self.client,
             api_key: self.api_key,
+            archive: self.archive,
             state: stage::Final {
                 meta: self.state.meta,
                 retry,
@@ -222,6 +298,14 @@ impl<'a> Request<'a, stage::Params> {
 impl<'a> Request<'a, stage::Final> {
     /// Sends the Sequencer request as a REST `GET` operation and parses the response into `T`.
     pub async fn get<T>(self) -> Result<T, SequencerError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.get_with_source().await.map(|(response, _)| response)
+    }
+
+    /// Same as [get](Self::get), but also returns the index of the URL which answered.
+    pub async fn get_with_source<T>(self) -> Result<(T, usize), SequencerError>
     where
         T: serde::de::DeserializeOwned,
     {
@@ -229,35 +313,32 @@ impl<'a> Request<'a, stage::Final> {
             url: reqwest::Url,
             api_key: Option<String>,
             client: &reqwest::Client,
+            archive: Option<&Archive>,
             meta: RequestMetadata,
         ) -> Result<T, SequencerError> {
             with_metrics(meta, async move {
                 tracing::trace!(%url, "Fetching data from feeder gateway");
-                let request = client.get(url);
+                let request = client.get(url.clone());
                 let request = match api_key {
                     Some(api_key) => request.header(X_THROTTLING_BYPASS, api_key),
                     None => request,
                 };
-                let response = request.send().await?;
+                let response = Archive::send(archive, &url, request).await?;
                 parse::<T>(response).await
             })
             .await
         }
 
-        match self.state.retry {
-            false => send_request(self.url, self.api_key, self.client, self.state.meta).await,
-            true => {
-                retry0(
-                    || async {
-                        let url = self.url.clone();
-                        let api_key = self.api_key.clone();
-                        send_request(url, api_key, self.client, self.state.meta).await
-                    },
-                    retry_condition,
-                )
-                .await
-            }
-        }
+        self.send(|url| {
+            send_request(
+                url,
+                self.api_key.clone(),
+                self.client,
+                self.archive,
+                self.state.meta,
+            )
+        })
+        .await
     }
 
     /// Sends the Sequencer request as a REST `GET` operation and returns the response's bytes.
@@ -266,16 +347,17 @@ impl<'a> Request<'a, stage::Final> {
             url: reqwest::Url,
             api_key: Option<String>,
             client: &reqwest::Client,
+            archive: Option<&Archive>,
             meta: RequestMetadata,
         ) -> Result<bytes::Bytes, SequencerError> {
             with_metrics(meta, async {
                 tracing::trace!(%url, "Fetching binary data from feeder gateway");
-                let request = client.get(url);
+                let request = client.get(url.clone());
                 let request = match api_key {
                     Some(api_key) => request.header(X_THROTTLING_BYPASS, api_key),
                     None => request,
                 };
-                let response = request.send().await?;
+                let response = Archive::send(archive, &url, request).await?;
                 let response = parse_raw(response).await?;
                 let bytes = response.bytes().await?;
                 Ok(bytes)
@@ -283,20 +365,17 @@ impl<'a> Request<'a, stage::Final> {
             .await
         }
 
-        match self.state.retry {
-            false => get_as_bytes_inner(self.url, self.api_key, self.client, self.state.meta).await,
-            true => {
-                retry0(
-                    || async {
-                        let url = self.url.clone();
-                        let api_key = self.api_key.clone();
-                        get_as_bytes_inner(url, api_key, self.client, self.state.meta).await
-                    },
-                    retry_condition,
-                )
-                .await
-            }
-        }
+        self.send(|url| {
+            get_as_bytes_inner(
+                url,
+                self.api_key.clone(),
+                self.client,
+                self.archive,
+                self.state.meta,
+            )
+        })
+        .await
+        .map(|(bytes, _)| bytes)
     }
 
     /// Sends the Sequencer request as a REST `POST` operation, in addition to the specified
@@ -318,6 +397,7 @@ impl<'a> Request<'a, stage::Final> {
             J: serde::Serialize + ?Sized,
         {
             with_metrics(meta, async {
+                tracing::trace!(%url, "Posting data to gateway");
                 let request = client.post(url);
                 let request = match api_key {
                     Some(api_key) => request.header(X_THROTTLING_BYPASS, api_key),
@@ -329,27 +409,88 @@ impl<'a> Request<'a, stage::Final> {
             .await
         }
 
-        match self.state.retry {
-            false => {
-                post_with_json_inner(self.url, self.api_key, self.client, self.state.meta, json)
-                    .await
+        self.send(|url| {
+            post_with_json_inner(
+                url,
+                self.api_key.clone(),
+                self.client,
+                self.state.meta,
+                json,
+            )
+        })
+        .await
+        .map(|(response, _)| response)
+    }
+
+    /// Sends the request using `send_to`, moving on to the next URL after each failed attempt.
+    ///
+    /// With retry enabled, attempts continue with an exponential backoff for as long as the
+    /// error is retryable. Otherwise, each URL is tried once unless a Starknet specific error is
+    /// returned. Requests for blocks or classes which the gateway does not have yet are tried
+    /// once at each of the other URLs in either case. Returns the index of the URL which answered
+    /// along with the response, which becomes the primary of the pool if it is not the URL which
+    /// was tried first.
+    async fn send<T, F, Fut>(&self, send_to: F) -> Result<(T, usize), SequencerError>
+    where
+        F: Fn(reqwest::Url) -> Fut,
+        Fut: futures::Future<Output = Result<T, SequencerError>>,
+    {
+        let mut attempt = 0;
+        let mut send_next = || {
+            let index = (self.first + attempt) % self.urls.len();
+            attempt += 1;
+            if attempt > 1 && self.urls.len() > 1 {
+                tracing::debug!(url=%self.urls[index], "Failing over to next gateway");
+            }
+
+            let response = send_to(self.urls[index].clone());
+            async move { response.await.map(|response| (response, index)) }
+        };
+
+        let result = if self.state.retry {
+            retry0(&mut send_next, retry_condition).await
+        } else {
+            let mut result = send_next().await;
+            for _ in 1..self.urls.len() {
+                match result {
+                    Err(SequencerError::StarknetError(_)) | Ok(_) => break,
+                    Err(_) => result = send_next().await,
+                }
             }
-            true => {
-                retry0(
-                    || async {
-                        tracing::trace!(url=%self.url, "Posting data to gateway");
-                        let url = self.url.clone();
-                        let api_key = self.api_key.clone();
-                        post_with_json_inner(url, api_key, self.client, self.state.meta, json).await
-                    },
-                    retry_condition,
-                )
-                .await
+            result
+        };
+
+        let mut result = result;
+        let mut lagging = false;
+        if self.fail_over_if_missing {
+            for _ in 1..self.urls.len() {
+                match &result {
+                    Err(SequencerError::StarknetError(e)) if is_missing(e) => {
+                        tracing::debug!(error=%e.message, "Gateway may be lagging behind");
+                        lagging = true;
+                        result = send_next().await;
+                    }
+                    _ => break,
+                }
             }
         }
+
+        // A gateway which merely lags behind remains the primary.
+        if let (Some(pool), Ok((_, index)), false) = (self.pool, &result, lagging) {
+            pool.fail_over(self.first, *index);
+        }
+        result
     }
 }
 
+/// Returns true if the error says that the requested block or class does not exist, which may
+/// only be because the gateway lags behind.
+fn is_missing(e: &starknet_gateway_types::error::StarknetError) -> bool {
+    use starknet_gateway_types::error::KnownStarknetErrorCode::{BlockNotFound, UndeclaredClass};
+
+    e.code == BlockNotFound.into() || e.code == UndeclaredClass.into()
+}
+
 async fn parse<T>(response: reqwest::Response) -> Result<T, SequencerError>
 where
     T: ::serde::de::DeserializeOwned,
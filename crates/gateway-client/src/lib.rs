@@ -5,10 +5,12 @@ use pathfinder_common::{
 use reqwest::Url;
 use starknet_gateway_types::trace::{BlockTrace, TransactionTrace};
 use starknet_gateway_types::{error::SequencerError, reply, request};
-use std::{fmt::Debug, result::Result, time::Duration};
+use std::{fmt::Debug, result::Result, sync::Arc, time::Duration};
 
+mod archive;
 mod builder;
 mod metrics;
+mod pool;
 
 #[allow(unused_variables)]
 #[mockall::automock]
@@ -217,19 +219,35 @@ impl<T: GatewayApi + Sync + Send> GatewayApi for std::sync::Arc<T> {
 /// `backoff [secs] = min((2 ^ N) * 15, 600) [secs]`
 ///
 /// where `N` is the consecutive retry iteration number `{1, 2, ...}`.
+///
+/// Feeder gateway requests for blocks by number are balanced across the configured feeder gateways
+/// by block number, so that all data of one block comes from the same feeder gateway. Requests for
+/// the latest or pending data, for blocks by hash and for classes are sent to a primary feeder
+/// gateway, so that the chain head does not jump between gateways. Each retry is sent to the next
+/// feeder gateway, and requests without retry fail over to the next feeder gateway on errors which
+/// are not Starknet specific. Requests for a block or class which a feeder gateway does not have
+/// also fail over, as it may lag behind. The feeder gateway which answers after the primary failed
+/// becomes the new primary. Feeder gateways returning different block
+/// hashes for the same block number are logged and counted in the
+/// `gateway_inconsistent_blocks_total` metric.
+///
+/// Feeder gateway traffic can be [recorded](Client::with_recording) into an archive, which a
+/// [replaying](Client::replay) client serves back to reproduce the recorded sync.
 #[derive(Debug, Clone)]
 pub struct Client {
     /// This client is internally refcounted
     inner: reqwest::Client,
     /// Starknet gateway URL.
     gateway: Url,
-    /// Starknet feeder gateway URL.
-    feeder_gateway: Url,
+    /// Starknet feeder gateway URLs.
+    feeder_gateways: Arc<pool::FeederGatewayPool>,
     /// Whether __read only__ requests should be retried, defaults to __true__ for production.
     /// Use [disable_retry_for_tests](Client::disable_retry_for_tests) to disable retry logic for all __read only__ requests when testing.
     retry: bool,
     /// Api key added to each request as a value for 'X-Throttling-Bypass' header.
     api_key: Option<String>,
+    /// Records feeder gateway responses, or replays them instead of sending requests.
+    archive: Option<Arc<archive::Archive>>,
 }
 
 impl Client {
@@ -269,6 +287,13 @@ impl Client {
 
     /// Create a Sequencer client for the given [Url]s.
     pub fn with_urls(gateway: Url, feeder_gateway: Url) -> anyhow::Result<Self> {
+        Self::with_feeder_gateways(gateway, vec![feeder_gateway])
+    }
+
+    /// Create a Sequencer client which balances requests and fails over between several feeder
+    /// gateways, which should serve the same network. The first feeder gateway is the initial
+    /// primary.
+    pub fn with_feeder_gateways(gateway: Url, feeder_gateways: Vec<Url>) -> anyhow::Result<Self> {
         metrics::register();
 
         Ok(Self {
@@ -277,9 +302,40 @@ impl Client {
                 .user_agent(pathfinder_common::consts::USER_AGENT)
                 .build()?,
             gateway,
-            feeder_gateway,
+            feeder_gateways: Arc::new(pool::FeederGatewayPool::new(feeder_gateways)?),
             retry: true,
             api_key: None,
+            archive: None,
+        })
+    }
+
+    /// Creates a [Client] which serves feeder gateway requests from an archive created by
+    /// [with_recording](Client::with_recording) instead of sending them.
+    ///
+    /// The responses to each request are served in their recorded order, and not before as much
+    /// time has passed since this call as had passed since the recording started. The last
+    /// response is repeated once they run out, and requests which were never recorded are
+    /// answered with `404 Not Found`.
+    pub fn replay(archive: &std::path::Path) -> anyhow::Result<Self> {
+        let base = Url::parse("http://replay.invalid/").unwrap();
+        let client = Self::with_base_url(base)?;
+
+        Ok(Self {
+            archive: Some(Arc::new(archive::Archive::Replay(archive::Replay::open(
+                archive,
+            )?))),
+            ..client
+        })
+    }
+
+    /// Records all feeder gateway responses into a new archive at `path`, which can be served
+    /// back by [replay](Client::replay).
+    pub fn with_recording(self, path: &std::path::Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            archive: Some(Arc::new(archive::Archive::Record(
+                archive::Recorder::create(path)?,
+            ))),
+            ..self
         })
     }
 
@@ -302,11 +358,8 @@ impl Client {
     }
 
     fn feeder_gateway_request(&self) -> builder::Request<'_, builder::stage::Method> {
-        builder::Request::builder(
-            &self.inner,
-            self.feeder_gateway.clone(),
-            self.api_key.clone(),
-        )
+        builder::Request::with_failover(&self.inner, &self.feeder_gateways, self.api_key.clone())
+            .with_archive(self.archive.as_deref())
     }
 
     async fn block_with_retry_behaviour(
@@ -314,12 +367,22 @@ impl Client {
         block: BlockId,
         retry: bool,
     ) -> Result<reply::MaybePendingBlock, SequencerError> {
-        self.feeder_gateway_request()
+        let (block, gateway) = self
+            .feeder_gateway_request()
             .get_block()
             .with_block(block)
             .with_retry(retry)
-            .get()
-            .await
+            .get_with_source()
+            .await?;
+        self.check_block(gateway, &block);
+        Ok(block)
+    }
+
+    fn check_block(&self, gateway: usize, block: &reply::MaybePendingBlock) {
+        if let reply::MaybePendingBlock::Block(block) = block {
+            self.feeder_gateways
+                .check_block(gateway, block.block_number, block.block_hash);
+        }
     }
 
     /// Returns the [network chain](Chain) this client is operating on.
@@ -372,14 +435,16 @@ impl GatewayApi for Client {
             pub block_number: BlockNumber,
         }
 
-        let header: BlockHeader = self
+        let (header, gateway): (BlockHeader, _) = self
             .feeder_gateway_request()
             .get_block()
             .with_block(block)
             .add_param("headerOnly", "true")
             .with_retry(self.retry)
-            .get()
+            .get_with_source()
             .await?;
+        self.feeder_gateways
+            .check_block(gateway, header.block_number, header.block_hash);
 
         Ok((header.block_number, header.block_hash))
     }
@@ -452,14 +517,15 @@ impl GatewayApi for Client {
         &self,
         block: BlockId,
     ) -> Result<(reply::MaybePendingBlock, StateUpdate), SequencerError> {
-        let result: reply::StateUpdateWithBlock = self
+        let (result, gateway): (reply::StateUpdateWithBlock, _) = self
             .feeder_gateway_request()
             .get_state_update()
             .with_block(block)
             .add_param("includeBlock", "true")
             .with_retry(self.retry)
-            .get()
+            .get_with_source()
             .await?;
+        self.check_block(gateway, &result.block);
         Ok((result.block, result.state_update.into()))
     }
 
@@ -1700,4 +1766,293 @@ mod tests {
                 .unwrap();
         }
     }
+
+    mod feeder_gateway_pool {
+        use super::*;
+        use httpmock::prelude::*;
+
+        /// Serves the latest block header, or a Starknet error if `header` is `None`.
+        fn serve_head<'a>(
+            server: &'a MockServer,
+            header: Option<(u64, &str)>,
+        ) -> httpmock::Mock<'a> {
+            server.mock(|when, then| {
+                when.method(GET)
+                    .path("/feeder_gateway/get_block")
+                    .query_param("blockNumber", "latest")
+                    .query_param("headerOnly", "true");
+                match header {
+                    Some((number, hash)) => then.status(200).json_body(serde_json::json!({
+                        "block_number": number,
+                        "block_hash": hash,
+                    })),
+                    None => {
+                        let (body, status) = response_from(KnownStarknetErrorCode::BlockNotFound);
+                        then.status(status).body(body)
+                    }
+                };
+            })
+        }
+
+        fn client(feeder_gateways: &[String]) -> Client {
+            let feeder_gateways = feeder_gateways
+                .iter()
+                .map(|url| Url::parse(url).unwrap().join("feeder_gateway").unwrap())
+                .collect();
+            Client::with_feeder_gateways(
+                Url::parse("http://localhost/gateway").unwrap(),
+                feeder_gateways,
+            )
+            .unwrap()
+            .disable_retry_for_tests()
+        }
+
+        #[tokio::test]
+        async fn sticky_primary() {
+            let (server_a, server_b) = (
+                MockServer::start_async().await,
+                MockServer::start_async().await,
+            );
+            let head_a = serve_head(&server_a, Some((10, "0xa")));
+            let head_b = serve_head(&server_b, Some((10, "0xa")));
+            let client = client(&[server_a.base_url(), server_b.base_url()]);
+
+            for _ in 0..4 {
+                client.head().await.unwrap();
+            }
+
+            head_a.assert_hits(4);
+            head_b.assert_hits(0);
+
+            // Requests stay with the next gateway once the primary fails.
+            head_a.delete_async().await;
+            for _ in 0..2 {
+                client.head().await.unwrap();
+            }
+
+            head_b.assert_hits(2);
+            let head_a = serve_head(&server_a, Some((10, "0xa")));
+            client.head().await.unwrap();
+            head_a.assert_hits(0);
+        }
+
+        /// Serves a block header by number, or a block not found error if `hash` is [None].
+        fn serve_header<'a>(
+            server: &'a MockServer,
+            number: u64,
+            hash: Option<&str>,
+        ) -> httpmock::Mock<'a> {
+            server.mock(|when, then| {
+                when.method(GET)
+                    .path("/feeder_gateway/get_block")
+                    .query_param("blockNumber", number.to_string())
+                    .query_param("headerOnly", "true");
+                match hash {
+                    Some(hash) => then.status(200).json_body(serde_json::json!({
+                        "block_number": number,
+                        "block_hash": hash,
+                    })),
+                    None => {
+                        let (body, status) = response_from(KnownStarknetErrorCode::BlockNotFound);
+                        then.status(status).body(body)
+                    }
+                };
+            })
+        }
+
+        #[tokio::test]
+        async fn blocks_by_number_are_balanced() {
+            let (server_a, server_b) = (
+                MockServer::start_async().await,
+                MockServer::start_async().await,
+            );
+            let header_a = serve_header(&server_a, 4, Some("0xa"));
+            let header_b = serve_header(&server_b, 5, Some("0xb"));
+            let head_a = serve_head(&server_a, Some((10, "0xa")));
+            let head_b = serve_head(&server_b, Some((10, "0xa")));
+            let client = client(&[server_a.base_url(), server_b.base_url()]);
+
+            for _ in 0..2 {
+                for number in [4, 5] {
+                    client
+                        .block_header(BlockNumber::new_or_panic(number).into())
+                        .await
+                        .unwrap();
+                }
+                client.head().await.unwrap();
+            }
+
+            // All requests for the same block are sent to the same gateway.
+            header_a.assert_hits(2);
+            header_b.assert_hits(2);
+            // The latest block is still requested from the primary only.
+            head_a.assert_hits(2);
+            head_b.assert_hits(0);
+        }
+
+        #[tokio::test]
+        async fn missing_block_fails_over_from_lagging_gateway() {
+            let (server_a, server_b) = (
+                MockServer::start_async().await,
+                MockServer::start_async().await,
+            );
+            let header_a = serve_header(&server_a, 5, Some("0xa"));
+            let header_b = serve_header(&server_b, 5, None);
+            let head_a = serve_head(&server_a, Some((10, "0xa")));
+            let client = client(&[server_a.base_url(), server_b.base_url()]);
+
+            let (_, hash) = client
+                .block_header(BlockNumber::new_or_panic(5).into())
+                .await
+                .unwrap();
+            assert_eq!(hash, block_hash!("0xa"));
+            header_b.assert_hits(1);
+            header_a.assert_hits(1);
+
+            // Failing over from a gateway which lags behind does not change the primary.
+            client.head().await.unwrap();
+            head_a.assert_hits(1);
+        }
+
+        #[tokio::test]
+        async fn pending_classes_are_requested_from_primary() {
+            let (server_a, server_b) = (
+                MockServer::start_async().await,
+                MockServer::start_async().await,
+            );
+            let class = |server: &MockServer| {
+                server.mock(|when, then| {
+                    when.method(GET)
+                        .path("/feeder_gateway/get_class_by_hash")
+                        .query_param("blockNumber", "pending");
+                    then.status(200).body("{}");
+                })
+            };
+            let class_a = class(&server_a);
+            let class_b = class(&server_b);
+            let client = client(&[server_a.base_url(), server_b.base_url()]);
+
+            for _ in 0..3 {
+                client
+                    .pending_class_by_hash(class_hash!("0x1"))
+                    .await
+                    .unwrap();
+            }
+
+            class_a.assert_hits(3);
+            class_b.assert_hits(0);
+        }
+
+        #[tokio::test]
+        async fn failover() {
+            // Nothing is listening on this port once the listener is dropped.
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let down = format!("http://{}", listener.local_addr().unwrap());
+            drop(listener);
+
+            let server = MockServer::start_async().await;
+            let head = serve_head(&server, Some((10, "0xa")));
+            let client = client(&[down, server.base_url()]);
+
+            for _ in 0..2 {
+                let result = client.head().await.unwrap();
+                assert_eq!(result, (BlockNumber::new_or_panic(10), block_hash!("0xa")));
+            }
+
+            head.assert_hits(2);
+        }
+
+        #[tokio::test]
+        async fn starknet_errors_are_not_failed_over() {
+            let (server_a, server_b) = (
+                MockServer::start_async().await,
+                MockServer::start_async().await,
+            );
+            let head_a = serve_head(&server_a, None);
+            let head_b = serve_head(&server_b, Some((10, "0xa")));
+            let client = client(&[server_a.base_url(), server_b.base_url()]);
+
+            let error = client.head().await.unwrap_err();
+
+            assert_matches!(
+                error,
+                SequencerError::StarknetError(e) => assert_eq!(e.code, KnownStarknetErrorCode::BlockNotFound.into())
+            );
+            head_a.assert_hits(1);
+            head_b.assert_hits(0);
+        }
+    }
+
+    mod recording {
+        use super::*;
+        use httpmock::prelude::*;
+
+        #[tokio::test]
+        async fn replays_recorded_responses() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("recording.jsonl");
+
+            let server = MockServer::start_async().await;
+            let serve_head = |hash: &'static str| {
+                server.mock(|when, then| {
+                    when.method(GET)
+                        .path("/feeder_gateway/get_block")
+                        .query_param("blockNumber", "latest")
+                        .query_param("headerOnly", "true");
+                    then.status(200).json_body(serde_json::json!({
+                        "block_number": 10,
+                        "block_hash": hash,
+                    }));
+                })
+            };
+            server.mock(|when, then| {
+                when.method(GET)
+                    .path("/feeder_gateway/get_block")
+                    .query_param("blockNumber", "11");
+                let (body, status) = response_from(KnownStarknetErrorCode::BlockNotFound);
+                then.status(status).body(body);
+            });
+
+            let client = Client::with_base_url(Url::parse(&server.base_url()).unwrap())
+                .unwrap()
+                .disable_retry_for_tests()
+                .with_recording(&path)
+                .unwrap();
+
+            // The head changes between requests, as it would during a reorg.
+            let head = serve_head("0xa");
+            client.head().await.unwrap();
+            head.delete_async().await;
+            serve_head("0xb");
+            client.head().await.unwrap();
+            client
+                .block_without_retry(BlockNumber::new_or_panic(11).into())
+                .await
+                .unwrap_err();
+            drop(client);
+
+            let replay = Client::replay(&path).unwrap().disable_retry_for_tests();
+
+            let number = BlockNumber::new_or_panic(10);
+            assert_eq!(replay.head().await.unwrap(), (number, block_hash!("0xa")));
+            assert_eq!(replay.head().await.unwrap(), (number, block_hash!("0xb")));
+            // The last response is repeated.
+            assert_eq!(replay.head().await.unwrap(), (number, block_hash!("0xb")));
+
+            let error = replay
+                .block_without_retry(BlockNumber::new_or_panic(11).into())
+                .await
+                .unwrap_err();
+            assert_matches!(
+                error,
+                SequencerError::StarknetError(e) => assert_eq!(e.code, KnownStarknetErrorCode::BlockNotFound.into())
+            );
+
+            // Requests which were not recorded.
+            replay
+                .block_without_retry(BlockNumber::new_or_panic(12).into())
+                .await
+                .unwrap_err();
+        }
+    }
 }
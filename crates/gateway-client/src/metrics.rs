@@ -9,6 +9,8 @@ use pathfinder_common::BlockId;
 const METRIC_REQUESTS: &str = "gateway_requests_total";
 const METRIC_FAILED_REQUESTS: &str = "gateway_requests_failed_total";
 const METRICS: [&str; 2] = [METRIC_REQUESTS, METRIC_FAILED_REQUESTS];
+/// Counts the times feeder gateways returned different hashes for the same block number.
+pub(crate) const METRIC_INCONSISTENT_BLOCKS: &str = "gateway_inconsistent_blocks_total";
 const TAG_LATEST: &str = "latest";
 const TAG_PENDING: &str = "pending";
 const TAGS: &[&str] = &[TAG_LATEST, TAG_PENDING];
@@ -50,6 +52,8 @@ pub fn register() {
             })
         })
     });
+
+    metrics::register_counter!(METRIC_INCONSISTENT_BLOCKS);
 }
 
 /// Used to mark methods that touch special block tags to avoid reparsing the url.
@@ -0,0 +1,180 @@
+//! A pool of feeder gateways which requests are balanced and fail over between, including checks
+//! that the gateways agree on the blocks they return.
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use pathfinder_common::{BlockHash, BlockNumber};
+use reqwest::Url;
+
+use crate::metrics::METRIC_INCONSISTENT_BLOCKS;
+
+/// The number of most recent block numbers whose hashes are kept for consistency checks.
+const TRACKED_BLOCKS: usize = 1024;
+
+#[derive(Debug)]
+pub(crate) struct FeederGatewayPool {
+    urls: Vec<Url>,
+    /// The gateway which requests for the latest data are sent to first.
+    primary: AtomicUsize,
+    /// The latest block hash returned for each block number, and the gateway which returned it.
+    blocks: Mutex<BTreeMap<BlockNumber, (BlockHash, usize)>>,
+}
+
+impl FeederGatewayPool {
+    pub fn new(urls: Vec<Url>) -> anyhow::Result<Self> {
+        anyhow::ensure!(!urls.is_empty(), "No feeder gateway URLs given");
+
+        Ok(Self {
+            urls,
+            primary: Default::default(),
+            blocks: Default::default(),
+        })
+    }
+
+    pub fn urls(&self) -> &[Url] {
+        &self.urls
+    }
+
+    /// Returns the gateway which a request for the latest data, such as the latest or pending
+    /// block, should be sent to first. It stays the same until it fails, so that the chain head
+    /// does not jump between gateways which are not exactly in sync.
+    pub fn primary(&self) -> usize {
+        self.primary.load(Ordering::Relaxed)
+    }
+
+    /// Returns the gateway which all requests for the block with `number` are sent to first. Blocks
+    /// are balanced across the gateways by their number, while the data of a single block always
+    /// comes from the same gateway unless it fails.
+    pub fn for_block(&self, number: BlockNumber) -> usize {
+        (number.get() % self.urls.len() as u64) as usize
+    }
+
+    /// Makes `gateway` the primary after it answered a request which was sent to `failed` first.
+    /// Does nothing if a concurrent request has already moved the primary on from `failed`.
+    pub fn fail_over(&self, failed: usize, gateway: usize) {
+        if failed == gateway {
+            return;
+        }
+
+        if self
+            .primary
+            .compare_exchange(failed, gateway, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            tracing::info!(gateway=%self.urls[gateway], "Failed over to feeder gateway");
+        }
+    }
+
+    /// Records the hash of a block returned by `gateway`, and reports it if another gateway
+    /// returned a different hash for the same block number. Returns whether it was reported.
+    ///
+    /// A reorg may briefly cause this while the gateways catch up with each other. A gateway
+    /// changing its own answer is not reported.
+    pub fn check_block(&self, gateway: usize, number: BlockNumber, hash: BlockHash) -> bool {
+        if self.urls.len() == 1 {
+            return false;
+        }
+
+        let mut blocks = self.blocks.lock().unwrap();
+        let inconsistent = match blocks.insert(number, (hash, gateway)) {
+            Some((other_hash, other)) if other_hash != hash && other != gateway => {
+                metrics::increment_counter!(METRIC_INCONSISTENT_BLOCKS);
+                tracing::warn!(
+                    block=%number,
+                    %hash,
+                    gateway=%self.urls[gateway],
+                    %other_hash,
+                    other_gateway=%self.urls[other],
+                    "Feeder gateways disagree on block hash"
+                );
+                true
+            }
+            _ => false,
+        };
+
+        while blocks.len() > TRACKED_BLOCKS {
+            blocks.pop_first();
+        }
+
+        inconsistent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    fn pool(gateways: usize) -> FeederGatewayPool {
+        let urls = (0..gateways)
+            .map(|i| Url::parse(&format!("http://gateway-{i}/feeder_gateway")).unwrap())
+            .collect();
+        FeederGatewayPool::new(urls).unwrap()
+    }
+
+    #[test]
+    fn sticky_primary() {
+        let pool = pool(3);
+        assert_eq!(pool.primary(), 0);
+
+        pool.fail_over(0, 0);
+        assert_eq!(pool.primary(), 0);
+
+        pool.fail_over(0, 2);
+        assert_eq!(pool.primary(), 2);
+
+        // A concurrent request which also failed on the previous primary.
+        pool.fail_over(0, 1);
+        assert_eq!(pool.primary(), 2);
+    }
+
+    #[test]
+    fn blocks_are_balanced() {
+        let pool = pool(3);
+
+        let gateways = (0..6)
+            .map(|number| pool.for_block(BlockNumber::new_or_panic(number)))
+            .collect::<Vec<_>>();
+        assert_eq!(gateways, vec![0, 1, 2, 0, 1, 2]);
+        // The same block is always requested from the same gateway.
+        assert_eq!(pool.for_block(BlockNumber::new_or_panic(4)), 1);
+        // Balanced requests do not move the primary.
+        assert_eq!(pool.primary(), 0);
+    }
+
+    #[test]
+    fn inconsistent_blocks() {
+        let pool = pool(2);
+        let number = BlockNumber::new_or_panic(10);
+
+        assert!(!pool.check_block(0, number, block_hash!("0xa")));
+        assert!(!pool.check_block(1, number, block_hash!("0xa")));
+        // A reorg seen by the same gateway.
+        assert!(!pool.check_block(1, number, block_hash!("0xb")));
+        assert!(pool.check_block(0, number, block_hash!("0xa")));
+    }
+
+    #[test]
+    fn tracked_blocks_are_bounded() {
+        let pool = pool(2);
+
+        for number in 0..=TRACKED_BLOCKS as u64 {
+            pool.check_block(0, BlockNumber::new_or_panic(number), block_hash!("0xa"));
+        }
+
+        // The oldest block is no longer tracked.
+        assert!(!pool.check_block(1, BlockNumber::GENESIS, block_hash!("0xb")));
+        assert!(pool.check_block(
+            1,
+            BlockNumber::new_or_panic(TRACKED_BLOCKS as u64),
+            block_hash!("0xb")
+        ));
+    }
+
+    #[test]
+    fn no_urls() {
+        FeederGatewayPool::new(vec![]).unwrap_err();
+    }
+}
@@ -0,0 +1,61 @@
+//! Kept in a separate binary for the same reason as `metrics.rs`, as it relies on the singleton
+//! metrics recorder.
+
+use httpmock::prelude::*;
+use pathfinder_common::macro_prelude::*;
+use pathfinder_common::test_utils::metrics::{FakeRecorder, ScopedRecorderGuard};
+use pathfinder_common::BlockNumber;
+use reqwest::Url;
+use starknet_gateway_client::{Client, GatewayApi};
+
+fn serve_header<'a>(server: &'a MockServer, block: &str, hash: &str) -> httpmock::Mock<'a> {
+    server.mock(|when, then| {
+        when.method(GET)
+            .path("/feeder_gateway/get_block")
+            .query_param("blockNumber", block)
+            .query_param("headerOnly", "true");
+        then.status(200).json_body(serde_json::json!({
+            "block_number": 11,
+            "block_hash": hash,
+        }));
+    })
+}
+
+#[tokio::test]
+async fn inconsistent_block_hashes_are_counted() {
+    let recorder = FakeRecorder::default();
+    let handle = recorder.handle();
+    let _guard = ScopedRecorderGuard::new(recorder);
+
+    let (server_a, server_b) = (
+        MockServer::start_async().await,
+        MockServer::start_async().await,
+    );
+    serve_header(&server_a, "latest", "0xa");
+    serve_header(&server_b, "11", "0xb");
+    let client = Client::with_feeder_gateways(
+        Url::parse("http://localhost/gateway").unwrap(),
+        vec![
+            Url::parse(&server_a.url("/feeder_gateway")).unwrap(),
+            Url::parse(&server_b.url("/feeder_gateway")).unwrap(),
+        ],
+    )
+    .unwrap()
+    .disable_retry_for_tests();
+
+    // The latest block is requested from the primary, while block 11 by number is requested from
+    // the second gateway.
+    let (_, hash_a) = client.head().await.unwrap();
+    let (_, hash_b) = client
+        .block_header(BlockNumber::new_or_panic(11).into())
+        .await
+        .unwrap();
+
+    // Gateways are not in sync, but the responses are returned as is.
+    assert_eq!(hash_a, block_hash!("0xa"));
+    assert_eq!(hash_b, block_hash!("0xb"));
+    assert_eq!(
+        handle.get_counter_value_by_label("gateway_inconsistent_blocks_total", []),
+        1
+    );
+}
@@ -2,7 +2,7 @@
 use pathfinder_common::{
     BlockCommitmentSignatureElem, BlockHash, BlockNumber, BlockTimestamp, ContractAddress,
     EthereumAddress, GasPrice, SequencerAddress, StarknetVersion, StateCommitment,
-    StateDiffCommitment,
+    StateDiffCommitment, TransactionIndex,
 };
 use pathfinder_serde::{EthereumAddressAsHexStr, GasPriceAsHexStr};
 use serde::{Deserialize, Serialize};
@@ -44,8 +44,7 @@ pub struct Block {
 }
 
 #[serde_as]
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
-#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq, serde::Serialize)]
 pub struct PendingBlock {
     /// TODO: remove alias after Starknet 0.13.0 is deployed on all networks
     #[serde_as(as = "GasPriceAsHexStr")]
@@ -187,6 +186,23 @@ pub struct TransactionStatus {
     pub execution_status: transaction_status::ExecutionStatus,
 }
 
+/// Used to serialize replies to Starknet transaction requests for transactions which are
+/// included in a block.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct TransactionWithStatus {
+    pub status: Status,
+    pub finality_status: Status,
+    pub execution_status: transaction_status::ExecutionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_error: Option<String>,
+    pub block_hash: BlockHash,
+    pub block_number: BlockNumber,
+    pub transaction_index: TransactionIndex,
+    #[serde_as(as = "transaction::Transaction")]
+    pub transaction: pathfinder_common::transaction::Transaction,
+}
+
 /// Types used when deserializing get_transaction replies.
 pub mod transaction_status {
     use serde::Deserialize;
@@ -203,7 +219,7 @@ pub mod transaction_status {
         AcceptedOnL2,
     }
 
-    #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+    #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq, serde::Serialize)]
     #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
     pub enum ExecutionStatus {
         #[default]
@@ -2008,6 +2024,84 @@ impl From<StateUpdate> for pathfinder_common::StateUpdate {
     }
 }
 
+impl From<pathfinder_common::StateUpdate> for StateUpdate {
+    fn from(state_update: pathfinder_common::StateUpdate) -> Self {
+        use pathfinder_common::state_update::ContractClassUpdate;
+
+        let mut storage_diffs = std::collections::HashMap::new();
+        let mut deployed_contracts = Vec::new();
+        let mut nonces = std::collections::HashMap::new();
+        let mut replaced_classes = Vec::new();
+
+        for (address, update) in state_update.contract_updates {
+            if let Some(nonce) = update.nonce {
+                nonces.insert(address, nonce);
+            }
+
+            match update.class {
+                Some(ContractClassUpdate::Deploy(class_hash)) => {
+                    deployed_contracts.push(state_update::DeployedContract {
+                        address,
+                        class_hash,
+                    })
+                }
+                Some(ContractClassUpdate::Replace(class_hash)) => {
+                    replaced_classes.push(state_update::ReplacedClass {
+                        address,
+                        class_hash,
+                    })
+                }
+                None => {}
+            }
+
+            if !update.storage.is_empty() {
+                let storage = update
+                    .storage
+                    .into_iter()
+                    .map(|(key, value)| state_update::StorageDiff { key, value })
+                    .collect();
+                storage_diffs.insert(address, storage);
+            }
+        }
+
+        // System contract updates are embedded in the normal storage diffs, see the reverse
+        // conversion.
+        for (address, update) in state_update.system_contract_updates {
+            let storage = update
+                .storage
+                .into_iter()
+                .map(|(key, value)| state_update::StorageDiff { key, value })
+                .collect();
+            storage_diffs.insert(address, storage);
+        }
+
+        let declared_classes = state_update
+            .declared_sierra_classes
+            .into_iter()
+            .map(
+                |(class_hash, compiled_class_hash)| state_update::DeclaredSierraClass {
+                    class_hash,
+                    compiled_class_hash,
+                },
+            )
+            .collect();
+
+        Self {
+            block_hash: state_update.block_hash,
+            new_root: state_update.state_commitment,
+            old_root: state_update.parent_state_commitment,
+            state_diff: state_update::StateDiff {
+                storage_diffs,
+                deployed_contracts,
+                old_declared_contracts: state_update.declared_cairo_classes,
+                declared_classes,
+                nonces,
+                replaced_classes,
+            },
+        }
+    }
+}
+
 /// Types used when deserializing state update related data.
 pub mod state_update {
     use pathfinder_common::{
@@ -2371,6 +2465,58 @@ mod tests {
         assert_eq!(common, expected);
     }
 
+    #[test]
+    fn to_state_update_roundtrip() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::ContractAddress;
+
+        let expected = pathfinder_common::StateUpdate::default()
+            .with_block_hash(block_hash_bytes!(b"block hash"))
+            .with_state_commitment(state_commitment_bytes!(b"state commitment"))
+            .with_parent_state_commitment(state_commitment_bytes!(b"parent commitment"))
+            .with_storage_update(
+                contract_address_bytes!(b"contract 0"),
+                storage_address_bytes!(b"storage key 0"),
+                storage_value_bytes!(b"storage val 0"),
+            )
+            .with_system_storage_update(
+                ContractAddress::ONE,
+                storage_address_bytes!(b"system key"),
+                storage_value_bytes!(b"system val"),
+            )
+            .with_deployed_contract(
+                contract_address_bytes!(b"deployed contract"),
+                class_hash_bytes!(b"deployed class"),
+            )
+            .with_declared_cairo_class(class_hash_bytes!(b"cairo 0 0"))
+            .with_declared_sierra_class(
+                sierra_hash_bytes!(b"sierra class"),
+                casm_hash_bytes!(b"casm hash"),
+            )
+            .with_contract_nonce(
+                contract_address_bytes!(b"contract 10"),
+                contract_nonce_bytes!(b"nonce 10"),
+            )
+            .with_replaced_class(
+                contract_address_bytes!(b"contract 0"),
+                class_hash_bytes!(b"replaced class"),
+            );
+
+        let gateway = super::StateUpdate::from(expected.clone());
+        // System contract updates are embedded in the storage diffs.
+        assert_eq!(
+            gateway.state_diff.storage_diffs[&ContractAddress::ONE],
+            vec![StorageDiff {
+                key: storage_address_bytes!(b"system key"),
+                value: storage_value_bytes!(b"system val"),
+            }]
+        );
+
+        let common = pathfinder_common::StateUpdate::from(gateway);
+
+        assert_eq!(common, expected);
+    }
+
     mod receipts {
         use crate::reply::transaction::{ExecutionStatus, Receipt};
 
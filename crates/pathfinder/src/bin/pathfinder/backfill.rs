@@ -0,0 +1,174 @@
+//! Background passes which migrate or index the data stored before a storage change.
+//!
+//! Each pass processes the stored rows in small batches, committing its progress with each batch
+//! so that sync is not blocked from writing for long and a restart resumes where it left off.
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_storage::{Storage, Transaction, TransactionBehavior};
+
+/// How often the passes are repeated. Each repetition trains the compression dictionaries which
+/// are still missing once enough data has been synced, and re-compresses the stored data with
+/// them.
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The pause between batches, which gives other writers a chance to acquire the database.
+const BATCH_DELAY: Duration = Duration::from_millis(50);
+/// The longest wait before retrying a failed batch.
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// Runs the background passes one after another every [INTERVAL], retrying failed batches until
+/// they succeed.
+///
+/// `storage` only requires a single connection.
+pub async fn run(storage: Storage) {
+    loop {
+        backfill_l1_handler_messages(&storage).await;
+        backfill_transaction_senders(&storage).await;
+        reencode_transactions(&storage).await;
+        recompress_class_definitions(&storage).await;
+
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+/// Links the messages consumed by the L1 handler transactions which were stored before messages
+/// were linked on insert.
+async fn backfill_l1_handler_messages(storage: &Storage) {
+    const BATCH_SIZE: usize = 1_000;
+
+    run_pass(
+        storage,
+        "L1 handler message linking",
+        BATCH_SIZE,
+        |tx, limit| tx.backfill_l1_handler_messages(limit),
+    )
+    .await;
+}
+
+/// Indexes the transactions which were stored before the transaction sender index was enabled.
+async fn backfill_transaction_senders(storage: &Storage) {
+    const BATCH_SIZE: usize = 1_000;
+
+    run_pass(
+        storage,
+        "transaction sender index",
+        BATCH_SIZE,
+        |tx, limit| tx.backfill_transaction_senders(limit),
+    )
+    .await;
+}
+
+/// Migrates stored transactions and receipts to the current storage format. The compression
+/// dictionaries are trained first, if they are missing and there is enough data to do so.
+async fn reencode_transactions(storage: &Storage) {
+    const SAMPLE_SIZE: usize = 10_000;
+    const BATCH_SIZE: usize = 1_000;
+
+    let trained = retry("transaction compression dictionary training", || {
+        in_transaction(storage, |tx| tx.train_compression_dictionaries(SAMPLE_SIZE))
+    })
+    .await;
+    if trained {
+        tracing::info!("Trained transaction compression dictionaries");
+    }
+
+    run_pass(
+        storage,
+        "transaction re-encoding",
+        BATCH_SIZE,
+        |tx, limit| tx.reencode_transactions(limit),
+    )
+    .await;
+}
+
+/// Re-compresses stored class definitions with the latest compression dictionaries, training the
+/// dictionaries first if they are missing and there is enough data to do so.
+async fn recompress_class_definitions(storage: &Storage) {
+    const SAMPLE_SIZE: usize = 500;
+    const BATCH_SIZE: usize = 100;
+
+    let trained = retry("class compression dictionary training", || {
+        in_transaction(storage, |tx| tx.train_class_dictionaries(SAMPLE_SIZE))
+    })
+    .await;
+    if trained > 0 {
+        tracing::info!(count=%trained, "Trained class definition compression dictionaries");
+    }
+
+    run_pass(
+        storage,
+        "class definition re-compression",
+        BATCH_SIZE,
+        |tx, limit| tx.recompress_class_definitions(limit),
+    )
+    .await;
+}
+
+/// Calls `batch` with the batch size until it returns `false`, committing after each batch.
+async fn run_pass(
+    storage: &Storage,
+    name: &str,
+    batch_size: usize,
+    batch: fn(&Transaction<'_>, usize) -> anyhow::Result<bool>,
+) {
+    let mut batches = 0usize;
+    while retry(name, || {
+        in_transaction(storage, move |tx| batch(tx, batch_size))
+    })
+    .await
+    {
+        batches += 1;
+        if batches % 1_000 == 0 {
+            tracing::info!(pass=%name, rows=%(batches * batch_size), "Processing stored data");
+        }
+
+        tokio::time::sleep(BATCH_DELAY).await;
+    }
+
+    if batches > 0 {
+        tracing::info!(pass=%name, "Processed stored data");
+    }
+}
+
+/// Runs `f` on a blocking thread in a new database transaction, which is committed if `f`
+/// succeeds.
+async fn in_transaction<T: Send + 'static>(
+    storage: &Storage,
+    f: impl FnOnce(&Transaction<'_>) -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+    let storage = storage.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Creating database transaction")?;
+        let result = f(&tx)?;
+        tx.commit().context("Committing database transaction")?;
+
+        Ok(result)
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+/// Calls `f` until it succeeds, waiting exponentially longer after each failure so that a
+/// persistent failure does not flood the logs.
+async fn retry<T, F: Future<Output = anyhow::Result<T>>>(
+    name: &str,
+    mut f: impl FnMut() -> F,
+) -> T {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match f().await {
+            Ok(result) => return result,
+            Err(error) => {
+                tracing::warn!(pass=%name, error=?error, ?backoff, "Background pass failed, retrying");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
@@ -3,7 +3,7 @@ use clap::{ArgAction, CommandFactory, Parser};
 use ipnet::IpNet;
 #[cfg(feature = "p2p")]
 use p2p::libp2p::Multiaddr;
-use pathfinder_common::AllowedOrigins;
+use pathfinder_common::{AllowedOrigins, PublicKey};
 use pathfinder_storage::JournalMode;
 use reqwest::Url;
 use std::collections::HashSet;
@@ -20,7 +20,11 @@ use pathfinder_common::consts::VERGEN_GIT_DESCRIBE;
 #[command(
     about = "A Starknet node implemented by Equilibrium Labs. Submit bug reports and issues at https://github.com/eqlabs/pathfinder."
 )]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(
         long,
         value_name = "DIR", 
@@ -41,16 +45,47 @@ struct Cli {
 
     #[arg(
         long = "ethereum.url",
-        long_help = r"This should point to the HTTP RPC endpoint of your Ethereum entry-point, typically a local Ethereum client or a hosted gateway service such as Infura or Cloudflare.
+        long_help = r"This should point to the HTTP or websocket RPC endpoint of your Ethereum entry-point, typically a local Ethereum client or a hosted gateway service such as Infura or Cloudflare.
+
+A websocket URL (ws:// or wss://) subscribes to new Ethereum blocks and core contract logs, so that L1 acceptance is observed without waiting for the poll interval. Requests are sent over HTTP(S) to the same URL while the websocket is reconnecting.
+
+A comma separated list of URLs may be given to use multiple providers. Requests are sent to one provider at a time, failing over to the next one on errors. See also ethereum.quorum.
 
 Examples:
     infura: https://goerli.infura.io/v3/<PROJECT_ID>
-    geth:   https://localhost:8545",
-        value_name = "HTTP(s) URL",
+    geth:   https://localhost:8545
+    geth:   ws://localhost:8546
+    a list: https://goerli.infura.io/v3/<PROJECT_ID>,ws://localhost:8546",
+        value_name = "HTTP(s) or WS(s) URL",
         value_hint = clap::ValueHint::Url,
+        value_delimiter = ',',
         env = "PATHFINDER_ETHEREUM_API_URL", 
+        required = true,
     )]
-    ethereum_url: Url,
+    ethereum_url: Vec<Url>,
+
+    #[arg(
+        long = "ethereum.quorum",
+        long_help = "The number of Ethereum providers which must agree on the Starknet state published on L1 before it is accepted. \
+            Every provider is queried for the Starknet state if this is above one.",
+        value_name = "N",
+        default_value = "1",
+        env = "PATHFINDER_ETHEREUM_QUORUM"
+    )]
+    ethereum_quorum: NonZeroUsize,
+
+    #[arg(
+        long = "ethereum.verify-proofs",
+        long_help = "Verify the Starknet state synced from the core contract on L1 using eth_getProof storage proofs, \
+            instead of trusting the logs and eth_call results of the Ethereum providers. The proofs are checked against the block hash which \
+            ethereum.quorum providers agree on, so this requires an ethereum.quorum of at least two. \
+            Requires providers which support eth_getProof for historical blocks while catching up.",
+        value_name = "BOOL",
+        default_value = "false",
+        action = clap::ArgAction::Set,
+        env = "PATHFINDER_ETHEREUM_VERIFY_PROOFS"
+    )]
+    ethereum_verify_proofs: bool,
 
     #[arg(
         long = "http-rpc",
@@ -92,6 +127,16 @@ Examples:
     )]
     execution_concurrency: Option<std::num::NonZeroU32>,
 
+    #[arg(
+        long = "rpc.trace-concurrency",
+        long_help = "The number of threads used to speculatively execute the transactions of a block in parallel when tracing it. \
+            Conflicting transactions are re-executed sequentially so the results are identical to sequential tracing. \
+            The threads have their own database connections, which are shared by all trace requests. \
+            Block tracing is sequential if this is not set.",
+        env = "PATHFINDER_RPC_TRACE_CONCURRENCY"
+    )]
+    trace_concurrency: Option<NonZeroUsize>,
+
     #[arg(
         long = "monitor-address",
         long_help = "The address at which pathfinder will serve monitoring related information",
@@ -218,6 +263,47 @@ This should only be enabled for debugging purposes as it adds substantial proces
     )]
     event_bloom_filter_cache_size: std::num::NonZeroUsize,
 
+    #[arg(
+        long = "storage.read-only",
+        long_help = "Open the database in read-only mode and serve RPC from it without syncing. \
+            The database must be kept up-to-date by another pathfinder instance using WAL mode. \
+            New blocks are picked up by polling the database, while pending data is still \
+            fetched from the gateway. This allows scaling RPC across multiple processes on a \
+            single machine. Neither Ethereum nor p2p are used, so the network must be set \
+            explicitly.",
+        env = "PATHFINDER_STORAGE_READ_ONLY",
+        default_value = "false",
+        action = ArgAction::Set
+    )]
+    storage_read_only: bool,
+
+    #[arg(
+        long = "storage.trie-backend",
+        long_help = "Where to store the nodes of the merkle tries, which make up the bulk of the \
+            database. 'sqlite' stores them in the database itself, while 'rocksdb' stores them in \
+            a RocksDB key-value store in a directory next to the database, which reduces write \
+            amplification and reclaims the space of nodes removed by reorgs. Defaults to \
+            'sqlite'. 'rocksdb' requires pathfinder to be built with the `rocksdb` feature. \
+            This only applies when creating a new database: \
+            an existing database keeps using the backend it was created with, changing it \
+            requires syncing from scratch.",
+        value_enum,
+        env = "PATHFINDER_STORAGE_TRIE_BACKEND"
+    )]
+    storage_trie_backend: Option<TrieBackend>,
+
+    #[arg(
+        long = "storage.index-transaction-senders",
+        long_help = "Maintain an index of transactions by sender address and nonce, which is \
+            required by the pathfinder_getTransactionsBySender RPC method. Enabling the index on \
+            an existing database indexes its transactions in the background, resuming after \
+            restarts, and the method is unavailable until this is complete. Disabling it removes the index again. The setting is stored in the database, \
+            so it only changes when this is passed. It is disabled for new databases.",
+        env = "PATHFINDER_STORAGE_INDEX_TRANSACTION_SENDERS",
+        action = ArgAction::Set
+    )]
+    storage_index_transaction_senders: Option<bool>,
+
     #[arg(
         long = "rpc.get-events-max-blocks-to-scan",
         long_help = "The number of blocks to scan for events when querying for events. \
@@ -235,6 +321,241 @@ This should only be enabled for debugging purposes as it adds substantial proces
         default_value = "100000"
     )]
     get_events_max_uncached_bloom_filters_to_load: std::num::NonZeroUsize,
+
+    #[arg(
+        long = "rpc.backup-directory",
+        long_help = "Enables the `pathfinder_backup` RPC method, which writes database backups \
+            to this directory. The method is disabled if this is not set. It is only served by \
+            the admin RPC server, see `--rpc.admin-address`.",
+        env = "PATHFINDER_RPC_BACKUP_DIRECTORY",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::DirPath
+    )]
+    rpc_backup_directory: Option<PathBuf>,
+
+    #[arg(
+        long = "rpc.admin-address",
+        long_help = "Starts a separate HTTP-RPC server at this address which serves the \
+            administrative methods, such as `pathfinder_backup`, at `/rpc/pathfinder/admin/v0.1`. \
+            These methods are not served at all if this is not set, and are only served to \
+            clients connecting from the local machine. Do not expose this address through a \
+            reverse proxy.",
+        env = "PATHFINDER_RPC_ADMIN_ADDRESS",
+        value_name = "IP:PORT"
+    )]
+    rpc_admin_address: Option<SocketAddr>,
+
+    #[arg(
+        long = "feeder-gateway.record",
+        long_help = "Records all feeder gateway responses into an archive at this path. The \
+            archive can be replayed by the gateway client to reproduce the sync in tests, \
+            including its timing. Startup fails if a file already exists at this path.",
+        env = "PATHFINDER_FEEDER_GATEWAY_RECORD",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    feeder_gateway_record: Option<PathBuf>,
+
+    #[arg(
+        long = "feeder-gateway.serve",
+        long_help = "The address at which pathfinder will serve the feeder gateway endpoints \
+            required to sync from this node, e.g. by other pathfinder instances",
+        value_name = "IP:PORT",
+        env = "PATHFINDER_FEEDER_GATEWAY_SERVE"
+    )]
+    feeder_gateway_serve: Option<SocketAddr>,
+
+    #[arg(
+        long = "sync.sequencer-public-key",
+        long_help = "The public key of the sequencer, used to verify block signatures during sync. \
+            Defaults to the known key of mainnet. For other networks, block signatures are not \
+            verified unless this is set.",
+        env = "PATHFINDER_SYNC_SEQUENCER_PUBLIC_KEY",
+        value_name = "HEX",
+        value_parser = parse_public_key
+    )]
+    sequencer_public_key: Option<PublicKey>,
+
+    #[arg(
+        long = "sync.reject-invalid-signatures",
+        long_help = "Stop syncing at blocks whose signature is invalid. If disabled, such blocks \
+            are stored and their signature is reported as invalid by the \
+            pathfinder_getBlockSignature RPC method instead.",
+        env = "PATHFINDER_SYNC_REJECT_INVALID_SIGNATURES",
+        default_value = "true",
+        action = ArgAction::Set
+    )]
+    reject_invalid_signatures: bool,
+}
+
+/// Tools which run instead of the node.
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Verify data in an existing database.
+    #[command(subcommand)]
+    Verify(VerifyCommand),
+    /// Maintain an existing database.
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(clap::Subcommand)]
+pub enum DbCommand {
+    /// Copy the database while a node keeps using it.
+    ///
+    /// The copy is a consistent snapshot which is checked for integrity once complete. The
+    /// database must use WAL mode so that the node is not blocked while the backup runs. The WAL
+    /// cannot be checkpointed until the backup completes, so it grows with everything the node
+    /// writes in the meantime.
+    Backup(DbBackupArgs),
+    /// Check the consistency of the stored blocks and state tries.
+    ///
+    /// Block hashes, transaction and event commitments, state updates and trie roots are
+    /// verified block by block, stopping at the first inconsistent block. The storage tries of
+    /// contracts are only checked for the contracts whose storage is updated by the checked
+    /// blocks.
+    Check(DbCheckArgs),
+    /// Delete all blocks after the given block.
+    ///
+    /// This is intended for recovering from bad data written for recent blocks. Sync resumes from
+    /// the given block once the node is restarted. The node must not be running.
+    Rollback(DbRollbackArgs),
+}
+
+#[derive(clap::Args)]
+pub struct DbRollbackArgs {
+    #[arg(
+        long = "database",
+        long_help = "Path to the pathfinder database",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    pub database: PathBuf,
+
+    #[arg(
+        long = "to",
+        long_help = "The block to roll back to. This becomes the latest block in the database.",
+        value_name = "BLOCK"
+    )]
+    pub to: u64,
+}
+
+#[derive(clap::Args)]
+pub struct DbCheckArgs {
+    #[arg(
+        long = "database",
+        long_help = "Path to the pathfinder database",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    pub database: PathBuf,
+
+    #[arg(
+        long = "from",
+        long_help = "The first block to check",
+        value_name = "BLOCK",
+        default_value = "0"
+    )]
+    pub from: u64,
+
+    #[arg(
+        long = "to",
+        long_help = "The last block to check. Defaults to the latest block in the database.",
+        value_name = "BLOCK"
+    )]
+    pub to: Option<u64>,
+
+    #[arg(
+        long = "chain-id",
+        long_help = "The chain ID of the network (e.g. SN_MAIN). Only required for custom networks, \
+            otherwise it is inferred from the genesis block.",
+        value_name = "CHAIN ID"
+    )]
+    pub chain_id: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct DbBackupArgs {
+    #[arg(
+        long = "database",
+        long_help = "Path to the pathfinder database",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    pub database: PathBuf,
+
+    #[arg(
+        long_help = "Path to write the backup to. This must not exist yet.",
+        value_name = "DEST",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    pub destination: PathBuf,
+
+    #[arg(
+        long = "pages-per-step",
+        long_help = "The number of database pages to copy at a time",
+        value_name = "PAGES",
+        default_value = "1024"
+    )]
+    pub pages_per_step: std::num::NonZeroU32,
+
+    #[arg(
+        long = "pause",
+        long_help = "Milliseconds to pause between copying pages, which limits the load placed on the node",
+        value_name = "MILLISECONDS",
+        default_value = "10"
+    )]
+    pub pause: u64,
+}
+
+#[derive(clap::Subcommand)]
+pub enum VerifyCommand {
+    /// Re-execute a range of blocks and compare the results against the stored receipts and
+    /// state updates.
+    ///
+    /// Every divergence is written to the report as a single line of JSON.
+    Execution(VerifyExecutionArgs),
+}
+
+#[derive(clap::Args)]
+pub struct VerifyExecutionArgs {
+    #[arg(
+        long = "database",
+        long_help = "Path to the pathfinder database",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    pub database: PathBuf,
+
+    #[arg(
+        long = "from",
+        long_help = "The first block to re-execute",
+        value_name = "BLOCK"
+    )]
+    pub from: u64,
+
+    #[arg(
+        long = "to",
+        long_help = "The last block to re-execute. Defaults to the latest block in the database.",
+        value_name = "BLOCK"
+    )]
+    pub to: Option<u64>,
+
+    #[arg(
+        long = "report",
+        long_help = "The file to write the divergence report to, as JSON lines",
+        value_name = "PATH",
+        default_value = "execution-divergences.jsonl"
+    )]
+    pub report: PathBuf,
+
+    #[arg(
+        long = "chain-id",
+        long_help = "The chain ID of the network (e.g. SN_MAIN). Only required for custom networks, \
+            otherwise it is inferred from the genesis block.",
+        value_name = "CHAIN ID"
+    )]
+    pub chain_id: Option<String>,
 }
 
 #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
@@ -257,6 +578,13 @@ impl Color {
     }
 }
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum TrieBackend {
+    Sqlite,
+    #[value(name = "rocksdb")]
+    RocksDb,
+}
+
 #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
 pub enum RpcVersion {
     V05,
@@ -287,11 +615,14 @@ Note that 'custom' requires also setting the --gateway-url and --feeder-gateway-
         long = "feeder-gateway-url",
         value_name = "URL",
         value_hint = clap::ValueHint::Url,
-        long_help = "Specify a custom Starknet feeder gateway url. Can be used to run pathfinder on a custom Starknet network, or to use a gateway proxy. Requires '--network custom'.",
+        long_help = "Specify a custom Starknet feeder gateway url. Can be used to run pathfinder on a custom Starknet network, or to use a gateway proxy. Requires '--network custom'.
+
+A comma separated list of URLs of feeder gateways of the same network may be given. Requests for blocks by number are balanced across them by block number, so that all data of a block comes from the same feeder gateway. Other requests, including those for the pending block and its classes, are sent to the first feeder gateway until it fails, after which they fail over to the next one and stay with it. Requests for a block or class which a feeder gateway does not have yet fail over to the others. Feeder gateways returning different blocks for the same block number are logged.",
+        value_delimiter = ',',
         env = "PATHFINDER_FEEDER_GATEWAY_URL", 
         required_if_eq("network", Network::Custom),
     )]
-    feeder_gateway: Option<Url>,
+    feeder_gateway: Vec<Url>,
 
     #[arg(
         long = "gateway-url",
@@ -489,6 +820,12 @@ fn parse_cors(inputs: Vec<String>) -> Result<Option<AllowedOrigins>, RpcCorsDoma
     )))
 }
 
+fn parse_public_key(input: &str) -> Result<PublicKey, String> {
+    pathfinder_crypto::Felt::from_hex_str(input)
+        .map(PublicKey)
+        .map_err(|e| format!("Invalid public key: {e}"))
+}
+
 pub fn parse_cors_or_exit(input: Vec<String>) -> Option<AllowedOrigins> {
     use clap::error::ErrorKind;
 
@@ -500,6 +837,25 @@ pub fn parse_cors_or_exit(input: Vec<String>) -> Option<AllowedOrigins> {
     }
 }
 
+/// Exits if the RocksDB trie backend is selected without the `rocksdb` feature, rather than
+/// failing once the database is opened.
+fn parse_trie_backend_or_exit(backend: TrieBackend) -> pathfinder_storage::TrieBackend {
+    use clap::error::ErrorKind;
+
+    match backend {
+        TrieBackend::Sqlite => pathfinder_storage::TrieBackend::Sqlite,
+        TrieBackend::RocksDb if cfg!(feature = "rocksdb") => {
+            pathfinder_storage::TrieBackend::RocksDb
+        }
+        TrieBackend::RocksDb => Cli::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "--storage.trie-backend=rocksdb requires pathfinder to be built with the `rocksdb` feature",
+            )
+            .exit(),
+    }
+}
+
 #[derive(Debug, thiserror::Error, PartialEq)]
 #[error("Invalid domain for CORS: {0}")]
 struct InvalidCorsDomainError(String);
@@ -524,6 +880,7 @@ pub struct Config {
     pub monitor_address: Option<SocketAddr>,
     pub network: Option<NetworkConfig>,
     pub execution_concurrency: Option<std::num::NonZeroU32>,
+    pub trace_concurrency: Option<NonZeroUsize>,
     pub sqlite_wal: JournalMode,
     pub max_rpc_connections: std::num::NonZeroUsize,
     pub poll_interval: std::time::Duration,
@@ -536,13 +893,24 @@ pub struct Config {
     pub is_rpc_enabled: bool,
     pub gateway_api_key: Option<String>,
     pub event_bloom_filter_cache_size: NonZeroUsize,
+    pub storage_read_only: bool,
+    pub storage_trie_backend: Option<pathfinder_storage::TrieBackend>,
+    pub storage_index_transaction_senders: Option<bool>,
     pub get_events_max_blocks_to_scan: NonZeroUsize,
     pub get_events_max_uncached_bloom_filters_to_load: NonZeroUsize,
+    pub rpc_backup_directory: Option<PathBuf>,
+    pub rpc_admin_address: Option<SocketAddr>,
+    pub feeder_gateway_record: Option<PathBuf>,
+    pub feeder_gateway_serve: Option<SocketAddr>,
+    pub sequencer_public_key: Option<PublicKey>,
+    pub reject_invalid_signatures: bool,
 }
 
 pub struct Ethereum {
-    pub url: Url,
+    pub urls: Vec<Url>,
     pub password: Option<String>,
+    pub quorum: NonZeroUsize,
+    pub verify_proofs: bool,
 }
 
 pub enum NetworkConfig {
@@ -553,7 +921,7 @@ pub enum NetworkConfig {
     SepoliaIntegration,
     Custom {
         gateway: Url,
-        feeder_gateway: Url,
+        feeder_gateways: Vec<Url>,
         chain_id: String,
     },
 }
@@ -583,17 +951,14 @@ pub struct DebugConfig {
 impl NetworkConfig {
     fn from_components(args: NetworkCli) -> Option<Self> {
         use Network::*;
-        let cfg = match (
-            args.network,
-            args.gateway,
-            args.feeder_gateway,
-            args.chain_id,
-        ) {
+        // An empty list is treated like a missing argument.
+        let feeder_gateways = Some(args.feeder_gateway).filter(|urls| !urls.is_empty());
+        let cfg = match (args.network, args.gateway, feeder_gateways, args.chain_id) {
             (None, None, None, None) => return None,
-            (Some(Custom), Some(gateway), Some(feeder_gateway), Some(chain_id)) => {
+            (Some(Custom), Some(gateway), Some(feeder_gateways), Some(chain_id)) => {
                 NetworkConfig::Custom {
                     gateway,
-                    feeder_gateway,
+                    feeder_gateways,
                     chain_id,
                 }
             }
@@ -688,17 +1053,36 @@ impl DebugConfig {
     }
 }
 
-impl Config {
+/// What the binary was asked to do.
+pub enum Invocation {
+    /// Run the node.
+    Node(Box<Config>),
+    /// Run a one-off command instead of the node.
+    Command(Command),
+}
+
+impl Invocation {
     pub fn parse() -> Self {
-        let cli = Cli::parse();
+        let mut cli = Cli::parse();
+
+        match cli.command.take() {
+            Some(command) => Self::Command(command),
+            None => Self::Node(Box::new(Config::from_cli(cli))),
+        }
+    }
+}
 
+impl Config {
+    fn from_cli(cli: Cli) -> Self {
         let network = NetworkConfig::from_components(cli.network);
 
         Config {
             data_directory: cli.data_directory,
             ethereum: Ethereum {
                 password: cli.ethereum_password,
-                url: cli.ethereum_url,
+                urls: cli.ethereum_url,
+                quorum: cli.ethereum_quorum,
+                verify_proofs: cli.ethereum_verify_proofs,
             },
             rpc_address: cli.rpc_address,
             rpc_cors_domains: parse_cors_or_exit(cli.rpc_cors_domains),
@@ -707,6 +1091,7 @@ impl Config {
             monitor_address: cli.monitor_address,
             network,
             execution_concurrency: cli.execution_concurrency,
+            trace_concurrency: cli.trace_concurrency,
             sqlite_wal: match cli.sqlite_wal {
                 true => JournalMode::WAL,
                 false => JournalMode::Rollback,
@@ -722,9 +1107,18 @@ impl Config {
             is_rpc_enabled: cli.is_rpc_enabled,
             gateway_api_key: cli.gateway_api_key,
             event_bloom_filter_cache_size: cli.event_bloom_filter_cache_size,
+            storage_read_only: cli.storage_read_only,
+            storage_trie_backend: cli.storage_trie_backend.map(parse_trie_backend_or_exit),
+            storage_index_transaction_senders: cli.storage_index_transaction_senders,
             get_events_max_blocks_to_scan: cli.get_events_max_blocks_to_scan,
             get_events_max_uncached_bloom_filters_to_load: cli
                 .get_events_max_uncached_bloom_filters_to_load,
+            rpc_backup_directory: cli.rpc_backup_directory,
+            rpc_admin_address: cli.rpc_admin_address,
+            feeder_gateway_record: cli.feeder_gateway_record,
+            feeder_gateway_serve: cli.feeder_gateway_serve,
+            sequencer_public_key: cli.sequencer_public_key,
+            reject_invalid_signatures: cli.reject_invalid_signatures,
         }
     }
 }
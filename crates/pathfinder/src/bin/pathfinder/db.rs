@@ -0,0 +1,708 @@
+//! Database maintenance commands.
+use std::collections::HashSet;
+use std::num::NonZeroU32;
+
+use anyhow::Context;
+use pathfinder_common::{
+    BlockHeader, BlockNumber, Chain, ChainId, ClassCommitment, StateCommitment, StorageCommitment,
+};
+use pathfinder_crypto::Felt;
+use pathfinder_lib::state::block_hash::{
+    calculate_event_commitment, calculate_transaction_commitment, verify_block_hash,
+    TransactionCommitmentFinalHashType, VerifyResult,
+};
+use pathfinder_storage::{
+    BackupOptions, BlockId, Storage, StoredNode, Transaction, TransactionBehavior,
+};
+use starknet_gateway_types::reply::{Block, Status};
+
+use crate::config::{DbBackupArgs, DbCheckArgs, DbRollbackArgs};
+
+/// Runs `pathfinder db backup`.
+pub fn backup(args: DbBackupArgs) -> anyhow::Result<()> {
+    let storage = Storage::open_read_only(args.database.clone(), 1)
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context("Creating database connection pool")?;
+
+    tracing::info!(source=%args.database.display(), destination=%args.destination.display(), "Starting backup");
+
+    let report = storage
+        .backup(
+            &args.destination,
+            BackupOptions {
+                pages_per_step: args.pages_per_step,
+                pause: std::time::Duration::from_millis(args.pause),
+            },
+        )
+        .context("Backing up database")?;
+
+    match report.blocks {
+        Some(blocks) => tracing::info!(
+            first=%blocks.start(),
+            last=%blocks.end(),
+            size=%report.size,
+            "Backup complete"
+        ),
+        None => tracing::info!(size=%report.size, "Backup complete, it contains no blocks"),
+    }
+
+    Ok(())
+}
+
+/// Runs `pathfinder db rollback`.
+///
+/// All blocks are removed in a single database transaction, so the rollback either completes or
+/// leaves the database untouched. The database is not migrated and keeps its journal mode, so it
+/// must already be at the latest schema version.
+pub fn rollback(args: DbRollbackArgs) -> anyhow::Result<()> {
+    let storage = Storage::open_without_migration(args.database.clone(), 1)
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context("Creating database connection pool")?;
+    let mut db = storage
+        .connection()
+        .context("Creating database connection")?;
+    let tx = db
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .context("Creating database transaction")?;
+
+    let to = BlockNumber::new_or_panic(args.to);
+    let latest = tx
+        .block_id(BlockId::Latest)
+        .context("Fetching latest block number")?
+        .context("Database is empty")?
+        .0;
+    anyhow::ensure!(
+        to < latest,
+        "Block {to} is not before the latest block in the database ({latest})"
+    );
+
+    tracing::info!(%to, %latest, "Rolling back database");
+
+    pathfinder_lib::state::rollback(&tx, to + 1).context("Removing blocks")?;
+    tx.commit().context("Committing database transaction")?;
+
+    tracing::info!(latest=%to, "Rollback complete");
+
+    Ok(())
+}
+
+/// Runs `pathfinder db check`.
+///
+/// The class and storage tries are checked for every block. The storage trie of a contract is
+/// only checked for the blocks which update its storage, so a contract whose storage is last
+/// updated before `--from` is not checked.
+///
+/// Trie nodes are shared between the tries of different blocks, so nodes which were already
+/// checked are skipped, see [Trie].
+pub fn check(args: DbCheckArgs) -> anyhow::Result<()> {
+    let storage = Storage::open_read_only(args.database.clone(), 1)
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context("Creating database connection pool")?;
+    let mut db = storage
+        .connection()
+        .context("Creating database connection")?;
+    let tx = db.transaction().context("Creating database transaction")?;
+
+    let latest = tx
+        .block_id(BlockId::Latest)
+        .context("Fetching latest block number")?
+        .context("Database is empty")?
+        .0;
+    let to = args.to.map(BlockNumber::new_or_panic).unwrap_or(latest);
+    anyhow::ensure!(
+        to <= latest,
+        "Block {to} is past the latest block in the database ({latest})"
+    );
+    anyhow::ensure!(args.from <= to.get(), "--from must not be after --to");
+
+    let (chain, chain_id) = network(&tx, args.chain_id)?;
+
+    tracing::info!(from=%args.from, %to, %chain, "Checking database");
+
+    let mut parent = match args.from.checked_sub(1) {
+        Some(parent) => tx
+            .block_header(BlockNumber::new_or_panic(parent).into())
+            .context("Fetching parent block header")?,
+        None => None,
+    };
+    let mut tries = Tries::default();
+
+    for number in args.from..=to.get() {
+        let number = BlockNumber::new_or_panic(number);
+
+        let (header, problems) =
+            check_block(&tx, chain, chain_id, number, parent.as_ref(), &mut tries)
+                .with_context(|| format!("Checking block {number}"))?;
+
+        if !problems.is_empty() {
+            for problem in &problems {
+                tracing::error!(block_number=%number, "{problem}");
+            }
+            anyhow::bail!("Block {number} is inconsistent");
+        }
+
+        if number.get() % 1000 == 0 {
+            tracing::info!(block_number=%number, "Progress");
+        }
+
+        parent = header;
+    }
+
+    tracing::info!(from=%args.from, %to, "Database is consistent");
+
+    Ok(())
+}
+
+/// Determines the network from the genesis block, unless a chain ID is given.
+fn network(tx: &Transaction<'_>, chain_id: Option<String>) -> anyhow::Result<(Chain, ChainId)> {
+    if let Some(network) = crate::verify::network_from_genesis(tx)? {
+        return Ok(network);
+    }
+
+    let chain_id = chain_id.context("Unknown network, use --chain-id to specify its chain ID")?;
+    let chain_id = ChainId(Felt::from_be_slice(chain_id.as_bytes()).context("Parsing chain ID")?);
+
+    Ok((Chain::Custom, chain_id))
+}
+
+/// Checks a single block, returning its header and a description of each inconsistency found.
+fn check_block(
+    tx: &Transaction<'_>,
+    chain: Chain,
+    chain_id: ChainId,
+    number: BlockNumber,
+    parent: Option<&BlockHeader>,
+    tries: &mut Tries,
+) -> anyhow::Result<(Option<BlockHeader>, Vec<String>)> {
+    let mut problems = Vec::new();
+
+    let Some(header) = tx
+        .block_header(number.into())
+        .context("Fetching block header")?
+    else {
+        problems.push("Block header is missing".to_owned());
+        return Ok((None, problems));
+    };
+
+    let canonical = tx
+        .block_id(number.into())
+        .context("Fetching canonical block hash")?
+        .map(|(_, hash)| hash);
+    if canonical != Some(header.hash) {
+        problems.push(format!(
+            "Canonical block hash {canonical:?} does not match the header's hash {}",
+            header.hash
+        ));
+    }
+
+    if let Some(parent) = parent {
+        if header.parent_hash != parent.hash {
+            problems.push(format!(
+                "Parent hash {} does not match the previous block's hash {}",
+                header.parent_hash, parent.hash
+            ));
+        }
+    }
+
+    match tx
+        .transaction_data_for_block(number.into())
+        .context("Fetching transactions")?
+    {
+        Some(data) => {
+            let (transactions, receipts): (Vec<_>, Vec<_>) = data.into_iter().unzip();
+            check_transactions(
+                chain,
+                chain_id,
+                &header,
+                transactions,
+                receipts,
+                &mut problems,
+            )?;
+        }
+        None => problems.push("Transactions are missing".to_owned()),
+    }
+
+    match tx
+        .state_update(number.into())
+        .context("Fetching state update")?
+    {
+        Some(state_update) => {
+            if state_update.block_hash != header.hash {
+                problems.push(format!(
+                    "State update's block hash {} does not match the header's hash {}",
+                    state_update.block_hash, header.hash
+                ));
+            }
+            if state_update.state_commitment != header.state_commitment {
+                problems.push(format!(
+                    "State update's state commitment {} does not match the header's {}",
+                    state_update.state_commitment, header.state_commitment
+                ));
+            }
+
+            let contracts = state_update
+                .contract_updates
+                .iter()
+                .filter(|(_, update)| !update.storage.is_empty())
+                .map(|(address, _)| address)
+                .chain(
+                    state_update
+                        .system_contract_updates
+                        .iter()
+                        .filter(|(_, update)| !update.storage.is_empty())
+                        .map(|(address, _)| address),
+                );
+            for contract in contracts {
+                if let Some(root) = tx
+                    .contract_root_index(number, *contract)
+                    .context("Fetching contract root index")?
+                {
+                    if let Err(e) = tries
+                        .contracts
+                        .check(root, |index| tx.contract_trie_node(index))
+                    {
+                        problems.push(format!("Storage trie of contract {contract}: {e}"));
+                    }
+                }
+            }
+        }
+        None => problems.push("State update is missing".to_owned()),
+    }
+
+    let expected = StateCommitment::calculate(header.storage_commitment, header.class_commitment);
+    if expected != header.state_commitment {
+        problems.push(format!(
+            "State commitment {} does not match the storage and class commitments",
+            header.state_commitment
+        ));
+    }
+
+    match tx
+        .storage_root_index(number)
+        .context("Fetching storage root index")?
+    {
+        Some(root) => {
+            let hash = tx
+                .storage_trie_node_hash(root)
+                .context("Fetching storage root hash")?;
+            if hash.map(StorageCommitment) != Some(header.storage_commitment) {
+                problems.push(format!(
+                    "Storage trie root hash {hash:?} does not match the storage commitment {}",
+                    header.storage_commitment
+                ));
+            }
+            if let Err(e) = tries
+                .storage
+                .check(root, |index| tx.storage_trie_node(index))
+            {
+                problems.push(format!("Storage trie: {e}"));
+            }
+        }
+        None if header.storage_commitment != StorageCommitment::ZERO => {
+            problems.push("Storage trie root is missing".to_owned())
+        }
+        None => {}
+    }
+
+    match tx
+        .class_root_index(number)
+        .context("Fetching class root index")?
+    {
+        Some(root) => {
+            let hash = tx
+                .class_trie_node_hash(root)
+                .context("Fetching class root hash")?;
+            if hash.map(ClassCommitment) != Some(header.class_commitment) {
+                problems.push(format!(
+                    "Class trie root hash {hash:?} does not match the class commitment {}",
+                    header.class_commitment
+                ));
+            }
+            if let Err(e) = tries.class.check(root, |index| tx.class_trie_node(index)) {
+                problems.push(format!("Class trie: {e}"));
+            }
+        }
+        None if header.class_commitment != ClassCommitment::ZERO => {
+            problems.push("Class trie root is missing".to_owned())
+        }
+        None => {}
+    }
+
+    Ok((Some(header), problems))
+}
+
+fn check_transactions(
+    chain: Chain,
+    chain_id: ChainId,
+    header: &BlockHeader,
+    transactions: Vec<pathfinder_common::transaction::Transaction>,
+    receipts: Vec<pathfinder_common::receipt::Receipt>,
+    problems: &mut Vec<String>,
+) -> anyhow::Result<()> {
+    if transactions.len() != header.transaction_count {
+        problems.push(format!(
+            "Block has {} transactions but the header expects {}",
+            transactions.len(),
+            header.transaction_count
+        ));
+    }
+
+    let event_count = receipts.iter().map(|r| r.events.len()).sum::<usize>();
+    if event_count != header.event_count {
+        problems.push(format!(
+            "Block has {event_count} events but the header expects {}",
+            header.event_count
+        ));
+    }
+
+    let final_hash_type =
+        TransactionCommitmentFinalHashType::for_version(&header.starknet_version)?;
+    let transaction_commitment = calculate_transaction_commitment(&transactions, final_hash_type)?;
+    let event_commitment = calculate_event_commitment(&receipts)?;
+
+    let block = Block {
+        block_hash: header.hash,
+        block_number: header.number,
+        eth_l1_gas_price: Some(header.eth_l1_gas_price),
+        strk_l1_gas_price: Some(header.strk_l1_gas_price),
+        parent_block_hash: header.parent_hash,
+        sequencer_address: Some(header.sequencer_address),
+        state_commitment: header.state_commitment,
+        status: Status::AcceptedOnL2,
+        timestamp: header.timestamp,
+        transaction_receipts: receipts,
+        transactions,
+        starknet_version: header.starknet_version.clone(),
+    };
+
+    // Commitments are only stored for blocks whose hash could be verified during sync.
+    let commitments_stored = match verify_block_hash(&block, chain, chain_id, header.hash)? {
+        VerifyResult::Match(_) => true,
+        VerifyResult::NotVerifiable => false,
+        VerifyResult::Mismatch => {
+            problems.push(format!("Block hash {} is incorrect", header.hash));
+            true
+        }
+    };
+
+    if commitments_stored && transaction_commitment != header.transaction_commitment {
+        problems.push(format!(
+            "Transaction commitment {} does not match the calculated {transaction_commitment}",
+            header.transaction_commitment
+        ));
+    }
+    if commitments_stored && event_commitment != header.event_commitment {
+        problems.push(format!(
+            "Event commitment {} does not match the calculated {event_commitment}",
+            header.event_commitment
+        ));
+    }
+
+    Ok(())
+}
+
+/// Tracks the trie nodes which have already been checked.
+#[derive(Default)]
+struct Tries {
+    class: Trie,
+    contracts: Trie,
+    storage: Trie,
+}
+
+/// The maximum number of checked node indices remembered per trie, which bounds the memory used
+/// by checking a large range of blocks.
+const MAX_VISITED_NODES: usize = 4_000_000;
+
+/// Remembers up to [MAX_VISITED_NODES] checked nodes. Once full, the set is cleared, so that
+/// nodes may be checked again but memory use stays bounded.
+struct Trie {
+    visited: HashSet<u64>,
+    max_visited: usize,
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Self {
+            visited: HashSet::new(),
+            max_visited: MAX_VISITED_NODES,
+        }
+    }
+}
+
+impl Trie {
+    /// Checks that every node reachable from `root` is stored, skipping nodes that were reached
+    /// by a previous check.
+    fn check(
+        &mut self,
+        root: u64,
+        node: impl Fn(u64) -> anyhow::Result<Option<StoredNode>>,
+    ) -> anyhow::Result<()> {
+        let mut pending = vec![root];
+
+        while let Some(index) = pending.pop() {
+            if self.visited.len() >= self.max_visited {
+                self.visited.clear();
+            }
+            if !self.visited.insert(index) {
+                continue;
+            }
+
+            let stored = node(index)
+                .with_context(|| format!("Fetching trie node {index}"))?
+                .with_context(|| format!("Trie node {index} is missing"))?;
+
+            match stored {
+                StoredNode::Binary { left, right } => pending.extend([left, right]),
+                StoredNode::Edge { child, .. } => pending.push(child),
+                StoredNode::LeafBinary | StoredNode::LeafEdge { .. } => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::state_update::StateUpdate;
+    use pathfinder_common::StarknetVersion;
+
+    use super::*;
+
+    /// Blocks at the start of Goerli integration can't be verified, so the block hashes and
+    /// commitments of these test blocks are not checked.
+    const CHAIN: Chain = Chain::GoerliIntegration;
+
+    /// Stores an empty genesis block and an empty block 1.
+    fn setup() -> (Storage, BlockHeader, BlockHeader) {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let genesis = BlockHeader::builder()
+            .with_starknet_version(StarknetVersion::new(0, 13, 0))
+            .finalize_with_hash(block_hash!("0xb00"));
+        let header = genesis
+            .child_builder()
+            .with_starknet_version(StarknetVersion::new(0, 13, 0))
+            .finalize_with_hash(block_hash!("0xb01"));
+
+        for header in [&genesis, &header] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_transaction_data(header.hash, header.number, &[])
+                .unwrap();
+            tx.insert_state_update(header.number, &StateUpdate::default())
+                .unwrap();
+        }
+        tx.commit().unwrap();
+
+        (storage, genesis, header)
+    }
+
+    #[test]
+    fn consistent_block_has_no_problems() {
+        let (storage, genesis, header) = setup();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let (checked, problems) = check_block(
+            &tx,
+            CHAIN,
+            ChainId::GOERLI_INTEGRATION,
+            header.number,
+            Some(&genesis),
+            &mut Tries::default(),
+        )
+        .unwrap();
+
+        assert_eq!(checked, Some(header));
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn parent_hash_mismatch_is_reported() {
+        let (storage, genesis, header) = setup();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let other_parent = BlockHeader {
+            hash: block_hash!("0xbad"),
+            ..genesis
+        };
+        let (_, problems) = check_block(
+            &tx,
+            CHAIN,
+            ChainId::GOERLI_INTEGRATION,
+            header.number,
+            Some(&other_parent),
+            &mut Tries::default(),
+        )
+        .unwrap();
+
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("Parent hash"), "{problems:?}");
+    }
+
+    #[test]
+    fn missing_block_is_reported() {
+        let (storage, _, header) = setup();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let (checked, problems) = check_block(
+            &tx,
+            CHAIN,
+            ChainId::GOERLI_INTEGRATION,
+            header.number + 1,
+            Some(&header),
+            &mut Tries::default(),
+        )
+        .unwrap();
+
+        assert_eq!(checked, None);
+        assert_eq!(problems, vec!["Block header is missing".to_owned()]);
+    }
+
+    #[test]
+    fn state_commitment_mismatch_is_reported() {
+        let (storage, genesis, header) = setup();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let header = genesis
+            .child_builder()
+            .with_number(header.number + 1)
+            .with_state_commitment(state_commitment!("0x123"))
+            .finalize_with_hash(block_hash!("0xb02"));
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_transaction_data(header.hash, header.number, &[])
+            .unwrap();
+        tx.insert_state_update(header.number, &StateUpdate::default())
+            .unwrap();
+
+        let (_, problems) = check_block(
+            &tx,
+            CHAIN,
+            ChainId::GOERLI_INTEGRATION,
+            header.number,
+            None,
+            &mut Tries::default(),
+        )
+        .unwrap();
+
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.ends_with("does not match the storage and class commitments")),
+            "{problems:?}"
+        );
+    }
+
+    mod trie {
+        use bitvec::prelude::*;
+
+        use super::*;
+
+        /// A binary root with an edge to a leaf on the left and a leaf on the right.
+        fn nodes() -> HashMap<u64, StoredNode> {
+            HashMap::from([
+                (1, StoredNode::Binary { left: 2, right: 3 }),
+                (
+                    2,
+                    StoredNode::Edge {
+                        child: 4,
+                        path: bitvec![u8, Msb0; 1, 0],
+                    },
+                ),
+                (3, StoredNode::LeafBinary),
+                (
+                    4,
+                    StoredNode::LeafEdge {
+                        path: bitvec![u8, Msb0; 1],
+                    },
+                ),
+            ])
+        }
+
+        #[test]
+        fn complete_trie() {
+            let nodes = nodes();
+
+            Trie::default()
+                .check(1, |index| Ok(nodes.get(&index).cloned()))
+                .unwrap();
+        }
+
+        #[test]
+        fn missing_node() {
+            let mut nodes = nodes();
+            nodes.remove(&4);
+
+            let error = Trie::default()
+                .check(1, |index| Ok(nodes.get(&index).cloned()))
+                .unwrap_err();
+
+            assert_eq!(error.to_string(), "Trie node 4 is missing");
+        }
+
+        #[test]
+        fn visited_nodes_are_skipped() {
+            let nodes = nodes();
+            let fetched = std::cell::RefCell::new(Vec::new());
+            let node = |index| {
+                fetched.borrow_mut().push(index);
+                Ok(nodes.get(&index).cloned())
+            };
+
+            let mut trie = Trie::default();
+            trie.check(2, node).unwrap();
+            trie.check(1, node).unwrap();
+
+            assert_eq!(fetched.into_inner(), vec![2, 4, 1, 3]);
+        }
+
+        #[test]
+        fn visited_nodes_are_bounded() {
+            let nodes = nodes();
+            let fetched = std::cell::RefCell::new(Vec::new());
+            let node = |index| {
+                fetched.borrow_mut().push(index);
+                Ok(nodes.get(&index).cloned())
+            };
+
+            let mut trie = Trie {
+                visited: HashSet::new(),
+                max_visited: 2,
+            };
+            trie.check(1, node).unwrap();
+
+            assert!(trie.visited.len() <= 2);
+            // Nodes forgotten when the set was cleared are checked again.
+            trie.check(1, node).unwrap();
+            assert_eq!(fetched.into_inner(), vec![1, 3, 2, 4, 1, 3, 2, 4]);
+        }
+    }
+
+    #[test]
+    fn rollback_does_not_migrate_outdated_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("test.sqlite");
+        // An empty file is a database at schema version zero.
+        std::fs::write(&database, b"").unwrap();
+
+        rollback(DbRollbackArgs {
+            database: database.clone(),
+            to: 0,
+        })
+        .unwrap_err();
+
+        assert_eq!(std::fs::metadata(&database).unwrap().len(), 0);
+        assert!(!dir.path().join("test.sqlite-wal").exists());
+    }
+}
@@ -4,8 +4,9 @@ use anyhow::Context;
 use metrics_exporter_prometheus::PrometheusBuilder;
 use mimalloc::MiMalloc;
 
-use pathfinder_common::{consts::VERGEN_GIT_DESCRIBE, BlockNumber, Chain, ChainId, EthereumChain};
-use pathfinder_ethereum::{EthereumApi, EthereumClient};
+use pathfinder_common::consts::{MAINNET_SEQUENCER_PUBLIC_KEY, VERGEN_GIT_DESCRIBE};
+use pathfinder_common::{BlockNumber, Chain, ChainId, EthereumChain, PublicKey};
+use pathfinder_ethereum::{EthereumApi, EthereumClient, FailoverClient};
 use pathfinder_lib::monitoring::{self};
 use pathfinder_lib::state;
 use pathfinder_lib::state::SyncContext;
@@ -22,8 +23,11 @@ use tracing::info;
 
 use crate::config::NetworkConfig;
 
+mod backfill;
 mod config;
+mod db;
 mod update;
+mod verify;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -43,7 +47,13 @@ async fn async_main() -> anyhow::Result<()> {
         std::env::set_var("RUST_LOG", "pathfinder=info");
     }
 
-    let config = config::Config::parse();
+    let config = match config::Invocation::parse() {
+        config::Invocation::Node(config) => *config,
+        config::Invocation::Command(command) => {
+            setup_tracing(config::Color::Auto, false);
+            return run_command(command).await;
+        }
+    };
 
     setup_tracing(config.color, config.debug.pretty_log);
 
@@ -65,16 +75,25 @@ async fn async_main() -> anyhow::Result<()> {
     // A readiness flag which is used to indicate that pathfinder is ready via monitoring.
     let readiness = Arc::new(AtomicBool::new(false));
 
-    let ethereum = EthereumContext::setup(config.ethereum.url, config.ethereum.password)
-        .await
-        .context("Creating Ethereum context")?;
+    // A read-only replica does not sync, so it does not connect to Ethereum.
+    let ethereum = if config.storage_read_only {
+        None
+    } else {
+        let ethereum = EthereumContext::setup(config.ethereum)
+            .await
+            .context("Creating Ethereum context")?;
+        Some(ethereum)
+    };
 
     // Use the default starknet network if none was configured.
-    let network = match config.network {
-        Some(network) => network,
-        None => ethereum
+    let network = match (config.network, &ethereum) {
+        (Some(network), _) => network,
+        (None, Some(ethereum)) => ethereum
             .default_network()
             .context("Using default Starknet network based on Ethereum configuration")?,
+        (None, None) => anyhow::bail!(
+            "The Starknet network must be set using --network when the database is read-only"
+        ),
     };
 
     // Spawn monitoring if configured.
@@ -92,7 +111,7 @@ async fn async_main() -> anyhow::Result<()> {
             .context("Starting monitoring task")?;
     }
 
-    let pathfinder_context = PathfinderContext::configure_and_proxy_check(
+    let mut pathfinder_context = PathfinderContext::configure_and_proxy_check(
         network,
         config.data_directory,
         config.gateway_api_key,
@@ -100,16 +119,59 @@ async fn async_main() -> anyhow::Result<()> {
     .await
     .context("Configuring pathfinder")?;
 
-    verify_networks(pathfinder_context.network, ethereum.chain)?;
+    if let Some(archive) = &config.feeder_gateway_record {
+        tracing::info!(path=%archive.display(), "Recording feeder gateway responses");
+        pathfinder_context.gateway = pathfinder_context
+            .gateway
+            .with_recording(archive)
+            .context("Creating feeder gateway archive")?;
+    }
+
+    if let Some(ethereum) = &ethereum {
+        verify_networks(pathfinder_context.network, ethereum.chain)?;
+    }
 
     // Setup and verify database
 
-    let storage_manager = Storage::migrate(
-        pathfinder_context.database.clone(),
-        config.sqlite_wal,
-        config.event_bloom_filter_cache_size.get(),
-    )
-    .unwrap();
+    let storage_manager = if config.storage_read_only {
+        Storage::open_read_only(
+            pathfinder_context.database.clone(),
+            config.event_bloom_filter_cache_size.get(),
+        )
+        .context("Opening database in read-only mode")?
+    } else {
+        let is_new_database = !pathfinder_context.database.exists();
+        let storage_manager = Storage::migrate(
+            pathfinder_context.database.clone(),
+            config.sqlite_wal,
+            config.event_bloom_filter_cache_size.get(),
+        )
+        .unwrap();
+
+        // The trie backend can only be selected when creating a database, existing databases
+        // keep using the backend they were created with.
+        let storage_manager = match config.storage_trie_backend {
+            Some(backend) if is_new_database => storage_manager
+                .with_trie_backend(backend)
+                .context("Selecting trie storage backend")?,
+            Some(backend) if backend != storage_manager.trie_backend() => {
+                tracing::warn!(
+                    requested=?backend,
+                    stored=?storage_manager.trie_backend(),
+                    "Ignoring the requested trie backend, since it can only be selected for new databases"
+                );
+                storage_manager
+            }
+            _ => storage_manager,
+        };
+
+        match config.storage_index_transaction_senders {
+            Some(enabled) => storage_manager
+                .with_transaction_sender_index(enabled)
+                .context("Configuring transaction sender index")?,
+            None => storage_manager,
+        }
+    };
     let sync_storage = storage_manager
         // 5 is enough for normal sync operations, and then `available_parallelism` for
         // the rayon thread pool workers to use.
@@ -146,6 +208,22 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         .create_pool(execution_storage_pool_size)
         .context(r"")?;
 
+    // Parallel tracing gets its own pool with one connection per worker, so that concurrent
+    // trace requests share the workers' connections instead of using up the execution pool.
+    let trace_storage = config
+        .trace_concurrency
+        .map(|concurrency| {
+            let pool_size = NonZeroU32::try_from(concurrency)
+                .context("Trace concurrency does not fit a connection pool size")?;
+            storage_manager.create_pool(pool_size).context(
+                r"Creating database connection pool for parallel tracing
+
+Hint: This is usually caused by exceeding the file descriptor limit of your system.
+      Try increasing the file limit to using `ulimit` or similar tooling.",
+            )
+        })
+        .transpose()?;
+
     let p2p_storage = storage_manager
         .create_pool(NonZeroU32::new(1).unwrap())
         .context(
@@ -155,7 +233,18 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
       Try increasing the file limit to using `ulimit` or similar tooling.",
         )?;
 
-    info!(location=?pathfinder_context.database, "Database migrated.");
+    let backfill_storage = storage_manager
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context(
+            r"Creating database connection pool for background passes
+
+Hint: This is usually caused by exceeding the file descriptor limit of your system.
+      Try increasing the file limit to using `ulimit` or similar tooling.",
+        )?;
+
+    if !config.storage_read_only {
+        info!(location=?pathfinder_context.database, "Database migrated.");
+    }
     verify_database(
         &sync_storage,
         pathfinder_context.network,
@@ -173,6 +262,23 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         get_events_max_blocks_to_scan: config.get_events_max_blocks_to_scan,
         get_events_max_uncached_bloom_filters_to_load: config
             .get_events_max_uncached_bloom_filters_to_load,
+        trace_concurrency: config.trace_concurrency,
+        backup_directory: config.rpc_backup_directory,
+    };
+
+    let feeder_gateway_handle = match config.feeder_gateway_serve {
+        Some(address) => {
+            let (handle, local_addr) = pathfinder_lib::feeder_gateway::spawn_server(
+                address,
+                rpc_storage.clone(),
+                pathfinder_rpc::PendingWatcher::new(rx_pending.clone()),
+                pathfinder_context.l1_core_address,
+            )
+            .context("Starting the feeder gateway server")?;
+            info!("🚚 Feeder gateway server started on: {}", local_addr);
+            handle
+        }
+        None => tokio::spawn(std::future::pending()),
     };
 
     let context = pathfinder_rpc::context::RpcContext::new(
@@ -185,6 +291,11 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         rpc_config,
     );
 
+    let context = match trace_storage {
+        Some(trace_storage) => context.with_trace_storage(trace_storage),
+        None => context,
+    };
+
     let context = if config.websocket.enabled {
         context.with_websockets(WebsocketContext::new(
             config.websocket.socket_buffer_capacity,
@@ -205,34 +316,64 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         None => rpc_server,
     };
 
-    let (p2p_handle, gossiper) =
-        start_p2p(pathfinder_context.network_id, p2p_storage, config.p2p).await?;
-
-    let sync_context = SyncContext {
-        storage: sync_storage,
-        ethereum: ethereum.client,
-        chain: pathfinder_context.network,
-        chain_id: pathfinder_context.network_id,
-        core_address: pathfinder_context.l1_core_address,
-        sequencer: pathfinder_context.gateway,
-        state: sync_state.clone(),
-        head_poll_interval: config.poll_interval,
-        pending_data: tx_pending,
-        // Currently p2p does not perform block hash and state commitment verification if p2p header lacks state commitment
-        block_validation_mode: state::l2::BlockValidationMode::Strict,
-        websocket_txs: rpc_server.get_topic_broadcasters().cloned(),
-        block_cache_size: 1_000,
-        restart_delay: config.debug.restart_delay,
-        verify_tree_hashes: config.verify_tree_hashes,
-        gossiper,
+    let (p2p_handle, gossiper) = if config.storage_read_only {
+        (tokio::spawn(std::future::pending()), Default::default())
+    } else {
+        start_p2p(pathfinder_context.network_id, p2p_storage, config.p2p).await?
     };
 
-    let sync_handle = if config.is_sync_enabled {
+    let sync_handle = if config.storage_read_only {
+        info!("Database opened in read-only mode, following updates made by another instance");
+        let replica_context = state::replica::ReplicaContext {
+            storage: sync_storage,
+            sequencer: pathfinder_context.gateway,
+            state: sync_state.clone(),
+            poll_interval: config.poll_interval,
+            pending_data: tx_pending,
+            websocket_txs: rpc_server.get_topic_broadcasters().cloned(),
+        };
+        tokio::spawn(state::replica::follow(replica_context))
+    } else if config.is_sync_enabled {
+        let ethereum = ethereum.expect("Ethereum is set up unless the database is read-only");
+        let sequencer_public_key =
+            sequencer_public_key(config.sequencer_public_key, pathfinder_context.network);
+        let sync_context = SyncContext {
+            storage: sync_storage,
+            ethereum: ethereum.client,
+            chain: pathfinder_context.network,
+            chain_id: pathfinder_context.network_id,
+            core_address: pathfinder_context.l1_core_address,
+            sequencer: pathfinder_context.gateway,
+            state: sync_state.clone(),
+            head_poll_interval: config.poll_interval,
+            pending_data: tx_pending,
+            // Currently p2p does not perform block hash and state commitment verification if p2p header lacks state commitment
+            block_validation_mode: state::l2::BlockValidationMode::Strict,
+            websocket_txs: rpc_server.get_topic_broadcasters().cloned(),
+            block_cache_size: 1_000,
+            restart_delay: config.debug.restart_delay,
+            verify_tree_hashes: config.verify_tree_hashes,
+            gossiper,
+            sequencer_public_key,
+            reject_invalid_signatures: config.reject_invalid_signatures,
+            verify_proofs: ethereum.verify_proofs,
+        };
         tokio::spawn(state::sync(sync_context, state::l1::sync, state::l2::sync))
     } else {
         tokio::spawn(std::future::pending())
     };
 
+    let admin_handle = match config.rpc_admin_address {
+        Some(addr) if config.is_rpc_enabled => {
+            let (admin_handle, local_addr) = rpc_server
+                .spawn_admin(addr)
+                .context("Starting the admin RPC server")?;
+            info!("📡 Admin HTTP-RPC server started on: {}", local_addr);
+            admin_handle
+        }
+        _ => tokio::spawn(std::future::pending()),
+    };
+
     let rpc_handle = if config.is_rpc_enabled {
         let (rpc_handle, local_addr) = rpc_server
             .with_max_connections(config.max_rpc_connections.get())
@@ -245,6 +386,9 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
     };
 
     tokio::spawn(update::poll_github_for_releases());
+    if !config.storage_read_only {
+        tokio::spawn(backfill::run(backfill_storage));
+    }
 
     // We are now ready.
     readiness.store(true, std::sync::atomic::Ordering::Relaxed);
@@ -263,6 +407,18 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
                 Err(err) => tracing::error!(error=%err, "RPC server process ended unexpectedly"),
             }
         }
+        result = admin_handle => {
+            match result {
+                Ok(_) => tracing::error!("Admin RPC server process ended unexpectedly"),
+                Err(err) => tracing::error!(error=%err, "Admin RPC server process ended unexpectedly"),
+            }
+        }
+        result = feeder_gateway_handle => {
+            match result {
+                Ok(_) => tracing::error!("Feeder gateway server process ended unexpectedly"),
+                Err(err) => tracing::error!(error=%err, "Feeder gateway server process ended unexpectedly"),
+            }
+        }
         result = p2p_handle => {
             match result {
                 Ok(_) => tracing::error!("P2P process ended unexpectedly"),
@@ -274,6 +430,31 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
     anyhow::bail!("Unexpected shutdown");
 }
 
+async fn run_command(command: config::Command) -> anyhow::Result<()> {
+    match command {
+        config::Command::Verify(config::VerifyCommand::Execution(args)) => {
+            tokio::task::spawn_blocking(move || verify::execution(args))
+                .await
+                .context("Joining verification task")?
+        }
+        config::Command::Db(config::DbCommand::Backup(args)) => {
+            tokio::task::spawn_blocking(move || db::backup(args))
+                .await
+                .context("Joining backup task")?
+        }
+        config::Command::Db(config::DbCommand::Check(args)) => {
+            tokio::task::spawn_blocking(move || db::check(args))
+                .await
+                .context("Joining check task")?
+        }
+        config::Command::Db(config::DbCommand::Rollback(args)) => {
+            tokio::task::spawn_blocking(move || db::rollback(args))
+                .await
+                .context("Joining rollback task")?
+        }
+    }
+}
+
 #[cfg(feature = "tokio-console")]
 fn setup_tracing(color: config::Color, pretty_log: bool) {
     use tracing_subscriber::prelude::*;
@@ -431,18 +612,43 @@ async fn spawn_monitoring(
 
 /// Convenience bundle for an Ethereum transport and chain.
 struct EthereumContext {
-    client: EthereumClient,
+    client: FailoverClient,
     chain: EthereumChain,
+    verify_proofs: bool,
 }
 
 impl EthereumContext {
     /// Configure an [EthereumContext]'s transport and read the chain ID using it.
-    async fn setup(url: reqwest::Url, password: Option<String>) -> anyhow::Result<Self> {
-        let client = if let Some(password) = password.as_ref() {
-            EthereumClient::with_password(url, password).context("Creating Ethereum client")?
-        } else {
-            EthereumClient::new(url).context("Creating Ethereum client")?
-        };
+    async fn setup(config: config::Ethereum) -> anyhow::Result<Self> {
+        let endpoints = config
+            .urls
+            .into_iter()
+            .enumerate()
+            .map(|(index, url)| {
+                // The path is left out as it may contain an API key, so the endpoint's position
+                // in the list tells apart endpoints on the same host.
+                let host = match url.port() {
+                    Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+                    None => url.host_str().unwrap_or_default().to_owned(),
+                };
+                let label = format!("{index}/{host}");
+                let client = if let Some(password) = config.password.as_ref() {
+                    EthereumClient::with_password(url, password)
+                } else {
+                    EthereumClient::new(url)
+                }
+                .context("Creating Ethereum client")?;
+                anyhow::Ok((label, client))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // The proofs are checked against the block hash which the quorum agrees on, as a single
+        // endpoint could otherwise report the hash of a block which matches its own forged proofs.
+        anyhow::ensure!(
+            !config.verify_proofs || config.quorum.get() >= 2,
+            "Verifying proofs requires a quorum of at least two Ethereum endpoints, but it is {}",
+            config.quorum
+        );
+        let client = FailoverClient::new(endpoints, config.quorum)?;
 
         let chain = client.get_chain().await.context(
             r"Determining Ethereum chain.
@@ -450,7 +656,11 @@ impl EthereumContext {
 Hint: Make sure the provided ethereum.url and ethereum.password are good.",
         )?;
 
-        Ok(Self { client, chain })
+        Ok(Self {
+            client,
+            chain,
+            verify_proofs: config.verify_proofs,
+        })
     }
 
     /// Maps the Ethereum network to its default Starknet network:
@@ -539,11 +749,11 @@ mod pathfinder_context {
                 },
                 NetworkConfig::Custom {
                     gateway,
-                    feeder_gateway,
+                    feeder_gateways,
                     chain_id,
                 } => Self::configure_custom(
                     gateway,
-                    feeder_gateway,
+                    feeder_gateways,
                     chain_id,
                     data_directory,
                     api_key,
@@ -560,7 +770,7 @@ mod pathfinder_context {
         /// the known networks.
         async fn configure_custom(
             gateway: Url,
-            feeder: Url,
+            feeder_gateways: Vec<Url>,
             chain_id: String,
             data_directory: PathBuf,
             api_key: Option<String>,
@@ -568,7 +778,7 @@ mod pathfinder_context {
             use pathfinder_crypto::Felt;
             use starknet_gateway_client::GatewayApi;
 
-            let gateway = GatewayClient::with_urls(gateway, feeder)
+            let gateway = GatewayClient::with_feeder_gateways(gateway, feeder_gateways)
                 .context("Creating gateway client")?
                 .with_api_key(api_key);
 
@@ -609,6 +819,26 @@ mod pathfinder_context {
     }
 }
 
+/// Returns the public key used to verify block signatures, or `None` if it is unknown in which
+/// case signatures are not verified.
+///
+/// The key is never fetched from the feeder gateway, as the signatures it serves would then only
+/// be checked against a key from the same source.
+fn sequencer_public_key(configured: Option<PublicKey>, network: Chain) -> Option<PublicKey> {
+    match (configured, network) {
+        (Some(public_key), _) => Some(public_key),
+        (None, Chain::Mainnet) => Some(MAINNET_SEQUENCER_PUBLIC_KEY),
+        (None, _) => {
+            tracing::warn!(
+                %network,
+                "No sequencer public key is known for this network, block signatures will NOT be \
+                verified. Set --sync.sequencer-public-key to verify them."
+            );
+            None
+        }
+    }
+}
+
 /// Errors if there is a mismatch between the starknet and ethereum networks.
 fn verify_networks(starknet: Chain, ethereum: EthereumChain) -> anyhow::Result<()> {
     if starknet != Chain::Custom {
@@ -0,0 +1,747 @@
+//! Re-executes stored blocks and reports where the results differ from what is in the database.
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroU32;
+
+use anyhow::Context;
+use pathfinder_common::receipt::{ExecutionStatus, Receipt};
+use pathfinder_common::state_update::StateUpdate;
+use pathfinder_common::transaction::Transaction;
+use pathfinder_common::{
+    BlockHeader, BlockNumber, Chain, ChainId, ClassHash, ContractAddress, ContractNonce,
+    StorageAddress, StorageValue, TransactionHash,
+};
+use pathfinder_crypto::Felt;
+use pathfinder_executor::types::{
+    ExecuteInvocation, FunctionInvocation, TransactionSimulation, TransactionTrace,
+};
+use pathfinder_executor::ExecutionState;
+use pathfinder_rpc::VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY;
+use pathfinder_storage::{BlockId, Storage};
+use rayon::prelude::*;
+use serde_json::json;
+
+use crate::config::VerifyExecutionArgs;
+
+/// Runs `pathfinder verify execution`.
+///
+/// Blocks are re-executed in parallel but the report is written in block order. The database is
+/// opened read-only and never migrated, so it must already be at the latest schema version.
+pub fn execution(args: VerifyExecutionArgs) -> anyhow::Result<()> {
+    let threads = rayon::current_num_threads();
+
+    let storage = Storage::open_read_only(args.database.clone(), 1)
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(threads as u32 + 1).unwrap())
+        .context("Creating database connection pool")?;
+
+    let (latest, chain_id) = {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let latest = tx
+            .block_id(BlockId::Latest)
+            .context("Fetching latest block number")?
+            .context("Database is empty")?
+            .0;
+
+        let chain_id = match args.chain_id {
+            Some(chain_id) => {
+                ChainId(Felt::from_be_slice(chain_id.as_bytes()).context("Parsing chain ID")?)
+            }
+            None => {
+                network_from_genesis(&tx)?
+                    .context("Unknown network, use --chain-id to specify its chain ID")?
+                    .1
+            }
+        };
+
+        (latest, chain_id)
+    };
+
+    let to = args.to.map(BlockNumber::new_or_panic).unwrap_or(latest);
+    anyhow::ensure!(
+        to <= latest,
+        "Block {to} is past the latest block in the database ({latest})"
+    );
+    anyhow::ensure!(args.from <= to.get(), "--from must not be after --to");
+
+    let report = File::create(&args.report)
+        .with_context(|| format!("Creating report file {}", args.report.display()))?;
+    let mut report = BufWriter::new(report);
+
+    tracing::info!(from=%args.from, %to, report=%args.report.display(), "Verifying execution");
+
+    let mut summary = Summary::default();
+
+    let blocks = (args.from..=to.get()).collect::<Vec<_>>();
+    for chunk in blocks.chunks(threads * 4) {
+        let outcomes = chunk
+            .par_iter()
+            .map(|&number| verify_block(&storage, chain_id, BlockNumber::new_or_panic(number)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Skipped => summary.skipped += 1,
+                Outcome::Verified {
+                    transactions,
+                    divergences,
+                } => {
+                    summary.verified += 1;
+                    summary.transactions += transactions;
+                    summary.divergences += divergences.len();
+
+                    for divergence in divergences {
+                        serde_json::to_writer(&mut report, &divergence)
+                            .context("Writing divergence to report")?;
+                        report.write_all(b"\n").context("Writing report")?;
+                    }
+                }
+            }
+        }
+
+        report.flush().context("Flushing report")?;
+
+        if let Some(last) = chunk.last() {
+            tracing::info!(block_number=%last, divergences=%summary.divergences, "Progress");
+        }
+    }
+
+    tracing::info!(
+        verified_blocks=%summary.verified,
+        skipped_blocks=%summary.skipped,
+        transactions=%summary.transactions,
+        divergences=%summary.divergences,
+        "Execution verification complete"
+    );
+
+    Ok(())
+}
+
+/// Identifies a known network by its genesis block hash, returning `None` for other networks.
+pub(crate) fn network_from_genesis(
+    tx: &pathfinder_storage::Transaction<'_>,
+) -> anyhow::Result<Option<(Chain, ChainId)>> {
+    use pathfinder_common::consts::{
+        GOERLI_INTEGRATION_GENESIS_HASH, GOERLI_TESTNET_GENESIS_HASH, MAINNET_GENESIS_HASH,
+        SEPOLIA_INTEGRATION_GENESIS_HASH, SEPOLIA_TESTNET_GENESIS_HASH,
+    };
+
+    let (_, genesis_hash) = tx
+        .block_id(BlockNumber::GENESIS.into())
+        .context("Fetching genesis hash")?
+        .context("Genesis block is missing")?;
+
+    let network = match genesis_hash {
+        MAINNET_GENESIS_HASH => (Chain::Mainnet, ChainId::MAINNET),
+        GOERLI_TESTNET_GENESIS_HASH => (Chain::GoerliTestnet, ChainId::GOERLI_TESTNET),
+        GOERLI_INTEGRATION_GENESIS_HASH => (Chain::GoerliIntegration, ChainId::GOERLI_INTEGRATION),
+        SEPOLIA_TESTNET_GENESIS_HASH => (Chain::SepoliaTestnet, ChainId::SEPOLIA_TESTNET),
+        SEPOLIA_INTEGRATION_GENESIS_HASH => {
+            (Chain::SepoliaIntegration, ChainId::SEPOLIA_INTEGRATION)
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(network))
+}
+
+#[derive(Default)]
+struct Summary {
+    verified: usize,
+    skipped: usize,
+    transactions: usize,
+    divergences: usize,
+}
+
+enum Outcome {
+    /// The block predates the versions the executor can faithfully reproduce.
+    Skipped,
+    Verified {
+        transactions: usize,
+        divergences: Vec<Divergence>,
+    },
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    ExecutionFailed,
+    ExecutionStatus,
+    Fee,
+    Events,
+    Messages,
+    StorageValue,
+    Nonce,
+    ClassHash,
+    DeclaredClasses,
+}
+
+/// A single line of the report.
+#[derive(Debug, serde::Serialize)]
+struct Divergence {
+    block_number: BlockNumber,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction_hash: Option<TransactionHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction_index: Option<usize>,
+    kind: Kind,
+    expected: serde_json::Value,
+    actual: serde_json::Value,
+}
+
+fn verify_block(
+    storage: &Storage,
+    chain_id: ChainId,
+    block_number: BlockNumber,
+) -> anyhow::Result<Outcome> {
+    let _span = tracing::debug_span!("verify", %block_number).entered();
+
+    let mut connection = storage
+        .connection()
+        .context("Creating database connection")?;
+    let db = connection
+        .transaction()
+        .context("Creating database transaction")?;
+
+    let block_id = BlockId::Number(block_number);
+    let header = db
+        .block_header(block_id)
+        .context("Fetching block header")?
+        .context("Block header is missing")?;
+
+    let version = header
+        .starknet_version
+        .parse_as_semver()
+        .context("Parsing starknet version")?
+        .unwrap_or(semver::Version::new(0, 0, 0));
+    if version < VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY {
+        return Ok(Outcome::Skipped);
+    }
+
+    let (transactions, receipts): (Vec<_>, Vec<_>) = db
+        .transaction_data_for_block(block_id)
+        .context("Fetching transactions")?
+        .context("Transaction data is missing")?
+        .into_iter()
+        .unzip();
+    let state_update = db
+        .state_update(block_id)
+        .context("Fetching state update")?
+        .context("State update is missing")?;
+
+    let simulations = execute(&db, chain_id, header.clone(), &transactions);
+
+    let simulations = match simulations {
+        Ok(simulations) => simulations,
+        Err((transaction_index, error)) => {
+            let divergence = Divergence {
+                block_number,
+                transaction_hash: transaction_index.map(|i| transactions[i].hash),
+                transaction_index,
+                kind: Kind::ExecutionFailed,
+                expected: json!(null),
+                actual: json!(format!("{error:#}")),
+            };
+
+            return Ok(Outcome::Verified {
+                transactions: transactions.len(),
+                divergences: vec![divergence],
+            });
+        }
+    };
+
+    let mut divergences = Vec::new();
+    for (index, (simulation, receipt)) in simulations.iter().zip(&receipts).enumerate() {
+        compare_receipt(block_number, index, simulation, receipt, &mut divergences);
+    }
+    compare_state_update(&db, &header, &simulations, &state_update, &mut divergences)?;
+
+    Ok(Outcome::Verified {
+        transactions: transactions.len(),
+        divergences,
+    })
+}
+
+/// Re-executes the transactions on top of the parent block's state.
+///
+/// On failure, returns the index of the failing transaction if it is known.
+fn execute(
+    db: &pathfinder_storage::Transaction<'_>,
+    chain_id: ChainId,
+    header: BlockHeader,
+    transactions: &[Transaction],
+) -> Result<Vec<TransactionSimulation>, (Option<usize>, anyhow::Error)> {
+    let executables = transactions
+        .iter()
+        .enumerate()
+        .map(|(i, tx)| {
+            pathfinder_rpc::compose_executor_transaction(tx, db)
+                .map_err(|e| (Some(i), e.context("Composing transaction")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let state = ExecutionState::trace(db, chain_id, header, None);
+
+    pathfinder_executor::simulate(state, executables, false, false).map_err(|error| match error {
+        pathfinder_executor::TransactionExecutionError::ExecutionError {
+            transaction_index,
+            error,
+        } => (Some(transaction_index), anyhow::anyhow!(error)),
+        pathfinder_executor::TransactionExecutionError::Internal(error)
+        | pathfinder_executor::TransactionExecutionError::Custom(error) => (None, error),
+    })
+}
+
+fn compare_receipt(
+    block_number: BlockNumber,
+    index: usize,
+    simulation: &TransactionSimulation,
+    receipt: &Receipt,
+    divergences: &mut Vec<Divergence>,
+) {
+    let mut diverged = |kind, expected, actual| {
+        divergences.push(Divergence {
+            block_number,
+            transaction_hash: Some(receipt.transaction_hash),
+            transaction_index: Some(index),
+            kind,
+            expected,
+            actual,
+        })
+    };
+
+    let expected_reason = match &receipt.execution_status {
+        ExecutionStatus::Succeeded => None,
+        ExecutionStatus::Reverted { reason } => Some(reason.as_str()),
+    };
+    let actual_reason = simulation.revert_reason();
+    if expected_reason.is_some() != actual_reason.is_some() {
+        diverged(
+            Kind::ExecutionStatus,
+            json!({ "reverted": expected_reason.is_some(), "revert_reason": expected_reason }),
+            json!({ "reverted": actual_reason.is_some(), "revert_reason": actual_reason }),
+        );
+    }
+
+    // L1 handler receipts have no fee, or a fee of zero.
+    if let Some(expected) = receipt.actual_fee.filter(|fee| fee.0 != Felt::ZERO) {
+        let expected = primitive_types::U256::from_big_endian(expected.0.as_be_bytes());
+        let actual = simulation.fee_estimation.overall_fee;
+
+        if actual != expected {
+            diverged(
+                Kind::Fee,
+                json!(format!("{expected:#x}")),
+                json!(format!("{actual:#x}")),
+            );
+        }
+    }
+
+    let invocations = invocations(&simulation.trace);
+
+    let actual_events = invocations
+        .iter()
+        .flat_map(|invocation| {
+            let mut events = Vec::new();
+            collect(invocation, &mut |invocation| {
+                events.extend(invocation.events.iter().map(|event| {
+                    let keys = event.keys.clone();
+                    (
+                        event.order,
+                        invocation.contract_address.0,
+                        keys,
+                        event.data.clone(),
+                    )
+                }))
+            });
+            events.sort_by_key(|event| event.0);
+            events
+        })
+        .map(|(_, from, keys, data)| (from, keys, data))
+        .collect::<Vec<_>>();
+    let expected_events = receipt
+        .events
+        .iter()
+        .map(|event| {
+            (
+                event.from_address.0,
+                event.keys.iter().map(|key| key.0).collect::<Vec<_>>(),
+                event.data.iter().map(|data| data.0).collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    if actual_events != expected_events {
+        diverged(Kind::Events, json!(expected_events), json!(actual_events));
+    }
+
+    let actual_messages = invocations
+        .iter()
+        .flat_map(|invocation| {
+            let mut messages = Vec::new();
+            collect(invocation, &mut |invocation| {
+                messages.extend(invocation.messages.iter().map(|message| {
+                    (
+                        message.order,
+                        message.from_address,
+                        message.to_address,
+                        message.payload.clone(),
+                    )
+                }))
+            });
+            messages.sort_by_key(|message| message.0);
+            messages
+        })
+        .map(|(_, from, to, payload)| (from, to, payload))
+        .collect::<Vec<_>>();
+    let expected_messages = receipt
+        .l2_to_l1_messages
+        .iter()
+        .map(|message| {
+            (
+                message.from_address.0,
+                Felt::from_be_slice(message.to_address.0.as_bytes())
+                    .expect("Ethereum address fits in a felt"),
+                message.payload.iter().map(|x| x.0).collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    if actual_messages != expected_messages {
+        diverged(
+            Kind::Messages,
+            json!(expected_messages),
+            json!(actual_messages),
+        );
+    }
+}
+
+/// The top-level invocations of a trace, in the order the sequencer emits their events.
+fn invocations(trace: &TransactionTrace) -> Vec<&FunctionInvocation> {
+    match trace {
+        TransactionTrace::Declare(trace) => [
+            trace.validate_invocation.as_ref(),
+            trace.fee_transfer_invocation.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        TransactionTrace::DeployAccount(trace) => [
+            trace.validate_invocation.as_ref(),
+            trace.constructor_invocation.as_ref(),
+            trace.fee_transfer_invocation.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        TransactionTrace::Invoke(trace) => {
+            let execute = match &trace.execute_invocation {
+                ExecuteInvocation::FunctionInvocation(invocation) => invocation.as_ref(),
+                ExecuteInvocation::RevertedReason(_) => None,
+            };
+            [
+                trace.validate_invocation.as_ref(),
+                execute,
+                trace.fee_transfer_invocation.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect()
+        }
+        TransactionTrace::L1Handler(trace) => trace.function_invocation.iter().collect(),
+    }
+}
+
+fn collect<'a>(invocation: &'a FunctionInvocation, f: &mut impl FnMut(&'a FunctionInvocation)) {
+    f(invocation);
+    for call in &invocation.internal_calls {
+        collect(call, f);
+    }
+}
+
+#[derive(Default)]
+struct ExecutedUpdate {
+    storage: HashMap<StorageAddress, StorageValue>,
+    class: Option<ClassHash>,
+    nonce: Option<ContractNonce>,
+}
+
+/// Compares the combined state diff of the re-executed transactions against the stored state update.
+///
+/// Writes which leave a value unchanged are not always part of the stored state update, so a value
+/// that is missing on one side is compared against the parent block's value instead.
+fn compare_state_update(
+    db: &pathfinder_storage::Transaction<'_>,
+    header: &BlockHeader,
+    simulations: &[TransactionSimulation],
+    expected: &StateUpdate,
+    divergences: &mut Vec<Divergence>,
+) -> anyhow::Result<()> {
+    let mut actual = HashMap::<ContractAddress, ExecutedUpdate>::new();
+    let mut deprecated_declared = HashSet::new();
+    let mut declared = HashMap::new();
+
+    for simulation in simulations {
+        let diff = match &simulation.trace {
+            TransactionTrace::Declare(trace) => &trace.state_diff,
+            TransactionTrace::DeployAccount(trace) => &trace.state_diff,
+            TransactionTrace::Invoke(trace) => &trace.state_diff,
+            TransactionTrace::L1Handler(trace) => &trace.state_diff,
+        };
+
+        for (address, storage) in &diff.storage_diffs {
+            let update = actual.entry(*address).or_default();
+            update
+                .storage
+                .extend(storage.iter().map(|diff| (diff.key, diff.value)));
+        }
+        for (address, nonce) in &diff.nonces {
+            actual.entry(*address).or_default().nonce = Some(*nonce);
+        }
+        for deployed in &diff.deployed_contracts {
+            actual.entry(deployed.address).or_default().class = Some(deployed.class_hash);
+        }
+        for replaced in &diff.replaced_classes {
+            actual.entry(replaced.contract_address).or_default().class = Some(replaced.class_hash);
+        }
+
+        deprecated_declared.extend(diff.deprecated_declared_classes.iter().copied());
+        declared.extend(
+            diff.declared_classes
+                .iter()
+                .map(|class| (class.class_hash, class.compiled_class_hash)),
+        );
+    }
+    // System contracts are written by the block itself and not by any of its transactions.
+    actual.remove(&ContractAddress::ONE);
+
+    let parent = header.number.parent().map(BlockId::Number);
+    let mut diverged = |kind, expected, actual| {
+        divergences.push(Divergence {
+            block_number: header.number,
+            transaction_hash: None,
+            transaction_index: None,
+            kind,
+            expected,
+            actual,
+        })
+    };
+
+    let addresses = actual
+        .keys()
+        .chain(expected.contract_updates.keys())
+        .collect::<HashSet<_>>();
+    for address in addresses {
+        let actual = actual.get(address);
+        let expected = expected.contract_updates.get(address);
+
+        let keys = actual
+            .into_iter()
+            .flat_map(|update| update.storage.keys())
+            .chain(
+                expected
+                    .into_iter()
+                    .flat_map(|update| update.storage.keys()),
+            )
+            .collect::<HashSet<_>>();
+        for key in keys {
+            let actual = actual.and_then(|update| update.storage.get(key)).copied();
+            let expected = expected.and_then(|update| update.storage.get(key)).copied();
+
+            if actual == expected {
+                continue;
+            }
+
+            let previous = match parent {
+                Some(parent) => db
+                    .storage_value(parent, *address, *key)
+                    .context("Fetching parent storage value")?,
+                None => None,
+            }
+            .unwrap_or_default();
+
+            let actual = actual.unwrap_or(previous);
+            let expected = expected.unwrap_or(previous);
+            if actual != expected {
+                diverged(
+                    Kind::StorageValue,
+                    json!({ "contract_address": address, "key": key, "value": expected }),
+                    json!({ "contract_address": address, "key": key, "value": actual }),
+                );
+            }
+        }
+
+        let actual_nonce = actual.and_then(|update| update.nonce);
+        let expected_nonce = expected.and_then(|update| update.nonce);
+        if actual_nonce != expected_nonce {
+            let previous = match parent {
+                Some(parent) => db
+                    .contract_nonce(*address, parent)
+                    .context("Fetching parent nonce")?,
+                None => None,
+            }
+            .unwrap_or_default();
+
+            let actual = actual_nonce.unwrap_or(previous);
+            let expected = expected_nonce.unwrap_or(previous);
+            if actual != expected {
+                diverged(
+                    Kind::Nonce,
+                    json!({ "contract_address": address, "nonce": expected }),
+                    json!({ "contract_address": address, "nonce": actual }),
+                );
+            }
+        }
+
+        let actual_class = actual.and_then(|update| update.class);
+        let expected_class =
+            expected.and_then(|update| update.class.as_ref().map(|class| class.class_hash()));
+        if actual_class != expected_class {
+            let previous = match parent {
+                Some(parent) => db
+                    .contract_class_hash(parent, *address)
+                    .context("Fetching parent class hash")?,
+                None => None,
+            };
+
+            let actual = actual_class.or(previous);
+            let expected = expected_class.or(previous);
+            if actual != expected {
+                diverged(
+                    Kind::ClassHash,
+                    json!({ "contract_address": address, "class_hash": expected }),
+                    json!({ "contract_address": address, "class_hash": actual }),
+                );
+            }
+        }
+    }
+
+    if deprecated_declared != expected.declared_cairo_classes
+        || declared != expected.declared_sierra_classes
+    {
+        diverged(
+            Kind::DeclaredClasses,
+            json!({
+                "cairo": expected.declared_cairo_classes,
+                "sierra": expected.declared_sierra_classes.iter().collect::<Vec<_>>(),
+            }),
+            json!({
+                "cairo": deprecated_declared,
+                "sierra": declared.iter().collect::<Vec<_>>(),
+            }),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::StarknetVersion;
+    use pathfinder_storage::JournalMode;
+
+    use super::*;
+
+    /// Creates a database with an empty genesis block and a block 1 without any transactions
+    /// but with the given state update.
+    fn setup(path: &Path, state_update: StateUpdate) {
+        let storage = Storage::migrate(path.to_owned(), JournalMode::WAL, 1)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let genesis = BlockHeader::builder()
+            .with_starknet_version(StarknetVersion::new(0, 13, 0))
+            .finalize_with_hash(block_hash!("0xb00"));
+        let header = genesis
+            .child_builder()
+            .with_starknet_version(StarknetVersion::new(0, 13, 0))
+            .finalize_with_hash(block_hash!("0xb01"));
+
+        for (header, state_update) in [(genesis, StateUpdate::default()), (header, state_update)] {
+            tx.insert_block_header(&header).unwrap();
+            tx.insert_transaction_data(header.hash, header.number, &[])
+                .unwrap();
+            tx.insert_state_update(header.number, &state_update.with_block_hash(header.hash))
+                .unwrap();
+        }
+
+        tx.commit().unwrap();
+    }
+
+    fn args(path: &Path, report: &Path) -> VerifyExecutionArgs {
+        VerifyExecutionArgs {
+            database: path.to_owned(),
+            from: 1,
+            to: None,
+            report: report.to_owned(),
+            chain_id: Some("SN_GOERLI".to_owned()),
+        }
+    }
+
+    fn verify(path: &Path, report: &Path) -> Vec<serde_json::Value> {
+        execution(args(path, report)).unwrap();
+
+        std::fs::read_to_string(report)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn matching_block_has_no_divergences() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("test.sqlite");
+        setup(&database, StateUpdate::default());
+
+        let divergences = verify(&database, &dir.path().join("report.jsonl"));
+
+        assert!(divergences.is_empty(), "{divergences:?}");
+    }
+
+    #[test]
+    fn storage_divergence_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("test.sqlite");
+        let state_update = StateUpdate::default().with_storage_update(
+            contract_address!("0x123"),
+            storage_address!("0x1"),
+            storage_value!("0x5"),
+        );
+        setup(&database, state_update);
+
+        let divergences = verify(&database, &dir.path().join("report.jsonl"));
+
+        assert_eq!(
+            divergences,
+            vec![json!({
+                "block_number": 1,
+                "kind": "storage_value",
+                "expected": { "contract_address": "0x123", "key": "0x1", "value": "0x5" },
+                "actual": { "contract_address": "0x123", "key": "0x1", "value": "0x0" },
+            })]
+        );
+    }
+
+    #[test]
+    fn outdated_database_is_not_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("test.sqlite");
+        // An empty file is a database at schema version zero.
+        std::fs::write(&database, b"").unwrap();
+
+        execution(args(&database, &dir.path().join("report.jsonl"))).unwrap_err();
+
+        assert_eq!(std::fs::metadata(&database).unwrap().len(), 0);
+        assert!(!dir.path().join("test.sqlite-wal").exists());
+    }
+}
@@ -0,0 +1,812 @@
+//! Serves the feeder gateway endpoints which are required to sync, using data from the node's
+//! own database. This allows other nodes to sync from this node instead of the Starknet gateway.
+//!
+//! Replies use the same JSON as the Starknet feeder gateway, see [starknet_gateway_types::reply].
+//! The following endpoints are served under `/feeder_gateway`:
+//!
+//! - `get_block`
+//! - `get_state_update`
+//! - `get_class_by_hash`
+//! - `get_compiled_class_by_class_hash`
+//! - `get_signature`
+//! - `get_transaction`
+//! - `get_contract_addresses`
+use std::net::SocketAddr;
+
+use anyhow::Context;
+use pathfinder_common::receipt::ExecutionStatus;
+use pathfinder_common::{BlockHash, BlockNumber, ClassHash, TransactionHash};
+use pathfinder_rpc::PendingWatcher;
+use pathfinder_storage::{BlockId, Storage};
+use primitive_types::H160;
+use serde::{Deserialize, Serialize};
+use starknet_gateway_types::error::{KnownStarknetErrorCode, StarknetError};
+use starknet_gateway_types::reply::{self, transaction_status, Status};
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+/// Spawns a server which serves the feeder gateway endpoints at `addr`.
+///
+/// Returns the server's task and the address it is listening on.
+pub fn spawn_server(
+    addr: SocketAddr,
+    storage: Storage,
+    pending: PendingWatcher,
+    core_address: H160,
+) -> anyhow::Result<(tokio::task::JoinHandle<()>, SocketAddr)> {
+    let (addr, server) = warp::serve(routes(storage, pending, core_address))
+        .try_bind_ephemeral(addr)
+        .context("Binding feeder gateway server address")?;
+
+    Ok((tokio::spawn(server), addr))
+}
+
+fn routes(
+    storage: Storage,
+    pending: PendingWatcher,
+    core_address: H160,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    let query = warp::any().map(move || (storage.clone(), pending.clone()));
+
+    let get_block = warp::path!("get_block")
+        .and(warp::query::<BlockParams>())
+        .and(query.clone())
+        .then(|params, (storage, pending)| {
+            reply(storage, pending, |tx, p| get_block(tx, p, params))
+        });
+
+    let get_state_update = warp::path!("get_state_update")
+        .and(warp::query::<BlockParams>())
+        .and(query.clone())
+        .then(|params, (storage, pending)| {
+            reply(storage, pending, |tx, p| get_state_update(tx, p, params))
+        });
+
+    let get_signature = warp::path!("get_signature")
+        .and(warp::query::<BlockParams>())
+        .and(query.clone())
+        .then(|params, (storage, pending)| {
+            reply(storage, pending, |tx, _| get_signature(tx, params))
+        });
+
+    let get_class_by_hash = warp::path!("get_class_by_hash")
+        .and(warp::query::<ClassParams>())
+        .and(query.clone())
+        .then(|params: ClassParams, (storage, pending)| {
+            reply(storage, pending, move |tx, _| {
+                let definition = tx
+                    .class_definition(params.class_hash)
+                    .context("Reading class definition from database")?;
+                definition.map(json_bytes).ok_or(Error::UndeclaredClass)
+            })
+        });
+
+    let get_compiled_class_by_class_hash = warp::path!("get_compiled_class_by_class_hash")
+        .and(warp::query::<ClassParams>())
+        .and(query.clone())
+        .then(|params: ClassParams, (storage, pending)| {
+            reply(storage, pending, move |tx, _| {
+                let definition = tx
+                    .casm_definition(params.class_hash)
+                    .context("Reading compiled class definition from database")?;
+                definition.map(json_bytes).ok_or(Error::UndeclaredClass)
+            })
+        });
+
+    let get_transaction = warp::path!("get_transaction")
+        .and(warp::query::<TransactionParams>())
+        .and(query)
+        .then(|params, (storage, pending)| {
+            reply(storage, pending, |tx, _| get_transaction(tx, params))
+        });
+
+    let get_contract_addresses = warp::path!("get_contract_addresses").map(move || {
+        let addresses = serde_json::json!({ "Starknet": format!("{core_address:#x}") });
+        warp::reply::json(&addresses).into_response()
+    });
+
+    warp::get()
+        .and(warp::path("feeder_gateway"))
+        .and(
+            get_block
+                .or(get_state_update)
+                .unify()
+                .or(get_signature)
+                .unify()
+                .or(get_class_by_hash)
+                .unify()
+                .or(get_compiled_class_by_class_hash)
+                .unify()
+                .or(get_transaction)
+                .unify()
+                .or(get_contract_addresses)
+                .unify(),
+        )
+        .with(warp::filters::trace::request())
+}
+
+/// Errors which are returned as Starknet errors, like the feeder gateway does.
+#[derive(Debug)]
+enum Error {
+    BlockNotFound,
+    /// The block is stored without its signature, which older databases may lack.
+    SignatureNotFound,
+    UndeclaredClass,
+    MalformedRequest(&'static str),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl Reply for Error {
+    fn into_response(self) -> Response {
+        let (code, message) = match self {
+            Error::BlockNotFound => (KnownStarknetErrorCode::BlockNotFound, "Block not found"),
+            Error::SignatureNotFound => (
+                KnownStarknetErrorCode::BlockNotFound,
+                "Block signature not found",
+            ),
+            Error::UndeclaredClass => (KnownStarknetErrorCode::UndeclaredClass, "Class not found"),
+            Error::MalformedRequest(message) => (KnownStarknetErrorCode::MalformedRequest, message),
+            Error::Internal(e) => {
+                tracing::error!(error=?e, "Feeder gateway request failed");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        let error = StarknetError {
+            code: code.into(),
+            message: message.to_owned(),
+        };
+        warp::reply::with_status(warp::reply::json(&error), StatusCode::BAD_REQUEST).into_response()
+    }
+}
+
+/// Runs `f` against a database transaction on a blocking thread and replies with its result.
+async fn reply<F>(storage: Storage, pending: PendingWatcher, f: F) -> Response
+where
+    F: FnOnce(&pathfinder_storage::Transaction<'_>, &PendingWatcher) -> Result<Response, Error>
+        + Send
+        + 'static,
+{
+    let result = tokio::task::spawn_blocking(move || {
+        let mut connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        f(&tx, &pending)
+    })
+    .await
+    .context("Joining blocking task")
+    .map_err(Error::Internal)
+    .and_then(|result| result);
+
+    match result {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Replies with JSON which is already serialized, such as class definitions.
+fn json_bytes(json: Vec<u8>) -> Response {
+    warp::reply::with_header(json, "content-type", "application/json").into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockParams {
+    #[serde(default, rename = "blockNumber")]
+    block_number: Option<String>,
+    #[serde(default, rename = "blockHash")]
+    block_hash: Option<BlockHash>,
+    #[serde(default, rename = "headerOnly")]
+    header_only: bool,
+    #[serde(default, rename = "includeBlock")]
+    include_block: bool,
+}
+
+/// A block selected by [BlockParams].
+enum Block {
+    Pending,
+    Stored(BlockId),
+}
+
+impl BlockParams {
+    fn block(&self) -> Result<Block, Error> {
+        match (&self.block_number, self.block_hash) {
+            (Some(number), None) => match number.as_str() {
+                "pending" => Ok(Block::Pending),
+                "latest" => Ok(Block::Stored(BlockId::Latest)),
+                number => number
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(BlockNumber::new)
+                    .map(|n| Block::Stored(n.into()))
+                    .ok_or(Error::MalformedRequest("Invalid block number")),
+            },
+            (None, Some(hash)) => Ok(Block::Stored(hash.into())),
+            // The feeder gateway defaults to the latest block.
+            (None, None) => Ok(Block::Stored(BlockId::Latest)),
+            (Some(_), Some(_)) => Err(Error::MalformedRequest(
+                "Only one of blockNumber and blockHash can be given",
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ClassParams {
+    #[serde(rename = "classHash")]
+    class_hash: ClassHash,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionParams {
+    #[serde(rename = "transactionHash")]
+    transaction_hash: TransactionHash,
+}
+
+fn get_block(
+    tx: &pathfinder_storage::Transaction<'_>,
+    pending: &PendingWatcher,
+    params: BlockParams,
+) -> Result<Response, Error> {
+    let block = match params.block()? {
+        Block::Pending => {
+            let pending = pending.get(tx).context("Fetching pending data")?;
+            return Ok(warp::reply::json(&*pending.block).into_response());
+        }
+        Block::Stored(block) => block,
+    };
+
+    if params.header_only {
+        #[derive(Serialize)]
+        struct Header {
+            block_hash: BlockHash,
+            block_number: BlockNumber,
+        }
+
+        let (block_number, block_hash) = tx
+            .block_id(block)
+            .context("Fetching block id")?
+            .ok_or(Error::BlockNotFound)?;
+        return Ok(warp::reply::json(&Header {
+            block_hash,
+            block_number,
+        })
+        .into_response());
+    }
+
+    let block = stored_block(tx, block)?;
+    Ok(warp::reply::json(&block).into_response())
+}
+
+fn get_state_update(
+    tx: &pathfinder_storage::Transaction<'_>,
+    pending: &PendingWatcher,
+    params: BlockParams,
+) -> Result<Response, Error> {
+    let response = match params.block()? {
+        Block::Pending => {
+            let pending = pending.get(tx).context("Fetching pending data")?;
+            let state_update = reply::StateUpdate::from((*pending.state_update).clone());
+
+            if params.include_block {
+                serde_json::json!({ "block": &*pending.block, "state_update": state_update })
+            } else {
+                serde_json::to_value(state_update).context("Serializing state update")?
+            }
+        }
+        Block::Stored(block) => {
+            let state_update = tx
+                .state_update(block)
+                .context("Fetching state update")?
+                .ok_or(Error::BlockNotFound)?;
+            let state_update = reply::StateUpdate::from(state_update);
+
+            if params.include_block {
+                let block = stored_block(tx, block)?;
+                serde_json::json!({ "block": block, "state_update": state_update })
+            } else {
+                serde_json::to_value(state_update).context("Serializing state update")?
+            }
+        }
+    };
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+fn get_signature(
+    tx: &pathfinder_storage::Transaction<'_>,
+    params: BlockParams,
+) -> Result<Response, Error> {
+    let Block::Stored(block) = params.block()? else {
+        return Err(Error::BlockNotFound);
+    };
+
+    let (block_number, block_hash) = tx
+        .block_id(block)
+        .context("Fetching block id")?
+        .ok_or(Error::BlockNotFound)?;
+    let signature = tx
+        .signature(block_number.into())
+        .context("Fetching signature")?
+        .ok_or(Error::SignatureNotFound)?;
+    let state_update = tx
+        .state_update(block_number.into())
+        .context("Fetching state update")?
+        .context("State update missing")?;
+
+    let signature = reply::BlockSignature {
+        block_number,
+        signature: [signature.r, signature.s],
+        signature_input: reply::BlockSignatureInput {
+            block_hash,
+            state_diff_commitment: state_update.compute_state_diff_commitment(),
+        },
+    };
+
+    Ok(warp::reply::json(&signature).into_response())
+}
+
+fn get_transaction(
+    tx: &pathfinder_storage::Transaction<'_>,
+    params: TransactionParams,
+) -> Result<Response, Error> {
+    let Some((transaction, receipt, block_hash)) = tx
+        .transaction_with_receipt(params.transaction_hash)
+        .context("Fetching transaction")?
+    else {
+        return Ok(warp::reply::json(&serde_json::json!({
+            "status": Status::NotReceived,
+            "finality_status": Status::NotReceived,
+        }))
+        .into_response());
+    };
+
+    let (block_number, _) = tx
+        .block_id(block_hash.into())
+        .context("Fetching block id")?
+        .context("Block of transaction is missing")?;
+    let status = block_status(tx, block_number)?;
+
+    let (execution_status, revert_error) = match receipt.execution_status {
+        ExecutionStatus::Succeeded => (transaction_status::ExecutionStatus::Succeeded, None),
+        ExecutionStatus::Reverted { reason } => {
+            (transaction_status::ExecutionStatus::Reverted, Some(reason))
+        }
+    };
+
+    Ok(warp::reply::json(&reply::TransactionWithStatus {
+        status,
+        finality_status: status,
+        execution_status,
+        revert_error,
+        block_hash,
+        block_number,
+        transaction_index: receipt.transaction_index,
+        transaction,
+    })
+    .into_response())
+}
+
+fn stored_block(
+    tx: &pathfinder_storage::Transaction<'_>,
+    block: BlockId,
+) -> Result<reply::Block, Error> {
+    let header = tx
+        .block_header(block)
+        .context("Fetching block header")?
+        .ok_or(Error::BlockNotFound)?;
+
+    let (transactions, transaction_receipts) = tx
+        .transaction_data_for_block(header.number.into())
+        .context("Fetching transactions")?
+        .context("Transaction data missing")?
+        .into_iter()
+        .unzip();
+
+    Ok(reply::Block {
+        block_hash: header.hash,
+        block_number: header.number,
+        eth_l1_gas_price: Some(header.eth_l1_gas_price),
+        strk_l1_gas_price: Some(header.strk_l1_gas_price),
+        parent_block_hash: header.parent_hash,
+        sequencer_address: Some(header.sequencer_address),
+        state_commitment: header.state_commitment,
+        status: block_status(tx, header.number)?,
+        timestamp: header.timestamp,
+        transaction_receipts,
+        transactions,
+        starknet_version: header.starknet_version,
+    })
+}
+
+fn block_status(
+    tx: &pathfinder_storage::Transaction<'_>,
+    number: BlockNumber,
+) -> Result<Status, Error> {
+    let l1_accepted = tx
+        .block_is_l1_accepted(number.into())
+        .context("Querying block status")?;
+
+    Ok(if l1_accepted {
+        Status::AcceptedOnL1
+    } else {
+        Status::AcceptedOnL2
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_storage::fake::StorageInitializer;
+
+    use super::*;
+
+    fn setup() -> (
+        impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone,
+        StorageInitializer,
+        Storage,
+    ) {
+        let storage = Storage::in_memory().unwrap();
+        let blocks = pathfinder_storage::fake::with_n_blocks(&storage, 3);
+        let (_, rx) = tokio::sync::watch::channel(Default::default());
+
+        let filter = routes(storage.clone(), PendingWatcher::new(rx), H160::zero());
+        (filter, blocks, storage)
+    }
+
+    async fn get(
+        filter: &(impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone + 'static),
+        path: &str,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = warp::test::request()
+            .path(&format!("/feeder_gateway/{path}"))
+            .reply(filter)
+            .await;
+        let body = serde_json::from_slice(response.body()).unwrap();
+        (response.status(), body)
+    }
+
+    /// Parses a reply body, going through a string so that fields which borrow can deserialize.
+    fn parse<T: serde::de::DeserializeOwned>(body: serde_json::Value) -> T {
+        serde_json::from_str(&body.to_string()).unwrap()
+    }
+
+    /// Sorts the state diff's lists, whose order depends on storage.
+    fn sorted(mut state_update: reply::StateUpdate) -> reply::StateUpdate {
+        let diff = &mut state_update.state_diff;
+        diff.storage_diffs
+            .values_mut()
+            .for_each(|diffs| diffs.sort());
+        diff.deployed_contracts.sort();
+        diff.declared_classes.sort();
+        diff.replaced_classes.sort();
+        state_update
+    }
+
+    #[tokio::test]
+    async fn block() {
+        let (filter, blocks, _) = setup();
+        let (header, _, transactions, ..) = &blocks[1];
+
+        let (status, body) = get(&filter, "get_block?blockNumber=1").await;
+        assert_eq!(status, StatusCode::OK);
+        let block = parse::<reply::Block>(body);
+
+        assert_eq!(block.block_hash, header.hash);
+        assert_eq!(block.block_number, header.number);
+        assert_eq!(block.status, Status::AcceptedOnL2);
+        let expected = transactions.iter().map(|(t, _)| t.hash).collect::<Vec<_>>();
+        let hashes = block
+            .transactions
+            .iter()
+            .map(|t| t.hash)
+            .collect::<Vec<_>>();
+        assert_eq!(hashes, expected);
+    }
+
+    #[tokio::test]
+    async fn block_header_only() {
+        let (filter, blocks, _) = setup();
+        let header = &blocks[0].0;
+
+        let path = format!("get_block?blockHash={}&headerOnly=true", header.hash);
+        let (status, body) = get(&filter, &path).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "block_hash": header.hash,
+                "block_number": header.number,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let (filter, ..) = setup();
+
+        let (status, body) = get(&filter, "get_block?blockNumber=10").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = serde_json::from_value::<StarknetError>(body).unwrap();
+        assert_eq!(error.code, KnownStarknetErrorCode::BlockNotFound.into());
+    }
+
+    #[tokio::test]
+    async fn class_by_hash() {
+        let (filter, _, storage) = setup();
+        let class_hash = ClassHash(pathfinder_crypto::Felt::from_u64(0xc1a55));
+        let definition = serde_json::json!({ "abi": [], "program": {} });
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        tx.insert_cairo_class(class_hash, definition.to_string().as_bytes())
+            .unwrap();
+        tx.commit().unwrap();
+
+        let path = format!("get_class_by_hash?classHash={class_hash}");
+        let (status, body) = get(&filter, &path).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, definition);
+
+        let path = "get_class_by_hash?classHash=0x1234";
+        let (status, body) = get(&filter, path).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = serde_json::from_value::<StarknetError>(body).unwrap();
+        assert_eq!(error.code, KnownStarknetErrorCode::UndeclaredClass.into());
+    }
+
+    #[tokio::test]
+    async fn state_update() {
+        let (filter, blocks, _) = setup();
+        let (header, _, _, state_update, ..) = &blocks[1];
+        let expected = sorted(reply::StateUpdate::from(state_update.clone()));
+
+        let (status, body) = get(&filter, "get_state_update?blockNumber=1").await;
+        assert_eq!(status, StatusCode::OK);
+        let state_update = parse::<reply::StateUpdate>(body);
+        assert_eq!(sorted(state_update), expected);
+
+        let (status, body) = get(&filter, "get_state_update?blockNumber=1&includeBlock=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["block"]["block_hash"],
+            serde_json::to_value(header.hash).unwrap()
+        );
+        let with_block = parse::<reply::StateUpdateWithBlock>(body);
+        assert_eq!(sorted(with_block.state_update), expected);
+    }
+
+    #[tokio::test]
+    async fn signature() {
+        let (filter, blocks, _) = setup();
+        let (header, signature, _, state_update, ..) = &blocks[1];
+
+        let (status, body) = get(&filter, "get_signature?blockNumber=1").await;
+        assert_eq!(status, StatusCode::OK);
+        let reply = parse::<reply::BlockSignature>(body);
+        assert_eq!(
+            reply,
+            reply::BlockSignature {
+                block_number: header.number,
+                signature: [signature.r, signature.s],
+                signature_input: reply::BlockSignatureInput {
+                    block_hash: header.hash,
+                    state_diff_commitment: state_update.compute_state_diff_commitment(),
+                },
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn signature_not_found() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        let header = pathfinder_common::BlockHeader::default();
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_state_update(header.number, &Default::default())
+            .unwrap();
+        tx.commit().unwrap();
+        let (_, rx) = tokio::sync::watch::channel(Default::default());
+        let filter = routes(storage, PendingWatcher::new(rx), H160::zero());
+
+        let (status, body) = get(&filter, "get_signature?blockNumber=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = serde_json::from_value::<StarknetError>(body).unwrap();
+        assert_eq!(error.code, KnownStarknetErrorCode::BlockNotFound.into());
+    }
+
+    #[tokio::test]
+    async fn compiled_class_by_class_hash() {
+        let (filter, _, storage) = setup();
+        let sierra_hash = sierra_hash!("0x5151e");
+        let definition = serde_json::json!({ "bytecode": [], "prime": "0x1" });
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        tx.insert_sierra_class(
+            &sierra_hash,
+            b"{}",
+            &casm_hash!("0xca5a"),
+            definition.to_string().as_bytes(),
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let path = format!("get_compiled_class_by_class_hash?classHash={sierra_hash}");
+        let (status, body) = get(&filter, &path).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, definition);
+
+        let path = "get_compiled_class_by_class_hash?classHash=0x1234";
+        let (status, body) = get(&filter, path).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let error = serde_json::from_value::<StarknetError>(body).unwrap();
+        assert_eq!(error.code, KnownStarknetErrorCode::UndeclaredClass.into());
+    }
+
+    #[tokio::test]
+    async fn pending() {
+        let storage = Storage::in_memory().unwrap();
+        let blocks = pathfinder_storage::fake::with_n_blocks(&storage, 3);
+        let latest = &blocks[2].0;
+        let block = reply::PendingBlock {
+            parent_hash: latest.hash,
+            status: Status::Pending,
+            timestamp: latest.timestamp,
+            ..Default::default()
+        };
+        let state_update = pathfinder_common::StateUpdate::default().with_storage_update(
+            contract_address!("0x123"),
+            storage_address!("0x1"),
+            storage_value!("0x2"),
+        );
+        let (_, rx) = tokio::sync::watch::channel(pathfinder_rpc::PendingData {
+            block: block.clone().into(),
+            state_update: state_update.clone().into(),
+            number: latest.number + 1,
+        });
+        let filter = routes(storage, PendingWatcher::new(rx), H160::zero());
+
+        let (status, body) = get(&filter, "get_block?blockNumber=pending").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::to_value(&block).unwrap());
+
+        let path = "get_state_update?blockNumber=pending&includeBlock=true";
+        let (status, body) = get(&filter, path).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["block"], serde_json::to_value(&block).unwrap());
+        assert_eq!(
+            body["state_update"],
+            serde_json::to_value(reply::StateUpdate::from(state_update)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction() {
+        let (filter, blocks, _) = setup();
+        let (header, _, transactions, ..) = &blocks[1];
+        let (transaction, receipt) = &transactions[0];
+
+        let path = format!("get_transaction?transactionHash={}", transaction.hash);
+        let (status, body) = get(&filter, &path).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ACCEPTED_ON_L2");
+        assert_eq!(body["finality_status"], "ACCEPTED_ON_L2");
+        assert_eq!(
+            body["block_hash"],
+            serde_json::to_value(header.hash).unwrap()
+        );
+        assert_eq!(body["block_number"], header.number.get());
+        assert_eq!(
+            body["transaction_index"],
+            serde_json::to_value(receipt.transaction_index).unwrap()
+        );
+        assert_eq!(
+            body["transaction"]["transaction_hash"],
+            serde_json::to_value(transaction.hash).unwrap()
+        );
+    }
+
+    /// Syncs the data of a block using the gateway client, as another node would.
+    #[tokio::test]
+    async fn gateway_client() {
+        use starknet_gateway_client::{Client, GatewayApi};
+        use starknet_gateway_types::error::SequencerError;
+
+        let storage = Storage::in_memory().unwrap();
+        let blocks = pathfinder_storage::fake::with_n_blocks(&storage, 3);
+        let class_hash = class_hash!("0xc1a55");
+        let class = serde_json::json!({ "abi": [], "program": {} }).to_string();
+        let sierra_hash = sierra_hash!("0x5151e");
+        let casm = serde_json::json!({ "bytecode": [], "prime": "0x1" }).to_string();
+        {
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+            tx.insert_cairo_class(class_hash, class.as_bytes()).unwrap();
+            tx.insert_sierra_class(&sierra_hash, b"{}", &casm_hash!("0xca5a"), casm.as_bytes())
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        let (_, rx) = tokio::sync::watch::channel(Default::default());
+        let (_server, addr) = spawn_server(
+            ([127, 0, 0, 1], 0).into(),
+            storage,
+            PendingWatcher::new(rx),
+            H160::zero(),
+        )
+        .unwrap();
+        let client = Client::with_base_url(format!("http://{addr}/").parse().unwrap())
+            .unwrap()
+            .disable_retry_for_tests();
+
+        let latest = &blocks[2].0;
+        assert_eq!(client.head().await.unwrap(), (latest.number, latest.hash));
+
+        let (header, signature, transactions, state_update, ..) = &blocks[1];
+        let (block, downloaded) = client
+            .state_update_with_block(header.number.into())
+            .await
+            .unwrap();
+        let block = block.as_block().unwrap();
+        assert_eq!(block.block_hash, header.hash);
+        assert_eq!(block.status, Status::AcceptedOnL2);
+        let hashes = block
+            .transactions
+            .iter()
+            .map(|t| t.hash)
+            .collect::<Vec<_>>();
+        let expected = transactions.iter().map(|(t, _)| t.hash).collect::<Vec<_>>();
+        assert_eq!(hashes, expected);
+        assert_eq!(downloaded.block_hash, header.hash);
+        assert_eq!(downloaded.state_commitment, header.state_commitment);
+        assert_eq!(
+            downloaded.compute_state_diff_commitment(),
+            state_update.compute_state_diff_commitment()
+        );
+
+        let reply = client.signature(header.number.into()).await.unwrap();
+        assert_eq!(reply.signature, [signature.r, signature.s]);
+        assert_eq!(reply.signature_input.block_hash, header.hash);
+
+        let status = client.transaction(expected[0]).await.unwrap();
+        assert_eq!(status.status, Status::AcceptedOnL2);
+
+        let downloaded = client.pending_class_by_hash(class_hash).await.unwrap();
+        assert_eq!(downloaded, class.as_bytes());
+        let downloaded = client
+            .pending_casm_by_hash(ClassHash(sierra_hash.0))
+            .await
+            .unwrap();
+        assert_eq!(downloaded, casm.as_bytes());
+
+        let error = client
+            .block_without_retry(BlockNumber::new_or_panic(10).into())
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(
+            error,
+            SequencerError::StarknetError(e) => assert_eq!(e.code, KnownStarknetErrorCode::BlockNotFound.into())
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction_not_received() {
+        let (filter, ..) = setup();
+
+        let path = format!(
+            "get_transaction?transactionHash={}",
+            TransactionHash(pathfinder_crypto::Felt::from_u64(0xabcdef))
+        );
+        let (status, body) = get(&filter, &path).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "NOT_RECEIVED");
+    }
+}
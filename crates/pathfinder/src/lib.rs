@@ -1,5 +1,6 @@
 #![deny(rust_2018_idioms)]
 
+pub mod feeder_gateway;
 pub mod monitoring;
 pub mod state;
 mod sync;
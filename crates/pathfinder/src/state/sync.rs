@@ -2,22 +2,23 @@ mod class;
 pub mod l1;
 pub mod l2;
 mod pending;
+pub mod replica;
 
 use anyhow::Context;
 use pathfinder_common::{
     BlockCommitmentSignature, BlockHash, BlockHeader, BlockNumber, CasmHash, Chain, ChainId,
-    ClassCommitment, ClassHash, EventCommitment, GasPrice, SequencerAddress, SierraHash,
-    StateCommitment, StateUpdate, StorageCommitment, TransactionCommitment,
+    ClassCommitment, ClassHash, EventCommitment, GasPrice, PublicKey, SequencerAddress, SierraHash,
+    SignatureStatus, StateCommitment, StateUpdate, StorageCommitment, TransactionCommitment,
 };
 use pathfinder_crypto::Felt;
-use pathfinder_ethereum::{EthereumApi, EthereumStateUpdate};
+use pathfinder_ethereum::{EthereumApi, L1ToL2MessageLog, L2ToL1MessageLog, StateUpdateLog};
 use pathfinder_merkle_tree::contract_state::update_contract_state;
 use pathfinder_merkle_tree::{ClassCommitmentTree, StorageCommitmentTree};
-use pathfinder_rpc::PendingData;
 use pathfinder_rpc::{
     v02::types::syncing::{self, NumberedBlock, Syncing},
     SyncState, TopicBroadcasters,
 };
+use pathfinder_rpc::{PendingData, PendingDiff, PendingUpdate};
 use pathfinder_storage::{Connection, Storage, Transaction, TransactionBehavior};
 use primitive_types::H160;
 use starknet_gateway_client::GatewayApi;
@@ -37,12 +38,18 @@ use tokio::sync::watch::Sender as WatchSender;
 
 #[derive(Debug)]
 pub enum SyncEvent {
-    L1Update(EthereumStateUpdate),
+    /// New L1 [state update logs](StateUpdateLog) found, in the order they were emitted.
+    L1Update(Vec<StateUpdateLog>),
+    /// New [L1 to L2 messages](L1ToL2MessageLog) found, in the order they were sent.
+    L1Messages(Vec<L1ToL2MessageLog>),
+    /// New [consumptions of L2 to L1 messages](L2ToL1MessageLog) found, in the order they were
+    /// consumed.
+    L1ConsumedMessages(Vec<L2ToL1MessageLog>),
     /// New L2 [block update](StateUpdate) found.
     Block(
         (Box<Block>, (TransactionCommitment, EventCommitment)),
         Box<StateUpdate>,
-        Box<BlockCommitmentSignature>,
+        Box<(BlockCommitmentSignature, SignatureStatus)>,
         l2::Timings,
     ),
     /// An L2 reorg was detected, contains the reorg-tail which
@@ -81,6 +88,9 @@ pub struct SyncContext<G, E> {
     pub restart_delay: Duration,
     pub verify_tree_hashes: bool,
     pub gossiper: Gossiper,
+    pub sequencer_public_key: Option<PublicKey>,
+    pub reject_invalid_signatures: bool,
+    pub verify_proofs: bool,
 }
 
 impl<G, E> From<&SyncContext<G, E>> for L1SyncContext<E>
@@ -93,6 +103,8 @@ where
             chain: value.chain,
             core_address: value.core_address,
             poll_interval: value.head_poll_interval,
+            storage: value.storage.clone(),
+            verify_proofs: value.verify_proofs,
         }
     }
 }
@@ -108,6 +120,8 @@ where
             chain_id: value.chain_id,
             block_validation_mode: value.block_validation_mode,
             storage: value.storage.clone(),
+            sequencer_public_key: value.sequencer_public_key,
+            reject_invalid_signatures: value.reject_invalid_signatures,
         }
     }
 }
@@ -183,6 +197,9 @@ where
         restart_delay,
         verify_tree_hashes: _,
         gossiper,
+        sequencer_public_key: _,
+        reject_invalid_signatures: _,
+        verify_proofs: _,
     } = context;
 
     let mut db_conn = storage
@@ -397,9 +414,19 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
     while let Some(event) = events.recv().await {
         use SyncEvent::*;
         match event {
-            L1Update(update) => {
-                l1_update(&mut db_conn, &update).await?;
-                tracing::info!("L1 sync updated to block {}", update.block_number);
+            L1Update(logs) => {
+                l1_update(&mut db_conn, &logs).await?;
+                if let Some(latest) = logs.last() {
+                    tracing::info!("L1 sync updated to block {}", latest.block_number);
+                }
+            }
+            L1Messages(messages) => {
+                l1_messages(&mut db_conn, &messages).await?;
+                tracing::debug!(count=%messages.len(), "Stored L1 to L2 messages");
+            }
+            L1ConsumedMessages(messages) => {
+                l1_consumed_messages(&mut db_conn, &messages).await?;
+                tracing::debug!(count=%messages.len(), "Stored consumed L2 to L1 messages");
             }
             Block((block, (tx_comm, ev_comm)), state_update, signature, timings) => {
                 if block.block_number < next_number {
@@ -569,6 +596,29 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                         state_update: pending.1.into(),
                         number: number + 1,
                     };
+
+                    if let Some(sender) = &websocket_txs {
+                        let previous = pending_data.borrow().clone();
+                        let diff = PendingDiff::new(
+                            (&previous.block, &previous.state_update),
+                            (&data.block, &data.state_update),
+                        );
+                        match diff {
+                            Some(diff) if diff.is_empty() => {}
+                            Some(diff) => {
+                                let update = PendingUpdate::new(data.number, diff);
+                                if let Err(e) = sender.pending_update.send_if_receiving(update) {
+                                    tracing::error!(error=?e, "Failed to send pending update over websocket broadcaster.");
+                                    // Disable websocket entirely, see the equivalent handling of new headers.
+                                    websocket_txs = None;
+                                }
+                            }
+                            None => tracing::debug!(
+                                "Pending data does not extend the previous pending data"
+                            ),
+                        }
+                    }
+
                     pending_data.send_replace(data);
                     tracing::debug!("Updated pending data");
                 }
@@ -686,9 +736,9 @@ async fn propagate_head(gossiper: &Gossiper, last_propagated: &mut Instant, head
     *last_propagated = Instant::now();
 }
 
-async fn l1_update(
+async fn l1_messages(
     connection: &mut Connection,
-    update: &EthereumStateUpdate,
+    messages: &[L1ToL2MessageLog],
 ) -> anyhow::Result<()> {
     tokio::task::block_in_place(move || {
         let transaction = connection
@@ -696,7 +746,47 @@ async fn l1_update(
             .context("Create database transaction")?;
 
         transaction
-            .upsert_l1_state(update)
+            .insert_message_logs(messages)
+            .context("Insert message logs")?;
+
+        transaction.commit().context("Commit database transaction")
+    })
+}
+
+async fn l1_consumed_messages(
+    connection: &mut Connection,
+    messages: &[L2ToL1MessageLog],
+) -> anyhow::Result<()> {
+    tokio::task::block_in_place(move || {
+        let transaction = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Create database transaction")?;
+
+        transaction
+            .insert_consumed_message_logs(messages)
+            .context("Insert consumed message logs")?;
+
+        transaction.commit().context("Commit database transaction")
+    })
+}
+
+async fn l1_update(connection: &mut Connection, logs: &[StateUpdateLog]) -> anyhow::Result<()> {
+    tokio::task::block_in_place(move || {
+        let transaction = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Create database transaction")?;
+
+        transaction
+            .insert_state_update_logs(logs)
+            .context("Insert state update logs")?;
+
+        // Logs emitted before Starknet v0.11 lack the block hash required to match L1 and L2.
+        let Some(update) = logs.last().and_then(StateUpdateLog::state_update) else {
+            return transaction.commit().context("Commit database transaction");
+        };
+
+        transaction
+            .upsert_l1_state(&update)
             .context("Insert update")?;
 
         let l2_hash = transaction
@@ -729,7 +819,7 @@ async fn l2_update(
     transaction_commitment: TransactionCommitment,
     event_commitment: EventCommitment,
     state_update: StateUpdate,
-    signature: BlockCommitmentSignature,
+    (signature, signature_status): (BlockCommitmentSignature, SignatureStatus),
     verify_tree_hashes: bool,
     // we need this so that we can create extra read-only transactions for
     // parallel contract state updates
@@ -828,6 +918,11 @@ async fn l2_update(
         transaction
             .insert_signature(block.block_number, &signature)
             .context("Insert signature into database")?;
+        if signature_status != SignatureStatus::Unverified {
+            transaction
+                .update_signature_status(block.block_number, signature_status)
+                .context("Update signature status in database")?;
+        }
 
         // Track combined L1 and L2 state.
         let l1_l2_head = transaction.l1_l2_pointer().context("Query L1-L2 head")?;
@@ -873,55 +968,68 @@ async fn l2_reorg(connection: &mut Connection, reorg_tail: BlockNumber) -> anyho
             .transaction_with_behavior(TransactionBehavior::Immediate)
             .context("Create database transaction")?;
 
-        let mut head = transaction
-            .block_id(pathfinder_storage::BlockId::Latest)
-            .context("Querying latest block number")?
-            .context("Latest block number is none during reorg")?
-            .0;
+        rollback(&transaction, reorg_tail)?;
 
-        transaction
-            .increment_reorg_counter()
-            .context("Incrementing reorg counter")?;
-
-        // Purge each block one at a time.
-        //
-        // This is done 1-by-1 to allow sending the reorg'd block data
-        // to websocket subscriptions while keeping a constant memory footprint.
-        //
-        // This is acceptable performance because reorgs are rare and need not be
-        // 100% optimal. However a large reorg could cause a massive memory spike
-        // which is not acceptable.
-        while head >= reorg_tail {
-            transaction
-                .purge_block(head)
-                .with_context(|| format!("Purging block {head} from database"))?;
+        transaction.commit().context("Commit database transaction")
+    })
+}
 
-            // No further blocks to purge if we just purged genesis.
-            if head == BlockNumber::GENESIS {
-                break;
-            }
+/// Removes all blocks from `reorg_tail` onwards, leaving `reorg_tail - 1` as the latest block.
+///
+/// Sync resumes from the new latest block once the transaction is committed.
+pub fn rollback(transaction: &Transaction<'_>, reorg_tail: BlockNumber) -> anyhow::Result<()> {
+    let mut head = transaction
+        .block_id(pathfinder_storage::BlockId::Latest)
+        .context("Querying latest block number")?
+        .context("Latest block number is none during reorg")?
+        .0;
 
-            head -= 1;
+    transaction
+        .increment_reorg_counter()
+        .context("Incrementing reorg counter")?;
+
+    // Purge each block one at a time.
+    //
+    // This is done 1-by-1 to allow sending the reorg'd block data
+    // to websocket subscriptions while keeping a constant memory footprint.
+    //
+    // This is acceptable performance because reorgs are rare and need not be
+    // 100% optimal. However a large reorg could cause a massive memory spike
+    // which is not acceptable.
+    while head >= reorg_tail {
+        transaction
+            .purge_block(head)
+            .with_context(|| format!("Purging block {head} from database"))?;
+
+        // No further blocks to purge if we just purged genesis.
+        if head == BlockNumber::GENESIS {
+            break;
         }
 
-        // Track combined L1 and L2 state.
-        let l1_l2_head = transaction.l1_l2_pointer().context("Query L1-L2 head")?;
-        if let Some(l1_l2_head) = l1_l2_head {
-            if reorg_tail == BlockNumber::GENESIS {
-                // If we purged genesis then unset the L1 L2 pointer as well since there
-                // are now no blocks remaining.
-                transaction
-                    .update_l1_l2_pointer(None)
-                    .context("Unsetting L1-L2 head")?;
-            } else if l1_l2_head >= reorg_tail {
-                transaction
-                    .update_l1_l2_pointer(Some(reorg_tail - 1))
-                    .context("Updating L1-L2 head")?;
-            }
+        head -= 1;
+    }
+
+    transaction
+        .truncate_tries()
+        .context("Removing unreferenced trie nodes")?;
+
+    // Track combined L1 and L2 state.
+    let l1_l2_head = transaction.l1_l2_pointer().context("Query L1-L2 head")?;
+    if let Some(l1_l2_head) = l1_l2_head {
+        if reorg_tail == BlockNumber::GENESIS {
+            // If we purged genesis then unset the L1 L2 pointer as well since there
+            // are now no blocks remaining.
+            transaction
+                .update_l1_l2_pointer(None)
+                .context("Unsetting L1-L2 head")?;
+        } else if l1_l2_head >= reorg_tail {
+            transaction
+                .update_l1_l2_pointer(Some(reorg_tail - 1))
+                .context("Updating L1-L2 head")?;
         }
+    }
 
-        transaction.commit().context("Commit database transaction")
-    })
+    Ok(())
 }
 
 fn update_starknet_state(
@@ -1080,10 +1188,12 @@ mod tests {
         felt_bytes, BlockHash, BlockHeader, BlockNumber, ClassHash, EventCommitment, SierraHash,
         StateCommitment, StateUpdate, TransactionCommitment,
     };
-    use pathfinder_common::{macro_prelude::*, BlockCommitmentSignature};
+    use pathfinder_common::{macro_prelude::*, BlockCommitmentSignature, SignatureStatus};
     use pathfinder_crypto::Felt;
+    use pathfinder_ethereum::StateUpdateLog;
     use pathfinder_rpc::SyncState;
     use pathfinder_storage::Storage;
+    use primitive_types::H256;
     use starknet_gateway_types::reply;
     use starknet_gateway_types::reply::Block;
     use std::sync::Arc;
@@ -1095,7 +1205,7 @@ mod tests {
     fn generate_block_data() -> Vec<(
         (Box<Block>, (TransactionCommitment, EventCommitment)),
         Box<StateUpdate>,
-        Box<BlockCommitmentSignature>,
+        Box<(BlockCommitmentSignature, SignatureStatus)>,
         l2::Timings,
     )> {
         let genesis_header =
@@ -1138,10 +1248,13 @@ mod tests {
                 starknet_version: header.starknet_version,
             });
 
-            let signature = Box::new(BlockCommitmentSignature {
-                r: block_commitment_signature_elem!("0x1001"),
-                s: block_commitment_signature_elem!("0x1002"),
-            });
+            let signature = Box::new((
+                BlockCommitmentSignature {
+                    r: block_commitment_signature_elem!("0x1001"),
+                    s: block_commitment_signature_elem!("0x1002"),
+                },
+                SignatureStatus::Valid,
+            ));
 
             data.push((
                 (
@@ -1195,6 +1308,11 @@ mod tests {
                 .block_exists(BlockNumber::new_or_panic(i as u64).into())
                 .unwrap();
             assert!(should_exist, "Block {i} should exist");
+
+            let signature_status = tx
+                .signature_status(BlockNumber::new_or_panic(i as u64).into())
+                .unwrap();
+            assert_eq!(signature_status, Some(SignatureStatus::Valid));
         }
 
         let should_not_exist = tx
@@ -1203,6 +1321,65 @@ mod tests {
         assert!(!should_not_exist);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn l1_update() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+
+        let block_data = generate_block_data();
+        let block_1_hash = block_data[1].0 .0.block_hash;
+        for (a, b, c, d) in block_data {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+
+        // A legacy log without block hash, followed by a log for block 1.
+        let logs = vec![
+            StateUpdateLog {
+                state_root: state_commitment!("0x1"),
+                block_number: BlockNumber::GENESIS,
+                block_hash: None,
+                l1_block_number: 10,
+                l1_transaction_hash: H256::from_low_u64_be(1),
+            },
+            StateUpdateLog {
+                state_root: state_commitment!("0x2"),
+                block_number: BlockNumber::new_or_panic(1),
+                block_hash: Some(block_1_hash),
+                l1_block_number: 11,
+                l1_transaction_hash: H256::from_low_u64_be(2),
+            },
+        ];
+        event_tx
+            .send(SyncEvent::L1Update(logs.clone()))
+            .await
+            .unwrap();
+        // Close the event channel which allows the consumer task to exit.
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: tx,
+            verify_tree_hashes: false,
+            websocket_txs: None,
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let pointer = tx.l1_l2_pointer().unwrap();
+        assert_eq!(pointer, Some(BlockNumber::new_or_panic(1)));
+
+        let latest = tx.latest_state_update_log().unwrap();
+        assert_eq!(latest.as_ref(), logs.last());
+
+        let genesis = tx.state_update_log_including(BlockNumber::GENESIS).unwrap();
+        assert_eq!(genesis.as_ref(), logs.first());
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn reorg() {
         let storage = Storage::in_memory().unwrap();
@@ -1338,6 +1515,67 @@ mod tests {
         assert!(!genesis_exists);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sync_resumes_after_rollback() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let blocks = generate_block_data();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in blocks.clone() {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: tx,
+            verify_tree_hashes: false,
+            websocket_txs: None,
+        };
+        consumer(event_rx, context).await.unwrap();
+
+        // Roll back to genesis.
+        let tx = connection.transaction().unwrap();
+        super::rollback(&tx, BlockNumber::new_or_panic(1)).unwrap();
+        tx.commit().unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let latest = tx
+            .block_id(pathfinder_storage::BlockId::Latest)
+            .unwrap()
+            .unwrap()
+            .0;
+        assert_eq!(latest, BlockNumber::GENESIS);
+        drop(tx);
+
+        // A restarted consumer continues from the new head.
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in blocks.into_iter().skip(1) {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: tx,
+            verify_tree_hashes: false,
+            websocket_txs: None,
+        };
+        consumer(event_rx, context).await.unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let block_2_exists = tx
+            .block_exists(BlockNumber::new_or_panic(2).into())
+            .unwrap();
+        assert!(block_2_exists);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn new_cairo_contract() {
         let storage = Storage::in_memory().unwrap();
@@ -1,13 +1,21 @@
 use std::{num::NonZeroU64, time::Duration};
 
+use anyhow::Context;
 use pathfinder_common::Chain;
-use pathfinder_ethereum::{EthereumApi, EthereumStateUpdate};
+use pathfinder_ethereum::{
+    core_deployment_block, EthereumApi, EthereumStateUpdate, StateUpdateLog,
+};
 use pathfinder_retry::Retry;
+use pathfinder_storage::Storage;
 use primitive_types::H160;
 use tokio::sync::mpsc;
 
 use crate::state::sync::SyncEvent;
 
+/// The maximum number of Ethereum blocks requested per `eth_getLogs` call, as providers limit the
+/// range of a single request.
+const LOG_CHUNK_SIZE: u64 = 10_000;
+
 #[derive(Clone)]
 pub struct L1SyncContext<EthereumClient> {
     pub ethereum: EthereumClient,
@@ -15,10 +23,27 @@ pub struct L1SyncContext<EthereumClient> {
     /// The Starknet core contract address on Ethereum
     pub core_address: H160,
     pub poll_interval: Duration,
+    pub storage: Storage,
+    /// Whether to verify each stored state update log against storage proofs of the core
+    /// contract's state at the end of the log's Ethereum block.
+    pub verify_proofs: bool,
 }
 
-/// Syncs L1 state update logs. Emits [Ethereum state update](EthereumStateUpdate)
+/// Syncs L1 state update and message logs. Emits the L1 to L2
+/// [messages](pathfinder_ethereum::L1ToL2MessageLog), the consumed L2 to L1
+/// [messages](pathfinder_ethereum::L2ToL1MessageLog) and then the
+/// [state updates](pathfinder_ethereum::StateUpdateLog) of each finalized Ethereum block range,
 /// which should be handled to update storage and respond to queries.
+///
+/// Syncing resumes after the Ethereum block of the latest stored state update log, starting from
+/// the deployment of the core contract for new databases. Messages of the resumed range may therefore be
+/// emitted again.
+///
+/// With `verify_proofs`, each state update log is only emitted once it matches the core contract's
+/// state at the end of its Ethereum block, read using [EthereumApi::get_proven_starknet_state]
+/// against the block hash reported by [EthereumApi::get_block_hash]. This requires endpoints which
+/// serve proofs of historic blocks. A log which is superseded within its own Ethereum block cannot
+/// be proven and is dropped, so the inclusion of its Starknet block is answered by the later log.
 pub async fn sync<T>(
     tx_event: mpsc::Sender<SyncEvent>,
     context: L1SyncContext<T>,
@@ -28,16 +53,29 @@ where
 {
     let L1SyncContext {
         ethereum,
-        chain: _,
+        chain,
         core_address,
         poll_interval,
+        storage,
+        verify_proofs,
     } = context;
 
-    let mut previous = EthereumStateUpdate::default();
+    let mut next_block = tokio::task::spawn_blocking(move || {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+        let latest = tx
+            .latest_state_update_log()
+            .context("Fetching latest state update log")?;
+        anyhow::Ok(latest.map_or(first_block(chain), |log| log.l1_block_number + 1))
+    })
+    .await
+    .context("Joining blocking task")??;
 
     loop {
-        let state_update = Retry::exponential(
-            || async { ethereum.get_starknet_state(&core_address).await },
+        let finalized = Retry::exponential(
+            || async { ethereum.get_finalized_block_number().await },
             NonZeroU64::new(1).unwrap(),
         )
         .factor(NonZeroU64::new(2).unwrap())
@@ -45,11 +83,278 @@ where
         .when(|_| true)
         .await?;
 
-        if previous != state_update {
-            previous = state_update.clone();
-            tx_event.send(SyncEvent::L1Update(state_update)).await?;
+        while next_block <= finalized {
+            let to_block = finalized.min(next_block + LOG_CHUNK_SIZE - 1);
+
+            let messages = Retry::exponential(
+                || async {
+                    ethereum
+                        .get_message_logs(&core_address, next_block, to_block)
+                        .await
+                },
+                NonZeroU64::new(1).unwrap(),
+            )
+            .factor(NonZeroU64::new(2).unwrap())
+            .max_delay(poll_interval / 2)
+            .when(|_| true)
+            .await?;
+
+            let consumed_messages = Retry::exponential(
+                || async {
+                    ethereum
+                        .get_consumed_message_logs(&core_address, next_block, to_block)
+                        .await
+                },
+                NonZeroU64::new(1).unwrap(),
+            )
+            .factor(NonZeroU64::new(2).unwrap())
+            .max_delay(poll_interval / 2)
+            .when(|_| true)
+            .await?;
+
+            let logs = Retry::exponential(
+                || async {
+                    ethereum
+                        .get_state_update_logs(&core_address, next_block, to_block)
+                        .await
+                },
+                NonZeroU64::new(1).unwrap(),
+            )
+            .factor(NonZeroU64::new(2).unwrap())
+            .max_delay(poll_interval / 2)
+            .when(|_| true)
+            .await?;
+
+            let logs = if verify_proofs {
+                let logs = latest_per_block(logs);
+                for log in &logs {
+                    let proven = Retry::exponential(
+                        || async {
+                            let block_hash = ethereum.get_block_hash(log.l1_block_number).await?;
+                            ethereum
+                                .get_proven_starknet_state(&core_address, block_hash)
+                                .await
+                        },
+                        NonZeroU64::new(1).unwrap(),
+                    )
+                    .factor(NonZeroU64::new(2).unwrap())
+                    .max_delay(poll_interval / 2)
+                    .when(|_| true)
+                    .await?;
+
+                    anyhow::ensure!(
+                        log_matches(log, &proven),
+                        "State update log {log:?} does not match the proven state {proven:?} of Ethereum block {}",
+                        log.l1_block_number
+                    );
+                }
+                logs
+            } else {
+                logs
+            };
+
+            // Messages are emitted first as syncing resumes based on the stored state update logs.
+            if !messages.is_empty() {
+                tx_event.send(SyncEvent::L1Messages(messages)).await?;
+            }
+            if !consumed_messages.is_empty() {
+                tx_event
+                    .send(SyncEvent::L1ConsumedMessages(consumed_messages))
+                    .await?;
+            }
+            if !logs.is_empty() {
+                tx_event.send(SyncEvent::L1Update(logs)).await?;
+            }
+
+            next_block = to_block + 1;
+        }
+
+        // Websocket clients are woken by new Ethereum blocks and core contract logs, which allows
+        // new finalized blocks to be picked up without waiting for the full poll interval.
+        ethereum
+            .wait_for_new_block(&core_address, poll_interval)
+            .await;
+    }
+}
+
+/// Keeps only the last of the `logs` emitted in each Ethereum block, as the core contract's state at
+/// the end of a block only proves its last state update.
+fn latest_per_block(logs: Vec<StateUpdateLog>) -> Vec<StateUpdateLog> {
+    let mut logs = logs.into_iter().peekable();
+    let mut latest = Vec::new();
+    while let Some(log) = logs.next() {
+        if logs
+            .peek()
+            .map_or(true, |next| next.l1_block_number != log.l1_block_number)
+        {
+            latest.push(log);
+        }
+    }
+    latest
+}
+
+/// Whether the core contract's state is the state update of `log`. Logs emitted before Starknet
+/// v0.11 lack the block hash, which is therefore only compared if present.
+fn log_matches(log: &StateUpdateLog, state: &EthereumStateUpdate) -> bool {
+    log.state_root == state.state_root
+        && log.block_number == state.block_number
+        && log.block_hash.map_or(true, |hash| hash == state.block_hash)
+}
+
+/// The Ethereum block from which to scan for the core contract's logs of a new database.
+fn first_block(chain: Chain) -> u64 {
+    match chain {
+        Chain::Mainnet => core_deployment_block::MAINNET,
+        Chain::GoerliTestnet => core_deployment_block::GOERLI_TESTNET,
+        Chain::GoerliIntegration => core_deployment_block::GOERLI_INTEGRATION,
+        Chain::SepoliaTestnet => core_deployment_block::SEPOLIA_TESTNET,
+        Chain::SepoliaIntegration => core_deployment_block::SEPOLIA_INTEGRATION,
+        // The deployment of a custom network's core contract is unknown.
+        Chain::Custom => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockNumber, EthereumChain};
+    use pathfinder_ethereum::{L1ToL2MessageLog, L2ToL1MessageLog};
+    use primitive_types::H256;
+
+    use super::*;
+
+    /// Serves `logs`, and `proven` as the core contract's state at the end of the Ethereum block
+    /// with the given number.
+    #[derive(Clone)]
+    struct MockEthereum {
+        logs: Vec<StateUpdateLog>,
+        proven: Vec<(u64, EthereumStateUpdate)>,
+    }
+
+    fn log(block_number: u64, l1_block_number: u64) -> StateUpdateLog {
+        StateUpdateLog {
+            state_root: state_commitment!("0x1"),
+            block_number: BlockNumber::new_or_panic(block_number),
+            block_hash: Some(block_hash!("0x2")),
+            l1_block_number,
+            l1_transaction_hash: H256::zero(),
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EthereumApi for MockEthereum {
+        async fn get_starknet_state(&self, _: &H160) -> anyhow::Result<EthereumStateUpdate> {
+            unimplemented!()
+        }
+
+        async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+            unimplemented!()
+        }
+
+        async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+            Ok(10)
+        }
+
+        async fn get_block_hash(&self, number: u64) -> anyhow::Result<H256> {
+            Ok(H256::from_low_u64_be(number))
+        }
+
+        async fn get_proven_starknet_state(
+            &self,
+            _: &H160,
+            block_hash: H256,
+        ) -> anyhow::Result<EthereumStateUpdate> {
+            let (_, proven) = self
+                .proven
+                .iter()
+                .find(|(number, _)| H256::from_low_u64_be(*number) == block_hash)
+                .expect("Only Ethereum blocks with state update logs are proven");
+            Ok(proven.clone())
+        }
+
+        async fn get_state_update_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<StateUpdateLog>> {
+            Ok(self.logs.clone())
+        }
+
+        async fn get_message_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+            Ok(vec![])
+        }
+
+        async fn get_consumed_message_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<L2ToL1MessageLog>> {
+            Ok(vec![])
+        }
+
+        async fn wait_for_new_block(&self, _: &H160, _: Duration) {
+            std::future::pending().await
         }
+    }
+
+    fn context(
+        logs: Vec<StateUpdateLog>,
+        proven: Vec<(u64, EthereumStateUpdate)>,
+    ) -> L1SyncContext<MockEthereum> {
+        L1SyncContext {
+            ethereum: MockEthereum { logs, proven },
+            chain: Chain::Custom,
+            core_address: H160::zero(),
+            poll_interval: Duration::from_secs(1),
+            storage: Storage::in_memory().unwrap(),
+            verify_proofs: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn proven_logs_are_emitted() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let logs = vec![log(1, 5), log(2, 7)];
+        let proven = logs
+            .iter()
+            .map(|log| (log.l1_block_number, log.state_update().unwrap()))
+            .collect();
+        let _sync = tokio::spawn(sync(tx, context(logs.clone(), proven)));
+
+        let event = rx.recv().await.unwrap();
+        assert_matches::assert_matches!(event, SyncEvent::L1Update(emitted) if emitted == logs);
+    }
+
+    #[tokio::test]
+    async fn logs_superseded_within_their_block_are_dropped() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let logs = vec![log(1, 5), log(2, 5)];
+        let proven = vec![(5, log(2, 5).state_update().unwrap())];
+        let _sync = tokio::spawn(sync(tx, context(logs, proven)));
+
+        let event = rx.recv().await.unwrap();
+        assert_matches::assert_matches!(event, SyncEvent::L1Update(emitted) if emitted == vec![log(2, 5)]);
+    }
+
+    #[tokio::test]
+    async fn unproven_logs_are_rejected() {
+        let (tx, mut rx) = mpsc::channel(1);
+        // Only the latest log matches the proven state, which is not enough.
+        let logs = vec![log(1, 5), log(2, 7)];
+        let forged = EthereumStateUpdate {
+            state_root: state_commitment!("0x3"),
+            ..log(1, 5).state_update().unwrap()
+        };
+        let proven = vec![(5, forged), (7, log(2, 7).state_update().unwrap())];
 
-        tokio::time::sleep(poll_interval).await;
+        sync(tx, context(logs, proven)).await.unwrap_err();
+        assert!(rx.recv().await.is_none());
     }
 }
@@ -4,14 +4,15 @@ use crate::state::sync::{pending, SyncEvent};
 use anyhow::{anyhow, Context};
 use pathfinder_common::state_update::ContractClassUpdate;
 use pathfinder_common::{
-    BlockHash, BlockNumber, Chain, ChainId, ClassHash, EventCommitment, StarknetVersion,
-    StateCommitment, StateUpdate, TransactionCommitment,
+    BlockCommitmentSignature, BlockHash, BlockNumber, Chain, ChainId, ClassHash, EventCommitment,
+    PublicKey, SignatureStatus, StarknetVersion, StateCommitment, StateUpdate,
+    TransactionCommitment,
 };
 use pathfinder_storage::Storage;
 use starknet_gateway_client::GatewayApi;
 use starknet_gateway_types::{
     error::SequencerError,
-    reply::{Block, Status},
+    reply::{Block, BlockSignature, Status},
 };
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -85,6 +86,10 @@ pub struct L2SyncContext<GatewayClient> {
     pub chain_id: ChainId,
     pub block_validation_mode: BlockValidationMode,
     pub storage: Storage,
+    /// Block signatures are only verified if the sequencer's public key is known.
+    pub sequencer_public_key: Option<PublicKey>,
+    /// Whether blocks with invalid signatures are rejected, or only flagged as such.
+    pub reject_invalid_signatures: bool,
 }
 
 pub async fn sync<GatewayClient>(
@@ -102,6 +107,8 @@ where
         chain_id,
         block_validation_mode,
         storage,
+        sequencer_public_key,
+        reject_invalid_signatures,
     } = context;
 
     let mut pending_handle = None;
@@ -142,19 +149,24 @@ where
                     break (block, commitments, state_update)
                 }
                 DownloadBlock::AtHead => {
-                    const PENDING_POLL_INTERVAL: std::time::Duration =
-                        std::time::Duration::from_secs(2);
+                    const PENDING_POLL_INTERVAL_MIN: std::time::Duration =
+                        std::time::Duration::from_secs(1);
+                    const PENDING_POLL_INTERVAL_MAX: std::time::Duration =
+                        std::time::Duration::from_secs(4);
 
                     if cfg!(feature = "p2p") {
                         // Not implemented yet for P2P
                         tracing::info!("Skipping the pending blocks polling");
-                        tokio::time::sleep(PENDING_POLL_INTERVAL).await;
+                        tokio::time::sleep(PENDING_POLL_INTERVAL_MAX).await;
                     } else if pending_handle.is_none() {
                         tracing::info!("At head of chain, enabling polling of pending data");
                         pending_handle = Some(tokio::spawn(pending::poll_pending(
                             tx_event.clone(),
                             sequencer.clone(),
-                            PENDING_POLL_INTERVAL,
+                            pending::PollInterval::new(
+                                PENDING_POLL_INTERVAL_MIN,
+                                PENDING_POLL_INTERVAL_MAX,
+                            ),
                             storage.clone(),
                         )));
                     }
@@ -268,7 +280,14 @@ where
             signature.signature_input.block_hash.0,
             block.block_hash.0,
         );
-        let signature = signature.into();
+        let signature_status = verify_signature(
+            &signature,
+            &state_update,
+            sequencer_public_key,
+            reject_invalid_signatures,
+        )
+        .with_context(|| format!("Verifying signature of block {next:?}"))?;
+        let signature = BlockCommitmentSignature::from(signature);
 
         head = Some((next, block.block_hash, state_update.state_commitment));
         blocks.push(next, block.block_hash, state_update.state_commitment);
@@ -283,7 +302,7 @@ where
             .send(SyncEvent::Block(
                 (block, commitments),
                 state_update,
-                Box::new(signature),
+                Box::new((signature, signature_status)),
                 timings,
             ))
             .await
@@ -291,6 +310,50 @@ where
     }
 }
 
+/// Verifies that the sequencer signed the block hash and state diff commitment of a block, and
+/// that the signed commitment is the one of the downloaded state update.
+///
+/// Fails if the signature is invalid and `reject_invalid` is set, otherwise invalid signatures are
+/// only flagged.
+fn verify_signature(
+    signature: &BlockSignature,
+    state_update: &StateUpdate,
+    public_key: Option<PublicKey>,
+    reject_invalid: bool,
+) -> anyhow::Result<SignatureStatus> {
+    let Some(public_key) = public_key else {
+        return Ok(SignatureStatus::Unverified);
+    };
+
+    let block_hash = signature.signature_input.block_hash;
+    let signed_commitment = signature.signature_input.state_diff_commitment;
+    let state_diff_commitment = state_update.compute_state_diff_commitment();
+    let result = if state_diff_commitment != signed_commitment {
+        Err(anyhow!(
+            "state diff commitment mismatch, actual {state_diff_commitment}, signed {signed_commitment}"
+        ))
+    } else {
+        let commitment_signature = BlockCommitmentSignature {
+            r: signature.signature[0],
+            s: signature.signature[1],
+        };
+        commitment_signature
+            .verify(public_key, block_hash, signed_commitment)
+            .map_err(|error| anyhow!(error))
+    };
+
+    match result {
+        Ok(()) => Ok(SignatureStatus::Valid),
+        Err(error) if reject_invalid => {
+            Err(anyhow!("Block signature verification failed: {error}"))
+        }
+        Err(error) => {
+            tracing::warn!(number=%signature.block_number, %block_hash, %error, "Block signature verification failed");
+            Ok(SignatureStatus::Invalid)
+        }
+    }
+}
+
 /// Download and emit new contract classes.
 ///
 /// New classes can come from:
@@ -810,6 +873,8 @@ mod tests {
                 chain_id: ChainId::GOERLI_TESTNET,
                 block_validation_mode: MODE,
                 storage,
+                sequencer_public_key: None,
+                reject_invalid_signatures: true,
             };
 
             tokio::spawn(sync(
@@ -1081,7 +1146,7 @@ mod tests {
                 assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), state_update, signature, _) => {
                     assert_eq!(*block, *BLOCK0);
                     assert_eq_sorted!(*state_update, *STATE_UPDATE0);
-                    assert_eq!(*signature, BLOCK0_COMMITMENT_SIGNATURE);
+                    assert_eq!(signature.0, BLOCK0_COMMITMENT_SIGNATURE);
                 });
                 assert_matches!(rx_event.recv().await.unwrap(),
                     SyncEvent::CairoClass { hash, .. } => {
@@ -1090,7 +1155,7 @@ mod tests {
                 assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), state_update, signature, _) => {
                     assert_eq!(*block, *BLOCK1);
                     assert_eq_sorted!(*state_update, *STATE_UPDATE1);
-                    assert_eq!(*signature, BLOCK1_COMMITMENT_SIGNATURE);
+                    assert_eq!(signature.0, BLOCK1_COMMITMENT_SIGNATURE);
                 });
             }
 
@@ -1149,6 +1214,8 @@ mod tests {
                     chain_id: ChainId::GOERLI_TESTNET,
                     block_validation_mode: MODE,
                     storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    reject_invalid_signatures: true,
                 };
 
                 let _jh = tokio::spawn(sync(
@@ -2309,4 +2376,138 @@ mod tests {
             assert!(uut.get(&BlockNumber::new_or_panic(3)).is_none());
         }
     }
+
+    mod verify_signature {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{BlockNumber, PublicKey, SignatureStatus, StateUpdate};
+        use pathfinder_crypto::signature::{ecdsa_sign, get_pk};
+        use pathfinder_crypto::Felt;
+        use starknet_gateway_types::reply;
+
+        use super::super::verify_signature;
+
+        const SECRET_KEY: Felt = Felt::from_u64(0x5ec2e7);
+
+        fn state_update() -> StateUpdate {
+            StateUpdate::default().with_storage_update(
+                contract_address!("0x123"),
+                storage_address!("0x1"),
+                storage_value!("0x2"),
+            )
+        }
+
+        fn signature() -> reply::BlockSignature {
+            let block_hash = block_hash!("0x1234");
+            let state_diff_commitment = state_update().compute_state_diff_commitment();
+            let message = pathfinder_crypto::hash::poseidon_hash_many(&[
+                block_hash.0.into(),
+                state_diff_commitment.0.into(),
+            ]);
+            let (r, s) = ecdsa_sign(SECRET_KEY, message.into()).unwrap();
+
+            reply::BlockSignature {
+                block_number: BlockNumber::new_or_panic(1),
+                signature: [
+                    pathfinder_common::BlockCommitmentSignatureElem(r),
+                    pathfinder_common::BlockCommitmentSignatureElem(s),
+                ],
+                signature_input: reply::BlockSignatureInput {
+                    block_hash,
+                    state_diff_commitment,
+                },
+            }
+        }
+
+        #[test]
+        fn valid() {
+            let public_key = PublicKey(get_pk(SECRET_KEY).unwrap());
+
+            let status =
+                verify_signature(&signature(), &state_update(), Some(public_key), true).unwrap();
+            assert_eq!(status, SignatureStatus::Valid);
+        }
+
+        #[test]
+        fn invalid() {
+            let public_key = PublicKey(get_pk(Felt::from_u64(0xbad)).unwrap());
+
+            verify_signature(&signature(), &state_update(), Some(public_key), true).unwrap_err();
+            let status =
+                verify_signature(&signature(), &state_update(), Some(public_key), false).unwrap();
+            assert_eq!(status, SignatureStatus::Invalid);
+        }
+
+        #[test]
+        fn state_diff_commitment_mismatch() {
+            let public_key = PublicKey(get_pk(SECRET_KEY).unwrap());
+            let state_update = state_update()
+                .with_contract_nonce(contract_address!("0x123"), contract_nonce!("0x1"));
+
+            verify_signature(&signature(), &state_update, Some(public_key), true).unwrap_err();
+            let status =
+                verify_signature(&signature(), &state_update, Some(public_key), false).unwrap();
+            assert_eq!(status, SignatureStatus::Invalid);
+        }
+
+        #[test]
+        fn unverified_without_public_key() {
+            let status = verify_signature(&signature(), &state_update(), None, true).unwrap();
+            assert_eq!(status, SignatureStatus::Unverified);
+        }
+    }
+
+    mod replay {
+        use assert_matches::assert_matches;
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{BlockNumber, Chain, ChainId};
+        use pathfinder_storage::Storage;
+        use tokio::sync::mpsc;
+
+        use super::super::{sync, BlockValidationMode, SyncEvent};
+        use crate::state::l2::{BlockChain, L2SyncContext};
+
+        /// Syncs from a feeder gateway archive holding two blocks of a custom network, the second
+        /// of which is published 100ms after the first.
+        #[tokio::test]
+        async fn syncs_from_gateway_archive() {
+            let archive = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures/gateway_archive.jsonl");
+            let sequencer = starknet_gateway_client::Client::replay(&archive).unwrap();
+            let context = L2SyncContext {
+                sequencer,
+                chain: Chain::Custom,
+                chain_id: ChainId::SEPOLIA_TESTNET,
+                // The archive's block hashes are made up.
+                block_validation_mode: BlockValidationMode::AllowMismatch,
+                storage: Storage::in_memory().unwrap(),
+                sequencer_public_key: None,
+                reject_invalid_signatures: true,
+            };
+
+            let (tx_event, mut rx_event) = mpsc::channel(1);
+            let _sync = tokio::spawn(sync(
+                tx_event,
+                context,
+                None,
+                BlockChain::with_capacity(100, vec![]),
+            ));
+
+            let expected = [
+                (BlockNumber::GENESIS, block_hash!("0x10"), 0),
+                (BlockNumber::new_or_panic(1), block_hash!("0x11"), 1),
+            ];
+            for (number, hash, storage_updates) in expected {
+                assert_matches!(
+                    rx_event.recv().await.unwrap(),
+                    SyncEvent::Block((block, _), state_update, signature, _) => {
+                        assert_eq!(block.block_number, number);
+                        assert_eq!(block.block_hash, hash);
+                        assert_eq!(state_update.block_hash, hash);
+                        assert_eq!(state_update.contract_updates.len(), storage_updates);
+                        assert_eq!(signature.0.r, block_commitment_signature_elem!("0x1"));
+                    }
+                );
+            }
+        }
+    }
 }
@@ -1,13 +1,55 @@
+use std::time::Duration;
+
 use anyhow::Context;
-use pathfinder_common::BlockHash;
 use pathfinder_common::BlockId;
+use pathfinder_common::StateUpdate;
+use pathfinder_rpc::PendingDiff;
 use pathfinder_storage::Storage;
 use starknet_gateway_client::GatewayApi;
-use starknet_gateway_types::reply::MaybePendingBlock;
+use starknet_gateway_types::reply::{MaybePendingBlock, PendingBlock};
 use tokio::time::Instant;
 
 use crate::state::sync::SyncEvent;
 
+/// The delay between polls of the pending block. It backs off while the pending block
+/// does not change, and speeds up again once it does.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PollInterval {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl PollInterval {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            current: min,
+        }
+    }
+
+    pub fn get(&self) -> Duration {
+        self.current
+    }
+
+    /// Doubles the interval, up to the maximum.
+    fn unchanged(&mut self) {
+        self.current = (self.current * 2).clamp(self.min, self.max);
+    }
+
+    /// Halves the interval, down to the minimum.
+    fn updated(&mut self) {
+        self.current = (self.current / 2).max(self.min);
+    }
+
+    /// Resets the interval to the minimum. A new pending block fills up quickly, and polling
+    /// it often keeps the pending data close to the sequencer's.
+    fn new_block(&mut self) {
+        self.current = self.min;
+    }
+}
+
 /// Poll's the Sequencer's pending block and emits [pending events](SyncEvent::Pending)
 /// until the pending block is no longer connected to our current head.
 ///
@@ -16,15 +58,16 @@ use crate::state::sync::SyncEvent;
 /// - `pending` is a fully formed block and not [PendingBlock](starknet_gateway_types::reply::MaybePendingBlock::Pending), or
 /// - the state update parent root does not match head.
 ///
+/// The delay between polls adapts to how often the pending block changes, see [PollInterval].
+///
 /// A full block or full state update can be returned from this function if it is encountered during polling.
 pub async fn poll_pending<S: GatewayApi + Clone + Send + 'static>(
     tx_event: tokio::sync::mpsc::Sender<SyncEvent>,
     sequencer: S,
-    poll_interval: std::time::Duration,
+    mut poll_interval: PollInterval,
     storage: Storage,
 ) -> anyhow::Result<()> {
-    let mut prev_tx_count = 0;
-    let mut prev_hash = BlockHash::default();
+    let mut previous: Option<(PendingBlock, StateUpdate)> = None;
 
     loop {
         let t_fetch = Instant::now();
@@ -37,18 +80,27 @@ pub async fn poll_pending<S: GatewayApi + Clone + Send + 'static>(
         // The sequencer sometimes returns full blocks, ignore these.
         let MaybePendingBlock::Pending(block) = block else {
             tracing::trace!("Full block received");
-            tokio::time::sleep_until(t_fetch + poll_interval).await;
+            tokio::time::sleep_until(t_fetch + poll_interval.get()).await;
             continue;
         };
 
-        // Use the transaction count as a proxy for freshness of the pending data.
-        //
-        // The sequencer has multiple feeder gateways which are not 100% in sync making
-        // it possible for us to receive stale data, older than the previous data.
-        if block.parent_hash == prev_hash && block.transactions.len() <= prev_tx_count {
-            tracing::trace!("No change in pending block data");
-            tokio::time::sleep_until(t_fetch + poll_interval).await;
-            continue;
+        if let Some((previous_block, previous_state)) = &previous {
+            // The sequencer has multiple feeder gateways which are not 100% in sync making
+            // it possible for us to receive stale data, older than the previous data.
+            match PendingDiff::new((previous_block, previous_state), (&block, &state_update)) {
+                None => {
+                    tracing::trace!("Stale pending block data received");
+                    tokio::time::sleep_until(t_fetch + poll_interval.get()).await;
+                    continue;
+                }
+                Some(diff) if diff.is_empty() => {
+                    tracing::trace!("No change in pending block data");
+                    poll_interval.unchanged();
+                    tokio::time::sleep_until(t_fetch + poll_interval.get()).await;
+                    continue;
+                }
+                Some(_) => {}
+            }
         }
 
         // Download, process and emit all missing classes. This can occasionally
@@ -66,26 +118,33 @@ pub async fn poll_pending<S: GatewayApi + Clone + Send + 'static>(
         {
             tracing::debug!(reason=?e, "Failed to download pending classes");
         } else {
-            prev_tx_count = block.transactions.len();
-            prev_hash = block.parent_hash;
-            tracing::trace!("Emitting a pending update");
+            match &previous {
+                Some((previous_block, _)) if previous_block.parent_hash == block.parent_hash => {
+                    poll_interval.updated()
+                }
+                _ => poll_interval.new_block(),
+            }
+
+            previous = Some((block.clone(), state_update.clone()));
+            tracing::trace!(interval=?poll_interval.get(), "Emitting a pending update");
             tx_event
                 .send(SyncEvent::Pending(Box::new((block, state_update))))
                 .await
                 .context("Event channel closed")?;
         }
 
-        tokio::time::sleep_until(t_fetch + poll_interval).await;
+        tokio::time::sleep_until(t_fetch + poll_interval.get()).await;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::time::Duration;
 
     use crate::state::sync::SyncEvent;
 
-    use super::poll_pending;
+    use super::{poll_pending, PollInterval};
     use assert_matches::assert_matches;
     use pathfinder_common::macro_prelude::*;
     use pathfinder_common::transaction::{L1HandlerTransaction, Transaction, TransactionVariant};
@@ -146,7 +205,7 @@ mod tests {
 
     /// Arbitrary timeout for receiving emits on the tokio channel. Otherwise failing tests will
     /// need to timeout naturally which may be forever.
-    const TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
 
     #[tokio::test]
     async fn success() {
@@ -167,7 +226,7 @@ mod tests {
             poll_pending(
                 tx,
                 sequencer,
-                std::time::Duration::ZERO,
+                PollInterval::new(Duration::ZERO, Duration::ZERO),
                 Storage::in_memory().unwrap(),
             )
             .await
@@ -239,7 +298,7 @@ mod tests {
             poll_pending(
                 tx,
                 sequencer,
-                std::time::Duration::ZERO,
+                PollInterval::new(Duration::ZERO, Duration::ZERO),
                 Storage::in_memory().unwrap(),
             )
             .await
@@ -259,4 +318,29 @@ mod tests {
 
         assert_matches!(result2, SyncEvent::Pending(x) if x.0 == b1 && x.1 == *PENDING_UPDATE);
     }
+
+    #[test]
+    fn poll_interval() {
+        let min = Duration::from_millis(500);
+        let max = Duration::from_secs(4);
+        let mut interval = PollInterval::new(min, max);
+        assert_eq!(interval.get(), min);
+
+        interval.unchanged();
+        interval.unchanged();
+        assert_eq!(interval.get(), Duration::from_secs(2));
+
+        interval.unchanged();
+        interval.unchanged();
+        assert_eq!(interval.get(), max);
+
+        interval.updated();
+        assert_eq!(interval.get(), Duration::from_secs(2));
+
+        interval.new_block();
+        assert_eq!(interval.get(), min);
+
+        interval.updated();
+        assert_eq!(interval.get(), min);
+    }
 }
@@ -0,0 +1,376 @@
+//! Follows a database which is kept up-to-date by another pathfinder instance.
+//!
+//! A replica opens the database read-only and therefore performs no syncing of its own. Instead
+//! it polls the database for new blocks, which are announced to websocket subscribers and used
+//! to track the sync status. Pending data is not stored in the database, and is polled from the
+//! gateway directly. Its changes are announced to websocket subscribers as pending updates, the
+//! same as by a syncing instance.
+//!
+//! Cached event bloom filters are keyed by the reorg counter stored in the database, so reorgs
+//! performed by the writing instance are observed without any further action.
+
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockHeader, BlockId, BlockNumber};
+use pathfinder_crypto::Felt;
+use pathfinder_rpc::v02::types::syncing::{NumberedBlock, Syncing};
+use pathfinder_rpc::{PendingData, PendingDiff, PendingUpdate, SyncState, TopicBroadcasters};
+use pathfinder_storage::{Storage, Transaction};
+use starknet_gateway_client::GatewayApi;
+use starknet_gateway_types::reply::MaybePendingBlock;
+use tokio::sync::watch::Sender as WatchSender;
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::Gossiper;
+
+/// The maximum number of new blocks announced in one go. Older blocks are skipped if the
+/// writer has progressed further than this since the last poll.
+const MAX_ANNOUNCED_BLOCKS: u64 = 100;
+
+pub struct ReplicaContext<G> {
+    pub storage: Storage,
+    pub sequencer: G,
+    pub state: Arc<SyncState>,
+    pub poll_interval: Duration,
+    pub pending_data: WatchSender<PendingData>,
+    pub websocket_txs: Option<TopicBroadcasters>,
+}
+
+/// Polls the database for new blocks and the gateway for pending data.
+pub async fn follow<G>(context: ReplicaContext<G>) -> anyhow::Result<()>
+where
+    G: GatewayApi + Clone + Send + Sync + 'static,
+{
+    let ReplicaContext {
+        storage,
+        sequencer,
+        state,
+        poll_interval,
+        pending_data,
+        mut websocket_txs,
+    } = context;
+
+    let mut db_conn = storage
+        .connection()
+        .context("Creating database connection")?;
+
+    let mut head = tokio::task::block_in_place(|| {
+        let tx = db_conn
+            .transaction()
+            .context("Creating database transaction")?;
+        tx.block_header(pathfinder_storage::BlockId::Latest)
+            .context("Fetching latest block header")
+    })?;
+
+    let (starting_block_num, starting_block_hash) = head
+        .as_ref()
+        .map(|header| (header.number, header.hash))
+        // Seems a better choice for an invalid block number than 0
+        .unwrap_or((BlockNumber::MAX, BlockHash(Felt::ZERO)));
+    let _status_sync = tokio::spawn(super::update_sync_status_latest(
+        Arc::clone(&state),
+        sequencer.clone(),
+        starting_block_hash,
+        starting_block_num,
+        poll_interval,
+        Gossiper::default(),
+    ));
+
+    loop {
+        let t_poll = Instant::now();
+
+        let headers = tokio::task::block_in_place(|| {
+            let tx = db_conn
+                .transaction()
+                .context("Creating database transaction")?;
+            new_headers(&tx, head.as_ref())
+        })
+        .context("Polling database for new blocks")?;
+
+        if let Some(latest) = headers.last() {
+            tracing::debug!(number=%latest.number, "New block found in database");
+
+            if let Syncing::Status(status) = &mut *state.status.write().await {
+                status.current = NumberedBlock::from((latest.hash, latest.number));
+                metrics::gauge!("current_block", latest.number.get() as f64);
+
+                if status.highest.number <= latest.number {
+                    status.highest = status.current;
+                    metrics::gauge!("highest_block", latest.number.get() as f64);
+                }
+            }
+
+            head = Some(latest.clone());
+        }
+
+        for header in headers {
+            if let Some(sender) = &websocket_txs {
+                if let Err(e) = sender.new_head.send_if_receiving(header.into()) {
+                    tracing::error!(error=?e, "Failed to send header over websocket broadcaster.");
+                    // Disable websocket entirely so that the closed channel doesn't spam this error.
+                    websocket_txs = None;
+                }
+            }
+        }
+
+        if let Some(head) = &head {
+            match sequencer.state_update_with_block(BlockId::Pending).await {
+                Ok((MaybePendingBlock::Pending(block), state_update))
+                    if block.parent_hash == head.hash =>
+                {
+                    let previous = pending_data.borrow().clone();
+                    // The gateway may return stale data, which does not extend the previous
+                    // pending data.
+                    match PendingDiff::new(
+                        (&previous.block, &previous.state_update),
+                        (&block, &state_update),
+                    ) {
+                        Some(diff) if diff.is_empty() => {}
+                        Some(diff) => {
+                            let number = head.number + 1;
+                            if let Some(sender) = &websocket_txs {
+                                let update = PendingUpdate::new(number, diff);
+                                if let Err(e) = sender.pending_update.send_if_receiving(update) {
+                                    tracing::error!(error=?e, "Failed to send pending update over websocket broadcaster.");
+                                    websocket_txs = None;
+                                }
+                            }
+
+                            pending_data.send_replace(PendingData {
+                                block: block.into(),
+                                state_update: state_update.into(),
+                                number,
+                            });
+                            tracing::debug!("Updated pending data");
+                        }
+                        None => tracing::trace!("Stale pending data received"),
+                    }
+                }
+                Ok(_) => tracing::trace!("Pending block is not connected to our head"),
+                Err(e) => tracing::debug!(reason=?e, "Failed to download pending block"),
+            }
+        }
+
+        tokio::time::sleep_until(t_poll + poll_interval).await;
+    }
+}
+
+/// Returns the headers of blocks which were added to the database after `head`, in ascending
+/// order.
+///
+/// If `head` is no longer part of the canonical chain then only the new latest header is
+/// returned.
+fn new_headers(
+    tx: &Transaction<'_>,
+    head: Option<&BlockHeader>,
+) -> anyhow::Result<Vec<BlockHeader>> {
+    let Some(latest) = tx
+        .block_header(pathfinder_storage::BlockId::Latest)
+        .context("Fetching latest block header")?
+    else {
+        return Ok(Vec::new());
+    };
+
+    let Some(head) = head else {
+        return Ok(vec![latest]);
+    };
+
+    if latest.hash == head.hash {
+        return Ok(Vec::new());
+    }
+
+    let canonical = tx
+        .block_id(head.number.into())
+        .context("Fetching block hash")?
+        .is_some_and(|(_, hash)| hash == head.hash);
+    if !canonical || latest.number <= head.number {
+        return Ok(vec![latest]);
+    }
+
+    let first = std::cmp::max(
+        head.number.get() + 1,
+        latest.number.get().saturating_sub(MAX_ANNOUNCED_BLOCKS - 1),
+    );
+
+    let mut headers = Vec::new();
+    for number in first..latest.number.get() {
+        let number = BlockNumber::new_or_panic(number);
+        let header = tx
+            .block_header(number.into())
+            .context("Fetching block header")?
+            .context("Block header is missing")?;
+        headers.push(header);
+    }
+    headers.push(latest);
+
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    /// Creates a chain of `n` headers with hashes derived from `seed`.
+    fn chain(n: u64, seed: u64) -> Vec<BlockHeader> {
+        let mut headers = vec![BlockHeader::builder()
+            .with_number(BlockNumber::GENESIS)
+            .finalize_with_hash(block_hash!("0x1"))];
+
+        for i in 1..n {
+            let header = headers
+                .last()
+                .unwrap()
+                .child_builder()
+                .finalize_with_hash(BlockHash(Felt::from_u64(seed * 1000 + i)));
+            headers.push(header);
+        }
+
+        headers
+    }
+
+    fn insert(tx: &Transaction<'_>, headers: &[BlockHeader]) {
+        for header in headers {
+            tx.insert_block_header(header).unwrap();
+        }
+    }
+
+    #[test]
+    fn empty_database() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        assert!(new_headers(&tx, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn first_poll_returns_latest() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let headers = chain(5, 1);
+        insert(&tx, &headers);
+
+        assert_eq!(new_headers(&tx, None).unwrap(), vec![headers[4].clone()]);
+    }
+
+    #[test]
+    fn unchanged() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let headers = chain(5, 1);
+        insert(&tx, &headers);
+
+        assert!(new_headers(&tx, Some(&headers[4])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn new_blocks_in_order() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let headers = chain(5, 1);
+        insert(&tx, &headers);
+
+        assert_eq!(
+            new_headers(&tx, Some(&headers[1])).unwrap(),
+            headers[2..].to_vec()
+        );
+    }
+
+    #[test]
+    fn reorg_returns_latest() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let old = chain(5, 1);
+        insert(&tx, &old[..2]);
+        let new = chain(4, 2);
+        insert(&tx, &new[2..]);
+
+        assert_eq!(
+            new_headers(&tx, Some(&old[4])).unwrap(),
+            vec![new[3].clone()]
+        );
+    }
+
+    #[test]
+    fn announcements_are_limited() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let headers = chain(MAX_ANNOUNCED_BLOCKS + 10, 1);
+        insert(&tx, &headers);
+
+        let result = new_headers(&tx, Some(&headers[0])).unwrap();
+        assert_eq!(result.len() as u64, MAX_ANNOUNCED_BLOCKS);
+        assert_eq!(result.last(), headers.last());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pending_updates_are_announced() {
+        use starknet_gateway_client::MockGatewayApi;
+        use starknet_gateway_types::error::SequencerError;
+        use starknet_gateway_types::reply::PendingBlock;
+
+        let storage = Storage::in_memory().unwrap();
+        let headers = chain(1, 1);
+        {
+            let mut db = storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            insert(&tx, &headers);
+            tx.commit().unwrap();
+        }
+
+        let block = PendingBlock {
+            parent_hash: headers[0].hash,
+            ..Default::default()
+        };
+        let mut sequencer = MockGatewayApi::new();
+        sequencer
+            .expect_head()
+            .returning(|| Err(SequencerError::InvalidStarknetErrorVariant));
+        sequencer
+            .expect_state_update_with_block()
+            .returning(move |_| {
+                Ok((
+                    MaybePendingBlock::Pending(block.clone()),
+                    Default::default(),
+                ))
+            });
+
+        let websocket_txs = TopicBroadcasters::default();
+        let mut updates = websocket_txs.pending_update.subscribe();
+        let (pending_data, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ReplicaContext {
+            storage,
+            sequencer: Arc::new(sequencer),
+            state: Default::default(),
+            poll_interval: Duration::from_millis(10),
+            pending_data,
+            websocket_txs: Some(websocket_txs),
+        };
+        let _follow = tokio::spawn(follow(context));
+
+        let update = tokio::time::timeout(Duration::from_secs(5), updates.recv())
+            .await
+            .expect("Pending update should be announced")
+            .unwrap();
+        assert_eq!(update["number"], serde_json::json!(1));
+        assert_eq!(update["new_block"], serde_json::json!(true));
+
+        // The unchanged pending block is not announced again.
+        tokio::time::timeout(Duration::from_millis(100), updates.recv())
+            .await
+            .unwrap_err();
+    }
+}
@@ -17,6 +17,11 @@ pub struct RpcConfig {
     pub batch_concurrency_limit: NonZeroUsize,
     pub get_events_max_blocks_to_scan: NonZeroUsize,
     pub get_events_max_uncached_bloom_filters_to_load: NonZeroUsize,
+    /// The number of workers used to trace block transactions in parallel.
+    /// Blocks are traced sequentially if this is [None].
+    pub trace_concurrency: Option<NonZeroUsize>,
+    /// The directory `pathfinder_backup` writes to. The method is disabled if this is [None].
+    pub backup_directory: Option<std::path::PathBuf>,
 }
 
 #[derive(Clone)]
@@ -24,6 +29,9 @@ pub struct RpcContext {
     pub cache: TraceCache,
     pub storage: Storage,
     pub execution_storage: Storage,
+    /// Used by the workers of parallel tracing, so that tracing cannot take the connections
+    /// needed by simulation and fee estimation.
+    pub trace_storage: Storage,
     pub pending_data: PendingWatcher,
     pub sync_status: Arc<SyncState>,
     pub chain_id: ChainId,
@@ -47,6 +55,7 @@ impl RpcContext {
         Self {
             cache: Default::default(),
             storage,
+            trace_storage: execution_storage.clone(),
             execution_storage,
             sync_status,
             chain_id,
@@ -87,6 +96,8 @@ impl RpcContext {
             batch_concurrency_limit: NonZeroUsize::new(8).unwrap(),
             get_events_max_blocks_to_scan: NonZeroUsize::new(1000).unwrap(),
             get_events_max_uncached_bloom_filters_to_load: NonZeroUsize::new(1000).unwrap(),
+            trace_concurrency: None,
+            backup_directory: None,
         };
 
         Self::new(
@@ -103,7 +114,15 @@ impl RpcContext {
     pub fn with_storage(self, storage: Storage) -> Self {
         Self {
             storage: storage.clone(),
-            execution_storage: storage,
+            execution_storage: storage.clone(),
+            trace_storage: storage,
+            ..self
+        }
+    }
+
+    pub fn with_trace_storage(self, trace_storage: Storage) -> Self {
+        Self {
+            trace_storage,
             ..self
         }
     }
@@ -83,6 +83,13 @@ pub enum ApplicationError {
         transaction_index: usize,
         error: String,
     },
+    #[error("Mid-block state is not supported for this block")]
+    MidBlockStateUnsupported { starknet_version: String },
+    #[error("Failed to replay the block up to the requested transaction")]
+    BlockReplayFailed {
+        transaction_index: usize,
+        error: String,
+    },
     /// Internal errors are errors whose details we don't want to show to the end user.
     /// These are logged, and a simple "internal error" message is shown to the end
     /// user.
@@ -131,6 +138,8 @@ impl ApplicationError {
             ApplicationError::UnexpectedError { .. } => 63,
             // doc/rpc/pathfinder_rpc_api.json
             ApplicationError::ProofLimitExceeded { .. } => 10000,
+            ApplicationError::MidBlockStateUnsupported { .. } => 10001,
+            ApplicationError::BlockReplayFailed { .. } => 10002,
             // https://www.jsonrpc.org/specification#error_object
             ApplicationError::GatewayError(_)
             | ApplicationError::Internal(_)
@@ -205,6 +214,16 @@ impl ApplicationError {
                 "requested": requested,
             })),
             ApplicationError::ValidationFailureV06(error) => Some(json!(error)),
+            ApplicationError::MidBlockStateUnsupported { starknet_version } => Some(json!({
+                "starknet_version": starknet_version,
+            })),
+            ApplicationError::BlockReplayFailed {
+                transaction_index,
+                error,
+            } => Some(json!({
+                "transaction_index": transaction_index,
+                "execution_error": error,
+            })),
         }
     }
 }
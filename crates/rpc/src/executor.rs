@@ -1,10 +1,12 @@
+use crate::context::RpcContext;
 use crate::v02::types::request::BroadcastedDeployAccountTransaction;
 use crate::v02::types::request::BroadcastedInvokeTransaction;
 use crate::v02::types::request::BroadcastedTransaction;
 use anyhow::Context;
 use pathfinder_common::transaction::TransactionVariant;
-use pathfinder_common::ChainId;
-use pathfinder_executor::IntoStarkFelt;
+use pathfinder_common::{BlockHeader, ChainId, TransactionHash};
+use pathfinder_executor::types::TransactionTrace;
+use pathfinder_executor::{ExecutionState, IntoStarkFelt, TraceCache, TransactionExecutionError};
 use starknet_api::core::PatriciaKey;
 
 pub enum ExecutionStateError {
@@ -433,6 +435,42 @@ fn map_transaction_variant(
     }
 }
 
+/// Traces all transactions of a block, in parallel if this is enabled by
+/// [RpcConfig::trace_concurrency](crate::context::RpcConfig::trace_concurrency).
+pub(crate) fn trace_block(
+    context: &RpcContext,
+    db: &pathfinder_storage::Transaction<'_>,
+    header: BlockHeader,
+    transactions: &[pathfinder_common::transaction::Transaction],
+    cache: TraceCache,
+) -> Result<Vec<(TransactionHash, TransactionTrace)>, TransactionExecutionError> {
+    let hash = header.hash;
+    let state = ExecutionState::trace(db, context.chain_id, header, None);
+
+    match context.config.trace_concurrency {
+        Some(concurrency) => pathfinder_executor::trace_parallel(
+            state,
+            context.trace_storage.clone(),
+            concurrency,
+            cache,
+            hash,
+            transactions,
+            compose_executor_transaction,
+            true,
+            true,
+        )
+        .map(|trace| trace.traces),
+        None => {
+            let transactions = transactions
+                .iter()
+                .map(|transaction| compose_executor_transaction(transaction, db))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            pathfinder_executor::trace(state, cache, hash, transactions, true, true)
+        }
+    }
+}
+
 /// Build the executor transaction out of the gateway one
 /// while pulling necessary data from the DB along the way.
 pub fn compose_executor_transaction(
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use anyhow::Context;
+use pathfinder_common::{BlockHeader, BlockNumber, GasPrice};
 use primitive_types::U256;
 use starknet_gateway_client::GatewayApi;
 use starknet_gateway_types::reply::MaybePendingBlock;
@@ -88,3 +90,193 @@ impl Cached {
         None
     }
 }
+
+/// The number of blocks considered when a fee estimate asks for a gas price [Percentile].
+pub const PERCENTILE_WINDOW: usize = 100;
+
+/// A percentile in the range `0..=100`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "u8")]
+pub struct Percentile(u8);
+
+impl TryFrom<u8> for Percentile {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value > 100 {
+            return Err(format!("Percentile must be at most 100, got {value}"));
+        }
+        Ok(Self(value))
+    }
+}
+
+/// L1 gas prices of a consecutive range of stored blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    pub oldest: BlockNumber,
+    pub eth_l1_gas_price: Vec<GasPrice>,
+    /// [None] for blocks which were stored without a STRK gas price. These are skipped by the
+    /// percentiles and the trend.
+    pub strk_l1_gas_price: Vec<Option<GasPrice>>,
+}
+
+impl History {
+    /// Loads the gas prices of up to `count` blocks ending at `newest`.
+    ///
+    /// Returns `None` if there are no blocks in that range.
+    pub fn load(
+        db: &pathfinder_storage::Transaction<'_>,
+        newest: BlockNumber,
+        count: usize,
+    ) -> anyhow::Result<Option<Self>> {
+        let prices = db
+            .gas_prices(newest, count)
+            .context("Fetching gas prices")?;
+
+        let Some((oldest, _, _)) = prices.first() else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            oldest: *oldest,
+            eth_l1_gas_price: prices.iter().map(|x| x.1).collect(),
+            strk_l1_gas_price: prices.iter().map(|x| x.2).collect(),
+        }))
+    }
+
+    pub fn eth_l1_gas_price_percentile(&self, percentile: Percentile) -> GasPrice {
+        nearest_rank(self.eth_l1_gas_price.iter().copied(), percentile)
+    }
+
+    pub fn strk_l1_gas_price_percentile(&self, percentile: Percentile) -> GasPrice {
+        nearest_rank(self.strk_l1_gas_price.iter().flatten().copied(), percentile)
+    }
+
+    /// The least-squares slope of the ETH gas price, in wei per block.
+    pub fn eth_l1_gas_price_trend(&self) -> f64 {
+        slope(self.eth_l1_gas_price.iter().copied().enumerate())
+    }
+
+    /// The least-squares slope of the STRK gas price, in fri per block.
+    pub fn strk_l1_gas_price_trend(&self) -> f64 {
+        slope(
+            self.strk_l1_gas_price
+                .iter()
+                .enumerate()
+                .filter_map(|(x, y)| y.map(|y| (x, y))),
+        )
+    }
+}
+
+/// Raises the header's gas prices to the given percentile of the last [PERCENTILE_WINDOW] stored
+/// blocks up to and including the header's block. For a pending header these are the blocks
+/// preceding it, as the pending block is not stored.
+///
+/// Gas prices are never lowered, so that a spike in the current block is not averaged away.
+pub(crate) fn apply_percentile(
+    db: &pathfinder_storage::Transaction<'_>,
+    header: &mut BlockHeader,
+    percentile: Percentile,
+) -> anyhow::Result<()> {
+    let Some(history) = History::load(db, header.number, PERCENTILE_WINDOW)? else {
+        return Ok(());
+    };
+
+    let eth = history.eth_l1_gas_price_percentile(percentile);
+    let strk = history.strk_l1_gas_price_percentile(percentile);
+
+    header.eth_l1_gas_price = GasPrice(header.eth_l1_gas_price.0.max(eth.0));
+    header.strk_l1_gas_price = GasPrice(header.strk_l1_gas_price.0.max(strk.0));
+
+    Ok(())
+}
+
+fn nearest_rank(prices: impl Iterator<Item = GasPrice>, percentile: Percentile) -> GasPrice {
+    let mut sorted = prices.map(|x| x.0).collect::<Vec<_>>();
+    sorted.sort_unstable();
+
+    if sorted.is_empty() {
+        return GasPrice::ZERO;
+    }
+
+    let rank = (percentile.0 as usize * sorted.len()).div_ceil(100);
+    GasPrice(sorted[rank.saturating_sub(1)])
+}
+
+/// The least-squares slope of gas prices, given along with their position in the range.
+fn slope(prices: impl Iterator<Item = (usize, GasPrice)>) -> f64 {
+    let prices = prices
+        .map(|(x, y)| (x as f64, y.0 as f64))
+        .collect::<Vec<_>>();
+    let n = prices.len() as f64;
+    if prices.len() < 2 {
+        return 0.0;
+    }
+
+    let mean_x = prices.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = prices.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let (covariance, variance) =
+        prices
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                let dx = x - mean_x;
+                (covariance + dx * (y - mean_y), variance + dx * dx)
+            });
+
+    covariance / variance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(values: &[u128]) -> impl Iterator<Item = GasPrice> + '_ {
+        values.iter().copied().map(GasPrice)
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let prices = || prices(&[15, 20, 35, 40, 50]);
+
+        assert_eq!(nearest_rank(prices(), Percentile(0)), GasPrice(15));
+        assert_eq!(nearest_rank(prices(), Percentile(5)), GasPrice(15));
+        assert_eq!(nearest_rank(prices(), Percentile(30)), GasPrice(20));
+        assert_eq!(nearest_rank(prices(), Percentile(40)), GasPrice(20));
+        assert_eq!(nearest_rank(prices(), Percentile(50)), GasPrice(35));
+        assert_eq!(nearest_rank(prices(), Percentile(100)), GasPrice(50));
+        assert_eq!(
+            nearest_rank(std::iter::empty(), Percentile(50)),
+            GasPrice::ZERO
+        );
+    }
+
+    #[test]
+    fn slope_of_gas_prices() {
+        assert_eq!(slope(prices(&[10, 20, 30, 40]).enumerate()), 10.0);
+        assert_eq!(slope(prices(&[40, 30, 20, 10]).enumerate()), -10.0);
+        assert_eq!(slope(prices(&[7, 7, 7]).enumerate()), 0.0);
+        assert_eq!(slope(prices(&[7]).enumerate()), 0.0);
+    }
+
+    #[test]
+    fn missing_strk_gas_prices_are_skipped() {
+        let history = History {
+            oldest: BlockNumber::GENESIS,
+            eth_l1_gas_price: prices(&[1, 1, 1, 1]).collect(),
+            strk_l1_gas_price: vec![None, Some(GasPrice(20)), None, Some(GasPrice(40))],
+        };
+
+        assert_eq!(
+            history.strk_l1_gas_price_percentile(Percentile(0)),
+            GasPrice(20)
+        );
+        assert_eq!(history.strk_l1_gas_price_trend(), 10.0);
+    }
+
+    #[test]
+    fn percentile_is_bounded() {
+        serde_json::from_value::<Percentile>(serde_json::json!(100)).unwrap();
+        serde_json::from_value::<Percentile>(serde_json::json!(101)).unwrap_err();
+    }
+}
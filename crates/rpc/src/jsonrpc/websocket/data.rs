@@ -95,6 +95,7 @@ pub(super) enum ResponseEvent {
     InvalidMethod(OwnedRequestId),
     InvalidParams(OwnedRequestId, String),
     Header(SubscriptionItem<Arc<Value>>),
+    PendingUpdate(SubscriptionItem<Arc<Value>>),
 }
 
 impl ResponseEvent {
@@ -103,6 +104,7 @@ impl ResponseEvent {
             ResponseEvent::InvalidRequest(_) => "InvalidRequest",
             ResponseEvent::InvalidMethod(_) => "InvalidMethod",
             ResponseEvent::Header(_) => "BlockHeader",
+            ResponseEvent::PendingUpdate(_) => "PendingUpdate",
             ResponseEvent::Subscribed { .. } => "Subscribed",
             ResponseEvent::Unsubscribed { .. } => "Unsubscribed",
             ResponseEvent::SubscriptionClosed { .. } => "SubscriptionClosed",
@@ -127,6 +129,7 @@ impl Serialize for ResponseEvent {
                 RpcResponse::invalid_params(id.into(), e.clone()).serialize(serializer)
             }
             ResponseEvent::Header(header) => header.serialize(serializer),
+            ResponseEvent::PendingUpdate(update) => update.serialize(serializer),
             ResponseEvent::Subscribed {
                 subscription_id,
                 request_id,
@@ -224,3 +227,54 @@ impl serde::Serialize for BlockHeader {
         map.end()
     }
 }
+
+/// An incremental update of the pending block, see [PendingDiff](crate::pending::PendingDiff).
+///
+/// Transactions and receipts use the same types as the v0.6 RPC API.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PendingUpdate {
+    number: pathfinder_common::BlockNumber,
+    parent_hash: pathfinder_common::BlockHash,
+    new_block: bool,
+    transactions: Vec<crate::v06::types::TransactionWithHash>,
+    receipts: Vec<crate::v06::method::get_transaction_receipt::types::PendingTransactionReceipt>,
+    state_diff: crate::v03::method::get_state_update::types::StateDiff,
+}
+
+impl PendingUpdate {
+    /// Receipts are matched to their transaction by hash. A receipt whose transaction is not
+    /// part of the diff is omitted, which only happens if the feeder gateway served the
+    /// transaction in an earlier snapshot than its receipt.
+    pub fn new(number: pathfinder_common::BlockNumber, diff: crate::pending::PendingDiff) -> Self {
+        use crate::v06::method::get_transaction_receipt::types::PendingTransactionReceipt;
+
+        let state_update =
+            crate::v03::method::get_state_update::types::StateUpdate::from(diff.state_diff);
+
+        let receipts = diff
+            .receipts
+            .into_iter()
+            .filter_map(|receipt| {
+                let Some(transaction) = diff
+                    .transactions
+                    .iter()
+                    .find(|tx| tx.hash == receipt.transaction_hash)
+                else {
+                    tracing::debug!(transaction_hash=%receipt.transaction_hash, "Omitting pending receipt without its transaction");
+                    return None;
+                };
+
+                Some(PendingTransactionReceipt::from(receipt, transaction))
+            })
+            .collect();
+
+        Self {
+            number,
+            parent_hash: diff.parent_hash,
+            new_block: diff.new_block,
+            transactions: diff.transactions.into_iter().map(Into::into).collect(),
+            receipts,
+            state_diff: state_update.state_diff,
+        }
+    }
+}
@@ -20,11 +20,12 @@ use tokio::sync::{broadcast, mpsc};
 use tracing::error;
 
 use crate::jsonrpc::websocket::data::{Kind, ResponseEvent, SubscriptionId, SubscriptionItem};
-use crate::BlockHeader;
+use crate::{BlockHeader, PendingUpdate};
 
 const SUBSCRIBE_METHOD: &str = "pathfinder_subscribe";
 const UNSUBSCRIBE_METHOD: &str = "pathfinder_unsubscribe";
 const NEW_HEADS_TOPIC: &str = "newHeads";
+const PENDING_UPDATES_TOPIC: &str = "pendingUpdates";
 
 #[derive(Clone)]
 pub struct WebsocketContext {
@@ -246,12 +247,20 @@ impl SubscriptionManager {
 
         let subscription_id = self.next_id;
         self.next_id += 1;
-        let receiver = websocket_source.new_head.subscribe();
         let handle = match kind.kind.as_ref() {
-            NEW_HEADS_TOPIC => tokio::spawn(header_subscription(
+            NEW_HEADS_TOPIC => tokio::spawn(topic_subscription(
                 response_sender,
-                receiver,
+                websocket_source.new_head.subscribe(),
+                subscription_id,
+                ResponseEvent::Header,
+                "headers",
+            )),
+            PENDING_UPDATES_TOPIC => tokio::spawn(topic_subscription(
+                response_sender,
+                websocket_source.pending_update.subscribe(),
                 subscription_id,
+                ResponseEvent::PendingUpdate,
+                "pending updates",
             )),
             _ => {
                 return ResponseEvent::InvalidParams(
@@ -276,30 +285,35 @@ impl SubscriptionManager {
     }
 }
 
-async fn header_subscription(
+/// Forwards the items of a topic to the client, wrapping each in the `event` of the topic.
+async fn topic_subscription(
     msg_sender: mpsc::Sender<ResponseEvent>,
-    mut headers: broadcast::Receiver<Arc<Value>>,
+    mut items: broadcast::Receiver<Arc<Value>>,
     subscription_id: u32,
+    event: fn(SubscriptionItem<Arc<Value>>) -> ResponseEvent,
+    item_name: &'static str,
 ) {
     use broadcast::error::RecvError;
     loop {
-        let response = match headers.recv().await {
-            Ok(header) => ResponseEvent::Header(SubscriptionItem {
+        let response = match items.recv().await {
+            Ok(item) => event(SubscriptionItem {
                 subscription_id,
-                item: header,
+                item,
             }),
             Err(RecvError::Closed) => break,
             Err(RecvError::Lagged(amount)) => {
                 tracing::info!(
                     amount,
-                    "Lagging header stream, missed some events, closing subscription"
+                    topic = item_name,
+                    "Lagging stream, missed some events, closing subscription"
                 );
 
                 // No explicit break here, the loop will be broken by the dropped receiver.
                 ResponseEvent::SubscriptionClosed {
                     subscription_id,
-                    reason: "Lagging stream, some headers were skipped. Closing subscription."
-                        .to_owned(),
+                    reason: format!(
+                        "Lagging stream, some {item_name} were skipped. Closing subscription."
+                    ),
                 }
             }
         };
@@ -351,6 +365,7 @@ where
 #[derive(Debug, Clone)]
 pub struct TopicBroadcasters {
     pub new_head: JsonBroadcaster<BlockHeader>,
+    pub pending_update: JsonBroadcaster<PendingUpdate>,
 }
 
 impl TopicBroadcasters {
@@ -360,6 +375,10 @@ impl TopicBroadcasters {
                 sender: broadcast::channel(capacity.get()).0,
                 item_type: PhantomData {},
             },
+            pending_update: JsonBroadcaster {
+                sender: broadcast::channel(capacity.get()).0,
+                item_type: PhantomData {},
+            },
         }
     }
 }
@@ -474,6 +493,88 @@ mod tests {
         client.destroy().await;
     }
 
+    #[tokio::test]
+    async fn can_subscribe_to_pending_updates() {
+        let mut client = Client::new().await;
+
+        let req_id = RequestId::Number(1);
+        client
+            .send_request(&RpcRequest {
+                method: Cow::from(SUBSCRIBE_METHOD),
+                params: RawParams(Some(&value(&Kind {
+                    kind: PENDING_UPDATES_TOPIC.into(),
+                }))),
+                id: req_id.clone(),
+            })
+            .await;
+        client
+            .expect_response(&successful_response(&0, req_id).unwrap())
+            .await;
+
+        let update = PendingUpdate::new(Default::default(), Default::default());
+        client
+            .pending_sender
+            .send_if_receiving(update.clone())
+            .unwrap();
+        client
+            .expect_response(&SubscriptionItem {
+                subscription_id: 0,
+                item: update,
+            })
+            .await;
+
+        // Headers are not sent to a pending updates subscription.
+        client
+            .head_sender
+            .send_if_receiving(header_sample())
+            .unwrap();
+        client.expect_no_response().await;
+
+        client.destroy().await;
+    }
+
+    #[test]
+    fn pending_update_uses_rpc_types() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::receipt::Receipt;
+        use pathfinder_common::transaction::{
+            L1HandlerTransaction, Transaction, TransactionVariant,
+        };
+
+        let tx0 = transaction_hash_bytes!(b"tx 0");
+        let tx1 = transaction_hash_bytes!(b"tx 1");
+        let diff = crate::PendingDiff {
+            transactions: vec![Transaction {
+                hash: tx0,
+                variant: TransactionVariant::L1Handler(L1HandlerTransaction::default()),
+            }],
+            // The transaction of the second receipt was announced in an earlier update.
+            receipts: vec![
+                Receipt {
+                    transaction_hash: tx0,
+                    ..Default::default()
+                },
+                Receipt {
+                    transaction_hash: tx1,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let update = serde_json::to_value(PendingUpdate::new(Default::default(), diff)).unwrap();
+
+        assert_eq!(update["transactions"][0]["transaction_hash"], json!(tx0));
+        assert_eq!(update["transactions"][0]["type"], json!("L1_HANDLER"));
+        assert_eq!(update["receipts"].as_array().unwrap().len(), 1);
+        assert_eq!(update["receipts"][0]["transaction_hash"], json!(tx0));
+        assert_eq!(update["receipts"][0]["type"], json!("L1_HANDLER"));
+        assert_eq!(
+            update["receipts"][0]["finality_status"],
+            json!("ACCEPTED_ON_L2")
+        );
+    }
+
     // TODO Prevent duplicate subscriptions?
     // This is actually tolerated by Alchemy, you can subscribe multiple times
     // to the same topic and receive duplicated messages as a result.
@@ -495,12 +596,14 @@ mod tests {
         receiver: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
         server_handle: JoinHandle<()>,
         head_sender: JsonBroadcaster<BlockHeader>,
+        pending_sender: JsonBroadcaster<PendingUpdate>,
     }
 
     impl Client {
         async fn new() -> Client {
             let context = WebsocketContext::default();
             let head_sender = context.broadcasters.new_head.clone();
+            let pending_sender = context.broadcasters.pending_update.clone();
 
             let router = axum::Router::new()
                 .route("/ws", get(websocket_handler))
@@ -528,6 +631,7 @@ mod tests {
 
             Client {
                 head_sender,
+                pending_sender,
                 sender,
                 receiver,
                 server_handle,
@@ -16,16 +16,19 @@ pub mod v04;
 pub mod v05;
 pub mod v06;
 
-pub use executor::compose_executor_transaction;
-pub use pending::PendingData;
+pub use executor::{
+    compose_executor_transaction,
+    VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY,
+};
+pub use pending::{PendingData, PendingDiff, PendingWatcher};
 
 use crate::jsonrpc::rpc_handler;
 use crate::jsonrpc::websocket::websocket_handler;
-pub use crate::jsonrpc::websocket::{BlockHeader, TopicBroadcasters};
+pub use crate::jsonrpc::websocket::{BlockHeader, PendingUpdate, TopicBroadcasters};
 use crate::v02::types::syncing::Syncing;
 use anyhow::Context;
 use axum::error_handling::HandleErrorLayer;
-use axum::extract::DefaultBodyLimit;
+use axum::extract::{ConnectInfo, DefaultBodyLimit};
 
 use axum::response::IntoResponse;
 use context::RpcContext;
@@ -39,6 +42,7 @@ use tower_http::cors::CorsLayer;
 use tower_http::ServiceBuilderExt;
 
 const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+const ADMIN_REQUEST_MAX_SIZE: usize = 64 * 1024;
 
 pub enum DefaultVersion {
     V05,
@@ -76,6 +80,40 @@ impl RpcServer {
         }
     }
 
+    /// Starts a separate HTTP-RPC server at `addr`, which serves only the administrative methods.
+    ///
+    /// These are not served by the public server, so that exposing it, for example through a
+    /// reverse proxy, does not expose them.
+    pub fn spawn_admin(
+        &self,
+        addr: SocketAddr,
+    ) -> Result<(JoinHandle<anyhow::Result<()>>, SocketAddr), anyhow::Error> {
+        use axum::routing::post;
+
+        let listener = std::net::TcpListener::bind(addr)
+            .with_context(|| format!("Binding admin RPC address {addr}"))?;
+        let addr = listener
+            .local_addr()
+            .context("Getting local address from listener")?;
+        let server = axum::Server::from_tcp(listener).context("Binding server to tcp listener")?;
+
+        let admin_routes = pathfinder::register_admin_routes().build(self.context.clone());
+        let router = axum::Router::new()
+            .route("/rpc/pathfinder/admin/v0.1", post(admin_rpc_handler))
+            .with_state(admin_routes)
+            .layer(DefaultBodyLimit::max(ADMIN_REQUEST_MAX_SIZE))
+            .layer(middleware::tracing::trace_layer());
+
+        let server_handle = tokio::spawn(async move {
+            server
+                .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .map_err(Into::into)
+        });
+
+        Ok((server_handle, addr))
+    }
+
     /// Starts the HTTP-RPC server.
     pub fn spawn(self) -> Result<(JoinHandle<anyhow::Result<()>>, SocketAddr), anyhow::Error> {
         use axum::routing::{get, post};
@@ -175,7 +213,7 @@ impl RpcServer {
 
         let server_handle = tokio::spawn(async move {
             server
-                .serve(router.into_make_service())
+                .serve(router.into_make_service_with_connect_info::<SocketAddr>())
                 .await
                 .map_err(Into::into)
         });
@@ -191,6 +229,21 @@ impl RpcServer {
     }
 }
 
+/// Only serves requests from the local machine, as administrative methods must not be exposed to
+/// the public even if the admin listener is reachable from elsewhere.
+async fn admin_rpc_handler(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    state: axum::extract::State<jsonrpc::RpcRouter>,
+    headers: http::HeaderMap,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
+    if !peer.ip().is_loopback() {
+        return http::StatusCode::FORBIDDEN.into_response();
+    }
+
+    rpc_handler(state, headers, body).await.into_response()
+}
+
 pub struct SyncState {
     pub status: RwLock<Syncing>,
 }
@@ -787,27 +840,57 @@ mod tests {
         assert!(!status.is_success());
     }
 
+    /// Pathfinder methods which are only served on the pathfinder route.
+    const PATHFINDER_ONLY: &[&str] = &[
+        "pathfinder_version",
+        "pathfinder_estimateFeeEach",
+        "pathfinder_feeHistory",
+        "pathfinder_getBlockSignature",
+        "pathfinder_getClassDeclaration",
+        "pathfinder_getContractHistory",
+        "pathfinder_getL1Inclusion",
+        "pathfinder_getL2ToL1MessagesStatus",
+        "pathfinder_getMessagesStatus",
+        "pathfinder_getStorageHistory",
+        "pathfinder_getTransactionsBySender",
+    ];
+
+    /// get_transaction_status is now part of the official spec, so we are phasing it out.
+    const PATHFINDER_ONLY_V06: &[&str] = &[
+        "pathfinder_version",
+        "pathfinder_getTransactionStatus",
+        "pathfinder_estimateFeeEach",
+        "pathfinder_feeHistory",
+        "pathfinder_getBlockSignature",
+        "pathfinder_getClassDeclaration",
+        "pathfinder_getContractHistory",
+        "pathfinder_getL1Inclusion",
+        "pathfinder_getL2ToL1MessagesStatus",
+        "pathfinder_getMessagesStatus",
+        "pathfinder_getStorageHistory",
+        "pathfinder_getTransactionsBySender",
+    ];
+
     #[rustfmt::skip]
     #[rstest::rstest]
     #[case::root_api  ("/", "v05/starknet_api_openrpc.json",       &[])]
     #[case::root_trace("/", "v05/starknet_trace_api_openrpc.json", &[])]
     #[case::root_write("/", "v05/starknet_write_api.json",         &[])]
-    #[case::root_pathfinder("/", "pathfinder_rpc_api.json", &["pathfinder_version"])]
+    #[case::root_pathfinder("/", "pathfinder_rpc_api.json", PATHFINDER_ONLY)]
 
     #[case::v0_6_api  ("/rpc/v0_6", "v06/starknet_api_openrpc.json", &[])]
     #[case::v0_6_trace("/rpc/v0_6", "v06/starknet_trace_api_openrpc.json", &[])]
     #[case::v0_6_write("/rpc/v0_6", "v06/starknet_write_api.json", &[])]
-    // get_transaction_status is now part of the official spec, so we are phasing it out.
-    #[case::v0_6_pathfinder("/rpc/v0_6", "pathfinder_rpc_api.json", &["pathfinder_version", "pathfinder_getTransactionStatus"])]
+    #[case::v0_6_pathfinder("/rpc/v0_6", "pathfinder_rpc_api.json", PATHFINDER_ONLY_V06)]
 
     #[case::v05_api  ("/rpc/v0.5", "v05/starknet_api_openrpc.json", &[])]
     #[case::v05_trace("/rpc/v0.5", "v05/starknet_trace_api_openrpc.json", &[])]
     #[case::v05_write("/rpc/v0.5", "v05/starknet_write_api.json",         &[])]
-    #[case::v05_pathfinder("/rpc/v0.5", "pathfinder_rpc_api.json", &["pathfinder_version"])]
+    #[case::v05_pathfinder("/rpc/v0.5", "pathfinder_rpc_api.json", PATHFINDER_ONLY)]
     #[case::v0_5_api  ("/rpc/v0_5", "v05/starknet_api_openrpc.json", &[])]
     #[case::v0_5_trace("/rpc/v0_5", "v05/starknet_trace_api_openrpc.json", &[])]
     #[case::v0_5_write("/rpc/v0_5", "v05/starknet_write_api.json",         &[])]
-    #[case::v0_5_pathfinder("/rpc/v0_5", "pathfinder_rpc_api.json", &["pathfinder_version"])]
+    #[case::v0_5_pathfinder("/rpc/v0_5", "pathfinder_rpc_api.json", PATHFINDER_ONLY)]
 
     #[case::pathfinder("/rpc/pathfinder/v0.1", "pathfinder_rpc_api.json", &[])]
 
@@ -908,4 +991,92 @@ mod tests {
             panic!("{failures:#?} were marked as excluded but are actually present");
         }
     }
+
+    #[tokio::test]
+    async fn admin_methods_are_not_public() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let mut context = RpcContext::for_tests();
+        context.config.backup_directory = Some(directory.path().to_owned());
+
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let server = RpcServer::new(addr, context, DefaultVersion::V06);
+        let (_admin_jh, admin_addr) = server.spawn_admin(addr).unwrap();
+        let (_jh, addr) = server.spawn().unwrap();
+
+        let client = reqwest::Client::new();
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "pathfinder_backup",
+            "params": { "filename": "backup.sqlite" },
+            "id": 0,
+        });
+
+        for route in ["/", "/rpc/v0_6", "/rpc/pathfinder/v0.1"] {
+            let res: serde_json::Value = client
+                .post(format!("http://{addr}{route}"))
+                .json(&request)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(res["error"]["code"], json!(-32601), "{route}");
+        }
+
+        // The admin route is only served by the admin server.
+        let status = client
+            .post(format!("http://{addr}/rpc/pathfinder/admin/v0.1"))
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, http::StatusCode::NOT_FOUND);
+        assert!(!directory.path().join("backup.sqlite").exists());
+
+        let res: serde_json::Value = client
+            .post(format!("http://{admin_addr}/rpc/pathfinder/admin/v0.1"))
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(res["result"]["filename"], json!("backup.sqlite"));
+        assert!(directory.path().join("backup.sqlite").exists());
+    }
+
+    #[tokio::test]
+    async fn admin_methods_reject_remote_clients() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let mut context = RpcContext::for_tests();
+        context.config.backup_directory = Some(directory.path().to_owned());
+        let router = pathfinder::register_admin_routes().build(context);
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "method": "pathfinder_backup",
+            "params": { "filename": "backup.sqlite" },
+            "id": 0,
+        });
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/json"),
+        );
+
+        let peer: SocketAddr = "192.0.2.1:4321".parse().unwrap();
+        let response = admin_rpc_handler(
+            ConnectInfo(peer),
+            axum::extract::State(router),
+            headers,
+            body.to_string().into(),
+        )
+        .await;
+
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+        assert!(!directory.path().join("backup.sqlite").exists());
+    }
 }
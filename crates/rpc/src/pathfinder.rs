@@ -5,7 +5,25 @@ pub(crate) mod methods;
 #[rustfmt::skip]
 pub fn register_routes() -> RpcRouterBuilder {
     RpcRouter::builder("v0.1")
-        .register("pathfinder_version",              || { pathfinder_common::consts::VERGEN_GIT_DESCRIBE })
-        .register("pathfinder_getProof",             methods::get_proof)
-        .register("pathfinder_getTransactionStatus", methods::get_transaction_status)
+        .register("pathfinder_version",                 || { pathfinder_common::consts::VERGEN_GIT_DESCRIBE })
+        .register("pathfinder_estimateFeeEach",         methods::estimate_fee_each)
+        .register("pathfinder_feeHistory",              methods::fee_history)
+        .register("pathfinder_getBlockSignature",       methods::get_block_signature)
+        .register("pathfinder_getClassDeclaration",     methods::get_class_declaration)
+        .register("pathfinder_getContractHistory",      methods::get_contract_history)
+        .register("pathfinder_getL1Inclusion",          methods::get_l1_inclusion)
+        .register("pathfinder_getL2ToL1MessagesStatus", methods::get_l2_to_l1_messages_status)
+        .register("pathfinder_getMessagesStatus",       methods::get_messages_status)
+        .register("pathfinder_getProof",                methods::get_proof)
+        .register("pathfinder_getStorageHistory",       methods::get_storage_history)
+        .register("pathfinder_getTransactionStatus",    methods::get_transaction_status)
+        .register("pathfinder_getTransactionsBySender", methods::get_transactions_by_sender)
+}
+
+/// Administrative methods, which are only served by the admin RPC server to clients on the local
+/// machine.
+#[rustfmt::skip]
+pub fn register_admin_routes() -> RpcRouterBuilder {
+    RpcRouter::builder("v0.1")
+        .register("pathfinder_backup", methods::backup)
 }
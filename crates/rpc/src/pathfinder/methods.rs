@@ -1,5 +1,27 @@
+mod backup;
+mod estimate_fee_each;
+mod fee_history;
+mod get_block_signature;
+mod get_class_declaration;
+mod get_contract_history;
+mod get_l1_inclusion;
+mod get_l2_to_l1_messages_status;
+mod get_messages_status;
 mod get_proof;
+mod get_storage_history;
 mod get_transaction_status;
+mod get_transactions_by_sender;
 
+pub(crate) use backup::backup;
+pub(crate) use estimate_fee_each::estimate_fee_each;
+pub(crate) use fee_history::fee_history;
+pub(crate) use get_block_signature::get_block_signature;
+pub(crate) use get_class_declaration::get_class_declaration;
+pub(crate) use get_contract_history::get_contract_history;
+pub(crate) use get_l1_inclusion::get_l1_inclusion;
+pub(crate) use get_l2_to_l1_messages_status::get_l2_to_l1_messages_status;
+pub(crate) use get_messages_status::get_messages_status;
 pub(crate) use get_proof::get_proof;
+pub(crate) use get_storage_history::get_storage_history;
 pub(crate) use get_transaction_status::get_transaction_status;
+pub(crate) use get_transactions_by_sender::get_transactions_by_sender;
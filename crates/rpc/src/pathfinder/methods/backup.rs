@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use pathfinder_storage::BackupOptions;
+
+use crate::context::RpcContext;
+
+/// The backup is throttled to limit its impact on other RPC requests and sync.
+const OPTIONS: BackupOptions = BackupOptions {
+    pages_per_step: match std::num::NonZeroU32::new(1024) {
+        Some(pages) => pages,
+        None => unreachable!(),
+    },
+    pause: std::time::Duration::from_millis(10),
+};
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BackupInput {
+    /// Name of the backup file, which is created in the configured backup directory.
+    filename: String,
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Internal(anyhow::Error),
+    Disabled,
+    InvalidFilename,
+    AlreadyExists,
+}
+
+impl From<anyhow::Error> for BackupError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl From<BackupError> for crate::error::ApplicationError {
+    fn from(x: BackupError) -> Self {
+        match x {
+            BackupError::Disabled => Self::Custom(anyhow::anyhow!(
+                "Backups are disabled as no backup directory is configured"
+            )),
+            BackupError::InvalidFilename => {
+                Self::Custom(anyhow::anyhow!("The filename must not contain a path"))
+            }
+            BackupError::AlreadyExists => Self::Custom(anyhow::anyhow!(
+                "A backup with this filename already exists"
+            )),
+            BackupError::Internal(internal) => Self::Internal(internal),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct BackupOutput {
+    /// Name of the backup file within the backup directory.
+    filename: String,
+    /// The first and last block included in the backup. Absent if the database is empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_block: Option<BlockNumber>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_block: Option<BlockNumber>,
+    size: u64,
+}
+
+/// Writes a consistent copy of the database to the node's backup directory.
+///
+/// This is an administrative method which is only available if a backup directory is configured,
+/// and which is only served by the admin RPC server to clients on the local machine.
+pub async fn backup(context: RpcContext, input: BackupInput) -> Result<BackupOutput, BackupError> {
+    let directory = context
+        .config
+        .backup_directory
+        .as_ref()
+        .ok_or(BackupError::Disabled)?;
+
+    if Path::new(&input.filename).file_name() != Some(input.filename.as_ref()) {
+        return Err(BackupError::InvalidFilename);
+    }
+
+    let path = directory.join(&input.filename);
+    if path.exists() {
+        return Err(BackupError::AlreadyExists);
+    }
+
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+
+        tracing::info!(path=%path.display(), "Starting backup");
+        let report = context
+            .storage
+            .backup(&path, OPTIONS)
+            .context("Backing up database")?;
+        tracing::info!(path=%path.display(), size=%report.size, "Backup complete");
+
+        Ok(BackupOutput {
+            first_block: report.blocks.as_ref().map(|blocks| *blocks.start()),
+            last_block: report.blocks.as_ref().map(|blocks| *blocks.end()),
+            size: report.size,
+            filename: input.filename,
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn context(directory: Option<PathBuf>) -> RpcContext {
+        let mut context = RpcContext::for_tests();
+        context.config.backup_directory = directory;
+        context
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let input = BackupInput {
+            filename: "backup.sqlite".to_owned(),
+        };
+
+        let result = backup(context(None), input).await;
+        assert!(matches!(result, Err(BackupError::Disabled)));
+    }
+
+    #[tokio::test]
+    async fn filename_must_not_be_a_path() {
+        let directory = tempfile::TempDir::new().unwrap();
+
+        for filename in [
+            "../backup.sqlite",
+            "nested/backup.sqlite",
+            "/backup.sqlite",
+            "",
+        ] {
+            let input = BackupInput {
+                filename: filename.to_owned(),
+            };
+
+            let result = backup(context(Some(directory.path().to_owned())), input).await;
+            assert!(
+                matches!(result, Err(BackupError::InvalidFilename)),
+                "{filename}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn existing_file() {
+        let directory = tempfile::TempDir::new().unwrap();
+        std::fs::write(directory.path().join("backup.sqlite"), b"").unwrap();
+
+        let input = BackupInput {
+            filename: "backup.sqlite".to_owned(),
+        };
+
+        let result = backup(context(Some(directory.path().to_owned())), input).await;
+        assert!(matches!(result, Err(BackupError::AlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn success() {
+        let directory = tempfile::TempDir::new().unwrap();
+
+        let input = BackupInput {
+            filename: "backup.sqlite".to_owned(),
+        };
+
+        let result = backup(context(Some(directory.path().to_owned())), input)
+            .await
+            .unwrap();
+
+        assert_eq!(result.filename, "backup.sqlite");
+        assert_eq!(result.first_block, Some(BlockNumber::GENESIS));
+        assert!(directory.path().join("backup.sqlite").exists());
+    }
+}
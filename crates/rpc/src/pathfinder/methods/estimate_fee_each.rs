@@ -0,0 +1,353 @@
+use anyhow::Context;
+use pathfinder_common::{BlockId, ContractAddress};
+use pathfinder_executor::types::{FailureStage, TransactionEstimate};
+use pathfinder_executor::ExecutionState;
+
+use crate::context::RpcContext;
+use crate::gas_price::Percentile;
+use crate::v02::types::request::BroadcastedTransaction;
+use crate::v06::method::estimate_fee::{
+    EstimateFeeError, FeeEstimate, SimulationFlag, SimulationFlags,
+};
+use crate::v06::method::simulate_transactions::dto::ExecutionResources;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EstimateFeeEachInput {
+    request: Vec<BroadcastedTransaction>,
+    simulation_flags: SimulationFlags,
+    block_id: BlockId,
+    /// Raises the block's gas prices to this percentile of recent blocks.
+    #[serde(default)]
+    gas_price_percentile: Option<Percentile>,
+}
+
+/// Result of a single transaction of the batch.
+#[derive(Debug, serde::Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum EstimateResult {
+    Success {
+        fee_estimate: FeeEstimate,
+        execution_resources: Box<ResourcesBreakdown>,
+    },
+    Failure {
+        error: Failure,
+    },
+}
+
+#[derive(Debug, serde::Serialize, PartialEq, Eq)]
+pub struct ResourcesBreakdown {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validate: Option<ExecutionResources>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execute: Option<ExecutionResources>,
+    calls: ExecutionResources,
+}
+
+#[derive(Debug, serde::Serialize, PartialEq, Eq)]
+pub struct Failure {
+    stage: Stage,
+    message: String,
+    call_path: Vec<ContractAddress>,
+}
+
+#[derive(Debug, serde::Serialize, PartialEq, Eq)]
+pub enum Stage {
+    #[serde(rename = "VALIDATE")]
+    Validate,
+    #[serde(rename = "EXECUTE")]
+    Execute,
+    #[serde(rename = "OTHER")]
+    Other,
+}
+
+impl From<TransactionEstimate> for EstimateResult {
+    fn from(value: TransactionEstimate) -> Self {
+        match value {
+            TransactionEstimate::Success {
+                fee_estimate,
+                resources,
+            } => Self::Success {
+                fee_estimate: fee_estimate.into(),
+                execution_resources: Box::new(ResourcesBreakdown {
+                    validate: resources.validate.map(Into::into),
+                    execute: resources.execute.map(Into::into),
+                    calls: resources.calls.into(),
+                }),
+            },
+            TransactionEstimate::Failure(failure) => Self::Failure {
+                error: Failure {
+                    stage: match failure.stage {
+                        FailureStage::Validate => Stage::Validate,
+                        FailureStage::Execute => Stage::Execute,
+                        FailureStage::Other => Stage::Other,
+                    },
+                    message: failure.error,
+                    call_path: failure.call_path,
+                },
+            },
+        }
+    }
+}
+
+/// Estimates the fee of each transaction separately, so that a failing transaction does not
+/// hide the estimates of the rest of the batch.
+pub async fn estimate_fee_each(
+    context: RpcContext,
+    input: EstimateFeeEachInput,
+) -> Result<Vec<EstimateResult>, EstimateFeeError> {
+    let span = tracing::Span::current();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Creating database connection")?;
+        let db = db.transaction().context("Creating database transaction")?;
+
+        let (mut header, pending) = match input.block_id {
+            BlockId::Pending => {
+                let pending = context
+                    .pending_data
+                    .get(&db)
+                    .context("Querying pending data")?;
+
+                (pending.header(), Some(pending.state_update.clone()))
+            }
+            other => {
+                let block_id = other.try_into().expect("Only pending cast should fail");
+                let header = db
+                    .block_header(block_id)
+                    .context("Querying block header")?
+                    .ok_or(EstimateFeeError::BlockNotFound)?;
+
+                (header, None)
+            }
+        };
+
+        if let Some(percentile) = input.gas_price_percentile {
+            crate::gas_price::apply_percentile(&db, &mut header, percentile)
+                .context("Applying gas price percentile")?;
+        }
+
+        let state = ExecutionState::simulation(&db, context.chain_id, header, pending);
+
+        let skip_validate = input
+            .simulation_flags
+            .0
+            .iter()
+            .any(|flag| flag == &SimulationFlag::SkipValidate);
+
+        let transactions = input
+            .request
+            .into_iter()
+            .map(|tx| crate::executor::map_broadcasted_transaction(&tx, context.chain_id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let result = pathfinder_executor::estimate_each(state, transactions, skip_validate)?;
+
+        Ok::<_, EstimateFeeError>(result)
+    })
+    .await
+    .context("Executing transaction")??;
+
+    Ok(result.into_iter().map(Into::into).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v02::types::request::{
+        BroadcastedInvokeTransaction, BroadcastedInvokeTransactionV1,
+    };
+    use crate::v06::method::simulate_transactions::dto::{
+        ExecuteInvocation, InvokeTxnTrace, TransactionTrace,
+    };
+    use crate::v06::method::simulate_transactions::{
+        simulate_transactions, SimulateTransactionInput,
+    };
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{CallParam, EntryPoint, Fee, TransactionNonce, TransactionVersion};
+
+    fn invoke(sender_address: ContractAddress, nonce: TransactionNonce) -> BroadcastedTransaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(
+            BroadcastedInvokeTransactionV1 {
+                version: TransactionVersion::ONE_WITH_QUERY_VERSION,
+                max_fee: Fee::default(),
+                signature: vec![],
+                nonce,
+                sender_address,
+                calldata: vec![
+                    CallParam(sender_address.0),
+                    // An entry point which the account does not have.
+                    CallParam(EntryPoint::hashed(b"does_not_exist").0),
+                    call_param!("0"),
+                ],
+            },
+        ))
+    }
+
+    fn decimals(
+        sender_address: ContractAddress,
+        nonce: TransactionNonce,
+    ) -> BroadcastedTransaction {
+        BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(
+            BroadcastedInvokeTransactionV1 {
+                version: TransactionVersion::ONE_WITH_QUERY_VERSION,
+                max_fee: Fee::default(),
+                signature: vec![],
+                nonce,
+                sender_address,
+                calldata: vec![
+                    CallParam(pathfinder_executor::ETH_FEE_TOKEN_ADDRESS.0),
+                    CallParam(EntryPoint::hashed(b"decimals").0),
+                    call_param!("0"),
+                ],
+            },
+        ))
+    }
+
+    #[test]
+    fn gas_price_percentile() {
+        let named_args = serde_json::json!({
+            "request": [],
+            "simulation_flags": [],
+            "block_id": "latest",
+            "gas_price_percentile": 90
+        });
+        let input = serde_json::from_value::<EstimateFeeEachInput>(named_args).unwrap();
+        assert_eq!(
+            input.gas_price_percentile,
+            Some(Percentile::try_from(90).unwrap())
+        );
+
+        let positional = serde_json::json!([[], [], "latest", 101]);
+        serde_json::from_value::<EstimateFeeEachInput>(positional).unwrap_err();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn failures_do_not_abort_the_batch() {
+        let (context, last_block_header, account_contract_address, _) =
+            crate::test_setup::test_context().await;
+
+        let input = EstimateFeeEachInput {
+            request: vec![
+                invoke(account_contract_address, transaction_nonce!("0x5")),
+                invoke(account_contract_address, transaction_nonce!("0x0")),
+            ],
+            simulation_flags: SimulationFlags(vec![]),
+            block_id: BlockId::Number(last_block_header.number),
+            gas_price_percentile: None,
+        };
+
+        let result = estimate_fee_each(context, input).await.unwrap();
+
+        assert_eq!(result.len(), 2);
+        assert_matches::assert_matches!(
+            &result[0],
+            EstimateResult::Failure {
+                error: Failure {
+                    stage: Stage::Other,
+                    ..
+                }
+            }
+        );
+        assert_matches::assert_matches!(
+            &result[1],
+            EstimateResult::Failure {
+                error: Failure {
+                    stage: Stage::Execute,
+                    call_path,
+                    ..
+                }
+            } => {
+                // Pins the error trace format of the blockifier version in use, which the call
+                // path is parsed from.
+                assert_eq!(call_path.first(), Some(&account_contract_address));
+            }
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn succeeds_after_failures() {
+        let (context, last_block_header, account_contract_address, _) =
+            crate::test_setup::test_context().await;
+
+        let input = EstimateFeeEachInput {
+            request: vec![
+                // Rejected before execution, so the nonce is not bumped.
+                invoke(account_contract_address, transaction_nonce!("0x5")),
+                // Reverted, which still bumps the nonce.
+                invoke(account_contract_address, transaction_nonce!("0x0")),
+                decimals(account_contract_address, transaction_nonce!("0x1")),
+            ],
+            simulation_flags: SimulationFlags(vec![]),
+            block_id: BlockId::Number(last_block_header.number),
+            gas_price_percentile: None,
+        };
+
+        let result = estimate_fee_each(context.clone(), input).await.unwrap();
+
+        assert_eq!(result.len(), 3);
+        assert_matches::assert_matches!(
+            &result[0],
+            EstimateResult::Failure {
+                error: Failure {
+                    stage: Stage::Other,
+                    ..
+                }
+            }
+        );
+        assert_matches::assert_matches!(
+            &result[1],
+            EstimateResult::Failure {
+                error: Failure {
+                    stage: Stage::Execute,
+                    ..
+                }
+            }
+        );
+
+        let EstimateResult::Success {
+            fee_estimate,
+            execution_resources,
+        } = &result[2]
+        else {
+            panic!("Expected a successful estimate, got {:?}", result[2]);
+        };
+        assert!(fee_estimate.overall_fee > 0.into());
+
+        let validate = execution_resources.validate.as_ref().unwrap();
+        let execute = execution_resources.execute.as_ref().unwrap();
+        assert!(validate.steps > 0);
+        assert_eq!(
+            execution_resources.calls.steps,
+            validate.steps + execute.steps
+        );
+        assert_eq!(
+            execution_resources.calls.range_check_builtin_applications,
+            validate.range_check_builtin_applications + execute.range_check_builtin_applications
+        );
+
+        // The same transactions simulated without the rejected one, which does not change the state.
+        let input = serde_json::from_value::<SimulateTransactionInput>(serde_json::json!({
+            "block_id": { "block_number": last_block_header.number },
+            "transactions": [
+                invoke(account_contract_address, transaction_nonce!("0x0")),
+                decimals(account_contract_address, transaction_nonce!("0x1")),
+            ],
+            "simulation_flags": ["SKIP_FEE_CHARGE"],
+        }))
+        .unwrap();
+        let simulated = simulate_transactions(context, input).await.unwrap();
+        let TransactionTrace::Invoke(InvokeTxnTrace {
+            execute_invocation: ExecuteInvocation::FunctionInvocation(execute_invocation),
+            ..
+        }) = &simulated.0[1].transaction_trace
+        else {
+            panic!("Expected an executed invoke, got {:?}", simulated.0[1]);
+        };
+        assert_eq!(execute, &execute_invocation.execution_resources);
+    }
+}
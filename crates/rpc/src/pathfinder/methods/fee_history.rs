@@ -0,0 +1,179 @@
+use anyhow::Context;
+use pathfinder_common::{BlockId, BlockNumber, GasPrice};
+use serde_with::serde_as;
+
+use crate::context::RpcContext;
+use crate::gas_price::{History, Percentile};
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FeeHistoryInput {
+    block_count: u64,
+    /// `pending` is treated as `latest` as the pending block's gas prices are not final.
+    newest_block: BlockId,
+    #[serde(default)]
+    percentiles: Vec<Percentile>,
+}
+
+#[derive(Debug)]
+pub enum FeeHistoryError {
+    Internal(anyhow::Error),
+    BlockNotFound,
+    TooManyBlocks { limit: u64, requested: u64 },
+}
+
+impl From<anyhow::Error> for FeeHistoryError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl From<FeeHistoryError> for crate::error::ApplicationError {
+    fn from(x: FeeHistoryError) -> Self {
+        match x {
+            FeeHistoryError::BlockNotFound => Self::BlockNotFound,
+            FeeHistoryError::TooManyBlocks { limit, requested } => Self::Custom(anyhow::anyhow!(
+                "At most {limit} blocks may be requested, got {requested}"
+            )),
+            FeeHistoryError::Internal(internal) => Self::Internal(internal),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct FeeHistory {
+    oldest_block: BlockNumber,
+    /// Gas prices of each block, starting from `oldest_block`.
+    #[serde_as(as = "Vec<pathfinder_serde::GasPriceAsHexStr>")]
+    eth_l1_gas_price: Vec<GasPrice>,
+    /// [None] for blocks which were stored without a STRK gas price.
+    #[serde_as(as = "Vec<Option<pathfinder_serde::GasPriceAsHexStr>>")]
+    strk_l1_gas_price: Vec<Option<GasPrice>>,
+    /// Least-squares slope of the gas prices, per block.
+    eth_l1_gas_price_trend: f64,
+    strk_l1_gas_price_trend: f64,
+    percentiles: Vec<PercentileGasPrice>,
+}
+
+#[serde_as]
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct PercentileGasPrice {
+    percentile: Percentile,
+    #[serde_as(as = "pathfinder_serde::GasPriceAsHexStr")]
+    eth_l1_gas_price: GasPrice,
+    #[serde_as(as = "pathfinder_serde::GasPriceAsHexStr")]
+    strk_l1_gas_price: GasPrice,
+}
+
+/// Returns the L1 gas prices of a range of blocks along with their percentiles and trends.
+pub async fn fee_history(
+    context: RpcContext,
+    input: FeeHistoryInput,
+) -> Result<FeeHistory, FeeHistoryError> {
+    const MAX_BLOCKS: u64 = 1024;
+    if input.block_count > MAX_BLOCKS {
+        return Err(FeeHistoryError::TooManyBlocks {
+            limit: MAX_BLOCKS,
+            requested: input.block_count,
+        });
+    }
+
+    let block_id = match input.newest_block {
+        BlockId::Pending => pathfinder_storage::BlockId::Latest,
+        other => other.try_into().expect("Only pending cast should fail"),
+    };
+
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let (newest, _) = tx
+            .block_id(block_id)
+            .context("Fetching block number")?
+            .ok_or(FeeHistoryError::BlockNotFound)?;
+
+        let history = History::load(&tx, newest, input.block_count as usize)?.unwrap_or(History {
+            oldest: newest,
+            eth_l1_gas_price: Vec::new(),
+            strk_l1_gas_price: Vec::new(),
+        });
+
+        let percentiles = input
+            .percentiles
+            .into_iter()
+            .map(|percentile| PercentileGasPrice {
+                percentile,
+                eth_l1_gas_price: history.eth_l1_gas_price_percentile(percentile),
+                strk_l1_gas_price: history.strk_l1_gas_price_percentile(percentile),
+            })
+            .collect();
+
+        Ok(FeeHistory {
+            oldest_block: history.oldest,
+            eth_l1_gas_price_trend: history.eth_l1_gas_price_trend(),
+            strk_l1_gas_price_trend: history.strk_l1_gas_price_trend(),
+            eth_l1_gas_price: history.eth_l1_gas_price,
+            strk_l1_gas_price: history.strk_l1_gas_price,
+            percentiles,
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn latest() {
+        let context = RpcContext::for_tests();
+        let input = FeeHistoryInput {
+            block_count: 10,
+            newest_block: BlockId::Latest,
+            percentiles: vec![Percentile::try_from(50).unwrap()],
+        };
+
+        let result = fee_history(context, input).await.unwrap();
+
+        assert_eq!(result.oldest_block, BlockNumber::GENESIS);
+        assert_eq!(result.eth_l1_gas_price.len(), 3);
+        assert_eq!(result.strk_l1_gas_price.len(), 3);
+        assert_eq!(result.percentiles.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn block_count_is_limited() {
+        let context = RpcContext::for_tests();
+        let input = FeeHistoryInput {
+            block_count: 1025,
+            newest_block: BlockId::Latest,
+            percentiles: vec![],
+        };
+
+        let error = fee_history(context, input).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, FeeHistoryError::TooManyBlocks { .. });
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = FeeHistoryInput {
+            block_count: 10,
+            newest_block: BlockId::Number(BlockNumber::new_or_panic(9999)),
+            percentiles: vec![],
+        };
+
+        let error = fee_history(context, input).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, FeeHistoryError::BlockNotFound);
+    }
+}
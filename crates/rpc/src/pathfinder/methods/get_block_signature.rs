@@ -0,0 +1,158 @@
+use anyhow::Context;
+use pathfinder_common::{
+    BlockCommitmentSignatureElem, BlockHash, BlockId, BlockNumber, SignatureStatus,
+};
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetBlockSignatureInput {
+    block_id: BlockId,
+}
+
+crate::error::generate_rpc_error_subset!(GetBlockSignatureError: BlockNotFound);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct BlockSignature {
+    block_number: BlockNumber,
+    block_hash: BlockHash,
+    signature: [BlockCommitmentSignatureElem; 2],
+    status: Status,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Status {
+    /// The signature was not verified, as the sequencer's public key was not known.
+    Unverified,
+    Valid,
+    Invalid,
+}
+
+impl From<SignatureStatus> for Status {
+    fn from(value: SignatureStatus) -> Self {
+        match value {
+            SignatureStatus::Unverified => Self::Unverified,
+            SignatureStatus::Valid => Self::Valid,
+            SignatureStatus::Invalid => Self::Invalid,
+        }
+    }
+}
+
+/// Returns the sequencer's signature of a block and whether it was verified during sync.
+///
+/// Pending blocks are not signed, and blocks synced without a signature are reported as not found.
+pub async fn get_block_signature(
+    context: RpcContext,
+    input: GetBlockSignatureInput,
+) -> Result<BlockSignature, GetBlockSignatureError> {
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let block = match input.block_id {
+            BlockId::Pending => return Err(GetBlockSignatureError::BlockNotFound),
+            other => other.try_into().expect("Only pending cast should fail"),
+        };
+        let (block_number, block_hash) = tx
+            .block_id(block)
+            .context("Fetching block id")?
+            .ok_or(GetBlockSignatureError::BlockNotFound)?;
+
+        let signature = tx
+            .signature(block_number.into())
+            .context("Fetching signature")?
+            .ok_or(GetBlockSignatureError::BlockNotFound)?;
+        let status = tx
+            .signature_status(block_number.into())
+            .context("Fetching signature status")?
+            .unwrap_or_default();
+
+        Ok(BlockSignature {
+            block_number,
+            block_hash,
+            signature: [signature.r, signature.s],
+            status: status.into(),
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::BlockCommitmentSignature;
+
+    use super::*;
+
+    /// Signs block 1 of the test fixture.
+    fn setup(status: SignatureStatus) -> RpcContext {
+        let context = RpcContext::for_tests();
+        let mut db = context.storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let number = BlockNumber::new_or_panic(1);
+        let signature = BlockCommitmentSignature {
+            r: block_commitment_signature_elem!("0x1"),
+            s: block_commitment_signature_elem!("0x2"),
+        };
+        tx.insert_signature(number, &signature).unwrap();
+        tx.update_signature_status(number, status).unwrap();
+        tx.commit().unwrap();
+
+        context
+    }
+
+    fn input(number: u64) -> GetBlockSignatureInput {
+        GetBlockSignatureInput {
+            block_id: BlockId::Number(BlockNumber::new_or_panic(number)),
+        }
+    }
+
+    #[tokio::test]
+    async fn status() {
+        for (status, expected) in [
+            (SignatureStatus::Unverified, Status::Unverified),
+            (SignatureStatus::Valid, Status::Valid),
+            (SignatureStatus::Invalid, Status::Invalid),
+        ] {
+            let context = setup(status);
+
+            let result = get_block_signature(context, input(1)).await.unwrap();
+            assert_eq!(result.block_number, BlockNumber::new_or_panic(1));
+            assert_eq!(
+                result.signature,
+                [
+                    block_commitment_signature_elem!("0x1"),
+                    block_commitment_signature_elem!("0x2")
+                ]
+            );
+            assert_eq!(result.status, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let context = setup(SignatureStatus::Valid);
+
+        // Block 0 has no signature, and block 10 does not exist.
+        for number in [0, 10] {
+            let result = get_block_signature(context.clone(), input(number)).await;
+            assert_matches::assert_matches!(result, Err(GetBlockSignatureError::BlockNotFound));
+        }
+
+        let pending = GetBlockSignatureInput {
+            block_id: BlockId::Pending,
+        };
+        let result = get_block_signature(context, pending).await;
+        assert_matches::assert_matches!(result, Err(GetBlockSignatureError::BlockNotFound));
+    }
+}
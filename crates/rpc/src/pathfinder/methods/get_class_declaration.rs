@@ -0,0 +1,185 @@
+use anyhow::Context;
+use pathfinder_common::transaction::TransactionVariant;
+use pathfinder_common::{BlockNumber, ClassHash, TransactionHash};
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetClassDeclarationInput {
+    class_hash: ClassHash,
+}
+
+crate::error::generate_rpc_error_subset!(GetClassDeclarationError: ClassHashNotFound);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct ClassDeclaration {
+    block_number: BlockNumber,
+    /// Omitted for Cairo 0 classes which were implicitly declared by their first deployment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction_hash: Option<TransactionHash>,
+}
+
+/// Returns the block and transaction in which a class was declared.
+///
+/// Only blocks which have been synced are considered, pending data is ignored.
+pub async fn get_class_declaration(
+    context: RpcContext,
+    input: GetClassDeclarationInput,
+) -> Result<ClassDeclaration, GetClassDeclarationError> {
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let block_number = tx
+            .class_declaration_block(input.class_hash)
+            .context("Querying class declaration block")?
+            .ok_or(GetClassDeclarationError::ClassHashNotFound)?;
+
+        let transaction_hash = tx
+            .transactions_for_block(block_number.into())
+            .context("Fetching declaration block transactions")?
+            .unwrap_or_default()
+            .into_iter()
+            .find(|transaction| {
+                let declared = match &transaction.variant {
+                    TransactionVariant::DeclareV0(declare)
+                    | TransactionVariant::DeclareV1(declare) => declare.class_hash,
+                    TransactionVariant::DeclareV2(declare) => declare.class_hash,
+                    TransactionVariant::DeclareV3(declare) => declare.class_hash,
+                    _ => return false,
+                };
+                declared == input.class_hash
+            })
+            .map(|transaction| transaction.hash);
+
+        Ok(ClassDeclaration {
+            block_number,
+            transaction_hash,
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::receipt::Receipt;
+    use pathfinder_common::transaction::{DeclareTransactionV2, Transaction};
+    use pathfinder_common::{BlockHeader, StateUpdate};
+    use pathfinder_storage::Storage;
+
+    use super::*;
+
+    /// Declares a Sierra class with a transaction in block 0, and implicitly declares a Cairo 0
+    /// class by deploying it in block 1.
+    fn setup() -> RpcContext {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let sierra_hash = sierra_hash_bytes!(b"sierra class");
+        let cairo_hash = class_hash_bytes!(b"cairo class");
+        let casm_hash = casm_hash_bytes!(b"casm");
+
+        tx.insert_sierra_class(&sierra_hash, b"sierra", &casm_hash, b"casm")
+            .unwrap();
+        tx.insert_cairo_class(cairo_hash, b"cairo").unwrap();
+        tx.insert_cairo_class(class_hash_bytes!(b"undeclared"), b"undeclared")
+            .unwrap();
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash!("0xa"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xb"));
+
+        let diff_0 = StateUpdate::default().with_declared_sierra_class(sierra_hash, casm_hash);
+        let diff_1 = StateUpdate::default()
+            .with_deployed_contract(contract_address_bytes!(b"contract"), cairo_hash);
+
+        for (header, diff) in [(&header_0, diff_0), (&header_1, diff_1)] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, &diff).unwrap();
+        }
+
+        let declare = Transaction {
+            hash: transaction_hash_bytes!(b"declare"),
+            variant: TransactionVariant::DeclareV2(DeclareTransactionV2 {
+                class_hash: ClassHash(sierra_hash.0),
+                compiled_class_hash: casm_hash,
+                ..Default::default()
+            }),
+        };
+        let receipt = Receipt {
+            transaction_hash: declare.hash,
+            ..Default::default()
+        };
+        tx.insert_transaction_data(header_0.hash, header_0.number, &[(declare, receipt)])
+            .unwrap();
+
+        tx.commit().unwrap();
+
+        RpcContext::for_tests().with_storage(storage)
+    }
+
+    #[tokio::test]
+    async fn declared_by_transaction() {
+        let context = setup();
+        let input = GetClassDeclarationInput {
+            class_hash: class_hash_bytes!(b"sierra class"),
+        };
+
+        let result = get_class_declaration(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            ClassDeclaration {
+                block_number: BlockNumber::GENESIS,
+                transaction_hash: Some(transaction_hash_bytes!(b"declare")),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn implicitly_declared() {
+        let context = setup();
+        let input = GetClassDeclarationInput {
+            class_hash: class_hash_bytes!(b"cairo class"),
+        };
+
+        let result = get_class_declaration(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            ClassDeclaration {
+                block_number: BlockNumber::new_or_panic(1),
+                transaction_hash: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn not_declared() {
+        let context = setup();
+
+        for class_hash in [
+            class_hash_bytes!(b"undeclared"),
+            class_hash_bytes!(b"missing"),
+        ] {
+            let input = GetClassDeclarationInput { class_hash };
+
+            let error = get_class_declaration(context.clone(), input)
+                .await
+                .unwrap_err();
+
+            assert_matches::assert_matches!(error, GetClassDeclarationError::ClassHashNotFound);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Context;
+use pathfinder_common::transaction::TransactionVariant;
+use pathfinder_common::{BlockNumber, ClassHash, ContractAddress, TransactionHash};
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetContractHistoryInput {
+    contract_address: ContractAddress,
+}
+
+crate::error::generate_rpc_error_subset!(GetContractHistoryError: ContractNotFound);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct ContractHistory {
+    deployment: Deployment,
+    class_replacements: Vec<ClassReplacement>,
+    /// The number of blocks in which the contract's nonce changed.
+    nonce_updates: u64,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct Deployment {
+    block_number: BlockNumber,
+    /// Omitted if the contract was deployed by another contract, e.g. using the universal
+    /// deployer, as there is no transaction dedicated to its deployment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction_hash: Option<TransactionHash>,
+    class_hash: ClassHash,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct ClassReplacement {
+    block_number: BlockNumber,
+    class_hash: ClassHash,
+}
+
+/// Returns the deployment, class replacements and number of nonce updates of a contract.
+///
+/// Only blocks which have been synced are considered, pending data is ignored.
+pub async fn get_contract_history(
+    context: RpcContext,
+    input: GetContractHistoryInput,
+) -> Result<ContractHistory, GetContractHistoryError> {
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let mut history = tx
+            .contract_class_history(input.contract_address)
+            .context("Querying contract class history")?
+            .into_iter();
+
+        let (block_number, class_hash) = history
+            .next()
+            .ok_or(GetContractHistoryError::ContractNotFound)?;
+
+        let transaction_hash = tx
+            .transactions_for_block(block_number.into())
+            .context("Fetching deployment block transactions")?
+            .unwrap_or_default()
+            .into_iter()
+            .find(|transaction| {
+                let deployed = match &transaction.variant {
+                    TransactionVariant::Deploy(deploy) => deploy.contract_address,
+                    TransactionVariant::DeployAccountV0V1(deploy) => deploy.contract_address,
+                    TransactionVariant::DeployAccountV3(deploy) => deploy.contract_address,
+                    _ => return false,
+                };
+                deployed == input.contract_address
+            })
+            .map(|transaction| transaction.hash);
+
+        let class_replacements = history
+            .map(|(block_number, class_hash)| ClassReplacement {
+                block_number,
+                class_hash,
+            })
+            .collect();
+
+        let nonce_updates = tx
+            .contract_nonce_update_count(input.contract_address)
+            .context("Counting nonce updates")?;
+
+        Ok(ContractHistory {
+            deployment: Deployment {
+                block_number,
+                transaction_hash,
+                class_hash,
+            },
+            class_replacements,
+            nonce_updates,
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::receipt::Receipt;
+    use pathfinder_common::transaction::{DeployAccountTransactionV0V1, Transaction};
+    use pathfinder_common::{BlockHeader, StateUpdate};
+    use pathfinder_storage::Storage;
+
+    use super::*;
+
+    /// Deploys `account` with a transaction in block 0, `contract` without a transaction in block 1
+    /// and replaces the class of `account` in block 2.
+    fn setup() -> RpcContext {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash!("0xa"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xb"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xc"));
+
+        let account = contract_address_bytes!(b"account");
+        let contract = contract_address_bytes!(b"contract");
+
+        let diff_0 = StateUpdate::default()
+            .with_deployed_contract(account, class_hash_bytes!(b"account class"))
+            .with_contract_nonce(account, contract_nonce!("0x1"));
+        let diff_1 = StateUpdate::default()
+            .with_deployed_contract(contract, class_hash_bytes!(b"contract class"))
+            .with_contract_nonce(account, contract_nonce!("0x2"));
+        let diff_2 =
+            StateUpdate::default().with_replaced_class(account, class_hash_bytes!(b"new class"));
+
+        for (header, diff) in [
+            (&header_0, diff_0),
+            (&header_1, diff_1),
+            (&header_2, diff_2),
+        ] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, &diff).unwrap();
+        }
+
+        let deploy_account = Transaction {
+            hash: transaction_hash_bytes!(b"deploy account"),
+            variant: TransactionVariant::DeployAccountV0V1(DeployAccountTransactionV0V1 {
+                contract_address: account,
+                ..Default::default()
+            }),
+        };
+        let receipt = Receipt {
+            transaction_hash: deploy_account.hash,
+            ..Default::default()
+        };
+        tx.insert_transaction_data(header_0.hash, header_0.number, &[(deploy_account, receipt)])
+            .unwrap();
+
+        tx.commit().unwrap();
+
+        RpcContext::for_tests().with_storage(storage)
+    }
+
+    #[tokio::test]
+    async fn deployed_by_transaction() {
+        let context = setup();
+        let input = GetContractHistoryInput {
+            contract_address: contract_address_bytes!(b"account"),
+        };
+
+        let result = get_contract_history(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            ContractHistory {
+                deployment: Deployment {
+                    block_number: BlockNumber::GENESIS,
+                    transaction_hash: Some(transaction_hash_bytes!(b"deploy account")),
+                    class_hash: class_hash_bytes!(b"account class"),
+                },
+                class_replacements: vec![ClassReplacement {
+                    block_number: BlockNumber::new_or_panic(2),
+                    class_hash: class_hash_bytes!(b"new class"),
+                }],
+                nonce_updates: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn deployed_without_transaction() {
+        let context = setup();
+        let input = GetContractHistoryInput {
+            contract_address: contract_address_bytes!(b"contract"),
+        };
+
+        let result = get_contract_history(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            ContractHistory {
+                deployment: Deployment {
+                    block_number: BlockNumber::new_or_panic(1),
+                    transaction_hash: None,
+                    class_hash: class_hash_bytes!(b"contract class"),
+                },
+                class_replacements: vec![],
+                nonce_updates: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn contract_not_found() {
+        let context = setup();
+        let input = GetContractHistoryInput {
+            contract_address: contract_address_bytes!(b"missing"),
+        };
+
+        let error = get_contract_history(context, input).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, GetContractHistoryError::ContractNotFound);
+    }
+}
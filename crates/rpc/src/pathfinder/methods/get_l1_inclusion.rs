@@ -0,0 +1,174 @@
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockId, BlockNumber, StateCommitment};
+use primitive_types::H256;
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetL1InclusionInput {
+    block_id: BlockId,
+}
+
+crate::error::generate_rpc_error_subset!(GetL1InclusionError: BlockNotFound);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct L1Inclusion {
+    /// The block whose state was published to L1, which is the requested block or a later one.
+    block_number: BlockNumber,
+    /// Omitted for state updates published before Starknet v0.11, as these did not include it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_hash: Option<BlockHash>,
+    state_root: StateCommitment,
+    l1_block_number: u64,
+    l1_transaction_hash: H256,
+}
+
+/// Returns the finalized L1 state update which included a block, or `null` if the block has not
+/// been accepted on L1 yet.
+///
+/// The state root published on L1 is checked against the local state commitment of the updated
+/// block. `null` is also returned if this block is not available locally yet.
+pub async fn get_l1_inclusion(
+    context: RpcContext,
+    input: GetL1InclusionInput,
+) -> Result<Option<L1Inclusion>, GetL1InclusionError> {
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let block = match input.block_id {
+            BlockId::Pending => return Ok(None),
+            other => other.try_into().expect("Only pending cast should fail"),
+        };
+        let (block_number, _) = tx
+            .block_id(block)
+            .context("Fetching block number")?
+            .ok_or(GetL1InclusionError::BlockNotFound)?;
+
+        let Some(log) = tx
+            .state_update_log_including(block_number)
+            .context("Fetching state update log")?
+        else {
+            return Ok(None);
+        };
+
+        let local = tx
+            .block_header(log.block_number.into())
+            .context("Fetching updated block header")?;
+        match local {
+            Some(header) if header.state_commitment == log.state_root => {}
+            Some(header) => {
+                tracing::warn!(
+                    block_number=%log.block_number,
+                    L1=?log.state_root,
+                    L2=?header.state_commitment,
+                    "L1/L2 state commitment mismatch"
+                );
+                return Ok(None);
+            }
+            None => return Ok(None),
+        }
+
+        Ok(Some(L1Inclusion {
+            block_number: log.block_number,
+            block_hash: log.block_hash,
+            state_root: log.state_root,
+            l1_block_number: log.l1_block_number,
+            l1_transaction_hash: log.l1_transaction_hash,
+        }))
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_ethereum::StateUpdateLog;
+
+    use super::*;
+
+    /// Publishes the state of block 1 of the test fixture on L1.
+    fn setup(state_root: Option<StateCommitment>) -> RpcContext {
+        let context = RpcContext::for_tests();
+        let mut db = context.storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let header = tx
+            .block_header(BlockNumber::new_or_panic(1).into())
+            .unwrap()
+            .unwrap();
+        let log = StateUpdateLog {
+            state_root: state_root.unwrap_or(header.state_commitment),
+            block_number: header.number,
+            block_hash: Some(header.hash),
+            l1_block_number: 100,
+            l1_transaction_hash: H256::from_low_u64_be(1),
+        };
+        tx.insert_state_update_logs(&[log]).unwrap();
+        tx.commit().unwrap();
+
+        context
+    }
+
+    fn input(number: u64) -> GetL1InclusionInput {
+        GetL1InclusionInput {
+            block_id: BlockId::Number(BlockNumber::new_or_panic(number)),
+        }
+    }
+
+    #[tokio::test]
+    async fn included() {
+        let context = setup(None);
+
+        for number in [0, 1] {
+            let result = get_l1_inclusion(context.clone(), input(number))
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(result.block_number, BlockNumber::new_or_panic(1));
+            assert_eq!(result.l1_block_number, 100);
+            assert_eq!(result.l1_transaction_hash, H256::from_low_u64_be(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn not_included() {
+        let context = setup(None);
+
+        let result = get_l1_inclusion(context.clone(), input(2)).await.unwrap();
+        assert_eq!(result, None);
+
+        let pending = GetL1InclusionInput {
+            block_id: BlockId::Pending,
+        };
+        let result = get_l1_inclusion(context, pending).await.unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn state_root_mismatch() {
+        let context = setup(Some(state_commitment!("0xbad")));
+
+        let result = get_l1_inclusion(context, input(1)).await.unwrap();
+
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = setup(None);
+
+        let error = get_l1_inclusion(context, input(100)).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, GetL1InclusionError::BlockNotFound);
+    }
+}
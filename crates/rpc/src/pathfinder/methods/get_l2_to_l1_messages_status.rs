@@ -0,0 +1,163 @@
+use anyhow::Context;
+use pathfinder_common::TransactionHash;
+use primitive_types::H256;
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetL2ToL1MessagesStatusInput {
+    /// The L2 transaction which sent the messages.
+    transaction_hash: TransactionHash,
+}
+
+crate::error::generate_rpc_error_subset!(GetL2ToL1MessagesStatusError: TxnHashNotFound);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct MessageStatus {
+    message_hash: H256,
+    /// The Ethereum transactions which consumed a message with this hash, in the order they were
+    /// consumed. Empty if the message has not been consumed yet.
+    ///
+    /// Identical messages have the same hash, so their consumptions cannot be told apart.
+    consumed_by: Vec<H256>,
+}
+
+/// Returns the status of the L2 to L1 messages sent by a transaction, in the order they were
+/// sent.
+///
+/// Messages are linked to the `ConsumedMessageToL1` events of the core contract by message hash.
+/// Only finalized Ethereum blocks are synced, and pending transactions are ignored.
+pub async fn get_l2_to_l1_messages_status(
+    context: RpcContext,
+    input: GetL2ToL1MessagesStatusInput,
+) -> Result<Vec<MessageStatus>, GetL2ToL1MessagesStatusError> {
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let (_, receipt, _) = tx
+            .transaction_with_receipt(input.transaction_hash)
+            .context("Fetching transaction")?
+            .ok_or(GetL2ToL1MessagesStatusError::TxnHashNotFound)?;
+
+        let statuses = receipt
+            .l2_to_l1_messages
+            .iter()
+            .map(|message| {
+                let message_hash = message.calculate_message_hash();
+                let consumed_by = tx
+                    .l2_to_l1_message_consumptions(message_hash)
+                    .context("Querying message consumptions")?;
+
+                anyhow::Ok(MessageStatus {
+                    message_hash,
+                    consumed_by,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(statuses)
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::receipt::{L2ToL1Message, Receipt};
+    use pathfinder_common::transaction::{InvokeTransactionV1, Transaction, TransactionVariant};
+    use pathfinder_common::{BlockHeader, EthereumAddress, L2ToL1MessagePayloadElem};
+    use pathfinder_crypto::Felt;
+    use pathfinder_ethereum::L2ToL1MessageLog;
+    use pathfinder_storage::Storage;
+    use primitive_types::H160;
+
+    use super::*;
+
+    fn message(payload: u64) -> L2ToL1Message {
+        L2ToL1Message {
+            from_address: contract_address_bytes!(b"bridge"),
+            payload: vec![L2ToL1MessagePayloadElem(Felt::from_u64(payload))],
+            to_address: EthereumAddress(H160::from_low_u64_be(0xb1)),
+        }
+    }
+
+    /// Stores a transaction which sends two messages, of which only the first was consumed on L1.
+    fn setup() -> RpcContext {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let header = BlockHeader::builder().finalize_with_hash(block_hash!("0xa"));
+        let transaction = Transaction {
+            hash: transaction_hash_bytes!(b"withdrawal"),
+            variant: TransactionVariant::InvokeV1(InvokeTransactionV1::default()),
+        };
+        let receipt = Receipt {
+            transaction_hash: transaction.hash,
+            l2_to_l1_messages: vec![message(1), message(2)],
+            ..Default::default()
+        };
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_transaction_data(header.hash, header.number, &[(transaction, receipt)])
+            .unwrap();
+
+        let log = L2ToL1MessageLog {
+            message_hash: message(1).calculate_message_hash(),
+            l1_block_number: 100,
+            l1_log_index: 0,
+            l1_transaction_hash: H256::from_low_u64_be(1),
+        };
+        tx.insert_consumed_message_logs(&[log]).unwrap();
+
+        tx.commit().unwrap();
+
+        RpcContext::for_tests().with_storage(storage)
+    }
+
+    #[tokio::test]
+    async fn statuses() {
+        let context = setup();
+        let input = GetL2ToL1MessagesStatusInput {
+            transaction_hash: transaction_hash_bytes!(b"withdrawal"),
+        };
+
+        let result = get_l2_to_l1_messages_status(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                MessageStatus {
+                    message_hash: message(1).calculate_message_hash(),
+                    consumed_by: vec![H256::from_low_u64_be(1)],
+                },
+                MessageStatus {
+                    message_hash: message(2).calculate_message_hash(),
+                    consumed_by: vec![],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction_not_found() {
+        let context = setup();
+        let input = GetL2ToL1MessagesStatusInput {
+            transaction_hash: transaction_hash_bytes!(b"unknown"),
+        };
+
+        let error = get_l2_to_l1_messages_status(context, input)
+            .await
+            .unwrap_err();
+
+        assert_matches::assert_matches!(error, GetL2ToL1MessagesStatusError::TxnHashNotFound);
+    }
+}
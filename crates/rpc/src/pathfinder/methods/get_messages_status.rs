@@ -0,0 +1,242 @@
+use anyhow::Context;
+use pathfinder_common::receipt::ExecutionStatus;
+use pathfinder_common::TransactionHash;
+use primitive_types::H256;
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetMessagesStatusInput {
+    /// The Ethereum transaction which sent the messages.
+    transaction_hash: H256,
+}
+
+crate::error::generate_rpc_error_subset!(GetMessagesStatusError: TxnHashNotFound);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct MessageStatus {
+    message_hash: H256,
+    /// The L1 handler transaction which consumed the message, omitted if it has not been consumed
+    /// yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transaction_hash: Option<TransactionHash>,
+    finality_status: FinalityStatus,
+    /// Omitted if the message has not been consumed yet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    execution_status: Option<MessageExecutionStatus>,
+    /// The revert reason of the L1 handler transaction, if it was reverted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure_reason: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, serde::Serialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FinalityStatus {
+    /// The message was sent on L1 but has not been consumed on L2 yet.
+    Received,
+    AcceptedOnL2,
+    AcceptedOnL1,
+}
+
+#[derive(Copy, Clone, Debug, serde::Serialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MessageExecutionStatus {
+    Succeeded,
+    Reverted,
+}
+
+/// Returns the status of the L1 to L2 messages sent by an Ethereum transaction, in the order they
+/// were sent.
+///
+/// Messages are linked to the L1 handler transaction which consumed them by message hash. Only
+/// finalized Ethereum blocks are synced, and pending L1 handler transactions are ignored.
+pub async fn get_messages_status(
+    context: RpcContext,
+    input: GetMessagesStatusInput,
+) -> Result<Vec<MessageStatus>, GetMessagesStatusError> {
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let messages = tx
+            .l1_to_l2_messages(input.transaction_hash)
+            .context("Querying L1 to L2 messages")?;
+        if messages.is_empty() {
+            return Err(GetMessagesStatusError::TxnHashNotFound);
+        }
+
+        let statuses = messages
+            .into_iter()
+            .map(|message| {
+                let Some(transaction_hash) = message.l1_handler_transaction else {
+                    return Ok(MessageStatus {
+                        message_hash: message.message_hash,
+                        transaction_hash: None,
+                        finality_status: FinalityStatus::Received,
+                        execution_status: None,
+                        failure_reason: None,
+                    });
+                };
+
+                let (_, receipt, block_hash) = tx
+                    .transaction_with_receipt(transaction_hash)
+                    .context("Fetching L1 handler transaction")?
+                    .context("L1 handler transaction missing from database")?;
+                let finality_status = match tx
+                    .block_is_l1_accepted(block_hash.into())
+                    .context("Querying block's status")?
+                {
+                    true => FinalityStatus::AcceptedOnL1,
+                    false => FinalityStatus::AcceptedOnL2,
+                };
+                let (execution_status, failure_reason) = match receipt.execution_status {
+                    ExecutionStatus::Succeeded => (MessageExecutionStatus::Succeeded, None),
+                    ExecutionStatus::Reverted { reason } => {
+                        (MessageExecutionStatus::Reverted, Some(reason))
+                    }
+                };
+
+                anyhow::Ok(MessageStatus {
+                    message_hash: message.message_hash,
+                    transaction_hash: Some(transaction_hash),
+                    finality_status,
+                    execution_status: Some(execution_status),
+                    failure_reason,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(statuses)
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::receipt::Receipt;
+    use pathfinder_common::transaction::{L1HandlerTransaction, Transaction, TransactionVariant};
+    use pathfinder_common::{BlockHeader, TransactionNonce};
+    use pathfinder_crypto::Felt;
+    use pathfinder_ethereum::L1ToL2MessageLog;
+    use pathfinder_storage::Storage;
+
+    use super::*;
+
+    fn l1_handler(nonce: u8) -> L1HandlerTransaction {
+        L1HandlerTransaction {
+            contract_address: contract_address_bytes!(b"bridge"),
+            entry_point_selector: entry_point_bytes!(b"handle_deposit"),
+            nonce: TransactionNonce(Felt::from_u64(nonce.into())),
+            calldata: vec![call_param_bytes!(b"L1 bridge"), call_param!("0x1")],
+        }
+    }
+
+    /// Sends three messages in L1 transaction 1, of which the first is consumed successfully in
+    /// block 0, the second is reverted in block 1 and the third is not consumed yet. Block 0 is
+    /// accepted on L1.
+    fn setup() -> RpcContext {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash!("0xa"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xb"));
+
+        let consumed = [
+            (&header_0, 1, ExecutionStatus::Succeeded),
+            (
+                &header_1,
+                2,
+                ExecutionStatus::Reverted {
+                    reason: "insufficient balance".to_owned(),
+                },
+            ),
+        ];
+        for (header, nonce, execution_status) in consumed {
+            let transaction = Transaction {
+                hash: transaction_hash_bytes!(&[b'l', nonce]),
+                variant: TransactionVariant::L1Handler(l1_handler(nonce)),
+            };
+            let receipt = Receipt {
+                transaction_hash: transaction.hash,
+                execution_status,
+                ..Default::default()
+            };
+            tx.insert_block_header(header).unwrap();
+            tx.insert_transaction_data(header.hash, header.number, &[(transaction, receipt)])
+                .unwrap();
+        }
+        tx.update_l1_l2_pointer(Some(header_0.number)).unwrap();
+
+        let logs = [1, 2, 3].map(|nonce| L1ToL2MessageLog {
+            message_hash: l1_handler(nonce).calculate_message_hash(),
+            l1_block_number: 100,
+            l1_transaction_hash: H256::from_low_u64_be(1),
+        });
+        tx.insert_message_logs(&logs).unwrap();
+
+        tx.commit().unwrap();
+
+        RpcContext::for_tests().with_storage(storage)
+    }
+
+    #[tokio::test]
+    async fn statuses() {
+        let context = setup();
+        let input = GetMessagesStatusInput {
+            transaction_hash: H256::from_low_u64_be(1),
+        };
+
+        let result = get_messages_status(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                MessageStatus {
+                    message_hash: l1_handler(1).calculate_message_hash(),
+                    transaction_hash: Some(transaction_hash_bytes!(b"l\x01")),
+                    finality_status: FinalityStatus::AcceptedOnL1,
+                    execution_status: Some(MessageExecutionStatus::Succeeded),
+                    failure_reason: None,
+                },
+                MessageStatus {
+                    message_hash: l1_handler(2).calculate_message_hash(),
+                    transaction_hash: Some(transaction_hash_bytes!(b"l\x02")),
+                    finality_status: FinalityStatus::AcceptedOnL2,
+                    execution_status: Some(MessageExecutionStatus::Reverted),
+                    failure_reason: Some("insufficient balance".to_owned()),
+                },
+                MessageStatus {
+                    message_hash: l1_handler(3).calculate_message_hash(),
+                    transaction_hash: None,
+                    finality_status: FinalityStatus::Received,
+                    execution_status: None,
+                    failure_reason: None,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction_not_found() {
+        let context = setup();
+        let input = GetMessagesStatusInput {
+            transaction_hash: H256::from_low_u64_be(2),
+        };
+
+        let error = get_messages_status(context, input).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, GetMessagesStatusError::TxnHashNotFound);
+    }
+}
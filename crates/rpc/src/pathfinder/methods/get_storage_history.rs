@@ -0,0 +1,295 @@
+use std::num::NonZeroUsize;
+
+use anyhow::Context;
+use pathfinder_common::{BlockId, BlockNumber, ContractAddress, StorageAddress, StorageValue};
+
+use crate::context::RpcContext;
+
+/// The maximum number of changes returned per page.
+const MAX_CHUNK_SIZE: usize = 1024;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetStorageHistoryInput {
+    contract_address: ContractAddress,
+    key: StorageAddress,
+    /// Defaults to the genesis block.
+    #[serde(default)]
+    from_block: Option<BlockId>,
+    /// Defaults to the latest block. `pending` is treated as `latest` as pending changes are not
+    /// final.
+    #[serde(default)]
+    to_block: Option<BlockId>,
+    /// Zero is rejected as invalid params, since it would never make progress.
+    chunk_size: NonZeroUsize,
+    #[serde(default)]
+    continuation_token: Option<String>,
+}
+
+crate::error::generate_rpc_error_subset!(
+    GetStorageHistoryError: BlockNotFound,
+    PageSizeTooBig,
+    InvalidContinuationToken
+);
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct StorageHistory {
+    changes: Vec<StorageChange>,
+    /// Set if there are more changes, which can be fetched by repeating the request with this
+    /// token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation_token: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct StorageChange {
+    block_number: BlockNumber,
+    value: StorageValue,
+}
+
+/// Returns every block in which a storage slot was changed, along with its new value.
+pub async fn get_storage_history(
+    context: RpcContext,
+    input: GetStorageHistoryInput,
+) -> Result<StorageHistory, GetStorageHistoryError> {
+    let chunk_size = input.chunk_size.get();
+    if chunk_size > MAX_CHUNK_SIZE {
+        return Err(GetStorageHistoryError::PageSizeTooBig);
+    }
+
+    let continuation_token = match input.continuation_token.as_deref() {
+        Some(token) => Some(
+            token
+                .parse()
+                .ok()
+                .and_then(BlockNumber::new)
+                .ok_or(GetStorageHistoryError::InvalidContinuationToken)?,
+        ),
+        None => None,
+    };
+
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let block_number = |block: BlockId| -> Result<BlockNumber, GetStorageHistoryError> {
+            let block = match block {
+                BlockId::Pending => pathfinder_storage::BlockId::Latest,
+                other => other.try_into().expect("Only pending cast should fail"),
+            };
+            let (number, _) = tx
+                .block_id(block)
+                .context("Fetching block number")?
+                .ok_or(GetStorageHistoryError::BlockNotFound)?;
+            Ok(number)
+        };
+
+        let from = match input.from_block {
+            Some(block) => block_number(block)?,
+            None => BlockNumber::GENESIS,
+        };
+        let to = block_number(input.to_block.unwrap_or(BlockId::Latest))?;
+
+        let from = match continuation_token {
+            Some(token) if token < from || token > to => {
+                return Err(GetStorageHistoryError::InvalidContinuationToken);
+            }
+            Some(token) => token,
+            None => from,
+        };
+
+        // Fetch an additional change to determine whether there is a next page.
+        let mut changes = tx
+            .storage_history(input.contract_address, input.key, from..=to, chunk_size + 1)
+            .context("Querying storage history")?;
+
+        let continuation_token = if changes.len() > chunk_size {
+            changes
+                .pop()
+                .map(|(block_number, _)| block_number.get().to_string())
+        } else {
+            None
+        };
+
+        let changes = changes
+            .into_iter()
+            .map(|(block_number, value)| StorageChange {
+                block_number,
+                value,
+            })
+            .collect();
+
+        Ok(StorageHistory {
+            changes,
+            continuation_token,
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    fn input(chunk_size: usize) -> GetStorageHistoryInput {
+        GetStorageHistoryInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            key: storage_address_bytes!(b"storage addr 0"),
+            from_block: None,
+            to_block: None,
+            chunk_size: NonZeroUsize::new(chunk_size).unwrap(),
+            continuation_token: None,
+        }
+    }
+
+    fn change(number: u64, value: StorageValue) -> StorageChange {
+        StorageChange {
+            block_number: BlockNumber::new_or_panic(number),
+            value,
+        }
+    }
+
+    #[tokio::test]
+    async fn full_history() {
+        let context = RpcContext::for_tests();
+
+        let result = get_storage_history(context, input(10)).await.unwrap();
+
+        assert_eq!(
+            result,
+            StorageHistory {
+                changes: vec![
+                    change(1, storage_value_bytes!(b"storage value 1")),
+                    change(2, storage_value_bytes!(b"storage value 2")),
+                ],
+                continuation_token: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn pagination() {
+        let context = RpcContext::for_tests();
+
+        let first = get_storage_history(context.clone(), input(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            first.changes,
+            vec![change(1, storage_value_bytes!(b"storage value 1"))]
+        );
+        assert_eq!(first.continuation_token, Some("2".to_owned()));
+
+        let second = GetStorageHistoryInput {
+            continuation_token: first.continuation_token,
+            ..input(1)
+        };
+        let second = get_storage_history(context, second).await.unwrap();
+        assert_eq!(
+            second,
+            StorageHistory {
+                changes: vec![change(2, storage_value_bytes!(b"storage value 2"))],
+                continuation_token: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn block_range() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            from_block: Some(BlockId::Number(BlockNumber::new_or_panic(2))),
+            to_block: Some(BlockId::Pending),
+            ..input(10)
+        };
+
+        let result = get_storage_history(context, input).await.unwrap();
+
+        assert_eq!(
+            result.changes,
+            vec![change(2, storage_value_bytes!(b"storage value 2"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_slot() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            key: storage_address_bytes!(b"unknown"),
+            ..input(10)
+        };
+
+        let result = get_storage_history(context, input).await.unwrap();
+
+        assert!(result.changes.is_empty());
+        assert_eq!(result.continuation_token, None);
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            to_block: Some(BlockId::Number(BlockNumber::new_or_panic(100))),
+            ..input(10)
+        };
+
+        let error = get_storage_history(context, input).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, GetStorageHistoryError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn page_size_too_big() {
+        let context = RpcContext::for_tests();
+
+        let error = get_storage_history(context, input(MAX_CHUNK_SIZE + 1))
+            .await
+            .unwrap_err();
+
+        assert_matches::assert_matches!(error, GetStorageHistoryError::PageSizeTooBig);
+    }
+
+    #[tokio::test]
+    async fn invalid_continuation_token() {
+        let context = RpcContext::for_tests();
+
+        for token in ["not a number", "100"] {
+            let input = GetStorageHistoryInput {
+                continuation_token: Some(token.to_owned()),
+                ..input(10)
+            };
+
+            let error = get_storage_history(context.clone(), input)
+                .await
+                .unwrap_err();
+
+            assert_matches::assert_matches!(
+                error,
+                GetStorageHistoryError::InvalidContinuationToken
+            );
+        }
+    }
+
+    #[test]
+    fn zero_chunk_size_is_rejected() {
+        let input = |chunk_size| {
+            serde_json::json!({
+                "contract_address": "0x1",
+                "key": "0x2",
+                "chunk_size": chunk_size,
+            })
+        };
+
+        serde_json::from_value::<GetStorageHistoryInput>(input(1)).unwrap();
+        serde_json::from_value::<GetStorageHistoryInput>(input(0)).unwrap_err();
+    }
+}
@@ -0,0 +1,345 @@
+use std::num::NonZeroUsize;
+
+use anyhow::Context;
+use pathfinder_common::{
+    BlockNumber, ContractAddress, TransactionHash, TransactionIndex, TransactionNonce,
+};
+use pathfinder_crypto::Felt;
+
+use crate::context::RpcContext;
+
+/// The maximum number of transactions returned per page.
+const MAX_CHUNK_SIZE: usize = 1024;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetTransactionsBySenderInput {
+    sender_address: ContractAddress,
+    /// Defaults to zero.
+    #[serde(default)]
+    from_nonce: Option<TransactionNonce>,
+    /// Zero is rejected as invalid params, since it would never make progress.
+    chunk_size: NonZeroUsize,
+    #[serde(default)]
+    continuation_token: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum GetTransactionsBySenderError {
+    Internal(anyhow::Error),
+    Disabled,
+    Incomplete,
+    PageSizeTooBig,
+    InvalidContinuationToken,
+}
+
+impl From<anyhow::Error> for GetTransactionsBySenderError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl From<GetTransactionsBySenderError> for crate::error::ApplicationError {
+    fn from(x: GetTransactionsBySenderError) -> Self {
+        match x {
+            GetTransactionsBySenderError::Disabled => {
+                Self::Custom(anyhow::anyhow!("The transaction sender index is disabled"))
+            }
+            GetTransactionsBySenderError::Incomplete => Self::Custom(anyhow::anyhow!(
+                "The transaction sender index is still being built"
+            )),
+            GetTransactionsBySenderError::PageSizeTooBig => Self::PageSizeTooBig,
+            GetTransactionsBySenderError::InvalidContinuationToken => {
+                Self::InvalidContinuationToken
+            }
+            GetTransactionsBySenderError::Internal(internal) => Self::Internal(internal),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct TransactionsBySender {
+    transactions: Vec<SenderTransaction>,
+    /// Set if there are more transactions, which can be fetched by repeating the request with this
+    /// token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continuation_token: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct SenderTransaction {
+    nonce: TransactionNonce,
+    transaction_hash: TransactionHash,
+    block_number: BlockNumber,
+    transaction_index: TransactionIndex,
+}
+
+/// Returns the transactions sent by an account, ordered by nonce.
+///
+/// Requires the transaction sender index to be enabled, and to have indexed the transactions
+/// stored before it was enabled. Version 0 transactions are not indexed as they carry no nonce.
+/// Pending transactions are ignored.
+pub async fn get_transactions_by_sender(
+    context: RpcContext,
+    input: GetTransactionsBySenderInput,
+) -> Result<TransactionsBySender, GetTransactionsBySenderError> {
+    let chunk_size = input.chunk_size.get();
+    if chunk_size > MAX_CHUNK_SIZE {
+        return Err(GetTransactionsBySenderError::PageSizeTooBig);
+    }
+
+    let from_nonce = match input.continuation_token.as_deref() {
+        Some(token) => Felt::from_hex_str(token)
+            .map(TransactionNonce)
+            .map_err(|_| GetTransactionsBySenderError::InvalidContinuationToken)?,
+        None => input.from_nonce.unwrap_or_default(),
+    };
+
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        if !tx
+            .transaction_sender_index_enabled()
+            .context("Querying transaction sender index state")?
+        {
+            return Err(GetTransactionsBySenderError::Disabled);
+        }
+        if !tx
+            .transaction_sender_index_complete()
+            .context("Querying transaction sender index state")?
+        {
+            return Err(GetTransactionsBySenderError::Incomplete);
+        }
+
+        // Fetch an additional transaction to determine whether there is a next page.
+        let mut transactions = tx
+            .transactions_by_sender(input.sender_address, from_nonce, chunk_size + 1)
+            .context("Querying transactions by sender")?;
+
+        let continuation_token = if transactions.len() > chunk_size {
+            transactions
+                .pop()
+                .map(|transaction| transaction.nonce.0.to_hex_str().into_owned())
+        } else {
+            None
+        };
+
+        let transactions = transactions
+            .into_iter()
+            .map(|transaction| SenderTransaction {
+                nonce: transaction.nonce,
+                transaction_hash: transaction.hash,
+                block_number: transaction.block_number,
+                transaction_index: transaction.index,
+            })
+            .collect();
+
+        Ok(TransactionsBySender {
+            transactions,
+            continuation_token,
+        })
+    })
+    .await
+    .context("Joining blocking task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::receipt::Receipt;
+    use pathfinder_common::transaction::{InvokeTransactionV1, Transaction, TransactionVariant};
+    use pathfinder_common::BlockHeader;
+    use pathfinder_storage::{JournalMode, Storage};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Stores three invoke transactions of `account` in block 0, with nonces 0 to 2.
+    fn setup(index_enabled: bool) -> (TempDir, RpcContext) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("db.sqlite"), JournalMode::WAL, 1)
+            .unwrap()
+            .with_transaction_sender_index(index_enabled)
+            .unwrap()
+            .create_pool(std::num::NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let header = BlockHeader::builder().finalize_with_hash(block_hash!("0xa"));
+        tx.insert_block_header(&header).unwrap();
+
+        let transactions = (0..3u8)
+            .map(|nonce| {
+                let transaction = Transaction {
+                    hash: transaction_hash_bytes!(&[b'a', nonce]),
+                    variant: TransactionVariant::InvokeV1(InvokeTransactionV1 {
+                        sender_address: contract_address_bytes!(b"account"),
+                        nonce: TransactionNonce(Felt::from_u64(nonce.into())),
+                        ..Default::default()
+                    }),
+                };
+                let receipt = Receipt {
+                    transaction_hash: transaction.hash,
+                    ..Default::default()
+                };
+                (transaction, receipt)
+            })
+            .collect::<Vec<_>>();
+        tx.insert_transaction_data(header.hash, header.number, &transactions)
+            .unwrap();
+
+        tx.commit().unwrap();
+        drop(db);
+
+        (dir, RpcContext::for_tests().with_storage(storage))
+    }
+
+    fn input(chunk_size: usize) -> GetTransactionsBySenderInput {
+        GetTransactionsBySenderInput {
+            sender_address: contract_address_bytes!(b"account"),
+            from_nonce: None,
+            chunk_size: NonZeroUsize::new(chunk_size).unwrap(),
+            continuation_token: None,
+        }
+    }
+
+    fn transaction(nonce: u8) -> SenderTransaction {
+        SenderTransaction {
+            nonce: TransactionNonce(Felt::from_u64(nonce.into())),
+            transaction_hash: transaction_hash_bytes!(&[b'a', nonce]),
+            block_number: BlockNumber::GENESIS,
+            transaction_index: TransactionIndex::new_or_panic(nonce.into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn all_transactions() {
+        let (_dir, context) = setup(true);
+
+        let result = get_transactions_by_sender(context, input(10))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result,
+            TransactionsBySender {
+                transactions: vec![transaction(0), transaction(1), transaction(2)],
+                continuation_token: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn pagination() {
+        let (_dir, context) = setup(true);
+
+        let first = get_transactions_by_sender(context.clone(), input(2))
+            .await
+            .unwrap();
+        assert_eq!(first.transactions, vec![transaction(0), transaction(1)]);
+        assert_eq!(first.continuation_token, Some("0x2".to_owned()));
+
+        let second = GetTransactionsBySenderInput {
+            continuation_token: first.continuation_token,
+            ..input(2)
+        };
+        let second = get_transactions_by_sender(context, second).await.unwrap();
+        assert_eq!(
+            second,
+            TransactionsBySender {
+                transactions: vec![transaction(2)],
+                continuation_token: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn from_nonce() {
+        let (_dir, context) = setup(true);
+        let input = GetTransactionsBySenderInput {
+            from_nonce: Some(transaction_nonce!("0x1")),
+            ..input(1)
+        };
+
+        let result = get_transactions_by_sender(context, input).await.unwrap();
+
+        assert_eq!(result.transactions, vec![transaction(1)]);
+        assert_eq!(result.continuation_token, Some("0x2".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn unknown_sender() {
+        let (_dir, context) = setup(true);
+        let input = GetTransactionsBySenderInput {
+            sender_address: contract_address_bytes!(b"unknown"),
+            ..input(10)
+        };
+
+        let result = get_transactions_by_sender(context, input).await.unwrap();
+
+        assert!(result.transactions.is_empty());
+        assert_eq!(result.continuation_token, None);
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let (_dir, context) = setup(false);
+
+        let error = get_transactions_by_sender(context, input(10))
+            .await
+            .unwrap_err();
+
+        assert_matches::assert_matches!(error, GetTransactionsBySenderError::Disabled);
+    }
+
+    #[tokio::test]
+    async fn page_size_too_big() {
+        let (_dir, context) = setup(true);
+
+        let error = get_transactions_by_sender(context, input(MAX_CHUNK_SIZE + 1))
+            .await
+            .unwrap_err();
+
+        assert_matches::assert_matches!(error, GetTransactionsBySenderError::PageSizeTooBig);
+    }
+
+    #[tokio::test]
+    async fn invalid_continuation_token() {
+        let (_dir, context) = setup(true);
+        let input = GetTransactionsBySenderInput {
+            continuation_token: Some("not a nonce".to_owned()),
+            ..input(10)
+        };
+
+        let error = get_transactions_by_sender(context, input)
+            .await
+            .unwrap_err();
+
+        assert_matches::assert_matches!(
+            error,
+            GetTransactionsBySenderError::InvalidContinuationToken
+        );
+    }
+
+    #[test]
+    fn zero_chunk_size_is_rejected() {
+        let input = |chunk_size| {
+            serde_json::json!({
+                "sender_address": "0x1",
+                "chunk_size": chunk_size,
+            })
+        };
+
+        serde_json::from_value::<GetTransactionsBySenderInput>(input(1)).unwrap();
+        serde_json::from_value::<GetTransactionsBySenderInput>(input(0)).unwrap_err();
+    }
+}
@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
 use anyhow::Context;
-use pathfinder_common::{BlockHeader, BlockNumber, StateUpdate};
+use pathfinder_common::receipt::Receipt;
+use pathfinder_common::state_update::{ContractUpdate, SystemContractUpdate};
+use pathfinder_common::{BlockHash, BlockHeader, BlockNumber, StateUpdate};
 use pathfinder_storage::Transaction;
 use starknet_gateway_types::reply::{PendingBlock, Status};
 
@@ -44,6 +46,156 @@ impl PendingData {
     }
 }
 
+/// The changes between two successive snapshots of the pending block.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PendingDiff {
+    pub parent_hash: BlockHash,
+    /// Whether the snapshot is of a pending block with a different parent than the previous
+    /// one. The diff then contains the complete pending block instead of only its changes.
+    pub new_block: bool,
+    pub transactions: Vec<pathfinder_common::transaction::Transaction>,
+    pub receipts: Vec<Receipt>,
+    /// The state changes which were not yet part of the previous snapshot.
+    pub state_diff: StateUpdate,
+}
+
+impl PendingDiff {
+    /// Computes the changes from the `previous` snapshot of the pending block to `next`.
+    ///
+    /// Returns `None` if `next` does not extend `previous`, i.e. it builds on the same parent
+    /// but is missing some of the previous transactions. This happens when a feeder gateway
+    /// which lags behind returns stale pending data.
+    pub fn new(
+        previous: (&PendingBlock, &StateUpdate),
+        next: (&PendingBlock, &StateUpdate),
+    ) -> Option<Self> {
+        let (previous_block, previous_state) = previous;
+        let (next_block, next_state) = next;
+
+        if previous_block.parent_hash != next_block.parent_hash {
+            return Some(Self {
+                parent_hash: next_block.parent_hash,
+                new_block: true,
+                transactions: next_block.transactions.clone(),
+                receipts: next_block.transaction_receipts.clone(),
+                state_diff: next_state.clone(),
+            });
+        }
+
+        let transactions_extended = is_prefix(
+            previous_block.transactions.iter().map(|tx| tx.hash),
+            next_block.transactions.iter().map(|tx| tx.hash),
+        );
+        let receipts_extended = is_prefix(
+            previous_block
+                .transaction_receipts
+                .iter()
+                .map(|r| r.transaction_hash),
+            next_block
+                .transaction_receipts
+                .iter()
+                .map(|r| r.transaction_hash),
+        );
+        if !transactions_extended || !receipts_extended {
+            return None;
+        }
+
+        Some(Self {
+            parent_hash: next_block.parent_hash,
+            new_block: false,
+            transactions: next_block.transactions[previous_block.transactions.len()..].to_vec(),
+            receipts: next_block.transaction_receipts[previous_block.transaction_receipts.len()..]
+                .to_vec(),
+            state_diff: state_changes(previous_state, next_state),
+        })
+    }
+
+    /// Returns true if the snapshots are of the same pending block and nothing changed.
+    pub fn is_empty(&self) -> bool {
+        !self.new_block
+            && self.transactions.is_empty()
+            && self.receipts.is_empty()
+            && self.state_diff.change_count() == 0
+    }
+}
+
+fn is_prefix<T: PartialEq>(
+    prefix: impl ExactSizeIterator<Item = T>,
+    items: impl ExactSizeIterator<Item = T>,
+) -> bool {
+    prefix.len() <= items.len() && prefix.zip(items).all(|(a, b)| a == b)
+}
+
+/// Returns the parts of the `next` state update which are not already contained in `previous`.
+fn state_changes(previous: &StateUpdate, next: &StateUpdate) -> StateUpdate {
+    let mut changes = StateUpdate {
+        block_hash: next.block_hash,
+        parent_state_commitment: next.parent_state_commitment,
+        state_commitment: next.state_commitment,
+        ..Default::default()
+    };
+
+    for (address, update) in &next.contract_updates {
+        let previous = previous.contract_updates.get(address);
+
+        let storage = update
+            .storage
+            .iter()
+            .filter(|(key, value)| previous.and_then(|p| p.storage.get(key)) != Some(value))
+            .map(|(key, value)| (*key, *value))
+            .collect::<std::collections::HashMap<_, _>>();
+        let class = update
+            .class
+            .clone()
+            .filter(|class| previous.and_then(|p| p.class.as_ref()) != Some(class));
+        let nonce = update
+            .nonce
+            .filter(|nonce| previous.and_then(|p| p.nonce.as_ref()) != Some(nonce));
+
+        if !storage.is_empty() || class.is_some() || nonce.is_some() {
+            changes.contract_updates.insert(
+                *address,
+                ContractUpdate {
+                    storage,
+                    class,
+                    nonce,
+                },
+            );
+        }
+    }
+
+    for (address, update) in &next.system_contract_updates {
+        let previous = previous.system_contract_updates.get(address);
+
+        let storage = update
+            .storage
+            .iter()
+            .filter(|(key, value)| previous.and_then(|p| p.storage.get(key)) != Some(value))
+            .map(|(key, value)| (*key, *value))
+            .collect::<std::collections::HashMap<_, _>>();
+
+        if !storage.is_empty() {
+            changes
+                .system_contract_updates
+                .insert(*address, SystemContractUpdate { storage });
+        }
+    }
+
+    changes.declared_cairo_classes = next
+        .declared_cairo_classes
+        .difference(&previous.declared_cairo_classes)
+        .copied()
+        .collect();
+    changes.declared_sierra_classes = next
+        .declared_sierra_classes
+        .iter()
+        .filter(|(sierra, _)| !previous.declared_sierra_classes.contains_key(sierra))
+        .map(|(sierra, casm)| (*sierra, *casm))
+        .collect();
+
+    changes
+}
+
 impl PendingWatcher {
     pub fn new(receiver: WatchReceiver<PendingData>) -> Self {
         Self(receiver)
@@ -190,4 +342,138 @@ mod tests {
 
         pretty_assertions_sorted::assert_eq_sorted!(result, expected);
     }
+
+    mod diff {
+        use pathfinder_common::transaction::{L1HandlerTransaction, TransactionVariant};
+        use pathfinder_common::TransactionHash;
+
+        use super::*;
+
+        fn transaction(hash: TransactionHash) -> pathfinder_common::transaction::Transaction {
+            pathfinder_common::transaction::Transaction {
+                hash,
+                variant: TransactionVariant::L1Handler(L1HandlerTransaction::default()),
+            }
+        }
+
+        fn receipt(hash: TransactionHash) -> Receipt {
+            Receipt {
+                transaction_hash: hash,
+                ..Default::default()
+            }
+        }
+
+        fn block(transactions: &[TransactionHash]) -> PendingBlock {
+            PendingBlock {
+                parent_hash: block_hash_bytes!(b"parent"),
+                transactions: transactions.iter().copied().map(transaction).collect(),
+                transaction_receipts: transactions.iter().copied().map(receipt).collect(),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn only_contains_changes() {
+            let tx0 = transaction_hash_bytes!(b"tx 0");
+            let tx1 = transaction_hash_bytes!(b"tx 1");
+            let contract = contract_address_bytes!(b"contract");
+
+            let previous_block = block(&[tx0]);
+            let previous_state = StateUpdate::default()
+                .with_storage_update(
+                    contract,
+                    storage_address_bytes!(b"key 0"),
+                    storage_value_bytes!(b"value 0"),
+                )
+                .with_contract_nonce(contract, contract_nonce_bytes!(b"nonce 0"))
+                .with_declared_cairo_class(class_hash_bytes!(b"cairo"));
+
+            let next_block = block(&[tx0, tx1]);
+            let next_state = previous_state
+                .clone()
+                .with_storage_update(
+                    contract,
+                    storage_address_bytes!(b"key 1"),
+                    storage_value_bytes!(b"value 1"),
+                )
+                .with_contract_nonce(contract, contract_nonce_bytes!(b"nonce 1"))
+                .with_declared_sierra_class(
+                    sierra_hash_bytes!(b"sierra"),
+                    casm_hash_bytes!(b"casm"),
+                );
+
+            let diff = PendingDiff::new(
+                (&previous_block, &previous_state),
+                (&next_block, &next_state),
+            )
+            .unwrap();
+
+            let expected = PendingDiff {
+                parent_hash: block_hash_bytes!(b"parent"),
+                new_block: false,
+                transactions: vec![transaction(tx1)],
+                receipts: vec![receipt(tx1)],
+                state_diff: StateUpdate::default()
+                    .with_storage_update(
+                        contract,
+                        storage_address_bytes!(b"key 1"),
+                        storage_value_bytes!(b"value 1"),
+                    )
+                    .with_contract_nonce(contract, contract_nonce_bytes!(b"nonce 1"))
+                    .with_declared_sierra_class(
+                        sierra_hash_bytes!(b"sierra"),
+                        casm_hash_bytes!(b"casm"),
+                    ),
+            };
+            pretty_assertions_sorted::assert_eq!(diff, expected);
+            assert!(!diff.is_empty());
+        }
+
+        #[test]
+        fn unchanged() {
+            let block = block(&[transaction_hash_bytes!(b"tx 0")]);
+            let state =
+                StateUpdate::default().with_declared_cairo_class(class_hash_bytes!(b"cairo"));
+
+            let diff = PendingDiff::new((&block, &state), (&block, &state)).unwrap();
+
+            assert!(diff.is_empty());
+        }
+
+        #[test]
+        fn stale_snapshot() {
+            let tx0 = transaction_hash_bytes!(b"tx 0");
+            let tx1 = transaction_hash_bytes!(b"tx 1");
+            let state = StateUpdate::default();
+
+            let diff = PendingDiff::new((&block(&[tx0, tx1]), &state), (&block(&[tx0]), &state));
+            assert_eq!(diff, None);
+
+            let diff = PendingDiff::new((&block(&[tx0]), &state), (&block(&[tx1, tx0]), &state));
+            assert_eq!(diff, None);
+        }
+
+        #[test]
+        fn new_block_contains_everything() {
+            let tx0 = transaction_hash_bytes!(b"tx 0");
+            let previous = block(&[tx0]);
+            let next = PendingBlock {
+                parent_hash: block_hash_bytes!(b"new parent"),
+                ..block(&[tx0])
+            };
+            let state =
+                StateUpdate::default().with_declared_cairo_class(class_hash_bytes!(b"cairo"));
+
+            let diff = PendingDiff::new((&previous, &state), (&next, &state)).unwrap();
+
+            let expected = PendingDiff {
+                parent_hash: block_hash_bytes!(b"new parent"),
+                new_block: true,
+                transactions: next.transactions.clone(),
+                receipts: next.transaction_receipts.clone(),
+                state_diff: state,
+            };
+            assert_eq!(diff, expected);
+        }
+    }
 }
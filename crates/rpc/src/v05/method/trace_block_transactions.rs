@@ -1,7 +1,7 @@
 use anyhow::Context;
 use pathfinder_common::transaction::Transaction;
 use pathfinder_common::{BlockId, TransactionHash};
-use pathfinder_executor::{ExecutionState, TraceCache, TransactionExecutionError};
+use pathfinder_executor::{TraceCache, TransactionExecutionError};
 use serde::{Deserialize, Serialize};
 use starknet_gateway_client::GatewayApi;
 use starknet_gateway_types::trace::TransactionTrace as GatewayTxTrace;
@@ -11,7 +11,7 @@ use crate::executor::VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE
 use crate::v05::method::simulate_transactions::dto::{
     DeclareTxnTrace, DeployAccountTxnTrace, ExecuteInvocation, InvokeTxnTrace, L1HandlerTxnTrace,
 };
-use crate::{compose_executor_transaction, context::RpcContext, executor::ExecutionStateError};
+use crate::{context::RpcContext, executor::ExecutionStateError};
 
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
@@ -141,6 +141,7 @@ pub async fn trace_block_transactions(
         Unsupported(Vec<Transaction>),
     }
 
+    let sequencer = context.sequencer.clone();
     let span = tracing::Span::current();
 
     let storage = context.storage.clone();
@@ -206,14 +207,7 @@ pub async fn trace_block_transactions(
             }
         }
 
-        let transactions = transactions
-            .iter()
-            .map(|transaction| compose_executor_transaction(transaction, &db))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let hash = header.hash;
-        let state = ExecutionState::trace(&db, context.chain_id, header, None);
-        let traces = pathfinder_executor::trace(state, cache, hash, transactions, true, true)?;
+        let traces = crate::executor::trace_block(&context, &db, header, &transactions, cache)?;
 
         let result = traces
             .into_iter()
@@ -233,8 +227,7 @@ pub async fn trace_block_transactions(
         LocalExecution::Unsupported(transactions) => transactions,
     };
 
-    context
-        .sequencer
+    sequencer
         .block_traces(input.block_id)
         .await
         .context("Forwarding to feeder gateway")
@@ -1,10 +1,9 @@
 use anyhow::Context;
 use pathfinder_common::TransactionHash;
-use pathfinder_executor::{ExecutionState, TraceCache, TransactionExecutionError};
+use pathfinder_executor::{TraceCache, TransactionExecutionError};
 use serde::{Deserialize, Serialize};
 use starknet_gateway_client::GatewayApi;
 
-use crate::compose_executor_transaction;
 use crate::executor::VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY;
 use crate::v05::method::trace_block_transactions::map_gateway_trace;
 use crate::{
@@ -104,6 +103,7 @@ pub async fn trace_transaction(
         Unsupported(pathfinder_common::transaction::Transaction),
     }
 
+    let sequencer = context.sequencer.clone();
     let span = tracing::Span::current();
     let local = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
@@ -183,15 +183,7 @@ pub async fn trace_transaction(
             (header, transactions.clone(), context.cache.clone())
         };
 
-        let hash = header.hash;
-        let state = ExecutionState::trace(&db, context.chain_id, header, None);
-
-        let transactions = transactions
-            .iter()
-            .map(|transaction| compose_executor_transaction(transaction, &db))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        pathfinder_executor::trace(state, cache, hash, transactions, true, true)
+        crate::executor::trace_block(&context, &db, header, &transactions, cache)
             .map_err(TraceTransactionError::from)
             .and_then(|txs| {
                 txs.into_iter()
@@ -219,8 +211,7 @@ pub async fn trace_transaction(
         LocalExecution::Unsupported(x) => x,
     };
 
-    let trace = context
-        .sequencer
+    let trace = sequencer
         .transaction_trace(input.transaction_hash)
         .await
         .context("Proxying call to feeder gateway")?;
@@ -1,14 +1,14 @@
 mod add_declare_transaction;
 pub(crate) mod add_deploy_account_transaction;
 pub(crate) mod add_invoke_transaction;
-mod estimate_fee;
+pub(crate) mod estimate_fee;
 pub(crate) mod estimate_message_fee;
 mod get_block_with_tx_hashes;
 mod get_block_with_txs;
 mod get_transaction_by_block_id_and_index;
 mod get_transaction_by_hash;
 pub(crate) mod get_transaction_receipt;
-mod simulate_transactions;
+pub(crate) mod simulate_transactions;
 mod trace_block_transactions;
 mod trace_transaction;
 
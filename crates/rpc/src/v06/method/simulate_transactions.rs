@@ -1,5 +1,10 @@
 use crate::{
-    context::RpcContext, executor::ExecutionStateError, v02::types::request::BroadcastedTransaction,
+    context::RpcContext,
+    executor::{
+        ExecutionStateError,
+        VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY,
+    },
+    v02::types::request::BroadcastedTransaction,
 };
 
 use anyhow::Context;
@@ -14,6 +19,10 @@ pub struct SimulateTransactionInput {
     block_id: BlockId,
     transactions: Vec<BroadcastedTransaction>,
     simulation_flags: dto::SimulationFlags,
+    /// Pathfinder extension: simulate on top of the state right after this transaction of the block,
+    /// instead of on top of the whole block.
+    #[serde(default)]
+    after_transaction_index: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Eq, PartialEq)]
@@ -28,6 +37,13 @@ pub enum SimulateTransactionError {
         transaction_index: usize,
         error: String,
     },
+    MidBlockStateUnsupported {
+        starknet_version: String,
+    },
+    BlockReplayFailed {
+        transaction_index: usize,
+        error: String,
+    },
 }
 
 impl From<anyhow::Error> for SimulateTransactionError {
@@ -49,6 +65,16 @@ impl From<SimulateTransactionError> for crate::error::ApplicationError {
                 transaction_index,
                 error,
             },
+            SimulateTransactionError::MidBlockStateUnsupported { starknet_version } => {
+                Self::MidBlockStateUnsupported { starknet_version }
+            }
+            SimulateTransactionError::BlockReplayFailed {
+                transaction_index,
+                error,
+            } => Self::BlockReplayFailed {
+                transaction_index,
+                error,
+            },
         }
     }
 }
@@ -56,6 +82,14 @@ impl From<SimulateTransactionError> for crate::error::ApplicationError {
 impl From<TransactionExecutionError> for SimulateTransactionError {
     fn from(value: TransactionExecutionError) -> Self {
         use TransactionExecutionError::*;
+
+        if let Some(replay) = value.prefix_replay_error() {
+            return Self::BlockReplayFailed {
+                transaction_index: replay.transaction_index,
+                error: replay.error.clone(),
+            };
+        }
+
         match value {
             ExecutionError {
                 transaction_index,
@@ -105,16 +139,26 @@ pub async fn simulate_transactions(
             .context("Creating database connection")?;
         let db = db.transaction().context("Creating database transaction")?;
 
-        let (header, pending) = match input.block_id {
-            BlockId::Pending => {
+        let state = match (input.block_id, input.after_transaction_index) {
+            (BlockId::Pending, Some(_)) => {
+                return Err(SimulateTransactionError::Custom(anyhow::anyhow!(
+                    "after_transaction_index is not supported for the pending block"
+                )));
+            }
+            (BlockId::Pending, None) => {
                 let pending = context
                     .pending_data
                     .get(&db)
                     .context("Querying pending data")?;
 
-                (pending.header(), Some(pending.state_update.clone()))
+                pathfinder_executor::ExecutionState::simulation(
+                    &db,
+                    context.chain_id,
+                    pending.header(),
+                    Some(pending.state_update.clone()),
+                )
             }
-            other => {
+            (other, after_transaction_index) => {
                 let block_id = other.try_into().expect("Only pending should fail");
 
                 let header = db
@@ -122,13 +166,27 @@ pub async fn simulate_transactions(
                     .context("Fetching block header")?
                     .ok_or(SimulateTransactionError::BlockNotFound)?;
 
-                (header, None)
+                match after_transaction_index {
+                    None => pathfinder_executor::ExecutionState::simulation(
+                        &db,
+                        context.chain_id,
+                        header,
+                        None,
+                    ),
+                    Some(index) => {
+                        ensure_replayable(&header)?;
+                        let prefix = block_prefix(&db, block_id, index)?;
+                        pathfinder_executor::ExecutionState::mid_block(
+                            &db,
+                            context.chain_id,
+                            header,
+                            prefix,
+                        )
+                    }
+                }
             }
         };
 
-        let state =
-            pathfinder_executor::ExecutionState::simulation(&db, context.chain_id, header, pending);
-
         let transactions = input
             .transactions
             .into_iter()
@@ -147,6 +205,52 @@ pub async fn simulate_transactions(
     .context("Simulating transaction")?
 }
 
+/// Blocks older than [VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY]
+/// can't be re-executed faithfully, so a mid-block state can't be reconstructed for them.
+fn ensure_replayable(
+    header: &pathfinder_common::BlockHeader,
+) -> Result<(), SimulateTransactionError> {
+    let starknet_version = header
+        .starknet_version
+        .parse_as_semver()
+        .context("Parsing starknet version")?
+        .unwrap_or(semver::Version::new(0, 0, 0));
+
+    if starknet_version < VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE_FROM_GATEWAY {
+        return Err(SimulateTransactionError::MidBlockStateUnsupported {
+            starknet_version: header.starknet_version.as_str().to_owned(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Composes the transactions of the block up to and including `index`.
+fn block_prefix(
+    db: &pathfinder_storage::Transaction<'_>,
+    block_id: pathfinder_storage::BlockId,
+    index: u64,
+) -> Result<Vec<pathfinder_executor::Transaction>, SimulateTransactionError> {
+    let transactions = db
+        .transaction_data_for_block(block_id)
+        .context("Fetching block transactions")?
+        .ok_or(SimulateTransactionError::BlockNotFound)?;
+
+    if index >= transactions.len() as u64 {
+        return Err(SimulateTransactionError::Custom(anyhow::anyhow!(
+            "after_transaction_index {index} is out of range for a block with {} transactions",
+            transactions.len()
+        )));
+    }
+
+    transactions
+        .iter()
+        .take(index as usize + 1)
+        .map(|(transaction, _)| crate::executor::compose_executor_transaction(transaction, db))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(Into::into)
+}
+
 pub mod dto {
     use serde_with::serde_as;
 
@@ -779,6 +883,7 @@ pub(crate) mod tests {
             block_id: last_block_header.number.into(),
             transactions: vec![declare],
             simulation_flags: dto::SimulationFlags(vec![]),
+            after_transaction_index: None,
         };
 
         let result = simulate_transactions(context, input).await.unwrap();
@@ -1647,6 +1752,124 @@ pub(crate) mod tests {
         }
     }
 
+    mod after_transaction_index {
+        use super::*;
+
+        #[tokio::test]
+        async fn pending_is_rejected() {
+            let context = RpcContext::for_tests_with_pending().await;
+            let input = SimulateTransactionInput {
+                transactions: vec![],
+                block_id: BlockId::Pending,
+                simulation_flags: dto::SimulationFlags(vec![]),
+                after_transaction_index: Some(0),
+            };
+
+            let error = simulate_transactions(context, input).await.unwrap_err();
+
+            assert_matches::assert_matches!(error, SimulateTransactionError::Custom(_));
+        }
+
+        #[tokio::test]
+        async fn index_out_of_range() {
+            let (context, next_block_header, _) =
+                crate::v06::method::trace_block_transactions::tests::setup_multi_tx_trace_test()
+                    .await
+                    .unwrap();
+            let input = SimulateTransactionInput {
+                transactions: vec![],
+                block_id: BlockId::Hash(next_block_header.hash),
+                simulation_flags: dto::SimulationFlags(vec![]),
+                after_transaction_index: Some(1000),
+            };
+
+            let error = simulate_transactions(context, input).await.unwrap_err();
+
+            assert_matches::assert_matches!(error, SimulateTransactionError::Custom(_));
+        }
+
+        #[tokio::test]
+        async fn old_blocks_are_rejected() {
+            // The test blocks have no Starknet version, so they predate re-execution support.
+            let context = RpcContext::for_tests();
+            let input = SimulateTransactionInput {
+                transactions: vec![],
+                block_id: BlockId::Number(pathfinder_common::BlockNumber::GENESIS),
+                simulation_flags: dto::SimulationFlags(vec![]),
+                after_transaction_index: Some(0),
+            };
+
+            let error = simulate_transactions(context, input).await.unwrap_err();
+
+            assert_matches::assert_matches!(
+                error,
+                SimulateTransactionError::MidBlockStateUnsupported { .. }
+            );
+        }
+
+        #[test]
+        fn replay_failure_is_not_internal() {
+            let error = TransactionExecutionError::Internal(anyhow::Error::new(
+                pathfinder_executor::PrefixReplayError {
+                    transaction_index: 2,
+                    error: "Out of gas".to_owned(),
+                },
+            ));
+
+            let error = SimulateTransactionError::from(error);
+
+            assert_matches::assert_matches!(
+                error,
+                SimulateTransactionError::BlockReplayFailed {
+                    transaction_index: 2,
+                    ..
+                }
+            );
+        }
+
+        #[tokio::test]
+        async fn matches_trace_of_the_next_transaction() {
+            let (context, next_block_header, traces) =
+                crate::v06::method::trace_block_transactions::tests::setup_multi_tx_trace_test()
+                    .await
+                    .unwrap();
+            let account_contract_address = contract_address!("0xc01");
+
+            // Replaying the declaration and the deployment should leave the state exactly as it
+            // was when the invoke was executed as part of the block.
+            let input = SimulateTransactionInput {
+                transactions: vec![fixtures::input::invoke(account_contract_address)],
+                block_id: BlockId::Hash(next_block_header.hash),
+                simulation_flags: dto::SimulationFlags(vec![]),
+                after_transaction_index: Some(1),
+            };
+
+            let result = simulate_transactions(context, input).await.unwrap();
+
+            assert_eq!(result.0.len(), 1);
+            pretty_assertions_sorted::assert_eq!(
+                result.0[0].transaction_trace,
+                traces[2].trace_root
+            );
+        }
+
+        #[test]
+        fn parsing() {
+            let input = serde_json::json!({
+                "block_id": {"block_number": 1},
+                "transactions": [],
+                "simulation_flags": [],
+                "after_transaction_index": 3,
+            });
+            let input = serde_json::from_value::<SimulateTransactionInput>(input).unwrap();
+            assert_eq!(input.after_transaction_index, Some(3));
+
+            let input = serde_json::json!([{"block_number": 1}, [], []]);
+            let input = serde_json::from_value::<SimulateTransactionInput>(input).unwrap();
+            assert_eq!(input.after_transaction_index, None);
+        }
+    }
+
     #[test_log::test(tokio::test)]
     async fn declare_deploy_and_invoke_sierra_class() {
         let (
@@ -1669,6 +1892,7 @@ pub(crate) mod tests {
             ],
             block_id: BlockId::Number(last_block_header.number),
             simulation_flags: dto::SimulationFlags(vec![]),
+            after_transaction_index: None,
         };
         let result = simulate_transactions(context, input).await.unwrap();
 
@@ -1712,6 +1936,7 @@ pub(crate) mod tests {
             ],
             block_id: BlockId::Number(last_block_header.number),
             simulation_flags: dto::SimulationFlags(vec![dto::SimulationFlag::SkipFeeCharge]),
+            after_transaction_index: None,
         };
         let result = simulate_transactions(context, input).await.unwrap();
 
@@ -1753,6 +1978,7 @@ pub(crate) mod tests {
             ],
             block_id: BlockId::Number(last_block_header.number),
             simulation_flags: dto::SimulationFlags(vec![dto::SimulationFlag::SkipValidate]),
+            after_transaction_index: None,
         };
         let result = simulate_transactions(context, input).await.unwrap();
 
@@ -1,6 +1,6 @@
 use anyhow::Context;
 use pathfinder_common::{BlockId, TransactionHash};
-use pathfinder_executor::{ExecutionState, TraceCache, TransactionExecutionError};
+use pathfinder_executor::{TraceCache, TransactionExecutionError};
 use serde::{Deserialize, Serialize};
 use starknet_gateway_client::GatewayApi;
 use starknet_gateway_types::trace::TransactionTrace as GatewayTxTrace;
@@ -9,7 +9,7 @@ use crate::executor::VERSIONS_LOWER_THAN_THIS_SHOULD_FALL_BACK_TO_FETCHING_TRACE
 use crate::v06::method::simulate_transactions::dto::{
     DeclareTxnTrace, DeployAccountTxnTrace, ExecuteInvocation, InvokeTxnTrace, L1HandlerTxnTrace,
 };
-use crate::{compose_executor_transaction, context::RpcContext, executor::ExecutionStateError};
+use crate::{context::RpcContext, executor::ExecutionStateError};
 
 use pathfinder_common::transaction::Transaction;
 
@@ -150,6 +150,7 @@ pub async fn trace_block_transactions(
         Unsupported(Vec<Transaction>),
     }
 
+    let sequencer = context.sequencer.clone();
     let span = tracing::Span::current();
 
     let storage = context.storage.clone();
@@ -215,14 +216,7 @@ pub async fn trace_block_transactions(
             }
         }
 
-        let transactions = transactions
-            .iter()
-            .map(|transaction| compose_executor_transaction(transaction, &db))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let hash = header.hash;
-        let state = ExecutionState::trace(&db, context.chain_id, header, None);
-        let traces = pathfinder_executor::trace(state, cache, hash, transactions, true, true)?;
+        let traces = crate::executor::trace_block(&context, &db, header, &transactions, cache)?;
 
         let result = traces
             .into_iter()
@@ -244,8 +238,7 @@ pub async fn trace_block_transactions(
         LocalExecution::Unsupported(transactions) => transactions,
     };
 
-    context
-        .sequencer
+    sequencer
         .block_traces(input.block_id)
         .await
         .context("Forwarding to feeder gateway")
@@ -392,6 +385,137 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_multiple_transactions_in_parallel() -> anyhow::Result<()> {
+        let (mut context, next_block_header, traces) = setup_multi_tx_trace_test().await?;
+        context.config.trace_concurrency = Some(std::num::NonZeroUsize::new(3).unwrap());
+
+        let input = TraceBlockTransactionsInput {
+            block_id: next_block_header.hash.into(),
+        };
+        let output = trace_block_transactions(context, input).await.unwrap();
+        let expected = TraceBlockTransactionsOutput(traces);
+
+        pretty_assertions_sorted::assert_eq!(output, expected);
+        Ok(())
+    }
+
+    /// Transactions sent by different accounts only touch the same state through the fee
+    /// transfer to the sequencer, so their speculative results should be accepted as is.
+    #[tokio::test]
+    async fn test_independent_transactions_in_parallel() -> anyhow::Result<()> {
+        use crate::v02::types::request::{
+            BroadcastedInvokeTransaction, BroadcastedInvokeTransactionV1, BroadcastedTransaction,
+        };
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{CallParam, EntryPoint, Fee, StorageAddress, TransactionVersion};
+        use pathfinder_executor::{ExecutionState, ETH_FEE_TOKEN_ADDRESS};
+        use starknet_gateway_test_fixtures::class_definitions::DUMMY_ACCOUNT_CLASS_HASH;
+
+        let accounts = [contract_address!("0xc03"), contract_address!("0xc04")];
+        let (storage, last_block_header, _, _) = crate::test_setup::test_storage(|state_update| {
+            accounts.iter().fold(state_update, |state_update, account| {
+                state_update
+                    .with_deployed_contract(*account, DUMMY_ACCOUNT_CLASS_HASH)
+                    .with_storage_update(
+                        ETH_FEE_TOKEN_ADDRESS,
+                        StorageAddress::from_map_name_and_key(b"ERC20_balances", account.0),
+                        storage_value!("0x10000000000000000000000000000"),
+                    )
+            })
+        })
+        .await;
+        let mut context = RpcContext::for_tests().with_storage(storage.clone());
+
+        let transactions = accounts
+            .iter()
+            .map(|account| {
+                BroadcastedTransaction::Invoke(BroadcastedInvokeTransaction::V1(
+                    BroadcastedInvokeTransactionV1 {
+                        nonce: transaction_nonce!("0x0"),
+                        version: TransactionVersion::ONE,
+                        max_fee: Fee(felt!("0x10000000")),
+                        signature: vec![],
+                        sender_address: *account,
+                        calldata: vec![
+                            CallParam(ETH_FEE_TOKEN_ADDRESS.0),
+                            CallParam(EntryPoint::hashed(b"decimals").0),
+                            call_param!("0"),
+                        ],
+                    },
+                ))
+                .into_common(context.chain_id)
+            })
+            .collect::<Vec<_>>();
+
+        let next_block_header = BlockHeader::builder()
+            .with_number(last_block_header.number + 1)
+            .with_eth_l1_gas_price(GasPrice(1))
+            .with_parent_hash(last_block_header.hash)
+            .with_starknet_version(last_block_header.starknet_version)
+            .with_sequencer_address(last_block_header.sequencer_address)
+            .with_timestamp(last_block_header.timestamp)
+            .finalize_with_hash(block_hash!("0x1"));
+        {
+            let mut db = storage.connection()?;
+            let tx = db.transaction()?;
+            tx.insert_block_header(&next_block_header)?;
+            let receipts = transactions
+                .iter()
+                .enumerate()
+                .map(|(i, transaction)| {
+                    let receipt = Receipt {
+                        transaction_hash: transaction.hash,
+                        transaction_index: TransactionIndex::new_or_panic(i as u64),
+                        ..Default::default()
+                    };
+                    (transaction.clone(), receipt)
+                })
+                .collect::<Vec<_>>();
+            tx.insert_transaction_data(
+                next_block_header.hash,
+                next_block_header.number,
+                &receipts,
+            )?;
+            tx.commit()?;
+        }
+
+        let parallel = {
+            let mut db = storage.connection()?;
+            let db = db.transaction()?;
+            let state =
+                ExecutionState::trace(&db, context.chain_id, next_block_header.clone(), None);
+            pathfinder_executor::trace_parallel(
+                state,
+                storage.clone(),
+                std::num::NonZeroUsize::new(2).unwrap(),
+                Default::default(),
+                next_block_header.hash,
+                &transactions,
+                crate::executor::compose_executor_transaction,
+                true,
+                true,
+            )
+            .unwrap()
+        };
+        assert_eq!(parallel.reexecuted, 0);
+        assert_eq!(parallel.traces.len(), transactions.len());
+
+        // The accepted results must match the sequential execution.
+        let input = TraceBlockTransactionsInput {
+            block_id: next_block_header.hash.into(),
+        };
+        let sequential = trace_block_transactions(context.clone(), input.clone())
+            .await
+            .unwrap();
+        context.config.trace_concurrency = Some(std::num::NonZeroUsize::new(2).unwrap());
+        context.cache = Default::default();
+        let output = trace_block_transactions(context, input).await.unwrap();
+
+        pretty_assertions_sorted::assert_eq!(output, sequential);
+        Ok(())
+    }
+
     /// Test that multiple requests for the same block return correctly. This checks that the
     /// trace request coalescing doesn't do anything unexpected.
     #[tokio::test]
@@ -0,0 +1,333 @@
+//! Online backups using SQLite's [backup API](https://www.sqlite.org/backup.html).
+
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use rusqlite::backup::{Backup, StepResult};
+
+use crate::Storage;
+
+/// The size of the WAL at which a running backup warns that it is preventing checkpoints.
+const WAL_WARNING_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Controls how quickly a backup is performed.
+#[derive(Debug, Clone, Copy)]
+pub struct BackupOptions {
+    /// The number of database pages copied per step.
+    pub pages_per_step: std::num::NonZeroU32,
+    /// The pause between steps, which gives other connections a chance to access the database.
+    pub pause: Duration,
+}
+
+/// Describes a completed backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupReport {
+    /// The range of blocks included in the backup, or [None] if it contains no blocks.
+    pub blocks: Option<RangeInclusive<BlockNumber>>,
+    /// The size of the backup file in bytes.
+    pub size: u64,
+}
+
+impl Storage {
+    /// Copies the database to `destination` while it remains in use.
+    ///
+    /// The copy is made from a single read transaction, so it is consistent with the state of the
+    /// database when the backup started and is not restarted by writes made in the meantime. With
+    /// WAL enabled, writers are not blocked by the backup. The copy is verified using SQLite's
+    /// integrity check before returning.
+    ///
+    /// The read transaction is held until the copy is complete, which prevents the WAL from being
+    /// checkpointed past its snapshot. The WAL therefore grows with every write made during the
+    /// backup, which is logged as a warning once it exceeds 1 GiB, and only shrinks again after the
+    /// backup has completed. Slower [BackupOptions] make this worse.
+    ///
+    /// Trie nodes which are not stored in the database itself are copied next to `destination`,
+    /// see [TrieStorage::backup](crate::TrieStorage::backup).
+    ///
+    /// Fails if `destination` already exists. A partially written backup is removed on failure.
+    pub fn backup(
+        &self,
+        destination: &Path,
+        options: BackupOptions,
+    ) -> anyhow::Result<BackupReport> {
+        anyhow::ensure!(
+            !destination.exists(),
+            "Backup destination {} already exists",
+            destination.display()
+        );
+
+        let trie_directory = crate::connection::trie_backend::rocksdb_directory(destination);
+        let trie_directory_existed = trie_directory.exists();
+
+        let result = self.backup_to(destination, options);
+        if result.is_err() {
+            if let Err(error) = std::fs::remove_file(destination) {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(path=%destination.display(), %error, "Removing failed backup");
+                }
+            }
+            if !trie_directory_existed && trie_directory.is_dir() {
+                if let Err(error) = std::fs::remove_dir_all(&trie_directory) {
+                    tracing::warn!(path=%trie_directory.display(), %error, "Removing failed backup");
+                }
+            }
+        }
+
+        result
+    }
+
+    fn backup_to(
+        &self,
+        destination: &Path,
+        options: BackupOptions,
+    ) -> anyhow::Result<BackupReport> {
+        let source = self.0.pool.get().context("Getting database connection")?;
+        let mut target = rusqlite::Connection::open(destination)
+            .with_context(|| format!("Creating backup database at {}", destination.display()))?;
+
+        // Pin the source to a single snapshot for the duration of the backup. Without this the
+        // backup would restart whenever another connection writes to the database.
+        let snapshot =
+            rusqlite::Transaction::new_unchecked(&source, rusqlite::TransactionBehavior::Deferred)
+                .context("Creating database transaction")?;
+        snapshot
+            .query_row("SELECT count(*) FROM sqlite_schema", [], |_| Ok(()))
+            .context("Starting read transaction")?;
+
+        self.0
+            .trie_storage
+            .backup(&snapshot, destination)
+            .context("Backing up trie nodes")?;
+
+        {
+            let backup = Backup::new(&snapshot, &mut target).context("Initializing backup")?;
+            let pages_per_step = options.pages_per_step.get().try_into().unwrap_or(i32::MAX);
+
+            let mut wal = self.0.database_path.as_os_str().to_owned();
+            wal.push("-wal");
+            let mut wal_warned = false;
+
+            let mut last_logged = None;
+            loop {
+                match backup
+                    .step(pages_per_step)
+                    .context("Copying database pages")?
+                {
+                    StepResult::Done => break,
+                    StepResult::More => {
+                        let progress = backup.progress();
+                        let total = progress.pagecount.max(1);
+                        let percent = 100 * (total - progress.remaining) / total;
+                        if last_logged.map_or(true, |last| percent >= last + 10) {
+                            tracing::info!(%percent, "Backup in progress");
+                            last_logged = Some(percent);
+                        }
+                    }
+                    // The source is busy or locked, so retry after the pause.
+                    _ => {}
+                }
+
+                if !wal_warned {
+                    let wal_size = std::fs::metadata(&wal).map_or(0, |metadata| metadata.len());
+                    if wal_size >= WAL_WARNING_SIZE {
+                        tracing::warn!(
+                            %wal_size,
+                            "The WAL cannot be checkpointed and keeps growing until the backup completes"
+                        );
+                        wal_warned = true;
+                    }
+                }
+
+                std::thread::sleep(options.pause);
+            }
+        }
+
+        drop(snapshot);
+
+        let integrity: Vec<String> = target
+            .prepare("PRAGMA integrity_check")
+            .context("Preparing integrity check")?
+            .query_map([], |row| row.get(0))
+            .context("Running integrity check")?
+            .collect::<Result<_, _>>()
+            .context("Reading integrity check results")?;
+        anyhow::ensure!(
+            integrity == ["ok"],
+            "Backup at {} failed the integrity check: {}",
+            destination.display(),
+            integrity.join("; ")
+        );
+
+        let (first, last): (Option<u64>, Option<u64>) = target
+            .query_row(
+                "SELECT min(number), max(number) FROM block_headers",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .context("Querying backup block range")?;
+        let blocks = first.zip(last).map(|(first, last)| {
+            BlockNumber::new_or_panic(first)..=BlockNumber::new_or_panic(last)
+        });
+
+        target
+            .close()
+            .map_err(|(_connection, error)| error)
+            .context("Closing backup database")?;
+
+        let size = std::fs::metadata(destination)
+            .context("Reading backup file metadata")?
+            .len();
+
+        Ok(BackupReport { blocks, size })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::BlockHeader;
+
+    use super::*;
+    use crate::{BlockId, JournalMode};
+
+    const OPTIONS: BackupOptions = BackupOptions {
+        pages_per_step: NonZeroU32::MIN,
+        pause: Duration::ZERO,
+    };
+
+    #[test]
+    fn backup_is_consistent() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let storage = Storage::migrate(db_dir.path().join("source.sqlite"), JournalMode::WAL, 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(2).unwrap())
+            .unwrap();
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash!("0x1"));
+        let child = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash!("0x2"));
+
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_block_header(&genesis).unwrap();
+        tx.insert_block_header(&child).unwrap();
+        tx.commit().unwrap();
+
+        let destination = db_dir.path().join("backup.sqlite");
+        let report = storage.backup(&destination, OPTIONS).unwrap();
+
+        assert_eq!(report.blocks, Some(genesis.number..=child.number));
+        assert_eq!(report.size, std::fs::metadata(&destination).unwrap().len());
+
+        let backup = Storage::migrate(destination, JournalMode::WAL, 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+        let mut db = backup.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        assert_eq!(
+            tx.block_id(BlockId::Latest).unwrap(),
+            Some((child.number, child.hash))
+        );
+    }
+
+    #[test]
+    fn empty_database() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let storage = Storage::migrate(db_dir.path().join("source.sqlite"), JournalMode::WAL, 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let report = storage
+            .backup(&db_dir.path().join("backup.sqlite"), OPTIONS)
+            .unwrap();
+        assert_eq!(report.blocks, None);
+    }
+
+    #[test]
+    fn existing_destination_is_not_overwritten() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let storage = Storage::migrate(db_dir.path().join("source.sqlite"), JournalMode::WAL, 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let destination = db_dir.path().join("backup.sqlite");
+        std::fs::write(&destination, b"precious").unwrap();
+
+        storage.backup(&destination, OPTIONS).unwrap_err();
+        assert_eq!(std::fs::read(&destination).unwrap(), b"precious");
+    }
+
+    #[cfg(feature = "rocksdb")]
+    mod rocksdb {
+        use std::collections::HashMap;
+
+        use super::*;
+        use crate::{Node, TrieBackend};
+
+        #[test]
+        fn rocksdb_trie_nodes_are_copied() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let storage =
+                Storage::migrate(db_dir.path().join("source.sqlite"), JournalMode::WAL, 16)
+                    .unwrap()
+                    .with_trie_backend(TrieBackend::RocksDb)
+                    .unwrap()
+                    .create_pool(NonZeroU32::new(2).unwrap())
+                    .unwrap();
+
+            let root = class_commitment!("0x1");
+            let nodes = HashMap::from([(root.0, Node::LeafBinary)]);
+
+            let mut db = storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            let root_idx = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.insert_class_root(BlockNumber::GENESIS, Some(root_idx))
+                .unwrap();
+            tx.commit().unwrap();
+
+            let destination = db_dir.path().join("backup.sqlite");
+            storage.backup(&destination, OPTIONS).unwrap();
+            assert!(db_dir.path().join("backup.tries").is_dir());
+
+            let backup = Storage::migrate(destination, JournalMode::WAL, 16)
+                .unwrap()
+                .create_pool(NonZeroU32::new(1).unwrap())
+                .unwrap();
+            assert_eq!(backup.trie_backend(), TrieBackend::RocksDb);
+
+            let mut db = backup.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            assert_eq!(tx.class_trie_node_hash(root_idx).unwrap(), Some(root.0));
+        }
+
+        #[test]
+        fn failed_backup_is_removed() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let storage =
+                Storage::migrate(db_dir.path().join("source.sqlite"), JournalMode::WAL, 16)
+                    .unwrap()
+                    .with_trie_backend(TrieBackend::RocksDb)
+                    .unwrap()
+                    .create_pool(NonZeroU32::new(1).unwrap())
+                    .unwrap();
+
+            // The trie nodes can't be copied to a path which is taken by a file.
+            let tries = db_dir.path().join("backup.tries");
+            std::fs::write(&tries, b"precious").unwrap();
+
+            let destination = db_dir.path().join("backup.sqlite");
+            storage.backup(&destination, OPTIONS).unwrap_err();
+            assert!(!destination.exists());
+            assert_eq!(std::fs::read(&tries).unwrap(), b"precious");
+        }
+    }
+}
@@ -2,8 +2,10 @@ use std::collections::HashMap;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 
+mod backfill;
 mod block;
 mod class;
+pub(crate) mod compression;
 mod ethereum;
 mod event;
 mod reference;
@@ -12,6 +14,7 @@ mod signature;
 mod state_update;
 pub(crate) mod transaction;
 mod trie;
+pub(crate) mod trie_backend;
 
 use pathfinder_common::receipt::Receipt;
 // Re-export this so users don't require rusqlite as a direct dep.
@@ -21,15 +24,24 @@ pub use event::KEY_FILTER_LIMIT as EVENT_KEY_FILTER_LIMIT;
 pub use event::PAGE_SIZE_LIMIT as EVENT_PAGE_SIZE_LIMIT;
 pub use event::{EmittedEvent, EventFilter, EventFilterError, PageOfEvents};
 
+pub use ethereum::L1ToL2Message;
+
 pub(crate) use reorg_counter::ReorgCounter;
 
-pub use transaction::TransactionStatus;
+pub use transaction::{SenderTransaction, TransactionStatus};
 
 pub use trie::{Child, Node, StoredNode};
 
+#[cfg(feature = "rocksdb")]
+pub use trie_backend::RocksDbTrieStorage;
+pub use trie_backend::{SqliteTrieStorage, TrieBackend, TrieKind, TrieStorage};
+
 use pathfinder_common::*;
 use pathfinder_crypto::Felt;
-use pathfinder_ethereum::EthereumStateUpdate;
+use pathfinder_ethereum::{
+    EthereumStateUpdate, L1ToL2MessageLog, L2ToL1MessageLog, StateUpdateLog,
+};
+use primitive_types::H256;
 
 use pathfinder_common::transaction::Transaction as StarknetTransaction;
 
@@ -40,16 +52,22 @@ type PooledConnection = r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionMana
 pub struct Connection {
     connection: PooledConnection,
     bloom_filter_cache: Arc<crate::bloom::Cache>,
+    compression_dictionaries: Arc<compression::DictionaryCache>,
+    trie_storage: Arc<dyn TrieStorage>,
 }
 
 impl Connection {
     pub(crate) fn new(
         connection: PooledConnection,
         bloom_filter_cache: Arc<crate::bloom::Cache>,
+        compression_dictionaries: Arc<compression::DictionaryCache>,
+        trie_storage: Arc<dyn TrieStorage>,
     ) -> Self {
         Self {
             connection,
             bloom_filter_cache,
+            compression_dictionaries,
+            trie_storage,
         }
     }
 
@@ -58,6 +76,9 @@ impl Connection {
         Ok(Transaction {
             transaction: tx,
             bloom_filter_cache: self.bloom_filter_cache.clone(),
+            compression_dictionaries: self.compression_dictionaries.clone(),
+            trie_storage: self.trie_storage.clone(),
+            trie_writes: Default::default(),
         })
     }
 
@@ -69,6 +90,9 @@ impl Connection {
         Ok(Transaction {
             transaction: tx,
             bloom_filter_cache: self.bloom_filter_cache.clone(),
+            compression_dictionaries: self.compression_dictionaries.clone(),
+            trie_storage: self.trie_storage.clone(),
+            trie_writes: Default::default(),
         })
     }
 }
@@ -76,6 +100,9 @@ impl Connection {
 pub struct Transaction<'inner> {
     transaction: rusqlite::Transaction<'inner>,
     bloom_filter_cache: Arc<crate::bloom::Cache>,
+    compression_dictionaries: Arc<compression::DictionaryCache>,
+    trie_storage: Arc<dyn TrieStorage>,
+    trie_writes: trie_backend::PendingTrieWrites,
 }
 
 impl<'inner> Transaction<'inner> {
@@ -87,6 +114,9 @@ impl<'inner> Transaction<'inner> {
         Self {
             transaction: tx,
             bloom_filter_cache: Arc::new(crate::bloom::Cache::with_size(1)),
+            compression_dictionaries: Default::default(),
+            trie_storage: Arc::new(SqliteTrieStorage),
+            trie_writes: Default::default(),
         }
     }
 
@@ -115,6 +145,17 @@ impl<'inner> Transaction<'inner> {
         block::block_header(self, block)
     }
 
+    /// Returns the gas prices of up to `count` blocks ending at `newest`, in ascending block order.
+    ///
+    /// The STRK gas price is [None] for blocks which were stored without one.
+    pub fn gas_prices(
+        &self,
+        newest: BlockNumber,
+        count: usize,
+    ) -> anyhow::Result<Vec<(BlockNumber, GasPrice, Option<GasPrice>)>> {
+        block::gas_prices(self, newest, count)
+    }
+
     /// Returns the closest ancestor header that is in storage.
     ///
     /// i.e. returns the latest header with number < target.
@@ -181,6 +222,46 @@ impl<'inner> Transaction<'inner> {
         ethereum::latest_l1_state(self)
     }
 
+    pub fn insert_state_update_logs(&self, logs: &[StateUpdateLog]) -> anyhow::Result<()> {
+        ethereum::insert_state_update_logs(self, logs)
+    }
+
+    /// Returns the L1 state update which included the given block, i.e. the first state update
+    /// log at or after it.
+    pub fn state_update_log_including(
+        &self,
+        block: BlockNumber,
+    ) -> anyhow::Result<Option<StateUpdateLog>> {
+        ethereum::state_update_log_including(self, block)
+    }
+
+    pub fn latest_state_update_log(&self) -> anyhow::Result<Option<StateUpdateLog>> {
+        ethereum::latest_state_update_log(self)
+    }
+
+    pub fn insert_message_logs(&self, logs: &[L1ToL2MessageLog]) -> anyhow::Result<()> {
+        ethereum::insert_message_logs(self, logs)
+    }
+
+    /// Returns the L1 to L2 messages sent by the given Ethereum transaction, in the order they
+    /// were sent.
+    pub fn l1_to_l2_messages(
+        &self,
+        l1_transaction_hash: H256,
+    ) -> anyhow::Result<Vec<L1ToL2Message>> {
+        ethereum::l1_to_l2_messages(self, l1_transaction_hash)
+    }
+
+    pub fn insert_consumed_message_logs(&self, logs: &[L2ToL1MessageLog]) -> anyhow::Result<()> {
+        ethereum::insert_consumed_message_logs(self, logs)
+    }
+
+    /// Returns the Ethereum transactions which consumed an L2 to L1 message with the given hash,
+    /// in the order they were consumed. Identical messages have the same hash.
+    pub fn l2_to_l1_message_consumptions(&self, message_hash: H256) -> anyhow::Result<Vec<H256>> {
+        ethereum::l2_to_l1_message_consumptions(self, message_hash)
+    }
+
     /// Inserts the transaction, receipt and event data.
     pub fn insert_transaction_data(
         &self,
@@ -191,6 +272,30 @@ impl<'inner> Transaction<'inner> {
         transaction::insert_transactions(self, block_hash, block_number, transaction_data)
     }
 
+    /// Trains the transaction and receipt compression dictionaries on the `sample_size` most recent
+    /// transactions.
+    ///
+    /// Returns `false` if the dictionaries already exist or there is not yet enough data to train on.
+    pub fn train_compression_dictionaries(&self, sample_size: usize) -> anyhow::Result<bool> {
+        transaction::train_compression_dictionaries(self, sample_size)
+    }
+
+    /// Re-encodes up to `limit` transactions using the current storage format, resuming from the
+    /// stored progress so that completed work is not repeated after a restart.
+    ///
+    /// Returns `false` once all transactions have been processed.
+    pub fn reencode_transactions(&self, limit: usize) -> anyhow::Result<bool> {
+        transaction::reencode_transactions(self, limit)
+    }
+
+    /// Links the messages consumed by up to `limit` L1 handler transactions which were stored
+    /// before messages were linked on insert.
+    ///
+    /// Returns `false` once all such transactions have been processed.
+    pub fn backfill_l1_handler_messages(&self, limit: usize) -> anyhow::Result<bool> {
+        transaction::backfill_l1_handler_messages(self, limit)
+    }
+
     pub fn transaction_block_hash(
         &self,
         hash: TransactionHash,
@@ -198,6 +303,42 @@ impl<'inner> Transaction<'inner> {
         transaction::transaction_block_hash(self, hash)
     }
 
+    /// Whether the transaction sender index is maintained, see
+    /// [StorageManager::with_transaction_sender_index](crate::StorageManager::with_transaction_sender_index).
+    pub fn transaction_sender_index_enabled(&self) -> anyhow::Result<bool> {
+        transaction::transaction_sender_index_enabled(self)
+    }
+
+    /// Whether the transaction sender index is enabled and includes all stored transactions. It
+    /// is incomplete until [Transaction::backfill_transaction_senders] has indexed the
+    /// transactions stored before it was enabled.
+    pub fn transaction_sender_index_complete(&self) -> anyhow::Result<bool> {
+        transaction::transaction_sender_index_complete(self)
+    }
+
+    /// Indexes up to `limit` transactions by sender which were stored before the transaction
+    /// sender index was enabled.
+    ///
+    /// Returns `false` once all such transactions have been processed.
+    pub fn backfill_transaction_senders(&self, limit: usize) -> anyhow::Result<bool> {
+        transaction::backfill_transaction_senders(self, limit)
+    }
+
+    /// Returns up to `limit` transactions sent by `sender` with a nonce of at least `from_nonce`,
+    /// ordered by nonce. Requires the transaction sender index to be enabled.
+    pub fn transactions_by_sender(
+        &self,
+        sender: ContractAddress,
+        from_nonce: TransactionNonce,
+        limit: usize,
+    ) -> anyhow::Result<Vec<SenderTransaction>> {
+        transaction::transactions_by_sender(self, sender, from_nonce, limit)
+    }
+
+    pub(crate) fn set_transaction_sender_index(&self, enabled: bool) -> anyhow::Result<()> {
+        transaction::set_transaction_sender_index(self, enabled)
+    }
+
     pub fn transaction(
         &self,
         hash: TransactionHash,
@@ -288,6 +429,23 @@ impl<'inner> Transaction<'inner> {
         class::insert_cairo_class(self, cairo_hash, definition)
     }
 
+    /// Trains the compression dictionaries of each class definition kind on its `sample_size` most
+    /// recent definitions.
+    ///
+    /// Returns the number of dictionaries trained, skipping those which exist already or lack data.
+    pub fn train_class_dictionaries(&self, sample_size: usize) -> anyhow::Result<usize> {
+        class::train_class_dictionaries(self, sample_size)
+    }
+
+    /// Re-compresses the definitions of up to `limit` classes using the latest compression
+    /// dictionaries, resuming from the stored progress so that completed work is not repeated after
+    /// a restart.
+    ///
+    /// Returns `false` once all classes have been processed.
+    pub fn recompress_class_definitions(&self, limit: usize) -> anyhow::Result<bool> {
+        class::recompress_class_definitions(self, limit)
+    }
+
     pub fn insert_class_commitment_leaf(
         &self,
         block: BlockNumber,
@@ -326,6 +484,9 @@ impl<'inner> Transaction<'inner> {
     }
 
     /// Returns the compressed class definition if it has been declared at `block_id`.
+    ///
+    /// The definition is a standalone zstd frame which does not require the database's
+    /// compression dictionaries to decompress.
     pub fn compressed_class_definition_at(
         &self,
         block_id: BlockId,
@@ -404,6 +565,14 @@ impl<'inner> Transaction<'inner> {
         class::casm_hash(self, class_hash)
     }
 
+    /// Returns the number of the block in which the class was declared.
+    pub fn class_declaration_block(
+        &self,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<BlockNumber>> {
+        class::class_declaration_block(self, class_hash)
+    }
+
     /// Returns the compiled class hash for a class if it has been declared at `block_id`.
     pub fn casm_hash_at(
         &self,
@@ -419,7 +588,8 @@ impl<'inner> Transaction<'inner> {
         root: ClassCommitment,
         nodes: &HashMap<Felt, Node>,
     ) -> anyhow::Result<u64> {
-        trie::trie_class::insert(self, root.0, nodes)
+        self.trie_storage
+            .insert(self, TrieKind::Class, root.0, nodes)
     }
 
     /// Stores a single contract's storage trie information.
@@ -428,7 +598,8 @@ impl<'inner> Transaction<'inner> {
         root: ContractRoot,
         nodes: &HashMap<Felt, Node>,
     ) -> anyhow::Result<u64> {
-        trie::trie_contracts::insert(self, root.0, nodes)
+        self.trie_storage
+            .insert(self, TrieKind::Contract, root.0, nodes)
     }
 
     /// Stores the global starknet storage trie information.
@@ -437,31 +608,46 @@ impl<'inner> Transaction<'inner> {
         root: StorageCommitment,
         nodes: &HashMap<Felt, Node>,
     ) -> anyhow::Result<u64> {
-        trie::trie_storage::insert(self, root.0, nodes)
+        self.trie_storage
+            .insert(self, TrieKind::Storage, root.0, nodes)
     }
 
     pub fn class_trie_node(&self, index: u64) -> anyhow::Result<Option<StoredNode>> {
-        trie::trie_class::node(self, index)
+        self.trie_storage.node(self, TrieKind::Class, index)
     }
 
     pub fn storage_trie_node(&self, index: u64) -> anyhow::Result<Option<StoredNode>> {
-        trie::trie_storage::node(self, index)
+        self.trie_storage.node(self, TrieKind::Storage, index)
     }
 
     pub fn contract_trie_node(&self, index: u64) -> anyhow::Result<Option<StoredNode>> {
-        trie::trie_contracts::node(self, index)
+        self.trie_storage.node(self, TrieKind::Contract, index)
     }
 
     pub fn class_trie_node_hash(&self, index: u64) -> anyhow::Result<Option<Felt>> {
-        trie::trie_class::hash(self, index)
+        self.trie_storage.hash(self, TrieKind::Class, index)
     }
 
     pub fn storage_trie_node_hash(&self, index: u64) -> anyhow::Result<Option<Felt>> {
-        trie::trie_storage::hash(self, index)
+        self.trie_storage.hash(self, TrieKind::Storage, index)
     }
 
     pub fn contract_trie_node_hash(&self, index: u64) -> anyhow::Result<Option<Felt>> {
-        trie::trie_contracts::hash(self, index)
+        self.trie_storage.hash(self, TrieKind::Contract, index)
+    }
+
+    /// Allows the trie storage to remove nodes which are no longer referenced by any root, for
+    /// example after blocks were purged.
+    pub fn truncate_tries(&self) -> anyhow::Result<()> {
+        for (kind, last) in trie_backend::last_root_indices(self.inner())? {
+            self.trie_storage.truncate(self, kind, last)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn trie_writes(&self) -> &trie_backend::PendingTrieWrites {
+        &self.trie_writes
     }
 
     pub fn class_root_index(&self, block: BlockNumber) -> anyhow::Result<Option<u64>> {
@@ -534,6 +720,35 @@ impl<'inner> Transaction<'inner> {
         state_update::storage_value(self, block, contract_address, key)
     }
 
+    /// Returns the blocks within `blocks` at which the storage `key` was changed, along with the
+    /// new value. At most `limit` changes are returned, ordered by block number.
+    pub fn storage_history(
+        &self,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+        blocks: std::ops::RangeInclusive<BlockNumber>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(BlockNumber, StorageValue)>> {
+        state_update::storage_history(self, contract_address, key, blocks, limit)
+    }
+
+    /// Returns the class of the contract after each of its class updates, starting with its
+    /// deployment.
+    pub fn contract_class_history(
+        &self,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<Vec<(BlockNumber, ClassHash)>> {
+        state_update::contract_class_history(self, contract_address)
+    }
+
+    /// Returns the number of blocks in which the contract's nonce was updated.
+    pub fn contract_nonce_update_count(
+        &self,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<u64> {
+        state_update::contract_nonce_update_count(self, contract_address)
+    }
+
     pub fn contract_nonce(
         &self,
         contract_address: ContractAddress,
@@ -562,6 +777,19 @@ impl<'inner> Transaction<'inner> {
         signature::signature(self, block)
     }
 
+    pub fn update_signature_status(
+        &self,
+        block_number: BlockNumber,
+        status: SignatureStatus,
+    ) -> anyhow::Result<()> {
+        signature::update_signature_status(self, block_number, status)
+    }
+
+    /// Returns the verification status of a block's signature, or `None` if it has no signature.
+    pub fn signature_status(&self, block: BlockId) -> anyhow::Result<Option<SignatureStatus>> {
+        signature::signature_status(self, block)
+    }
+
     pub fn increment_reorg_counter(&self) -> anyhow::Result<()> {
         reorg_counter::increment_reorg_counter(self)
     }
@@ -575,6 +803,8 @@ impl<'inner> Transaction<'inner> {
     }
 
     pub fn commit(self) -> anyhow::Result<()> {
-        Ok(self.transaction.commit()?)
+        self.trie_storage.before_commit(&self)?;
+        self.transaction.commit()?;
+        self.trie_storage.after_commit(self.trie_writes)
     }
 }
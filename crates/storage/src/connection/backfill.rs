@@ -0,0 +1,77 @@
+//! Progress of resumable background passes over stored rows.
+
+use anyhow::Context;
+
+use crate::prelude::*;
+
+/// A pass over the rows of `source` which were stored when it was scheduled.
+///
+/// Its progress is kept as a single `(next_rowid, end_rowid)` row in `table`, which is updated
+/// with each batch so that a restart resumes where it left off, and deleted once the pass is
+/// complete.
+pub(super) struct Backfill {
+    table: &'static str,
+    source: &'static str,
+}
+
+impl Backfill {
+    pub(super) const fn new(table: &'static str, source: &'static str) -> Self {
+        Self { table, source }
+    }
+
+    /// Returns the exclusive start and inclusive end of the rowids which remain to be processed,
+    /// or `None` if the pass is not scheduled.
+    pub(super) fn remaining(&self, tx: &Transaction<'_>) -> anyhow::Result<Option<(i64, i64)>> {
+        tx.inner()
+            .query_row(
+                &format!("SELECT next_rowid, end_rowid FROM {}", self.table),
+                [],
+                |row| Ok((row.get_i64(0)?, row.get_i64(1)?)),
+            )
+            .optional()
+            .with_context(|| format!("Querying {} progress", self.table))
+    }
+
+    /// Records that all rows up to and including `last` were processed, or completes the pass if
+    /// the batch was empty.
+    ///
+    /// Returns `false` once the pass is complete.
+    pub(super) fn advance(&self, tx: &Transaction<'_>, last: Option<i64>) -> anyhow::Result<bool> {
+        match last {
+            Some(rowid) => {
+                tx.inner()
+                    .execute(
+                        &format!("UPDATE {} SET next_rowid = ?", self.table),
+                        params![&rowid],
+                    )
+                    .with_context(|| format!("Updating {} progress", self.table))?;
+                Ok(true)
+            }
+            None => {
+                self.cancel(tx)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Schedules all rows which are currently stored, replacing any earlier progress.
+    pub(super) fn restart(&self, tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.inner()
+            .execute_batch(&format!(
+                r"DELETE FROM {table};
+                INSERT INTO {table} (next_rowid, end_rowid)
+                    SELECT 0, MAX(rowid) FROM {source} HAVING MAX(rowid) IS NOT NULL",
+                table = self.table,
+                source = self.source,
+            ))
+            .with_context(|| format!("Restarting {}", self.table))
+    }
+
+    /// Removes the pass, whether or not it is complete.
+    pub(super) fn cancel(&self, tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.inner()
+            .execute(&format!("DELETE FROM {}", self.table), [])
+            .with_context(|| format!("Removing {} progress", self.table))?;
+        Ok(())
+    }
+}
@@ -136,6 +136,20 @@ pub(super) fn purge_block(tx: &Transaction<'_>, block: BlockNumber) -> anyhow::R
         )
         .context("Deleting transactions")?;
 
+    tx.inner()
+        .execute(
+            "DELETE FROM transaction_senders WHERE block_number = ?",
+            params![&block],
+        )
+        .context("Deleting block from transaction_senders table")?;
+
+    tx.inner()
+        .execute(
+            "DELETE FROM l1_handler_messages WHERE block_number = ?",
+            params![&block],
+        )
+        .context("Deleting block from l1_handler_messages table")?;
+
     tx.inner()
         .execute(
             "DELETE FROM canonical_blocks WHERE number = ?",
@@ -363,6 +377,36 @@ pub(super) fn block_header(
     Ok(Some(header))
 }
 
+pub(super) fn gas_prices(
+    tx: &Transaction<'_>,
+    newest: BlockNumber,
+    count: usize,
+) -> anyhow::Result<Vec<(BlockNumber, GasPrice, Option<GasPrice>)>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            "SELECT number, eth_l1_gas_price, strk_l1_gas_price FROM block_headers
+                WHERE number <= ?
+                ORDER BY number DESC LIMIT ?",
+        )
+        .context("Preparing statement")?;
+
+    let mut prices = stmt
+        .query_map(params![&newest, &count.try_into_sql_int()?], |row| {
+            let number = row.get_block_number(0)?;
+            let eth = row.get_gas_price(1)?;
+            let strk = row.get_optional_gas_price(2)?;
+            Ok((number, eth, strk))
+        })
+        .context("Querying gas prices")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over rows")?;
+
+    prices.reverse();
+
+    Ok(prices)
+}
+
 pub(super) fn block_is_l1_accepted(tx: &Transaction<'_>, block: BlockId) -> anyhow::Result<bool> {
     let Some(l1_l2) = tx.l1_l2_pointer().context("Querying L1-L2 pointer")? else {
         return Ok(false);
@@ -459,6 +503,26 @@ mod tests {
         assert_eq!(&result, expected);
     }
 
+    #[test]
+    fn gas_prices() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+
+        let expected = headers[1..]
+            .iter()
+            .map(|h| (h.number, h.eth_l1_gas_price, Some(h.strk_l1_gas_price)))
+            .collect::<Vec<_>>();
+        let result = tx.gas_prices(headers[2].number, 2).unwrap();
+        assert_eq!(result, expected);
+
+        let expected = headers[..2]
+            .iter()
+            .map(|h| (h.number, h.eth_l1_gas_price, Some(h.strk_l1_gas_price)))
+            .collect::<Vec<_>>();
+        let result = tx.gas_prices(headers[1].number, 10).unwrap();
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn get_by_number() {
         let (mut connection, headers) = setup();
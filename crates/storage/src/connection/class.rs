@@ -1,8 +1,13 @@
 use anyhow::Context;
 use pathfinder_common::{BlockNumber, CasmHash, ClassCommitmentLeafHash, ClassHash, SierraHash};
 
+use super::backfill::Backfill;
+use super::compression::{self, DictionaryKind};
 use crate::{prelude::*, BlockId};
 
+/// Re-compresses the stored class definitions, see [recompress_class_definitions].
+const RECOMPRESSION: Backfill = Backfill::new("class_recompression", "class_definitions");
+
 pub(super) fn insert_sierra_class(
     transaction: &Transaction<'_>,
     sierra_hash: &SierraHash,
@@ -10,11 +15,17 @@ pub(super) fn insert_sierra_class(
     casm_hash: &CasmHash,
     casm_definition: &[u8],
 ) -> anyhow::Result<()> {
-    let mut compressor = zstd::bulk::Compressor::new(10).context("Creating zstd compressor")?;
-    let sierra_definition = compressor
+    let sierra_dictionary = transaction
+        .compression_dictionaries
+        .latest(transaction, DictionaryKind::SierraDefinition)?;
+    let sierra_definition = compression::Compressor::new(sierra_dictionary.as_deref())?
         .compress(sierra_definition)
         .context("Compressing sierra definition")?;
-    let casm_definition = compressor
+
+    let casm_dictionary = transaction
+        .compression_dictionaries
+        .latest(transaction, DictionaryKind::CasmDefinition)?;
+    let casm_definition = compression::Compressor::new(casm_dictionary.as_deref())?
         .compress(casm_definition)
         .context("Compressing casm definition")?;
 
@@ -49,8 +60,10 @@ pub(super) fn insert_cairo_class(
     cairo_hash: ClassHash,
     definition: &[u8],
 ) -> anyhow::Result<()> {
-    let mut compressor = zstd::bulk::Compressor::new(10).context("Creating zstd compressor")?;
-    let definition = compressor
+    let dictionary = transaction
+        .compression_dictionaries
+        .latest(transaction, DictionaryKind::CairoDefinition)?;
+    let definition = compression::Compressor::new(dictionary.as_deref())?
         .compress(definition)
         .context("Compressing cairo definition")?;
 
@@ -110,12 +123,32 @@ pub(super) fn class_definition_with_block_number(
     let Some((block_number, definition)) = result else {
         return Ok(None);
     };
-    let definition =
-        zstd::decode_all(definition.as_slice()).context("Decompressing class definition")?;
+    let definition = compression::decompress(transaction, &definition)
+        .context("Decompressing class definition")?;
 
     Ok(Some((block_number, definition)))
 }
 
+/// Returns the number of the block in which the class was declared, or [None] if it has not
+/// been declared.
+pub(super) fn class_declaration_block(
+    transaction: &Transaction<'_>,
+    class_hash: ClassHash,
+) -> anyhow::Result<Option<BlockNumber>> {
+    let mut stmt = transaction
+        .inner()
+        .prepare_cached("SELECT block_number FROM class_definitions WHERE hash = ?")?;
+
+    let block_number = stmt
+        .query_row(params![&class_hash], |row| row.get_optional_block_number(0))
+        .optional()
+        .context("Querying class declaration block")?;
+
+    Ok(block_number.flatten())
+}
+
+/// Returns the class definition as a standalone zstd frame, which can be decompressed without
+/// access to the database's compression dictionaries.
 pub(super) fn compressed_class_definition_at(
     tx: &Transaction<'_>,
     block_id: BlockId,
@@ -129,6 +162,19 @@ pub(super) fn compressed_class_definition_at_with_block_number(
     tx: &Transaction<'_>,
     block_id: BlockId,
     class_hash: ClassHash,
+) -> anyhow::Result<Option<(BlockNumber, Vec<u8>)>> {
+    stored_class_definition_at_with_block_number(tx, block_id, class_hash)?
+        .map(|(block_number, definition)| {
+            compression::standalone(tx, definition).map(|definition| (block_number, definition))
+        })
+        .transpose()
+}
+
+/// Returns the class definition as stored, i.e. possibly compressed using a dictionary.
+fn stored_class_definition_at_with_block_number(
+    tx: &Transaction<'_>,
+    block_id: BlockId,
+    class_hash: ClassHash,
 ) -> anyhow::Result<Option<(BlockNumber, Vec<u8>)>> {
     let from_row = |row: &rusqlite::Row<'_>| {
         let definition = row.get_blob(0).map(|x| x.to_vec())?;
@@ -184,12 +230,12 @@ pub(super) fn class_definition_at_with_block_number(
     block_id: BlockId,
     class_hash: ClassHash,
 ) -> anyhow::Result<Option<(BlockNumber, Vec<u8>)>> {
-    let definition = compressed_class_definition_at_with_block_number(tx, block_id, class_hash)?;
+    let definition = stored_class_definition_at_with_block_number(tx, block_id, class_hash)?;
     let Some((block_number, definition)) = definition else {
         return Ok(None);
     };
     let definition =
-        zstd::decode_all(definition.as_slice()).context("Decompressing class definition")?;
+        compression::decompress(tx, &definition).context("Decompressing class definition")?;
 
     Ok(Some((block_number, definition)))
 }
@@ -212,7 +258,7 @@ pub(super) fn casm_definition(
     let Some(definition) = definition else {
         return Ok(None);
     };
-    let definition = zstd::decode_all(definition.as_slice())
+    let definition = compression::decompress(transaction, &definition)
         .context("Decompressing compiled class definition")?;
 
     Ok(Some(definition))
@@ -251,7 +297,7 @@ pub(super) fn casm_definition_with_block_number(
     let Some((block_number, definition)) = result else {
         return Ok(None);
     };
-    let definition = zstd::decode_all(definition.as_slice())
+    let definition = compression::decompress(transaction, &definition)
         .context("Decompressing compiled class definition")?;
 
     Ok(Some((block_number, definition)))
@@ -330,7 +376,7 @@ pub(super) fn casm_definition_at_with_block_number(
     let Some((block_number, definition)) = definition else {
         return Ok(None);
     };
-    let definition = zstd::decode_all(definition.as_slice())
+    let definition = compression::decompress(tx, &definition)
         .context("Decompressing compiled class definition")?;
 
     Ok(Some((block_number, definition)))
@@ -446,6 +492,200 @@ pub(super) fn class_commitment_leaf(
         .map_err(Into::into)
 }
 
+/// Trains the class definition compression dictionaries on the `sample_size` most recent definitions
+/// of each kind.
+///
+/// Kinds which already have a dictionary, or which do not have enough data yet, are skipped. Returns
+/// the number of dictionaries trained.
+pub(super) fn train_class_dictionaries(
+    tx: &Transaction<'_>,
+    sample_size: usize,
+) -> anyhow::Result<usize> {
+    let queries = [
+        (
+            DictionaryKind::CairoDefinition,
+            r"SELECT definition FROM class_definitions
+            WHERE definition IS NOT NULL AND hash NOT IN (SELECT hash FROM casm_definitions)
+            ORDER BY rowid DESC LIMIT ?",
+        ),
+        (
+            DictionaryKind::SierraDefinition,
+            r"SELECT class_definitions.definition FROM class_definitions
+            JOIN casm_definitions ON (casm_definitions.hash = class_definitions.hash)
+            WHERE class_definitions.definition IS NOT NULL
+            ORDER BY class_definitions.rowid DESC LIMIT ?",
+        ),
+        (
+            DictionaryKind::CasmDefinition,
+            "SELECT definition FROM casm_definitions ORDER BY rowid DESC LIMIT ?",
+        ),
+    ];
+
+    let mut trained = 0;
+    for (kind, query) in queries {
+        if train_class_dictionary(tx, kind, query, sample_size)
+            .with_context(|| format!("Training {kind:?} dictionary"))?
+        {
+            trained += 1;
+        }
+    }
+
+    if trained > 0 {
+        restart_recompression(tx)?;
+    }
+
+    Ok(trained)
+}
+
+/// Schedules all currently stored class definitions to be re-compressed by
+/// [recompress_class_definitions].
+pub(super) fn restart_recompression(tx: &Transaction<'_>) -> anyhow::Result<()> {
+    RECOMPRESSION.restart(tx)
+}
+
+fn train_class_dictionary(
+    tx: &Transaction<'_>,
+    kind: DictionaryKind,
+    query: &str,
+    sample_size: usize,
+) -> anyhow::Result<bool> {
+    /// Training on fewer samples produces dictionaries that hurt more than they help.
+    const MIN_SAMPLES: usize = 50;
+    /// Class definitions are large, so a larger than default dictionary pays off.
+    const DICTIONARY_SIZE: usize = 256 * 1024;
+
+    if compression::dictionary_exists(tx, kind)? {
+        return Ok(false);
+    }
+
+    let mut stmt = tx.inner().prepare(query).context("Preparing statement")?;
+    let mut rows = stmt
+        .query(params![&sample_size.try_into_sql_int()?])
+        .context("Executing query")?;
+
+    let mut samples = Vec::new();
+    while let Some(row) = rows.next()? {
+        let definition = row.get_blob(0)?;
+        samples.push(compression::decompress(tx, definition).context("Decompressing definition")?);
+    }
+
+    if samples.len() < MIN_SAMPLES {
+        return Ok(false);
+    }
+
+    let dictionary =
+        zstd::dict::from_samples(&samples, DICTIONARY_SIZE).context("Training dictionary")?;
+    compression::insert_dictionary(tx, kind, &dictionary)?;
+
+    Ok(true)
+}
+
+/// Re-compresses the definitions of up to `limit` classes which are not compressed using the latest
+/// dictionary of their kind, continuing from the progress stored by the previous call. The CASM
+/// definitions of Sierra classes are included.
+///
+/// Returns `false` once all scheduled classes have been processed.
+pub(super) fn recompress_class_definitions(
+    tx: &Transaction<'_>,
+    limit: usize,
+) -> anyhow::Result<bool> {
+    let Some((next_rowid, end_rowid)) = RECOMPRESSION.remaining(tx)? else {
+        return Ok(false);
+    };
+
+    let cairo = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::CairoDefinition)?;
+    let sierra = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::SierraDefinition)?;
+    let casm = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::CasmDefinition)?;
+    let id = |dictionary: &Option<std::sync::Arc<compression::Dictionary>>| {
+        Some(dictionary.as_ref().map_or(0, |x| x.id()))
+    };
+    let (cairo_id, sierra_id, casm_id) = (id(&cairo), id(&sierra), id(&casm));
+    let mut cairo_compressor = compression::Compressor::new(cairo.as_deref())?;
+    let mut sierra_compressor = compression::Compressor::new(sierra.as_deref())?;
+    let mut casm_compressor = compression::Compressor::new(casm.as_deref())?;
+
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT class_definitions.rowid, class_definitions.hash, class_definitions.definition,
+                casm_definitions.definition AS casm_definition
+            FROM class_definitions
+            LEFT JOIN casm_definitions ON (casm_definitions.hash = class_definitions.hash)
+            WHERE class_definitions.rowid > ? AND class_definitions.rowid <= ?
+            ORDER BY class_definitions.rowid ASC LIMIT ?",
+        )
+        .context("Preparing select statement")?;
+    let mut rows = stmt
+        .query(params![&next_rowid, &end_rowid, &limit.try_into_sql_int()?])
+        .context("Executing query")?;
+
+    let mut last = None;
+    // Updates are applied once the query is complete as SQLite does not define the behaviour
+    // of modifying a table while it is being read by the same connection.
+    let mut definitions = Vec::new();
+    let mut casm_definitions = Vec::new();
+    while let Some(row) = rows.next()? {
+        last = Some(row.get_i64(0)?);
+        let hash = row.get_class_hash(1)?;
+        let casm_definition = row.get_optional_blob(3)?;
+
+        if let Some(definition) = row.get_optional_blob(2)? {
+            let (compressor, latest) = match casm_definition {
+                Some(_) => (&mut sierra_compressor, sierra_id),
+                None => (&mut cairo_compressor, cairo_id),
+            };
+
+            if compression::dictionary_id(definition) != latest {
+                let definition = compression::decompress(tx, definition)
+                    .context("Decompressing class definition")?;
+                let definition = compressor
+                    .compress(&definition)
+                    .context("Compressing class definition")?;
+                definitions.push((hash, definition));
+            }
+        }
+
+        if let Some(definition) = casm_definition {
+            if compression::dictionary_id(definition) != casm_id {
+                let definition = compression::decompress(tx, definition)
+                    .context("Decompressing compiled class definition")?;
+                let definition = casm_compressor
+                    .compress(&definition)
+                    .context("Compressing compiled class definition")?;
+                casm_definitions.push((hash, definition));
+            }
+        }
+    }
+
+    let mut update = tx
+        .inner()
+        .prepare("UPDATE class_definitions SET definition = ? WHERE hash = ?")
+        .context("Preparing class update statement")?;
+    for (hash, definition) in definitions {
+        update
+            .execute(params![&definition, &hash])
+            .context("Updating class definition")?;
+    }
+
+    let mut update = tx
+        .inner()
+        .prepare("UPDATE casm_definitions SET definition = ? WHERE hash = ?")
+        .context("Preparing casm update statement")?;
+    for (hash, definition) in casm_definitions {
+        update
+            .execute(params![&definition, &hash])
+            .context("Updating compiled class definition")?;
+    }
+
+    RECOMPRESSION.advance(tx, last)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -496,6 +736,33 @@ mod tests {
         assert_eq!(definition, cairo_definition);
     }
 
+    #[test]
+    fn class_declaration_block() {
+        let mut connection = Storage::in_memory().unwrap().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let (hash, _, _) = setup_class(&tx);
+        assert_eq!(super::class_declaration_block(&tx, hash).unwrap(), None);
+
+        let header =
+            pathfinder_common::BlockHeader::builder().finalize_with_hash(block_hash!("0x1"));
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_state_update(
+            header.number,
+            &pathfinder_common::StateUpdate::default().with_declared_cairo_class(hash),
+        )
+        .unwrap();
+
+        assert_eq!(
+            super::class_declaration_block(&tx, hash).unwrap(),
+            Some(header.number)
+        );
+        assert_eq!(
+            super::class_declaration_block(&tx, class_hash!("0x456")).unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn insert_sierra() {
         let mut connection = Storage::in_memory().unwrap().connection().unwrap();
@@ -526,7 +793,7 @@ mod tests {
                     let casm_hash = CasmHash(Felt::from_be_slice(casm_hash).unwrap());
 
                     let casm_definition = row.get_blob("definition").unwrap().to_vec();
-                    let casm_definition = zstd::decode_all(casm_definition.as_slice()).unwrap();
+                    let casm_definition = compression::decompress(&tx, &casm_definition).unwrap();
 
                     Ok((casm_hash, casm_definition))
                 },
@@ -542,6 +809,111 @@ mod tests {
         assert_eq!(definition, sierra_definition);
     }
 
+    fn stored_definition(tx: &Transaction<'_>, hash: ClassHash) -> Vec<u8> {
+        tx.inner()
+            .query_row(
+                "SELECT definition FROM class_definitions WHERE hash = ?",
+                params![&hash],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn dictionary_compression() {
+        let mut connection = Storage::in_memory().unwrap().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        // Any data is a valid raw content dictionary.
+        let dictionary =
+            compression::insert_dictionary(&tx, DictionaryKind::CairoDefinition, b"cairo program")
+                .unwrap();
+
+        let cairo_hash = class_hash_bytes!(b"cairo hash");
+        let cairo_definition = b"example cairo program";
+        insert_cairo_class(&tx, cairo_hash, cairo_definition).unwrap();
+
+        let stored = stored_definition(&tx, cairo_hash);
+        assert_eq!(compression::dictionary_id(&stored), Some(dictionary));
+
+        let definition = class_definition(&tx, cairo_hash).unwrap().unwrap();
+        assert_eq!(definition, cairo_definition);
+
+        let standalone = compression::standalone(&tx, stored).unwrap();
+        assert_eq!(
+            zstd::decode_all(standalone.as_slice()).unwrap(),
+            cairo_definition
+        );
+    }
+
+    #[test]
+    fn recompress_legacy_definitions() {
+        let mut connection = Storage::in_memory().unwrap().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let cairo_hash = class_hash_bytes!(b"cairo hash");
+        let cairo_definition = b"example cairo program";
+        let legacy = zstd::encode_all(cairo_definition.as_slice(), 10).unwrap();
+        tx.inner()
+            .execute(
+                "INSERT INTO class_definitions (hash, definition) VALUES (?, ?)",
+                params![&cairo_hash, &legacy],
+            )
+            .unwrap();
+
+        let sierra_hash = sierra_hash_bytes!(b"sierra hash");
+        insert_sierra_class(
+            &tx,
+            &sierra_hash,
+            b"example sierra program",
+            &casm_hash_bytes!(b"casm hash"),
+            b"compiled sierra program",
+        )
+        .unwrap();
+
+        let cairo_dictionary =
+            compression::insert_dictionary(&tx, DictionaryKind::CairoDefinition, b"cairo program")
+                .unwrap();
+        let sierra_dictionary = compression::insert_dictionary(
+            &tx,
+            DictionaryKind::SierraDefinition,
+            b"sierra program",
+        )
+        .unwrap();
+        let casm_dictionary = compression::insert_dictionary(
+            &tx,
+            DictionaryKind::CasmDefinition,
+            b"compiled program",
+        )
+        .unwrap();
+
+        restart_recompression(&tx).unwrap();
+        while recompress_class_definitions(&tx, 1).unwrap() {}
+        // Progress is kept, so finished work is not repeated.
+        assert!(!recompress_class_definitions(&tx, 1).unwrap());
+
+        let stored = stored_definition(&tx, cairo_hash);
+        assert_eq!(compression::dictionary_id(&stored), Some(cairo_dictionary));
+        let stored = stored_definition(&tx, ClassHash(sierra_hash.0));
+        assert_eq!(compression::dictionary_id(&stored), Some(sierra_dictionary));
+        let stored: Vec<u8> = tx
+            .inner()
+            .query_row(
+                "SELECT definition FROM casm_definitions WHERE hash = ?",
+                params![&sierra_hash],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(compression::dictionary_id(&stored), Some(casm_dictionary));
+
+        let definition = class_definition(&tx, cairo_hash).unwrap().unwrap();
+        assert_eq!(definition, cairo_definition);
+        let definition = casm_definition(&tx, ClassHash(sierra_hash.0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(definition, b"compiled sierra program");
+    }
+
     #[test]
     fn compiled_class_leaves() {
         let mut connection = Storage::in_memory().unwrap().connection().unwrap();
@@ -0,0 +1,223 @@
+//! Dictionary based zstd compression of stored blobs.
+//!
+//! A compressed blob consists of a format version byte, the little-endian `u32` id of the
+//! compression dictionary used (`0` for none) and a zstd frame.
+//!
+//! Rows written before dictionaries existed contain a bare zstd frame. These are recognised
+//! by the zstd frame magic, which can never be a valid version byte.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+
+use crate::prelude::*;
+
+/// The current blob format version. It also covers the binary encoding of the compressed
+/// transactions and receipts, and is bumped whenever that changes.
+const VERSION: u8 = 1;
+
+/// The first bytes of every zstd frame, used to recognise legacy rows.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Size of the version and dictionary id prefix.
+const HEADER_LEN: usize = 5;
+
+const COMPRESSION_LEVEL: i32 = 10;
+
+/// The kind of data a compression dictionary was trained on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DictionaryKind {
+    Transaction = 0,
+    Receipt = 1,
+    CairoDefinition = 2,
+    SierraDefinition = 3,
+    CasmDefinition = 4,
+}
+
+pub(crate) struct Dictionary {
+    id: u32,
+    encoder: zstd::dict::EncoderDictionary<'static>,
+    decoder: zstd::dict::DecoderDictionary<'static>,
+}
+
+impl Dictionary {
+    pub(super) fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Caches the prepared compression dictionaries of a database.
+///
+/// Dictionaries are immutable once stored, so entries never need to be invalidated.
+#[derive(Default)]
+pub(crate) struct DictionaryCache(Mutex<HashMap<u32, Arc<Dictionary>>>);
+
+impl DictionaryCache {
+    /// Returns the dictionary with the given id, loading it from the database if required.
+    fn get(&self, tx: &Transaction<'_>, id: u32) -> anyhow::Result<Arc<Dictionary>> {
+        if let Some(dictionary) = self.0.lock().unwrap().get(&id) {
+            return Ok(dictionary.clone());
+        }
+
+        let data: Vec<u8> = tx
+            .inner()
+            .query_row(
+                "SELECT dictionary FROM compression_dictionaries WHERE id = ?",
+                params![&id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying compression dictionary")?
+            .with_context(|| format!("Compression dictionary {id} is missing"))?;
+
+        let dictionary = Arc::new(Dictionary {
+            id,
+            encoder: zstd::dict::EncoderDictionary::copy(&data, COMPRESSION_LEVEL),
+            decoder: zstd::dict::DecoderDictionary::copy(&data),
+        });
+
+        self.0.lock().unwrap().insert(id, dictionary.clone());
+
+        Ok(dictionary)
+    }
+
+    /// Returns the most recently trained dictionary of the given kind, if any.
+    pub(super) fn latest(
+        &self,
+        tx: &Transaction<'_>,
+        kind: DictionaryKind,
+    ) -> anyhow::Result<Option<Arc<Dictionary>>> {
+        let id: Option<u32> = tx
+            .inner()
+            .query_row(
+                "SELECT max(id) FROM compression_dictionaries WHERE kind = ?",
+                params![&(kind as u8)],
+                |row| row.get(0),
+            )
+            .context("Querying latest compression dictionary id")?;
+
+        id.map(|id| self.get(tx, id)).transpose()
+    }
+}
+
+/// Stores a new compression dictionary and returns its id.
+pub(super) fn insert_dictionary(
+    tx: &Transaction<'_>,
+    kind: DictionaryKind,
+    dictionary: &[u8],
+) -> anyhow::Result<u32> {
+    tx.inner()
+        .query_row(
+            "INSERT INTO compression_dictionaries (kind, dictionary) VALUES (?, ?) RETURNING id",
+            params![&(kind as u8), &dictionary],
+            |row| row.get(0),
+        )
+        .context("Inserting compression dictionary")
+}
+
+/// Returns true if a dictionary of the given kind has been stored.
+pub(super) fn dictionary_exists(
+    tx: &Transaction<'_>,
+    kind: DictionaryKind,
+) -> anyhow::Result<bool> {
+    tx.inner()
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM compression_dictionaries WHERE kind = ?)",
+            params![&(kind as u8)],
+            |row| row.get(0),
+        )
+        .context("Querying compression dictionaries")
+}
+
+/// Returns true if the blob is a bare zstd frame, as written before dictionaries existed.
+pub(super) fn is_legacy(blob: &[u8]) -> bool {
+    blob.starts_with(&ZSTD_MAGIC)
+}
+
+/// Returns the id of the dictionary the blob was compressed with, or `None` for legacy blobs.
+pub(super) fn dictionary_id(blob: &[u8]) -> Option<u32> {
+    if is_legacy(blob) || blob.len() < HEADER_LEN {
+        return None;
+    }
+
+    Some(u32::from_le_bytes(
+        blob[1..HEADER_LEN].try_into().expect("Slice length is 4"),
+    ))
+}
+
+/// Compresses blobs using a fixed dictionary.
+pub(super) struct Compressor<'a> {
+    dictionary_id: u32,
+    compressor: zstd::bulk::Compressor<'a>,
+}
+
+impl<'a> Compressor<'a> {
+    pub(super) fn new(dictionary: Option<&'a Dictionary>) -> anyhow::Result<Self> {
+        let (dictionary_id, compressor) = match dictionary {
+            Some(dictionary) => (
+                dictionary.id,
+                zstd::bulk::Compressor::with_prepared_dictionary(&dictionary.encoder),
+            ),
+            None => (0, zstd::bulk::Compressor::new(COMPRESSION_LEVEL)),
+        };
+        let compressor = compressor.context("Creating zstd compressor")?;
+
+        Ok(Self {
+            dictionary_id,
+            compressor,
+        })
+    }
+
+    pub(super) fn compress(&mut self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let compressed = self.compressor.compress(data).context("Compressing")?;
+
+        let mut blob = Vec::with_capacity(HEADER_LEN + compressed.len());
+        blob.push(VERSION);
+        blob.extend_from_slice(&self.dictionary_id.to_le_bytes());
+        blob.extend_from_slice(&compressed);
+
+        Ok(blob)
+    }
+}
+
+/// Decompresses a blob in either the current or the legacy format.
+pub(super) fn decompress(tx: &Transaction<'_>, blob: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if is_legacy(blob) {
+        return zstd::decode_all(blob).context("Decompressing");
+    }
+
+    let version = *blob.first().context("Blob is empty")?;
+    anyhow::ensure!(version == VERSION, "Unknown blob format version {version}");
+    let dictionary_id = dictionary_id(blob).context("Blob header is truncated")?;
+    let compressed = &blob[HEADER_LEN..];
+
+    if dictionary_id == 0 {
+        return zstd::decode_all(compressed).context("Decompressing");
+    }
+
+    let dictionary = tx.compression_dictionaries.get(tx, dictionary_id)?;
+    let mut decoder =
+        zstd::stream::read::Decoder::with_prepared_dictionary(compressed, &dictionary.decoder)
+            .context("Creating zstd decoder")?;
+    let mut data = Vec::new();
+    decoder.read_to_end(&mut data).context("Decompressing")?;
+
+    Ok(data)
+}
+
+/// Converts a blob in either format into a bare zstd frame which can be decompressed without
+/// access to the database's dictionaries.
+pub(super) fn standalone(tx: &Transaction<'_>, blob: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    if is_legacy(&blob) {
+        return Ok(blob);
+    }
+
+    if dictionary_id(&blob) == Some(0) {
+        return Ok(blob[HEADER_LEN..].to_vec());
+    }
+
+    let data = decompress(tx, &blob)?;
+    zstd::bulk::compress(&data, COMPRESSION_LEVEL).context("Compressing")
+}
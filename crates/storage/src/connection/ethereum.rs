@@ -1,8 +1,20 @@
-use pathfinder_common::BlockNumber;
-use pathfinder_ethereum::EthereumStateUpdate;
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockNumber, TransactionHash};
+use pathfinder_ethereum::{
+    EthereumStateUpdate, L1ToL2MessageLog, L2ToL1MessageLog, StateUpdateLog,
+};
+use primitive_types::H256;
 
 use crate::prelude::*;
 
+/// An L1 to L2 message, linked to the L1 handler transaction which consumed it on L2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1ToL2Message {
+    pub message_hash: H256,
+    /// `None` if the message has not been consumed on L2 yet.
+    pub l1_handler_transaction: Option<TransactionHash>,
+}
+
 pub(super) fn upsert_l1_state(
     tx: &Transaction<'_>,
     update: &EthereumStateUpdate,
@@ -75,6 +87,198 @@ pub(super) fn latest_l1_state(tx: &Transaction<'_>) -> anyhow::Result<Option<Eth
         .map_err(|e| e.into())
 }
 
+pub(super) fn insert_state_update_logs(
+    tx: &Transaction<'_>,
+    logs: &[StateUpdateLog],
+) -> anyhow::Result<()> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR REPLACE INTO l1_state_update_logs (
+                    starknet_block_number,
+                    starknet_block_hash,
+                    starknet_state_root,
+                    l1_block_number,
+                    l1_transaction_hash
+                ) VALUES (?, ?, ?, ?, ?)",
+        )
+        .context("Preparing statement")?;
+
+    for log in logs {
+        stmt.execute(params![
+            &log.block_number,
+            &log.block_hash,
+            &log.state_root,
+            &log.l1_block_number,
+            &log.l1_transaction_hash.as_bytes(),
+        ])
+        .context("Inserting state update log")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the first state update log at or after `block`, which is the L1 state update that
+/// included the block.
+pub(super) fn state_update_log_including(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<Option<StateUpdateLog>> {
+    tx.inner()
+        .query_row(
+            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root, l1_block_number, l1_transaction_hash
+            FROM l1_state_update_logs WHERE starknet_block_number >= ?
+            ORDER BY starknet_block_number ASC
+            LIMIT 1",
+            params![&block],
+            parse_state_update_log,
+        )
+        .optional()
+        .map_err(|e| e.into())
+}
+
+pub(super) fn latest_state_update_log(
+    tx: &Transaction<'_>,
+) -> anyhow::Result<Option<StateUpdateLog>> {
+    tx.inner()
+        .query_row(
+            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root, l1_block_number, l1_transaction_hash
+            FROM l1_state_update_logs
+            ORDER BY starknet_block_number DESC
+            LIMIT 1",
+            [],
+            parse_state_update_log,
+        )
+        .optional()
+        .map_err(|e| e.into())
+}
+
+pub(super) fn insert_message_logs(
+    tx: &Transaction<'_>,
+    logs: &[L1ToL2MessageLog],
+) -> anyhow::Result<()> {
+    // Messages are ignored if already present, so that their order is kept if a range of
+    // Ethereum blocks is synced again.
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR IGNORE INTO l1_to_l2_messages (message_hash, l1_block_number, l1_transaction_hash)
+            VALUES (?, ?, ?)",
+        )
+        .context("Preparing statement")?;
+
+    for log in logs {
+        stmt.execute(params![
+            &log.message_hash.as_bytes(),
+            &log.l1_block_number,
+            &log.l1_transaction_hash.as_bytes(),
+        ])
+        .context("Inserting message log")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the L1 to L2 messages sent by an Ethereum transaction, in the order they were sent.
+pub(super) fn l1_to_l2_messages(
+    tx: &Transaction<'_>,
+    l1_transaction_hash: H256,
+) -> anyhow::Result<Vec<L1ToL2Message>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT l1_to_l2_messages.message_hash AS message_hash, transaction_hash
+            FROM l1_to_l2_messages
+            LEFT JOIN l1_handler_messages ON l1_to_l2_messages.message_hash = l1_handler_messages.message_hash
+            WHERE l1_transaction_hash = ?
+            ORDER BY l1_to_l2_messages.rowid ASC",
+        )
+        .context("Preparing statement")?;
+
+    let messages = stmt
+        .query_map(params![&l1_transaction_hash.as_bytes()], |row| {
+            let message_hash = H256::from_slice(row.get_blob("message_hash")?);
+            let l1_handler_transaction = row
+                .get_optional_felt("transaction_hash")?
+                .map(TransactionHash);
+
+            Ok(L1ToL2Message {
+                message_hash,
+                l1_handler_transaction,
+            })
+        })
+        .context("Querying messages")?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(messages)
+}
+
+pub(super) fn insert_consumed_message_logs(
+    tx: &Transaction<'_>,
+    logs: &[L2ToL1MessageLog],
+) -> anyhow::Result<()> {
+    // Consumptions are ignored if already present, in case a range of Ethereum blocks is synced
+    // again.
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR IGNORE INTO l2_to_l1_message_consumptions (l1_block_number, l1_log_index, l1_transaction_hash, message_hash)
+            VALUES (?, ?, ?, ?)",
+        )
+        .context("Preparing statement")?;
+
+    for log in logs {
+        stmt.execute(params![
+            &log.l1_block_number,
+            &log.l1_log_index,
+            &log.l1_transaction_hash.as_bytes(),
+            &log.message_hash.as_bytes(),
+        ])
+        .context("Inserting consumed message log")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the Ethereum transactions which consumed an L2 to L1 message with the given hash, in
+/// the order they were consumed.
+pub(super) fn l2_to_l1_message_consumptions(
+    tx: &Transaction<'_>,
+    message_hash: H256,
+) -> anyhow::Result<Vec<H256>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT l1_transaction_hash FROM l2_to_l1_message_consumptions
+            WHERE message_hash = ?
+            ORDER BY l1_block_number ASC, l1_log_index ASC",
+        )
+        .context("Preparing statement")?;
+
+    let transactions = stmt
+        .query_map(params![&message_hash.as_bytes()], |row| {
+            Ok(H256::from_slice(row.get_blob("l1_transaction_hash")?))
+        })
+        .context("Querying message consumptions")?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(transactions)
+}
+
+fn parse_state_update_log(row: &rusqlite::Row<'_>) -> rusqlite::Result<StateUpdateLog> {
+    let block_hash = row.get_optional_felt("starknet_block_hash")?.map(BlockHash);
+    let l1_block_number = row.get_i64("l1_block_number")?;
+    let l1_transaction_hash = H256::from_slice(row.get_blob("l1_transaction_hash")?);
+
+    Ok(StateUpdateLog {
+        state_root: row.get_state_commitment("starknet_state_root")?,
+        block_number: row.get_block_number("starknet_block_number")?,
+        block_hash,
+        l1_block_number: l1_block_number as u64,
+        l1_transaction_hash,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,6 +350,79 @@ mod tests {
         }
     }
 
+    #[test]
+    fn state_update_logs() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let result = latest_state_update_log(&tx).unwrap();
+        assert_eq!(result, None);
+
+        let logs = [
+            StateUpdateLog {
+                state_root: state_commitment!("0x1"),
+                block_number: BlockNumber::new_or_panic(5),
+                block_hash: None,
+                l1_block_number: 100,
+                l1_transaction_hash: H256::from_low_u64_be(1),
+            },
+            StateUpdateLog {
+                state_root: state_commitment!("0x2"),
+                block_number: BlockNumber::new_or_panic(10),
+                block_hash: Some(block_hash!("0xabc")),
+                l1_block_number: 110,
+                l1_transaction_hash: H256::from_low_u64_be(2),
+            },
+        ];
+        insert_state_update_logs(&tx, &logs).unwrap();
+
+        let result = latest_state_update_log(&tx).unwrap();
+        assert_eq!(result.as_ref(), logs.last());
+
+        for (block, expected) in [
+            (0, Some(&logs[0])),
+            (5, Some(&logs[0])),
+            (6, Some(&logs[1])),
+            (10, Some(&logs[1])),
+            (11, None),
+        ] {
+            let result = state_update_log_including(&tx, BlockNumber::new_or_panic(block)).unwrap();
+            assert_eq!(result.as_ref(), expected, "block {block}");
+        }
+    }
+
+    #[test]
+    fn consumed_message_logs() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let message_hash = H256::from_low_u64_be(0xabc);
+        let log = |l1_block_number, l1_log_index, l1_transaction_hash| L2ToL1MessageLog {
+            message_hash,
+            l1_block_number,
+            l1_log_index,
+            l1_transaction_hash: H256::from_low_u64_be(l1_transaction_hash),
+        };
+
+        let result = l2_to_l1_message_consumptions(&tx, message_hash).unwrap();
+        assert!(result.is_empty());
+
+        // Identical messages are consumed separately, and syncing a range again is ignored.
+        insert_consumed_message_logs(&tx, &[log(110, 0, 2)]).unwrap();
+        insert_consumed_message_logs(&tx, &[log(100, 3, 1), log(110, 0, 2)]).unwrap();
+
+        let result = l2_to_l1_message_consumptions(&tx, message_hash).unwrap();
+        assert_eq!(
+            result,
+            vec![H256::from_low_u64_be(1), H256::from_low_u64_be(2)]
+        );
+
+        let result = l2_to_l1_message_consumptions(&tx, H256::from_low_u64_be(1)).unwrap();
+        assert!(result.is_empty());
+    }
+
     #[test]
     fn upsert_overwrites() {
         let storage = Storage::in_memory().unwrap();
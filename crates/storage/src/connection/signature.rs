@@ -1,5 +1,5 @@
 use anyhow::Context;
-use pathfinder_common::{BlockCommitmentSignature, BlockNumber};
+use pathfinder_common::{BlockCommitmentSignature, BlockNumber, SignatureStatus};
 
 use crate::{prelude::*, BlockId};
 
@@ -64,6 +64,61 @@ pub(super) fn signature(
     .map_err(|e| e.into())
 }
 
+pub(super) fn update_signature_status(
+    tx: &Transaction<'_>,
+    block_number: BlockNumber,
+    status: SignatureStatus,
+) -> anyhow::Result<()> {
+    let verified = match status {
+        SignatureStatus::Unverified => None,
+        SignatureStatus::Valid => Some(true),
+        SignatureStatus::Invalid => Some(false),
+    };
+
+    tx.inner()
+        .execute(
+            "UPDATE block_signatures SET verified = ? WHERE block_number = ?",
+            params![&verified, &block_number],
+        )
+        .context("Updating signature status")?;
+
+    Ok(())
+}
+
+pub(super) fn signature_status(
+    tx: &Transaction<'_>,
+    block: BlockId,
+) -> anyhow::Result<Option<SignatureStatus>> {
+    let verified = match block {
+        BlockId::Latest => tx.inner().query_row(
+            "SELECT verified FROM block_signatures ORDER BY block_number DESC LIMIT 1",
+            [],
+            |row| row.get::<_, Option<bool>>(0),
+        ),
+        BlockId::Number(number) => tx.inner().query_row(
+            "SELECT verified FROM block_signatures WHERE block_number = ?",
+            params![&number],
+            |row| row.get::<_, Option<bool>>(0),
+        ),
+        BlockId::Hash(hash) => tx.inner().query_row(
+            r"SELECT verified
+                FROM block_signatures
+                JOIN block_headers ON block_signatures.block_number = block_headers.number
+                WHERE block_headers.hash = ?",
+            params![&hash],
+            |row| row.get::<_, Option<bool>>(0),
+        ),
+    }
+    .optional()
+    .context("Querying signature status")?;
+
+    Ok(verified.map(|verified| match verified {
+        None => SignatureStatus::Unverified,
+        Some(true) => SignatureStatus::Valid,
+        Some(false) => SignatureStatus::Invalid,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use pathfinder_common::macro_prelude::*;
@@ -146,4 +201,27 @@ mod tests {
         let result = tx.signature(past_head.into()).unwrap();
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn status() {
+        let (mut connection, headers, _signatures) = setup();
+        let tx = connection.transaction().unwrap();
+
+        let status = tx.signature_status(headers[0].number.into()).unwrap();
+        assert_eq!(status, Some(SignatureStatus::Unverified));
+
+        tx.update_signature_status(headers[0].number, SignatureStatus::Valid)
+            .unwrap();
+        tx.update_signature_status(headers[1].number, SignatureStatus::Invalid)
+            .unwrap();
+
+        let status = tx.signature_status(headers[0].hash.into()).unwrap();
+        assert_eq!(status, Some(SignatureStatus::Valid));
+        let status = tx.signature_status(BlockId::Latest).unwrap();
+        assert_eq!(status, Some(SignatureStatus::Invalid));
+
+        let past_head = headers.last().unwrap().number + 1;
+        let status = tx.signature_status(past_head.into()).unwrap();
+        assert_eq!(status, None);
+    }
 }
@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use anyhow::Context;
 use pathfinder_common::state_update::ContractClassUpdate;
 use pathfinder_common::{
@@ -352,6 +354,84 @@ pub(super) fn storage_value(
     .map_err(|e| e.into())
 }
 
+/// Returns the blocks within `blocks` at which the storage `key` of `contract_address` was
+/// changed, along with the new value. Changes are ordered by block number and at most `limit`
+/// changes are returned.
+pub(super) fn storage_history(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+    key: StorageAddress,
+    blocks: RangeInclusive<BlockNumber>,
+    limit: usize,
+) -> anyhow::Result<Vec<(BlockNumber, StorageValue)>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT block_number, storage_value FROM storage_updates
+            WHERE contract_address = ? AND storage_address = ? AND block_number BETWEEN ? AND ?
+            ORDER BY block_number ASC LIMIT ?",
+        )
+        .context("Preparing storage history query")?;
+
+    let history = stmt
+        .query_map(
+            params![
+                &contract_address,
+                &key,
+                blocks.start(),
+                blocks.end(),
+                &limit.try_into_sql_int()?
+            ],
+            |row| Ok((row.get_block_number(0)?, row.get_storage_value(1)?)),
+        )
+        .context("Querying storage history")?
+        .collect::<Result<_, _>>()
+        .context("Iterating over storage history")?;
+
+    Ok(history)
+}
+
+/// Returns the class of `contract_address` after each of its class updates, ordered by block
+/// number. The first entry is the contract's deployment and any further entries are class
+/// replacements.
+pub(super) fn contract_class_history(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+) -> anyhow::Result<Vec<(BlockNumber, ClassHash)>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT block_number, class_hash FROM contract_updates
+            WHERE contract_address = ?
+            ORDER BY block_number ASC",
+        )
+        .context("Preparing contract class history query")?;
+
+    let history = stmt
+        .query_map(params![&contract_address], |row| {
+            Ok((row.get_block_number(0)?, row.get_class_hash(1)?))
+        })
+        .context("Querying contract class history")?
+        .collect::<Result<_, _>>()
+        .context("Iterating over contract class history")?;
+
+    Ok(history)
+}
+
+/// Returns the number of blocks in which the nonce of `contract_address` was updated.
+pub(super) fn contract_nonce_update_count(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+) -> anyhow::Result<u64> {
+    tx.inner()
+        .query_row(
+            "SELECT count(*) FROM nonce_updates WHERE contract_address = ?",
+            params![&contract_address],
+            |row| row.get(0),
+        )
+        .context("Counting nonce updates")
+}
+
 pub(super) fn contract_exists(
     tx: &Transaction<'_>,
     contract_address: ContractAddress,
@@ -652,6 +732,118 @@ mod tests {
         assert_eq!(non_existent, None);
     }
 
+    #[test]
+    fn storage_history() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+        let key = storage_address_bytes!(b"key");
+
+        // The slot is changed in blocks 1, 2 and 4, while block 3 changes a different slot.
+        let mut header = BlockHeader::builder().finalize_with_hash(block_hash!("0x0"));
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_state_update(header.number, &StateUpdate::default())
+            .unwrap();
+        for i in 1..=4u8 {
+            header = header
+                .child_builder()
+                .finalize_with_hash(block_hash_bytes!(&[i]));
+            let state_update = match i {
+                3 => StateUpdate::default().with_storage_update(
+                    contract,
+                    storage_address_bytes!(b"other key"),
+                    storage_value_bytes!(&[i]),
+                ),
+                _ => StateUpdate::default().with_storage_update(
+                    contract,
+                    key,
+                    storage_value_bytes!(&[i]),
+                ),
+            };
+            tx.insert_block_header(&header).unwrap();
+            tx.insert_state_update(header.number, &state_update)
+                .unwrap();
+        }
+
+        let all = BlockNumber::GENESIS..=BlockNumber::MAX;
+        let result = super::storage_history(&tx, contract, key, all.clone(), 10).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                (BlockNumber::new_or_panic(1), storage_value_bytes!(&[1])),
+                (BlockNumber::new_or_panic(2), storage_value_bytes!(&[2])),
+                (BlockNumber::new_or_panic(4), storage_value_bytes!(&[4])),
+            ]
+        );
+
+        let result = super::storage_history(&tx, contract, key, all.clone(), 2).unwrap();
+        assert_eq!(result.len(), 2);
+
+        let range = BlockNumber::new_or_panic(2)..=BlockNumber::new_or_panic(3);
+        let result = super::storage_history(&tx, contract, key, range, 10).unwrap();
+        assert_eq!(
+            result,
+            vec![(BlockNumber::new_or_panic(2), storage_value_bytes!(&[2]))]
+        );
+
+        let other = contract_address_bytes!(b"other contract");
+        let result = super::storage_history(&tx, other, key, all, 10).unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn contract_class_history() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address!("0x12345");
+        let original_class = class_hash!("0x1");
+        let replaced_class = class_hash!("0x2");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash!("0xa"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xb"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xc"));
+
+        let diff_0 = StateUpdate::default()
+            .with_deployed_contract(contract, original_class)
+            .with_contract_nonce(contract, contract_nonce!("0x1"));
+        let diff_1 = StateUpdate::default().with_contract_nonce(contract, contract_nonce!("0x2"));
+        let diff_2 = StateUpdate::default().with_replaced_class(contract, replaced_class);
+
+        for (header, diff) in [
+            (&header_0, diff_0),
+            (&header_1, diff_1),
+            (&header_2, diff_2),
+        ] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, &diff).unwrap();
+        }
+
+        let history = super::contract_class_history(&tx, contract).unwrap();
+        assert_eq!(
+            history,
+            vec![
+                (header_0.number, original_class),
+                (header_2.number, replaced_class)
+            ]
+        );
+        assert_eq!(
+            super::contract_nonce_update_count(&tx, contract).unwrap(),
+            2
+        );
+
+        let other = contract_address!("0xabc");
+        assert!(super::contract_class_history(&tx, other)
+            .unwrap()
+            .is_empty());
+        assert_eq!(super::contract_nonce_update_count(&tx, other).unwrap(), 0);
+    }
+
     mod contract_state {
         //! Tests involving contract nonces and storage.
         use super::*;
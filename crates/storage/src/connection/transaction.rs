@@ -2,16 +2,43 @@
 
 use anyhow::Context;
 use pathfinder_common::receipt::Receipt;
-use pathfinder_common::transaction::Transaction as StarknetTransaction;
-use pathfinder_common::{BlockHash, BlockNumber, TransactionHash};
+use pathfinder_common::transaction::{Transaction as StarknetTransaction, TransactionVariant};
+use pathfinder_common::{
+    BlockHash, BlockNumber, ContractAddress, TransactionHash, TransactionIndex, TransactionNonce,
+};
 
 use crate::{prelude::*, BlockId};
 
+mod codec;
+
+use super::backfill::Backfill;
+use super::compression::{self, DictionaryKind};
+
+/// Re-encodes the stored transactions, see [reencode_transactions].
+const REENCODING: Backfill = Backfill::new("transaction_reencoding", "starknet_transactions");
+/// Indexes the transactions stored before the sender index was enabled, see
+/// [backfill_transaction_senders].
+const SENDERS_BACKFILL: Backfill =
+    Backfill::new("transaction_senders_backfill", "starknet_transactions");
+/// Links the messages of the L1 handler transactions stored before messages were linked on
+/// insert, see [backfill_l1_handler_messages].
+const L1_HANDLER_BACKFILL: Backfill =
+    Backfill::new("l1_handler_messages_backfill", "starknet_transactions");
+
 pub enum TransactionStatus {
     L1Accepted,
     L2Accepted,
 }
 
+/// A transaction found in the transaction sender index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenderTransaction {
+    pub nonce: TransactionNonce,
+    pub hash: TransactionHash,
+    pub block_number: BlockNumber,
+    pub index: TransactionIndex,
+}
+
 pub(super) fn insert_transactions(
     tx: &Transaction<'_>,
     block_hash: BlockHash,
@@ -22,31 +49,34 @@ pub(super) fn insert_transactions(
         return Ok(());
     }
 
-    let mut compressor = zstd::bulk::Compressor::new(10).context("Create zstd compressor")?;
-    for (i, (transaction, receipt)) in transaction_data.iter().enumerate() {
-        // Serialize and compress transaction data.
-        let transaction = dto::Transaction::from(transaction);
-        let receipt = dto::Receipt::from(receipt);
+    let tx_dictionary = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::Transaction)?;
+    let receipt_dictionary = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::Receipt)?;
+    let mut tx_compressor = compression::Compressor::new(tx_dictionary.as_deref())?;
+    let mut receipt_compressor = compression::Compressor::new(receipt_dictionary.as_deref())?;
 
-        let tx_data = serde_json::to_vec(&transaction).context("Serializing transaction")?;
-        let tx_data = compressor
+    for (i, (transaction, receipt)) in transaction_data.iter().enumerate() {
+        let tx_data = codec::serialize(&codec::StoredTransaction::from(transaction))?;
+        let tx_data = tx_compressor
             .compress(&tx_data)
             .context("Compressing transaction")?;
-
-        let serialized_receipt = serde_json::to_vec(&receipt).context("Serializing receipt")?;
-        let serialized_receipt = compressor
+        let serialized_receipt = codec::serialize(&codec::StoredReceipt::from(receipt))?;
+        let serialized_receipt = receipt_compressor
             .compress(&serialized_receipt)
             .context("Compressing receipt")?;
 
-        let execution_status = match receipt.execution_status {
-            dto::ExecutionStatus::Succeeded => 0,
-            dto::ExecutionStatus::Reverted => 1,
+        let execution_status = match receipt.is_reverted() {
+            false => 0,
+            true => 1,
         };
 
         tx.inner().execute(r"INSERT OR REPLACE INTO starknet_transactions (hash,  idx,  block_hash,  tx,  receipt,  execution_status) 
                                                                   VALUES (:hash, :idx, :block_hash, :tx, :receipt, :execution_status)",
             named_params![
-            ":hash": &transaction.hash(),
+            ":hash": &transaction.hash,
             ":idx": &i.try_into_sql_int()?,
             ":block_hash": &block_hash,
             ":tx": &tx_data,
@@ -60,9 +90,292 @@ pub(super) fn insert_transactions(
         .flat_map(|(_, receipt)| &receipt.events);
     super::event::insert_block_events(tx, block_number, events)
         .context("Inserting events into Bloom filter")?;
+
+    if transaction_sender_index_enabled(tx)? {
+        let transactions = transaction_data
+            .iter()
+            .map(|(transaction, _)| transaction)
+            .enumerate();
+        insert_transaction_senders(tx, block_number, transactions)
+            .context("Inserting into transaction sender index")?;
+    }
+
+    let transactions = transaction_data.iter().map(|(transaction, _)| transaction);
+    insert_l1_handler_messages(tx, block_number, transactions)
+        .context("Inserting L1 handler messages")?;
+
     Ok(())
 }
 
+/// Links the messages consumed by L1 handler transactions to the consuming transaction.
+fn insert_l1_handler_messages<'a>(
+    tx: &Transaction<'_>,
+    block_number: BlockNumber,
+    transactions: impl Iterator<Item = &'a StarknetTransaction>,
+) -> anyhow::Result<()> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR REPLACE INTO l1_handler_messages (message_hash, transaction_hash, block_number)
+            VALUES (?, ?, ?)",
+        )
+        .context("Preparing statement")?;
+
+    for transaction in transactions {
+        let TransactionVariant::L1Handler(l1_handler) = &transaction.variant else {
+            continue;
+        };
+
+        stmt.execute(params![
+            &l1_handler.calculate_message_hash().as_bytes(),
+            &transaction.hash,
+            &block_number,
+        ])
+        .context("Inserting L1 handler message")?;
+    }
+
+    Ok(())
+}
+
+/// Links the messages consumed by up to `limit` L1 handler transactions which were stored before
+/// messages were linked on insert.
+///
+/// Returns `false` once all such transactions have been processed.
+pub(super) fn backfill_l1_handler_messages(
+    tx: &Transaction<'_>,
+    limit: usize,
+) -> anyhow::Result<bool> {
+    let Some((next_rowid, end_rowid)) = L1_HANDLER_BACKFILL.remaining(tx)? else {
+        return Ok(false);
+    };
+
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT starknet_transactions.rowid AS rowid, tx, number
+            FROM starknet_transactions JOIN canonical_blocks ON starknet_transactions.block_hash = canonical_blocks.hash
+            WHERE starknet_transactions.rowid > ? AND starknet_transactions.rowid <= ?
+            ORDER BY starknet_transactions.rowid ASC
+            LIMIT ?",
+        )
+        .context("Preparing select statement")?;
+    let mut rows = stmt
+        .query(params![&next_rowid, &end_rowid, &limit.try_into_sql_int()?])
+        .context("Executing query")?;
+
+    let mut last = None;
+    while let Some(row) = rows.next()? {
+        last = Some(row.get_i64("rowid")?);
+
+        let Some(data) = row.get_ref_unwrap("tx").as_blob_or_null()? else {
+            continue;
+        };
+        let transaction = decode_transaction(tx, data)?;
+        let block_number = row.get_block_number("number")?;
+
+        insert_l1_handler_messages(tx, block_number, std::iter::once(&transaction))?;
+    }
+
+    L1_HANDLER_BACKFILL.advance(tx, last)
+}
+
+/// Returns the sender and nonce of transactions which are indexed by sender.
+///
+/// Version 0 transactions, deploy and L1 handler transactions have no meaningful sender nonce and
+/// are therefore not indexed.
+fn sender_and_nonce(
+    transaction: &StarknetTransaction,
+) -> Option<(ContractAddress, TransactionNonce)> {
+    match &transaction.variant {
+        TransactionVariant::DeclareV1(declare) => Some((declare.sender_address, declare.nonce)),
+        TransactionVariant::DeclareV2(declare) => Some((declare.sender_address, declare.nonce)),
+        TransactionVariant::DeclareV3(declare) => Some((declare.sender_address, declare.nonce)),
+        TransactionVariant::DeployAccountV0V1(deploy) => {
+            Some((deploy.contract_address, deploy.nonce))
+        }
+        TransactionVariant::DeployAccountV3(deploy) => {
+            Some((deploy.contract_address, deploy.nonce))
+        }
+        TransactionVariant::InvokeV1(invoke) => Some((invoke.sender_address, invoke.nonce)),
+        TransactionVariant::InvokeV3(invoke) => Some((invoke.sender_address, invoke.nonce)),
+        TransactionVariant::DeclareV0(_)
+        | TransactionVariant::Deploy(_)
+        | TransactionVariant::InvokeV0(_)
+        | TransactionVariant::L1Handler(_) => None,
+    }
+}
+
+/// Indexes `transactions` of block `block_number`, along with their index in the block.
+fn insert_transaction_senders<'a>(
+    tx: &Transaction<'_>,
+    block_number: BlockNumber,
+    transactions: impl Iterator<Item = (usize, &'a StarknetTransaction)>,
+) -> anyhow::Result<()> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR REPLACE INTO transaction_senders (block_number, idx, sender_address, nonce, transaction_hash)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .context("Preparing statement")?;
+
+    for (i, transaction) in transactions {
+        let Some((sender, nonce)) = sender_and_nonce(transaction) else {
+            continue;
+        };
+
+        stmt.execute(params![
+            &block_number,
+            &i.try_into_sql_int()?,
+            &sender,
+            &nonce.0.as_be_bytes().as_slice(),
+            &transaction.hash,
+        ])
+        .context("Inserting transaction sender")?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn transaction_sender_index_enabled(tx: &Transaction<'_>) -> anyhow::Result<bool> {
+    tx.inner()
+        .query_row(
+            "SELECT enabled FROM transaction_sender_index WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Querying transaction sender index state")
+}
+
+/// Whether the transaction sender index is enabled and includes all stored transactions, rather
+/// than still being populated by [backfill_transaction_senders].
+pub(super) fn transaction_sender_index_complete(tx: &Transaction<'_>) -> anyhow::Result<bool> {
+    if !transaction_sender_index_enabled(tx)? {
+        return Ok(false);
+    }
+
+    Ok(SENDERS_BACKFILL.remaining(tx)?.is_none())
+}
+
+/// Enables or disables the transaction sender index.
+///
+/// Enabling the index indexes new transactions on insert, while the transactions which are
+/// already stored are indexed in the background by [backfill_transaction_senders]. Disabling it
+/// removes all its entries.
+pub(super) fn set_transaction_sender_index(
+    tx: &Transaction<'_>,
+    enabled: bool,
+) -> anyhow::Result<()> {
+    if transaction_sender_index_enabled(tx)? == enabled {
+        return Ok(());
+    }
+
+    if enabled {
+        SENDERS_BACKFILL.restart(tx)?;
+    } else {
+        SENDERS_BACKFILL.cancel(tx)?;
+        tx.inner()
+            .execute("DELETE FROM transaction_senders", [])
+            .context("Deleting transaction sender index")?;
+    }
+
+    tx.inner()
+        .execute(
+            "UPDATE transaction_sender_index SET enabled = ? WHERE id = 1",
+            params![&i64::from(enabled)],
+        )
+        .context("Updating transaction sender index state")?;
+
+    Ok(())
+}
+
+/// Indexes up to `limit` transactions by sender which were stored before the transaction sender
+/// index was enabled.
+///
+/// Returns `false` once all such transactions have been processed.
+pub(super) fn backfill_transaction_senders(
+    tx: &Transaction<'_>,
+    limit: usize,
+) -> anyhow::Result<bool> {
+    let Some((next_rowid, end_rowid)) = SENDERS_BACKFILL.remaining(tx)? else {
+        return Ok(false);
+    };
+
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT starknet_transactions.rowid AS rowid, idx, tx, number
+            FROM starknet_transactions JOIN canonical_blocks ON starknet_transactions.block_hash = canonical_blocks.hash
+            WHERE starknet_transactions.rowid > ? AND starknet_transactions.rowid <= ?
+            ORDER BY starknet_transactions.rowid ASC
+            LIMIT ?",
+        )
+        .context("Preparing select statement")?;
+    let mut rows = stmt
+        .query(params![&next_rowid, &end_rowid, &limit.try_into_sql_int()?])
+        .context("Executing query")?;
+
+    let mut last = None;
+    while let Some(row) = rows.next()? {
+        last = Some(row.get_i64("rowid")?);
+
+        let Some(data) = row.get_ref_unwrap("tx").as_blob_or_null()? else {
+            continue;
+        };
+        let transaction = decode_transaction(tx, data)?;
+        let block_number = row.get_block_number("number")?;
+        let index: usize = row
+            .get_i64("idx")?
+            .try_into()
+            .context("Transaction index is negative")?;
+
+        insert_transaction_senders(tx, block_number, std::iter::once((index, &transaction)))?;
+    }
+
+    SENDERS_BACKFILL.advance(tx, last)
+}
+
+/// Returns up to `limit` indexed transactions of `sender` with a nonce of at least `from_nonce`,
+/// ordered by nonce.
+pub(super) fn transactions_by_sender(
+    tx: &Transaction<'_>,
+    sender: ContractAddress,
+    from_nonce: TransactionNonce,
+    limit: usize,
+) -> anyhow::Result<Vec<SenderTransaction>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT nonce, transaction_hash, block_number, idx FROM transaction_senders
+            WHERE sender_address = ? AND nonce >= ?
+            ORDER BY nonce ASC, block_number ASC, idx ASC
+            LIMIT ?",
+        )
+        .context("Preparing statement")?;
+
+    let transactions = stmt
+        .query_map(
+            params![
+                &sender,
+                &from_nonce.0.as_be_bytes().as_slice(),
+                &limit.try_into_sql_int()?
+            ],
+            |row| {
+                let index = row.get_i64("idx")?;
+                Ok(SenderTransaction {
+                    nonce: row.get_transaction_nonce("nonce")?,
+                    hash: row.get_transaction_hash("transaction_hash")?,
+                    block_number: row.get_block_number("block_number")?,
+                    index: TransactionIndex::new_or_panic(index as u64),
+                })
+            },
+        )
+        .context("Executing query")?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(transactions)
+}
+
 pub(super) fn transaction(
     tx: &Transaction<'_>,
     transaction: TransactionHash,
@@ -82,11 +395,9 @@ pub(super) fn transaction(
     };
 
     let transaction = row.get_ref_unwrap(0).as_blob()?;
-    let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-    let transaction: dto::Transaction =
-        serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+    let transaction = decode_transaction(tx, transaction)?;
 
-    Ok(Some(transaction.into()))
+    Ok(Some(transaction))
 }
 
 pub(super) fn transaction_with_receipt(
@@ -106,21 +417,17 @@ pub(super) fn transaction_with_receipt(
     };
 
     let transaction = row.get_ref_unwrap("tx").as_blob()?;
-    let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-    let transaction: dto::Transaction =
-        serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+    let transaction = decode_transaction(tx, transaction)?;
 
     let receipt = match row.get_ref_unwrap("receipt").as_blob_or_null()? {
         Some(data) => data,
         None => return Ok(None),
     };
-    let receipt = zstd::decode_all(receipt).context("Decompressing receipt")?;
-    let receipt: dto::Receipt =
-        serde_json::from_slice(&receipt).context("Deserializing receipt")?;
+    let receipt = decode_receipt(tx, receipt)?;
 
     let block_hash = row.get_block_hash("block_hash")?;
 
-    Ok(Some((transaction.into(), receipt.into(), block_hash)))
+    Ok(Some((transaction, receipt, block_hash)))
 }
 
 pub(super) fn transaction_at_block(
@@ -152,11 +459,9 @@ pub(super) fn transaction_at_block(
         None => return Ok(None),
     };
 
-    let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-    let transaction: dto::Transaction =
-        serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+    let transaction = decode_transaction(tx, transaction)?;
 
-    Ok(Some(transaction.into()))
+    Ok(Some(transaction))
 }
 
 pub(super) fn transaction_count(tx: &Transaction<'_>, block: BlockId) -> anyhow::Result<usize> {
@@ -216,19 +521,15 @@ pub(super) fn transaction_data_for_block(
             .get_ref_unwrap("receipt")
             .as_blob_or_null()?
             .context("Receipt data missing")?;
-        let receipt = zstd::decode_all(receipt).context("Decompressing transaction receipt")?;
-        let receipt: dto::Receipt =
-            serde_json::from_slice(&receipt).context("Deserializing transaction receipt")?;
+        let receipt = decode_receipt(tx, receipt)?;
 
         let transaction = row
             .get_ref_unwrap("tx")
             .as_blob_or_null()?
             .context("Transaction data missing")?;
-        let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-        let transaction: dto::Transaction =
-            serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+        let transaction = decode_transaction(tx, transaction)?;
 
-        data.push((transaction.into(), receipt.into()));
+        data.push((transaction, receipt));
     }
 
     Ok(Some(data))
@@ -257,11 +558,9 @@ pub(super) fn transactions_for_block(
             .get_ref_unwrap("tx")
             .as_blob_or_null()?
             .context("Transaction data missing")?;
-        let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-        let transaction: dto::Transaction =
-            serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+        let transaction = decode_transaction(tx, transaction)?;
 
-        data.push(transaction.into());
+        data.push(transaction);
     }
 
     Ok(Some(data))
@@ -290,11 +589,9 @@ pub(super) fn receipts_for_block(
             .get_ref_unwrap("receipt")
             .as_blob_or_null()?
             .context("Transaction data missing")?;
-        let receipt = zstd::decode_all(receipt).context("Decompressing receipt")?;
-        let receipt: dto::Receipt =
-            serde_json::from_slice(&receipt).context("Deserializing receipt")?;
+        let receipt = decode_receipt(tx, receipt)?;
 
-        data.push(receipt.into());
+        data.push(receipt);
     }
 
     Ok(Some(data))
@@ -335,8 +632,182 @@ pub(super) fn transaction_block_hash(
         .map_err(|e| e.into())
 }
 
-/// A copy of the gateway definitions which are currently used as the storage serde implementation. Having a copy here
-/// allows us to decouple this crate from the gateway types, while only exposing the common types via the storage API.
+/// Trains the transaction and receipt compression dictionaries on the `sample_size` most recent rows.
+///
+/// Returns `false` without training if the dictionaries already exist or there is not enough data yet.
+pub(super) fn train_compression_dictionaries(
+    tx: &Transaction<'_>,
+    sample_size: usize,
+) -> anyhow::Result<bool> {
+    /// Training on fewer samples produces dictionaries that hurt more than they help.
+    const MIN_SAMPLES: usize = 1000;
+    /// The zstd default dictionary size.
+    const DICTIONARY_SIZE: usize = 112_640;
+
+    if compression::dictionary_exists(tx, DictionaryKind::Transaction)? {
+        return Ok(false);
+    }
+
+    let mut stmt = tx
+        .inner()
+        .prepare("SELECT tx, receipt FROM starknet_transactions ORDER BY rowid DESC LIMIT ?")
+        .context("Preparing statement")?;
+    let mut rows = stmt
+        .query(params![&sample_size.try_into_sql_int()?])
+        .context("Executing query")?;
+
+    let mut transactions = Vec::new();
+    let mut receipts = Vec::new();
+    while let Some(row) = rows.next()? {
+        let (Some(transaction), Some(receipt)) = (
+            row.get_ref_unwrap("tx").as_blob_or_null()?,
+            row.get_ref_unwrap("receipt").as_blob_or_null()?,
+        ) else {
+            continue;
+        };
+
+        let transaction = decode_transaction(tx, transaction)?;
+        transactions.push(codec::serialize(&codec::StoredTransaction::from(
+            &transaction,
+        ))?);
+
+        let receipt = decode_receipt(tx, receipt)?;
+        receipts.push(codec::serialize(&codec::StoredReceipt::from(&receipt))?);
+    }
+
+    if transactions.len() < MIN_SAMPLES {
+        return Ok(false);
+    }
+
+    let dictionary = zstd::dict::from_samples(&transactions, DICTIONARY_SIZE)
+        .context("Training transaction dictionary")?;
+    compression::insert_dictionary(tx, DictionaryKind::Transaction, &dictionary)?;
+
+    let dictionary = zstd::dict::from_samples(&receipts, DICTIONARY_SIZE)
+        .context("Training receipt dictionary")?;
+    compression::insert_dictionary(tx, DictionaryKind::Receipt, &dictionary)?;
+
+    restart_reencoding(tx)?;
+
+    Ok(true)
+}
+
+/// Schedules all currently stored transactions to be re-encoded by [reencode_transactions].
+pub(super) fn restart_reencoding(tx: &Transaction<'_>) -> anyhow::Result<()> {
+    REENCODING.restart(tx)
+}
+
+/// Re-encodes up to `limit` rows which are not yet stored using the binary encoding and latest
+/// compression dictionaries, continuing from the progress stored by the previous call.
+///
+/// Returns `false` once all scheduled rows have been processed.
+pub(super) fn reencode_transactions(tx: &Transaction<'_>, limit: usize) -> anyhow::Result<bool> {
+    let Some((next_rowid, end_rowid)) = REENCODING.remaining(tx)? else {
+        return Ok(false);
+    };
+
+    let tx_dictionary = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::Transaction)?;
+    let receipt_dictionary = tx
+        .compression_dictionaries
+        .latest(tx, DictionaryKind::Receipt)?;
+    let tx_dictionary_id = tx_dictionary.as_ref().map_or(0, |x| x.id());
+    let receipt_dictionary_id = receipt_dictionary.as_ref().map_or(0, |x| x.id());
+    let mut tx_compressor = compression::Compressor::new(tx_dictionary.as_deref())?;
+    let mut receipt_compressor = compression::Compressor::new(receipt_dictionary.as_deref())?;
+
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT rowid, tx, receipt FROM starknet_transactions
+            WHERE rowid > ? AND rowid <= ?
+            ORDER BY rowid ASC
+            LIMIT ?",
+        )
+        .context("Preparing select statement")?;
+    let mut rows = stmt
+        .query(params![&next_rowid, &end_rowid, &limit.try_into_sql_int()?])
+        .context("Executing query")?;
+
+    let mut last = None;
+    // Updates are applied once the query is complete as SQLite does not define the behaviour
+    // of modifying a table while it is being read by the same connection.
+    let mut updates = Vec::new();
+    while let Some(row) = rows.next()? {
+        let rowid = row.get_i64("rowid")?;
+        last = Some(rowid);
+
+        let (Some(transaction), Some(receipt)) = (
+            row.get_ref_unwrap("tx").as_blob_or_null()?,
+            row.get_ref_unwrap("receipt").as_blob_or_null()?,
+        ) else {
+            continue;
+        };
+
+        if compression::dictionary_id(transaction) == Some(tx_dictionary_id)
+            && compression::dictionary_id(receipt) == Some(receipt_dictionary_id)
+        {
+            continue;
+        }
+
+        let transaction = decode_transaction(tx, transaction)?;
+        let transaction = codec::serialize(&codec::StoredTransaction::from(&transaction))?;
+        let transaction = tx_compressor
+            .compress(&transaction)
+            .context("Compressing transaction")?;
+        let receipt = decode_receipt(tx, receipt)?;
+        let receipt = codec::serialize(&codec::StoredReceipt::from(&receipt))?;
+        let receipt = receipt_compressor
+            .compress(&receipt)
+            .context("Compressing receipt")?;
+
+        updates.push((rowid, transaction, receipt));
+    }
+
+    let mut update = tx
+        .inner()
+        .prepare("UPDATE starknet_transactions SET tx = ?, receipt = ? WHERE rowid = ?")
+        .context("Preparing update statement")?;
+    for (rowid, transaction, receipt) in updates {
+        update
+            .execute(params![&transaction, &receipt, &rowid])
+            .context("Updating transaction data")?;
+    }
+
+    REENCODING.advance(tx, last)
+}
+
+/// Decodes a stored transaction in either the binary or the legacy JSON format.
+fn decode_transaction(tx: &Transaction<'_>, data: &[u8]) -> anyhow::Result<StarknetTransaction> {
+    if compression::is_legacy(data) {
+        let data = zstd::decode_all(data).context("Decompressing transaction")?;
+        let transaction: dto::Transaction =
+            serde_json::from_slice(&data).context("Deserializing transaction")?;
+        return Ok(transaction.into());
+    }
+
+    let transaction: codec::StoredTransaction =
+        codec::decode(tx, data).context("Decoding transaction")?;
+    Ok(transaction.into())
+}
+
+/// Decodes a stored receipt in either the binary or the legacy JSON format.
+fn decode_receipt(tx: &Transaction<'_>, data: &[u8]) -> anyhow::Result<Receipt> {
+    if compression::is_legacy(data) {
+        let data = zstd::decode_all(data).context("Decompressing receipt")?;
+        let receipt: dto::Receipt =
+            serde_json::from_slice(&data).context("Deserializing receipt")?;
+        return Ok(receipt.into());
+    }
+
+    let receipt: codec::StoredReceipt = codec::decode(tx, data).context("Decoding receipt")?;
+    Ok(receipt.into())
+}
+
+/// A copy of the gateway definitions which were used as the storage serde implementation before the binary encoding.
+/// Having a copy here allows us to decouple this crate from the gateway types, while only exposing the common types
+/// via the storage API. Only used to read rows which have not yet been re-encoded.
 pub(crate) mod dto {
     use fake::{Dummy, Fake, Faker};
     use pathfinder_common::*;
@@ -2198,4 +2669,266 @@ mod tests {
             super::transaction_block_hash(&tx, transaction_hash_bytes!(b"invalid hash")).unwrap();
         assert_eq!(invalid, None);
     }
+
+    #[test]
+    fn transaction_sender_index() {
+        let (mut db, header, _) = setup();
+        let tx = db.transaction().unwrap();
+
+        let sender = contract_address_bytes!(b"invoke v1 contract address");
+        let original = SenderTransaction {
+            nonce: transaction_nonce_bytes!(b"invoke v1 tx nonce"),
+            hash: transaction_hash_bytes!(b"invoke v1 tx hash"),
+            block_number: header.number,
+            index: TransactionIndex::new_or_panic(6),
+        };
+
+        // The index is disabled by default.
+        assert!(!super::transaction_sender_index_enabled(&tx).unwrap());
+        let result =
+            super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 10).unwrap();
+        assert!(result.is_empty());
+
+        // Existing transactions are indexed by the backfill once the index is enabled.
+        super::set_transaction_sender_index(&tx, true).unwrap();
+        assert!(super::transaction_sender_index_enabled(&tx).unwrap());
+        assert!(!super::transaction_sender_index_complete(&tx).unwrap());
+        let result =
+            super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 10).unwrap();
+        assert!(result.is_empty());
+
+        while super::backfill_transaction_senders(&tx, 2).unwrap() {}
+        assert!(super::transaction_sender_index_complete(&tx).unwrap());
+        let result =
+            super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 10).unwrap();
+        assert_eq!(result, vec![original.clone()]);
+
+        // Version 0 transactions are not indexed.
+        let v0_sender = contract_address_bytes!(b"invoke v0 contract address");
+        let result =
+            super::transactions_by_sender(&tx, v0_sender, TransactionNonce::ZERO, 10).unwrap();
+        assert!(result.is_empty());
+
+        // New transactions are indexed once enabled.
+        let header_1 = header
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"block 1 hash"));
+        let invoke = StarknetTransaction {
+            hash: transaction_hash_bytes!(b"block 1 invoke"),
+            variant: TransactionVariant::InvokeV1(InvokeTransactionV1 {
+                sender_address: sender,
+                nonce: transaction_nonce!("0x1"),
+                ..Default::default()
+            }),
+        };
+        let receipt = Receipt {
+            transaction_hash: invoke.hash,
+            ..Default::default()
+        };
+        tx.insert_block_header(&header_1).unwrap();
+        tx.insert_transaction_data(header_1.hash, header_1.number, &[(invoke, receipt)])
+            .unwrap();
+
+        let new = SenderTransaction {
+            nonce: transaction_nonce!("0x1"),
+            hash: transaction_hash_bytes!(b"block 1 invoke"),
+            block_number: header_1.number,
+            index: TransactionIndex::new_or_panic(0),
+        };
+        let result =
+            super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 10).unwrap();
+        assert_eq!(result, vec![new.clone(), original.clone()]);
+
+        let result = super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 1).unwrap();
+        assert_eq!(result, vec![new]);
+
+        let result =
+            super::transactions_by_sender(&tx, sender, transaction_nonce!("0x2"), 10).unwrap();
+        assert_eq!(result, vec![original.clone()]);
+
+        // Purged blocks are removed from the index.
+        tx.purge_block(header_1.number).unwrap();
+        let result =
+            super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 10).unwrap();
+        assert_eq!(result, vec![original]);
+
+        // Disabling the index clears it.
+        super::set_transaction_sender_index(&tx, false).unwrap();
+        assert!(!super::transaction_sender_index_enabled(&tx).unwrap());
+        assert!(!super::transaction_sender_index_complete(&tx).unwrap());
+        let result =
+            super::transactions_by_sender(&tx, sender, TransactionNonce::ZERO, 10).unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn l1_handler_messages() {
+        let (mut db, header, body) = setup();
+        let tx = db.transaction().unwrap();
+
+        let (l1_handler, _) = body.last().unwrap();
+        let TransactionVariant::L1Handler(l1_handler_variant) = &l1_handler.variant else {
+            panic!("Expected the last transaction to be an L1 handler");
+        };
+        let message_hash = l1_handler_variant.calculate_message_hash();
+
+        let log = pathfinder_ethereum::L1ToL2MessageLog {
+            message_hash,
+            l1_block_number: 100,
+            l1_transaction_hash: primitive_types::H256::from_low_u64_be(1),
+        };
+        tx.insert_message_logs(&[log.clone()]).unwrap();
+
+        let linked = vec![crate::L1ToL2Message {
+            message_hash,
+            l1_handler_transaction: Some(l1_handler.hash),
+        }];
+        let unlinked = vec![crate::L1ToL2Message {
+            message_hash,
+            l1_handler_transaction: None,
+        }];
+
+        // Messages are linked on insert.
+        let result = tx.l1_to_l2_messages(log.l1_transaction_hash).unwrap();
+        assert_eq!(result, linked);
+
+        // Transactions stored before messages were linked on insert are linked by the backfill.
+        tx.inner()
+            .execute("DELETE FROM l1_handler_messages", [])
+            .unwrap();
+        super::L1_HANDLER_BACKFILL.restart(&tx).unwrap();
+        let result = tx.l1_to_l2_messages(log.l1_transaction_hash).unwrap();
+        assert_eq!(result, unlinked);
+
+        while super::backfill_l1_handler_messages(&tx, 2).unwrap() {}
+        let result = tx.l1_to_l2_messages(log.l1_transaction_hash).unwrap();
+        assert_eq!(result, linked);
+        assert!(!super::backfill_l1_handler_messages(&tx, 2).unwrap());
+
+        // Purged blocks are unlinked.
+        tx.purge_block(header.number).unwrap();
+        let result = tx.l1_to_l2_messages(log.l1_transaction_hash).unwrap();
+        assert_eq!(result, unlinked);
+
+        let result = tx
+            .l1_to_l2_messages(primitive_types::H256::from_low_u64_be(2))
+            .unwrap();
+        assert!(result.is_empty());
+    }
+
+    /// Overwrites the stored data with the legacy zstd compressed JSON format.
+    fn store_as_legacy(tx: &crate::Transaction<'_>, body: &[(StarknetTransaction, Receipt)]) {
+        for (transaction, receipt) in body {
+            let transaction_data = zstd::encode_all(
+                &*serde_json::to_vec(&dto::Transaction::from(transaction)).unwrap(),
+                10,
+            )
+            .unwrap();
+            let receipt_data = zstd::encode_all(
+                &*serde_json::to_vec(&dto::Receipt::from(receipt)).unwrap(),
+                10,
+            )
+            .unwrap();
+
+            tx.inner()
+                .execute(
+                    "UPDATE starknet_transactions SET tx = ?, receipt = ? WHERE hash = ?",
+                    params![&transaction_data, &receipt_data, &transaction.hash],
+                )
+                .unwrap();
+        }
+    }
+
+    fn stored_blobs(tx: &crate::Transaction<'_>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        tx.inner()
+            .prepare("SELECT tx, receipt FROM starknet_transactions ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    mod encoding {
+        use super::*;
+
+        #[test]
+        fn legacy_rows_are_readable() {
+            let (mut db, header, body) = setup();
+            let tx = db.transaction().unwrap();
+            store_as_legacy(&tx, &body);
+
+            let result = super::super::transaction_data_for_block(&tx, header.number.into())
+                .unwrap()
+                .unwrap();
+            assert_eq!(result, body);
+        }
+
+        #[test]
+        fn reencode_legacy_rows() {
+            let (mut db, header, body) = setup();
+            let tx = db.transaction().unwrap();
+            store_as_legacy(&tx, &body);
+
+            super::super::restart_reencoding(&tx).unwrap();
+            while super::super::reencode_transactions(&tx, 3).unwrap() {}
+            // Progress is kept, so finished work is not repeated.
+            assert!(!super::super::reencode_transactions(&tx, 3).unwrap());
+
+            for (transaction, receipt) in stored_blobs(&tx) {
+                assert_eq!(compression::dictionary_id(&transaction), Some(0));
+                assert_eq!(compression::dictionary_id(&receipt), Some(0));
+            }
+
+            let result = super::super::transaction_data_for_block(&tx, header.number.into())
+                .unwrap()
+                .unwrap();
+            assert_eq!(result, body);
+        }
+
+        #[test]
+        fn latest_dictionary_is_used() {
+            let (mut db, header, body) = setup();
+            let tx = db.transaction().unwrap();
+
+            // Any data is a valid raw content dictionary.
+            let tx_dictionary = compression::insert_dictionary(
+                &tx,
+                DictionaryKind::Transaction,
+                b"transaction hash",
+            )
+            .unwrap();
+            let receipt_dictionary =
+                compression::insert_dictionary(&tx, DictionaryKind::Receipt, b"receipt events")
+                    .unwrap();
+
+            super::super::restart_reencoding(&tx).unwrap();
+            super::super::reencode_transactions(&tx, body.len()).unwrap();
+
+            for (transaction, receipt) in stored_blobs(&tx) {
+                assert_eq!(
+                    compression::dictionary_id(&transaction),
+                    Some(tx_dictionary)
+                );
+                assert_eq!(
+                    compression::dictionary_id(&receipt),
+                    Some(receipt_dictionary)
+                );
+            }
+
+            let result = super::super::transaction_data_for_block(&tx, header.number.into())
+                .unwrap()
+                .unwrap();
+            assert_eq!(result, body);
+        }
+
+        #[test]
+        fn training_requires_enough_samples() {
+            let (mut db, _, _) = setup();
+            let tx = db.transaction().unwrap();
+
+            let trained = super::super::train_compression_dictionaries(&tx, 10_000).unwrap();
+            assert!(!trained);
+        }
+    }
 }
@@ -0,0 +1,911 @@
+//! Versioned binary encoding of transactions and receipts.
+//!
+//! Values are [bincode] encoded and then compressed into the
+//! [dictionary compressed blob format](super::super::compression).
+//!
+//! Rows written before this encoding existed contain zstd compressed JSON instead. These
+//! are recognised as [legacy](super::super::compression::is_legacy) blobs.
+//!
+//! The types in this module define the wire format. bincode is not self-describing, so any
+//! change to them, including appending a field or an enum variant, makes existing rows
+//! undecodable. Every such change must bump the version byte of the
+//! [blob format](super::super::compression) and keep the previous types, so that rows written
+//! with an older version are still decoded by the types they were written with.
+
+use anyhow::Context;
+use pathfinder_common::receipt as common_receipt;
+use pathfinder_common::transaction as common;
+use pathfinder_common::*;
+use pathfinder_crypto::Felt;
+use primitive_types::H160;
+
+use crate::connection::compression;
+use crate::prelude::*;
+
+const CODEC_CFG: bincode::config::Configuration = bincode::config::standard();
+
+/// The uncompressed bincode encoding, as used for compression and dictionary training samples.
+pub(super) fn serialize<T: bincode::Encode>(value: &T) -> anyhow::Result<Vec<u8>> {
+    bincode::encode_to_vec(value, CODEC_CFG).context("Encoding")
+}
+
+/// Decodes a blob in the binary encoding. Legacy blobs must be handled by the caller.
+pub(super) fn decode<T: bincode::Decode>(tx: &Transaction<'_>, blob: &[u8]) -> anyhow::Result<T> {
+    let data = compression::decompress(tx, blob)?;
+    let (value, _) = bincode::decode_from_slice(&data, CODEC_CFG).context("Decoding")?;
+    Ok(value)
+}
+
+/// A felt encoded as its raw 32 big-endian bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct RawFelt(Felt);
+
+impl bincode::Encode for RawFelt {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        bincode::Encode::encode(self.0.as_be_bytes(), encoder)
+    }
+}
+
+impl bincode::Decode for RawFelt {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let bytes = <[u8; 32] as bincode::Decode>::decode(decoder)?;
+        Felt::from_be_bytes(bytes)
+            .map(Self)
+            .map_err(|_| bincode::error::DecodeError::Other("Felt exceeds the field modulus"))
+    }
+}
+
+bincode::impl_borrow_decode!(RawFelt);
+
+/// Felt newtypes which are stored as lists.
+trait FeltNewtype: Copy {
+    fn to_felt(self) -> Felt;
+    fn from_felt(felt: Felt) -> Self;
+}
+
+macro_rules! felt_newtype {
+    ($($target:ty),+ $(,)?) => {
+        $(impl FeltNewtype for $target {
+            fn to_felt(self) -> Felt {
+                self.0
+            }
+
+            fn from_felt(felt: Felt) -> Self {
+                Self(felt)
+            }
+        })+
+    };
+}
+
+felt_newtype!(
+    AccountDeploymentDataElem,
+    CallParam,
+    ConstructorParam,
+    EventData,
+    EventKey,
+    L2ToL1MessagePayloadElem,
+    PaymasterDataElem,
+    TransactionSignatureElem,
+);
+
+fn raw<T: FeltNewtype>(values: &[T]) -> Vec<RawFelt> {
+    values.iter().map(|x| RawFelt(x.to_felt())).collect()
+}
+
+fn typed<T: FeltNewtype>(values: Vec<RawFelt>) -> Vec<T> {
+    values.into_iter().map(|x| T::from_felt(x.0)).collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub(super) struct StoredTransaction {
+    hash: RawFelt,
+    variant: TransactionVariant,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+enum TransactionVariant {
+    DeclareV0(DeclareTransactionV0V1),
+    DeclareV1(DeclareTransactionV0V1),
+    DeclareV2(DeclareTransactionV2),
+    DeclareV3(DeclareTransactionV3),
+    Deploy(DeployTransaction),
+    DeployAccountV0V1(DeployAccountTransactionV0V1),
+    DeployAccountV3(DeployAccountTransactionV3),
+    InvokeV0(InvokeTransactionV0),
+    InvokeV1(InvokeTransactionV1),
+    InvokeV3(InvokeTransactionV3),
+    L1Handler(L1HandlerTransaction),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct DeclareTransactionV0V1 {
+    class_hash: RawFelt,
+    max_fee: RawFelt,
+    nonce: RawFelt,
+    signature: Vec<RawFelt>,
+    sender_address: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct DeclareTransactionV2 {
+    class_hash: RawFelt,
+    max_fee: RawFelt,
+    nonce: RawFelt,
+    signature: Vec<RawFelt>,
+    sender_address: RawFelt,
+    compiled_class_hash: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct DeclareTransactionV3 {
+    class_hash: RawFelt,
+    nonce: RawFelt,
+    nonce_data_availability_mode: DataAvailabilityMode,
+    fee_data_availability_mode: DataAvailabilityMode,
+    resource_bounds: ResourceBounds,
+    tip: u64,
+    paymaster_data: Vec<RawFelt>,
+    signature: Vec<RawFelt>,
+    account_deployment_data: Vec<RawFelt>,
+    sender_address: RawFelt,
+    compiled_class_hash: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct DeployTransaction {
+    class_hash: RawFelt,
+    contract_address: RawFelt,
+    version: RawFelt,
+    contract_address_salt: RawFelt,
+    constructor_calldata: Vec<RawFelt>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct DeployAccountTransactionV0V1 {
+    contract_address: RawFelt,
+    max_fee: RawFelt,
+    version: RawFelt,
+    signature: Vec<RawFelt>,
+    nonce: RawFelt,
+    contract_address_salt: RawFelt,
+    constructor_calldata: Vec<RawFelt>,
+    class_hash: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct DeployAccountTransactionV3 {
+    contract_address: RawFelt,
+    signature: Vec<RawFelt>,
+    nonce: RawFelt,
+    nonce_data_availability_mode: DataAvailabilityMode,
+    fee_data_availability_mode: DataAvailabilityMode,
+    resource_bounds: ResourceBounds,
+    tip: u64,
+    paymaster_data: Vec<RawFelt>,
+    contract_address_salt: RawFelt,
+    constructor_calldata: Vec<RawFelt>,
+    class_hash: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct InvokeTransactionV0 {
+    calldata: Vec<RawFelt>,
+    sender_address: RawFelt,
+    entry_point_selector: RawFelt,
+    entry_point_type: Option<EntryPointType>,
+    max_fee: RawFelt,
+    signature: Vec<RawFelt>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct InvokeTransactionV1 {
+    calldata: Vec<RawFelt>,
+    sender_address: RawFelt,
+    max_fee: RawFelt,
+    signature: Vec<RawFelt>,
+    nonce: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct InvokeTransactionV3 {
+    signature: Vec<RawFelt>,
+    nonce: RawFelt,
+    nonce_data_availability_mode: DataAvailabilityMode,
+    fee_data_availability_mode: DataAvailabilityMode,
+    resource_bounds: ResourceBounds,
+    tip: u64,
+    paymaster_data: Vec<RawFelt>,
+    account_deployment_data: Vec<RawFelt>,
+    calldata: Vec<RawFelt>,
+    sender_address: RawFelt,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct L1HandlerTransaction {
+    contract_address: RawFelt,
+    entry_point_selector: RawFelt,
+    nonce: RawFelt,
+    calldata: Vec<RawFelt>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+enum EntryPointType {
+    External,
+    L1Handler,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct ResourceBounds {
+    l1_gas: ResourceBound,
+    l2_gas: ResourceBound,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct ResourceBound {
+    max_amount: u64,
+    max_price_per_unit: u128,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+enum DataAvailabilityMode {
+    L1,
+    L2,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub(super) struct StoredReceipt {
+    actual_fee: Option<RawFelt>,
+    events: Vec<Event>,
+    execution_resources: Option<ExecutionResources>,
+    l2_to_l1_messages: Vec<L2ToL1Message>,
+    execution_status: ExecutionStatus,
+    transaction_hash: RawFelt,
+    transaction_index: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct Event {
+    data: Vec<RawFelt>,
+    from_address: RawFelt,
+    keys: Vec<RawFelt>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct ExecutionResources {
+    builtin_instance_counter: BuiltinCounters,
+    n_steps: u64,
+    n_memory_holes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct BuiltinCounters {
+    output_builtin: u64,
+    pedersen_builtin: u64,
+    range_check_builtin: u64,
+    ecdsa_builtin: u64,
+    bitwise_builtin: u64,
+    ec_op_builtin: u64,
+    keccak_builtin: u64,
+    poseidon_builtin: u64,
+    segment_arena_builtin: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+struct L2ToL1Message {
+    from_address: RawFelt,
+    payload: Vec<RawFelt>,
+    to_address: [u8; 20],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+enum ExecutionStatus {
+    Succeeded,
+    Reverted { reason: String },
+}
+
+impl From<&common::Transaction> for StoredTransaction {
+    fn from(value: &common::Transaction) -> Self {
+        use common::TransactionVariant::*;
+
+        let variant = match &value.variant {
+            DeclareV0(tx) => TransactionVariant::DeclareV0(tx.into()),
+            DeclareV1(tx) => TransactionVariant::DeclareV1(tx.into()),
+            DeclareV2(tx) => TransactionVariant::DeclareV2(DeclareTransactionV2 {
+                class_hash: RawFelt(tx.class_hash.0),
+                max_fee: RawFelt(tx.max_fee.0),
+                nonce: RawFelt(tx.nonce.0),
+                signature: raw(&tx.signature),
+                sender_address: RawFelt(tx.sender_address.0),
+                compiled_class_hash: RawFelt(tx.compiled_class_hash.0),
+            }),
+            DeclareV3(tx) => TransactionVariant::DeclareV3(DeclareTransactionV3 {
+                class_hash: RawFelt(tx.class_hash.0),
+                nonce: RawFelt(tx.nonce.0),
+                nonce_data_availability_mode: tx.nonce_data_availability_mode.into(),
+                fee_data_availability_mode: tx.fee_data_availability_mode.into(),
+                resource_bounds: tx.resource_bounds.into(),
+                tip: tx.tip.0,
+                paymaster_data: raw(&tx.paymaster_data),
+                signature: raw(&tx.signature),
+                account_deployment_data: raw(&tx.account_deployment_data),
+                sender_address: RawFelt(tx.sender_address.0),
+                compiled_class_hash: RawFelt(tx.compiled_class_hash.0),
+            }),
+            Deploy(tx) => TransactionVariant::Deploy(DeployTransaction {
+                class_hash: RawFelt(tx.class_hash.0),
+                contract_address: RawFelt(tx.contract_address.0),
+                version: RawFelt(tx.version.0),
+                contract_address_salt: RawFelt(tx.contract_address_salt.0),
+                constructor_calldata: raw(&tx.constructor_calldata),
+            }),
+            DeployAccountV0V1(tx) => {
+                TransactionVariant::DeployAccountV0V1(DeployAccountTransactionV0V1 {
+                    contract_address: RawFelt(tx.contract_address.0),
+                    max_fee: RawFelt(tx.max_fee.0),
+                    version: RawFelt(tx.version.0),
+                    signature: raw(&tx.signature),
+                    nonce: RawFelt(tx.nonce.0),
+                    contract_address_salt: RawFelt(tx.contract_address_salt.0),
+                    constructor_calldata: raw(&tx.constructor_calldata),
+                    class_hash: RawFelt(tx.class_hash.0),
+                })
+            }
+            DeployAccountV3(tx) => {
+                TransactionVariant::DeployAccountV3(DeployAccountTransactionV3 {
+                    contract_address: RawFelt(tx.contract_address.0),
+                    signature: raw(&tx.signature),
+                    nonce: RawFelt(tx.nonce.0),
+                    nonce_data_availability_mode: tx.nonce_data_availability_mode.into(),
+                    fee_data_availability_mode: tx.fee_data_availability_mode.into(),
+                    resource_bounds: tx.resource_bounds.into(),
+                    tip: tx.tip.0,
+                    paymaster_data: raw(&tx.paymaster_data),
+                    contract_address_salt: RawFelt(tx.contract_address_salt.0),
+                    constructor_calldata: raw(&tx.constructor_calldata),
+                    class_hash: RawFelt(tx.class_hash.0),
+                })
+            }
+            InvokeV0(tx) => TransactionVariant::InvokeV0(InvokeTransactionV0 {
+                calldata: raw(&tx.calldata),
+                sender_address: RawFelt(tx.sender_address.0),
+                entry_point_selector: RawFelt(tx.entry_point_selector.0),
+                entry_point_type: tx.entry_point_type.map(|x| match x {
+                    common::EntryPointType::External => EntryPointType::External,
+                    common::EntryPointType::L1Handler => EntryPointType::L1Handler,
+                }),
+                max_fee: RawFelt(tx.max_fee.0),
+                signature: raw(&tx.signature),
+            }),
+            InvokeV1(tx) => TransactionVariant::InvokeV1(InvokeTransactionV1 {
+                calldata: raw(&tx.calldata),
+                sender_address: RawFelt(tx.sender_address.0),
+                max_fee: RawFelt(tx.max_fee.0),
+                signature: raw(&tx.signature),
+                nonce: RawFelt(tx.nonce.0),
+            }),
+            InvokeV3(tx) => TransactionVariant::InvokeV3(InvokeTransactionV3 {
+                signature: raw(&tx.signature),
+                nonce: RawFelt(tx.nonce.0),
+                nonce_data_availability_mode: tx.nonce_data_availability_mode.into(),
+                fee_data_availability_mode: tx.fee_data_availability_mode.into(),
+                resource_bounds: tx.resource_bounds.into(),
+                tip: tx.tip.0,
+                paymaster_data: raw(&tx.paymaster_data),
+                account_deployment_data: raw(&tx.account_deployment_data),
+                calldata: raw(&tx.calldata),
+                sender_address: RawFelt(tx.sender_address.0),
+            }),
+            L1Handler(tx) => TransactionVariant::L1Handler(L1HandlerTransaction {
+                contract_address: RawFelt(tx.contract_address.0),
+                entry_point_selector: RawFelt(tx.entry_point_selector.0),
+                nonce: RawFelt(tx.nonce.0),
+                calldata: raw(&tx.calldata),
+            }),
+        };
+
+        Self {
+            hash: RawFelt(value.hash.0),
+            variant,
+        }
+    }
+}
+
+impl From<StoredTransaction> for common::Transaction {
+    fn from(value: StoredTransaction) -> Self {
+        use common::TransactionVariant as Variant;
+
+        let variant = match value.variant {
+            TransactionVariant::DeclareV0(tx) => Variant::DeclareV0(tx.into()),
+            TransactionVariant::DeclareV1(tx) => Variant::DeclareV1(tx.into()),
+            TransactionVariant::DeclareV2(tx) => Variant::DeclareV2(common::DeclareTransactionV2 {
+                class_hash: ClassHash(tx.class_hash.0),
+                max_fee: Fee(tx.max_fee.0),
+                nonce: TransactionNonce(tx.nonce.0),
+                signature: typed(tx.signature),
+                sender_address: ContractAddress(tx.sender_address.0),
+                compiled_class_hash: CasmHash(tx.compiled_class_hash.0),
+            }),
+            TransactionVariant::DeclareV3(tx) => Variant::DeclareV3(common::DeclareTransactionV3 {
+                class_hash: ClassHash(tx.class_hash.0),
+                nonce: TransactionNonce(tx.nonce.0),
+                nonce_data_availability_mode: tx.nonce_data_availability_mode.into(),
+                fee_data_availability_mode: tx.fee_data_availability_mode.into(),
+                resource_bounds: tx.resource_bounds.into(),
+                tip: Tip(tx.tip),
+                paymaster_data: typed(tx.paymaster_data),
+                signature: typed(tx.signature),
+                account_deployment_data: typed(tx.account_deployment_data),
+                sender_address: ContractAddress(tx.sender_address.0),
+                compiled_class_hash: CasmHash(tx.compiled_class_hash.0),
+            }),
+            TransactionVariant::Deploy(tx) => Variant::Deploy(common::DeployTransaction {
+                class_hash: ClassHash(tx.class_hash.0),
+                contract_address: ContractAddress(tx.contract_address.0),
+                version: TransactionVersion(tx.version.0),
+                contract_address_salt: ContractAddressSalt(tx.contract_address_salt.0),
+                constructor_calldata: typed(tx.constructor_calldata),
+            }),
+            TransactionVariant::DeployAccountV0V1(tx) => {
+                Variant::DeployAccountV0V1(common::DeployAccountTransactionV0V1 {
+                    contract_address: ContractAddress(tx.contract_address.0),
+                    max_fee: Fee(tx.max_fee.0),
+                    version: TransactionVersion(tx.version.0),
+                    signature: typed(tx.signature),
+                    nonce: TransactionNonce(tx.nonce.0),
+                    contract_address_salt: ContractAddressSalt(tx.contract_address_salt.0),
+                    constructor_calldata: typed(tx.constructor_calldata),
+                    class_hash: ClassHash(tx.class_hash.0),
+                })
+            }
+            TransactionVariant::DeployAccountV3(tx) => {
+                Variant::DeployAccountV3(common::DeployAccountTransactionV3 {
+                    contract_address: ContractAddress(tx.contract_address.0),
+                    signature: typed(tx.signature),
+                    nonce: TransactionNonce(tx.nonce.0),
+                    nonce_data_availability_mode: tx.nonce_data_availability_mode.into(),
+                    fee_data_availability_mode: tx.fee_data_availability_mode.into(),
+                    resource_bounds: tx.resource_bounds.into(),
+                    tip: Tip(tx.tip),
+                    paymaster_data: typed(tx.paymaster_data),
+                    contract_address_salt: ContractAddressSalt(tx.contract_address_salt.0),
+                    constructor_calldata: typed(tx.constructor_calldata),
+                    class_hash: ClassHash(tx.class_hash.0),
+                })
+            }
+            TransactionVariant::InvokeV0(tx) => Variant::InvokeV0(common::InvokeTransactionV0 {
+                calldata: typed(tx.calldata),
+                sender_address: ContractAddress(tx.sender_address.0),
+                entry_point_selector: EntryPoint(tx.entry_point_selector.0),
+                entry_point_type: tx.entry_point_type.map(|x| match x {
+                    EntryPointType::External => common::EntryPointType::External,
+                    EntryPointType::L1Handler => common::EntryPointType::L1Handler,
+                }),
+                max_fee: Fee(tx.max_fee.0),
+                signature: typed(tx.signature),
+            }),
+            TransactionVariant::InvokeV1(tx) => Variant::InvokeV1(common::InvokeTransactionV1 {
+                calldata: typed(tx.calldata),
+                sender_address: ContractAddress(tx.sender_address.0),
+                max_fee: Fee(tx.max_fee.0),
+                signature: typed(tx.signature),
+                nonce: TransactionNonce(tx.nonce.0),
+            }),
+            TransactionVariant::InvokeV3(tx) => Variant::InvokeV3(common::InvokeTransactionV3 {
+                signature: typed(tx.signature),
+                nonce: TransactionNonce(tx.nonce.0),
+                nonce_data_availability_mode: tx.nonce_data_availability_mode.into(),
+                fee_data_availability_mode: tx.fee_data_availability_mode.into(),
+                resource_bounds: tx.resource_bounds.into(),
+                tip: Tip(tx.tip),
+                paymaster_data: typed(tx.paymaster_data),
+                account_deployment_data: typed(tx.account_deployment_data),
+                calldata: typed(tx.calldata),
+                sender_address: ContractAddress(tx.sender_address.0),
+            }),
+            TransactionVariant::L1Handler(tx) => Variant::L1Handler(common::L1HandlerTransaction {
+                contract_address: ContractAddress(tx.contract_address.0),
+                entry_point_selector: EntryPoint(tx.entry_point_selector.0),
+                nonce: TransactionNonce(tx.nonce.0),
+                calldata: typed(tx.calldata),
+            }),
+        };
+
+        Self {
+            hash: TransactionHash(value.hash.0),
+            variant,
+        }
+    }
+}
+
+impl From<&common::DeclareTransactionV0V1> for DeclareTransactionV0V1 {
+    fn from(tx: &common::DeclareTransactionV0V1) -> Self {
+        Self {
+            class_hash: RawFelt(tx.class_hash.0),
+            max_fee: RawFelt(tx.max_fee.0),
+            nonce: RawFelt(tx.nonce.0),
+            signature: raw(&tx.signature),
+            sender_address: RawFelt(tx.sender_address.0),
+        }
+    }
+}
+
+impl From<DeclareTransactionV0V1> for common::DeclareTransactionV0V1 {
+    fn from(tx: DeclareTransactionV0V1) -> Self {
+        Self {
+            class_hash: ClassHash(tx.class_hash.0),
+            max_fee: Fee(tx.max_fee.0),
+            nonce: TransactionNonce(tx.nonce.0),
+            signature: typed(tx.signature),
+            sender_address: ContractAddress(tx.sender_address.0),
+        }
+    }
+}
+
+impl From<common::ResourceBounds> for ResourceBounds {
+    fn from(value: common::ResourceBounds) -> Self {
+        let bound = |x: common::ResourceBound| ResourceBound {
+            max_amount: x.max_amount.0,
+            max_price_per_unit: x.max_price_per_unit.0,
+        };
+
+        Self {
+            l1_gas: bound(value.l1_gas),
+            l2_gas: bound(value.l2_gas),
+        }
+    }
+}
+
+impl From<ResourceBounds> for common::ResourceBounds {
+    fn from(value: ResourceBounds) -> Self {
+        let bound = |x: ResourceBound| common::ResourceBound {
+            max_amount: ResourceAmount(x.max_amount),
+            max_price_per_unit: ResourcePricePerUnit(x.max_price_per_unit),
+        };
+
+        Self {
+            l1_gas: bound(value.l1_gas),
+            l2_gas: bound(value.l2_gas),
+        }
+    }
+}
+
+impl From<common::DataAvailabilityMode> for DataAvailabilityMode {
+    fn from(value: common::DataAvailabilityMode) -> Self {
+        match value {
+            common::DataAvailabilityMode::L1 => Self::L1,
+            common::DataAvailabilityMode::L2 => Self::L2,
+        }
+    }
+}
+
+impl From<DataAvailabilityMode> for common::DataAvailabilityMode {
+    fn from(value: DataAvailabilityMode) -> Self {
+        match value {
+            DataAvailabilityMode::L1 => Self::L1,
+            DataAvailabilityMode::L2 => Self::L2,
+        }
+    }
+}
+
+impl From<&common_receipt::Receipt> for StoredReceipt {
+    fn from(value: &common_receipt::Receipt) -> Self {
+        Self {
+            actual_fee: value.actual_fee.map(|x| RawFelt(x.0)),
+            events: value
+                .events
+                .iter()
+                .map(|x| Event {
+                    data: raw(&x.data),
+                    from_address: RawFelt(x.from_address.0),
+                    keys: raw(&x.keys),
+                })
+                .collect(),
+            execution_resources: value.execution_resources.as_ref().map(|x| {
+                let b = &x.builtin_instance_counter;
+                ExecutionResources {
+                    builtin_instance_counter: BuiltinCounters {
+                        output_builtin: b.output_builtin,
+                        pedersen_builtin: b.pedersen_builtin,
+                        range_check_builtin: b.range_check_builtin,
+                        ecdsa_builtin: b.ecdsa_builtin,
+                        bitwise_builtin: b.bitwise_builtin,
+                        ec_op_builtin: b.ec_op_builtin,
+                        keccak_builtin: b.keccak_builtin,
+                        poseidon_builtin: b.poseidon_builtin,
+                        segment_arena_builtin: b.segment_arena_builtin,
+                    },
+                    n_steps: x.n_steps,
+                    n_memory_holes: x.n_memory_holes,
+                }
+            }),
+            l2_to_l1_messages: value
+                .l2_to_l1_messages
+                .iter()
+                .map(|x| L2ToL1Message {
+                    from_address: RawFelt(x.from_address.0),
+                    payload: raw(&x.payload),
+                    to_address: x.to_address.0.to_fixed_bytes(),
+                })
+                .collect(),
+            execution_status: match &value.execution_status {
+                common_receipt::ExecutionStatus::Succeeded => ExecutionStatus::Succeeded,
+                common_receipt::ExecutionStatus::Reverted { reason } => ExecutionStatus::Reverted {
+                    reason: reason.clone(),
+                },
+            },
+            transaction_hash: RawFelt(value.transaction_hash.0),
+            transaction_index: value.transaction_index.get(),
+        }
+    }
+}
+
+impl From<StoredReceipt> for common_receipt::Receipt {
+    fn from(value: StoredReceipt) -> Self {
+        Self {
+            actual_fee: value.actual_fee.map(|x| Fee(x.0)),
+            events: value
+                .events
+                .into_iter()
+                .map(|x| pathfinder_common::event::Event {
+                    data: typed(x.data),
+                    from_address: ContractAddress(x.from_address.0),
+                    keys: typed(x.keys),
+                })
+                .collect(),
+            execution_resources: value.execution_resources.map(|x| {
+                let b = x.builtin_instance_counter;
+                common_receipt::ExecutionResources {
+                    builtin_instance_counter: common_receipt::BuiltinCounters {
+                        output_builtin: b.output_builtin,
+                        pedersen_builtin: b.pedersen_builtin,
+                        range_check_builtin: b.range_check_builtin,
+                        ecdsa_builtin: b.ecdsa_builtin,
+                        bitwise_builtin: b.bitwise_builtin,
+                        ec_op_builtin: b.ec_op_builtin,
+                        keccak_builtin: b.keccak_builtin,
+                        poseidon_builtin: b.poseidon_builtin,
+                        segment_arena_builtin: b.segment_arena_builtin,
+                    },
+                    n_steps: x.n_steps,
+                    n_memory_holes: x.n_memory_holes,
+                }
+            }),
+            l2_to_l1_messages: value
+                .l2_to_l1_messages
+                .into_iter()
+                .map(|x| common_receipt::L2ToL1Message {
+                    from_address: ContractAddress(x.from_address.0),
+                    payload: typed(x.payload),
+                    to_address: EthereumAddress(H160(x.to_address)),
+                })
+                .collect(),
+            execution_status: match value.execution_status {
+                ExecutionStatus::Succeeded => common_receipt::ExecutionStatus::Succeeded,
+                ExecutionStatus::Reverted { reason } => {
+                    common_receipt::ExecutionStatus::Reverted { reason }
+                }
+            },
+            transaction_hash: TransactionHash(value.transaction_hash.0),
+            transaction_index: TransactionIndex::new_or_panic(value.transaction_index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    /// Encodes `value` as stored, compresses it without a dictionary and decodes it again.
+    fn round_trip<T, S>(value: &T) -> T
+    where
+        for<'a> S: From<&'a T>,
+        S: bincode::Encode + bincode::Decode,
+        T: From<S>,
+    {
+        let storage = crate::Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let data = serialize(&S::from(value)).unwrap();
+        let blob = compression::Compressor::new(None)
+            .unwrap()
+            .compress(&data)
+            .unwrap();
+
+        T::from(decode::<S>(&tx, &blob).unwrap())
+    }
+
+    fn resource_bounds() -> common::ResourceBounds {
+        common::ResourceBounds {
+            l1_gas: common::ResourceBound {
+                max_amount: ResourceAmount(10),
+                max_price_per_unit: ResourcePricePerUnit(u128::MAX),
+            },
+            l2_gas: common::ResourceBound {
+                max_amount: ResourceAmount(u64::MAX),
+                max_price_per_unit: ResourcePricePerUnit(20),
+            },
+        }
+    }
+
+    /// A transaction of every variant, with every field set to a non-default value.
+    fn variants() -> Vec<common::TransactionVariant> {
+        use common::TransactionVariant::*;
+
+        let declare_v0_v1 = common::DeclareTransactionV0V1 {
+            class_hash: class_hash!("0x1"),
+            max_fee: fee!("0x2"),
+            nonce: transaction_nonce!("0x3"),
+            signature: vec![transaction_signature_elem!("0x4")],
+            sender_address: contract_address!("0x5"),
+        };
+
+        vec![
+            DeclareV0(declare_v0_v1.clone()),
+            DeclareV1(declare_v0_v1),
+            DeclareV2(common::DeclareTransactionV2 {
+                class_hash: class_hash!("0x1"),
+                max_fee: fee!("0x2"),
+                nonce: transaction_nonce!("0x3"),
+                signature: vec![transaction_signature_elem!("0x4")],
+                sender_address: contract_address!("0x5"),
+                compiled_class_hash: casm_hash!("0x6"),
+            }),
+            DeclareV3(common::DeclareTransactionV3 {
+                class_hash: class_hash!("0x1"),
+                nonce: transaction_nonce!("0x3"),
+                nonce_data_availability_mode: common::DataAvailabilityMode::L2,
+                fee_data_availability_mode: common::DataAvailabilityMode::L1,
+                resource_bounds: resource_bounds(),
+                tip: Tip(7),
+                paymaster_data: vec![paymaster_data_elem!("0x8")],
+                signature: vec![transaction_signature_elem!("0x4")],
+                account_deployment_data: vec![account_deployment_data_elem!("0x9")],
+                sender_address: contract_address!("0x5"),
+                compiled_class_hash: casm_hash!("0x6"),
+            }),
+            Deploy(common::DeployTransaction {
+                class_hash: class_hash!("0x1"),
+                contract_address: contract_address!("0x5"),
+                version: TransactionVersion::ONE,
+                contract_address_salt: contract_address_salt!("0xa"),
+                constructor_calldata: vec![constructor_param!("0xb")],
+            }),
+            DeployAccountV0V1(common::DeployAccountTransactionV0V1 {
+                contract_address: contract_address!("0x5"),
+                max_fee: fee!("0x2"),
+                version: TransactionVersion::ONE_WITH_QUERY_VERSION,
+                signature: vec![transaction_signature_elem!("0x4")],
+                nonce: transaction_nonce!("0x3"),
+                contract_address_salt: contract_address_salt!("0xa"),
+                constructor_calldata: vec![call_param!("0xc")],
+                class_hash: class_hash!("0x1"),
+            }),
+            DeployAccountV3(common::DeployAccountTransactionV3 {
+                contract_address: contract_address!("0x5"),
+                signature: vec![transaction_signature_elem!("0x4")],
+                nonce: transaction_nonce!("0x3"),
+                nonce_data_availability_mode: common::DataAvailabilityMode::L1,
+                fee_data_availability_mode: common::DataAvailabilityMode::L2,
+                resource_bounds: resource_bounds(),
+                tip: Tip(7),
+                paymaster_data: vec![paymaster_data_elem!("0x8")],
+                contract_address_salt: contract_address_salt!("0xa"),
+                constructor_calldata: vec![call_param!("0xc")],
+                class_hash: class_hash!("0x1"),
+            }),
+            InvokeV0(common::InvokeTransactionV0 {
+                calldata: vec![call_param!("0xc"), call_param!("0xd")],
+                sender_address: contract_address!("0x5"),
+                entry_point_selector: entry_point!("0xe"),
+                entry_point_type: Some(common::EntryPointType::L1Handler),
+                max_fee: fee!("0x2"),
+                signature: vec![transaction_signature_elem!("0x4")],
+            }),
+            InvokeV1(common::InvokeTransactionV1 {
+                calldata: vec![call_param!("0xc")],
+                sender_address: contract_address!("0x5"),
+                max_fee: fee!("0x2"),
+                signature: vec![transaction_signature_elem!("0x4")],
+                nonce: transaction_nonce!("0x3"),
+            }),
+            InvokeV3(common::InvokeTransactionV3 {
+                signature: vec![transaction_signature_elem!("0x4")],
+                nonce: transaction_nonce!("0x3"),
+                nonce_data_availability_mode: common::DataAvailabilityMode::L2,
+                fee_data_availability_mode: common::DataAvailabilityMode::L2,
+                resource_bounds: resource_bounds(),
+                tip: Tip(7),
+                paymaster_data: vec![paymaster_data_elem!("0x8")],
+                account_deployment_data: vec![account_deployment_data_elem!("0x9")],
+                calldata: vec![call_param!("0xc")],
+                sender_address: contract_address!("0x5"),
+            }),
+            L1Handler(common::L1HandlerTransaction {
+                contract_address: contract_address!("0x5"),
+                entry_point_selector: entry_point!("0xe"),
+                nonce: transaction_nonce!("0x3"),
+                calldata: vec![call_param!("0xc")],
+            }),
+        ]
+    }
+
+    #[test]
+    fn transactions_round_trip() {
+        for variant in variants() {
+            let transaction = common::Transaction {
+                hash: transaction_hash!("0xff"),
+                variant,
+            };
+
+            let decoded = round_trip::<_, StoredTransaction>(&transaction);
+
+            assert_eq!(decoded, transaction);
+        }
+    }
+
+    #[test]
+    fn receipts_round_trip() {
+        let receipt = common_receipt::Receipt {
+            actual_fee: Some(fee!("0x2")),
+            events: vec![
+                pathfinder_common::event::Event {
+                    data: vec![event_data!("0x1"), event_data!("0x2")],
+                    from_address: contract_address!("0x3"),
+                    keys: vec![event_key!("0x4")],
+                },
+                pathfinder_common::event::Event {
+                    data: vec![],
+                    from_address: contract_address!("0x5"),
+                    keys: vec![event_key!("0x6"), event_key!("0x7")],
+                },
+            ],
+            execution_resources: Some(common_receipt::ExecutionResources {
+                builtin_instance_counter: common_receipt::BuiltinCounters {
+                    output_builtin: 1,
+                    pedersen_builtin: 2,
+                    range_check_builtin: 3,
+                    ecdsa_builtin: 4,
+                    bitwise_builtin: 5,
+                    ec_op_builtin: 6,
+                    keccak_builtin: 7,
+                    poseidon_builtin: 8,
+                    segment_arena_builtin: 9,
+                },
+                n_steps: 10,
+                n_memory_holes: 11,
+            }),
+            l2_to_l1_messages: vec![common_receipt::L2ToL1Message {
+                from_address: contract_address!("0x8"),
+                payload: vec![l2_to_l1_message_payload_elem!("0x9")],
+                to_address: EthereumAddress(H160::from_low_u64_be(10)),
+            }],
+            execution_status: common_receipt::ExecutionStatus::Reverted {
+                reason: "Out of gas".to_owned(),
+            },
+            transaction_hash: transaction_hash!("0xff"),
+            transaction_index: TransactionIndex::new_or_panic(12),
+        };
+
+        let decoded = round_trip::<_, StoredReceipt>(&receipt);
+        assert_eq!(decoded, receipt);
+
+        // Receipts of older blocks lack the fee and execution resources.
+        let receipt = common_receipt::Receipt {
+            actual_fee: None,
+            execution_resources: None,
+            execution_status: common_receipt::ExecutionStatus::Succeeded,
+            ..receipt
+        };
+
+        let decoded = round_trip::<_, StoredReceipt>(&receipt);
+        assert_eq!(decoded, receipt);
+    }
+}
@@ -63,16 +63,13 @@ pub(super) fn contract_root(
     block_number: BlockNumber,
     contract: ContractAddress,
 ) -> anyhow::Result<Option<ContractRoot>> {
-    tx.inner()
-        .query_row(
-            r"SELECT hash FROM trie_contracts WHERE idx = (
-                SELECT root_index FROM contract_roots WHERE block_number <= ? AND contract_address = ? ORDER BY block_number DESC LIMIT 1
-            )",
-            params![&block_number, &contract],
-            |row| row.get_contract_root(0),
-        )
-        .optional()
-        .map_err(Into::into)
+    let Some(index) = contract_root_index(tx, block_number, contract)? else {
+        return Ok(None);
+    };
+
+    let root = tx.contract_trie_node_hash(index)?.map(ContractRoot);
+
+    Ok(root)
 }
 
 pub(super) fn insert_class_root(
@@ -139,6 +136,70 @@ pub(super) fn insert_contract_root(
     Ok(())
 }
 
+/// Encodes the nodes reachable from `root` which have not been stored yet and passes each of them
+/// to `store`, which persists the encoded node and returns its new index.
+///
+/// Children are always stored before their parents, so that the parent can reference the index
+/// of its children. Returns the index of the root.
+pub(super) fn insert_nodes(
+    root: Felt,
+    nodes: &HashMap<Felt, Node>,
+    mut store: impl FnMut(Felt, &[u8]) -> anyhow::Result<u64>,
+) -> anyhow::Result<u64> {
+    let mut to_insert = Vec::new();
+    let mut to_process = vec![Child::Hash(root)];
+
+    while let Some(node) = to_process.pop() {
+        // Only hash variants need to be stored.
+        //
+        // Leaf nodes never get stored and a node having an
+        // ID indicates it has already been stored as part of a
+        // previous tree - and its children as well.
+        let Child::Hash(hash) = node else {
+            continue;
+        };
+
+        let node = nodes.get(&hash).context("New node data is missing")?;
+        to_insert.push(hash);
+
+        match node {
+            Node::Binary { left, right } => {
+                to_process.push(left.clone());
+                to_process.push(right.clone());
+            }
+            Node::Edge { child, .. } => {
+                to_process.push(child.clone());
+            }
+            // Leaves are not stored as separate nodes but are instead serialized in-line in their parents.
+            Node::LeafEdge { .. } | Node::LeafBinary { .. } => {}
+        }
+    }
+
+    let mut indices = HashMap::new();
+
+    // Reusable (and oversized) buffer for encoding.
+    let mut buffer = vec![0u8; 256];
+
+    // Insert nodes in reverse to ensure children always have an assigned index for the parent to use.
+    for hash in to_insert.into_iter().rev() {
+        let node = nodes
+            .get(&hash)
+            .expect("Node must exist as hash is dependent on this");
+
+        let node = node.as_stored(&indices)?;
+
+        let length = node.encode(&mut buffer).context("Encoding node")?;
+
+        let idx = store(hash, &buffer[..length])?;
+
+        indices.insert(hash, idx);
+    }
+
+    Ok(*indices
+        .get(&root)
+        .expect("Root index must exist as we just inserted it"))
+}
+
 mod macros {
     /// Generates the `insert`, `node` and `hash` trie functions for the given table name, within
     /// a module with the table name.
@@ -162,63 +223,12 @@ mod macros {
                         ))
                         .context("Creating insert statement")?;
 
-                    let mut to_insert = Vec::new();
-                    let mut to_process = vec![Child::Hash(root)];
-
-                    while let Some(node) = to_process.pop() {
-                        // Only hash variants need to be stored.
-                        //
-                        // Leaf nodes never get stored and a node having an
-                        // ID indicates it has already been stored as part of a
-                        // previous tree - and its children as well.
-                        let Child::Hash(hash) = node else {
-                            continue;
-                        };
-
-                        let node = nodes.get(&hash).context("New node data is missing")?;
-                        to_insert.push(hash);
-
-                        match node {
-                            Node::Binary { left, right } => {
-                                to_process.push(left.clone());
-                                to_process.push(right.clone());
-                            }
-                            Node::Edge { child, .. } => {
-                                to_process.push(child.clone());
-                            }
-                            // Leaves are not stored as separate nodes but are instead serialized in-line in their parents.
-                            Node::LeafEdge { .. } | Node::LeafBinary { .. } => {}
-                        }
-                    }
-
-                    let mut indices = HashMap::new();
-
-                    // Reusable (and oversized) buffer for encoding.
-                    let mut buffer = vec![0u8; 256];
-
-                    // Insert nodes in reverse to ensure children always have an assigned index for the parent to use.
-                    for hash in to_insert.into_iter().rev() {
-                        let node = nodes
-                            .get(&hash)
-                            .expect("Node must exist as hash is dependent on this");
-
-                        let node = node.as_stored(&indices)?;
-
-                        let length = node.encode(&mut buffer).context("Encoding node")?;
-
-                        let idx: u64 = stmt
-                            .query_row(
-                                params![&hash.as_be_bytes().as_slice(), &&buffer[..length]],
-                                |row| row.get(0),
-                            )
-                            .context("Inserting node")?;
-
-                        indices.insert(hash, idx);
-                    }
-
-                    Ok(*indices
-                        .get(&root)
-                        .expect("Root index must exist as we just inserted it"))
+                    insert_nodes(root, nodes, |hash, data| {
+                        stmt.query_row(params![&hash.as_be_bytes().as_slice(), &data], |row| {
+                            row.get(0)
+                        })
+                        .context("Inserting node")
+                    })
                 }
 
                 /// Returns the node with the given index.
@@ -351,7 +361,7 @@ impl StoredNode {
         bincode::encode_into_slice(helper, buffer, Self::CODEC_CFG)
     }
 
-    fn decode(data: &[u8]) -> Result<Self, bincode::error::DecodeError> {
+    pub(super) fn decode(data: &[u8]) -> Result<Self, bincode::error::DecodeError> {
         let helper = bincode::borrow_decode_from_slice(data, Self::CODEC_CFG)?;
 
         let node = match helper.0 {
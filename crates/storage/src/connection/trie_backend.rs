@@ -0,0 +1,331 @@
+//! Pluggable storage of trie nodes.
+//!
+//! The nodes of the class, contract and storage tries make up the bulk of the database, and
+//! writing them is the main source of SQLite's write amplification. [TrieStorage] abstracts over
+//! where these nodes are kept, while the root indices of each trie remain in SQLite.
+//!
+//! Two implementations are provided:
+//! - [SqliteTrieStorage] keeps the nodes in the `trie_*` tables of the database. This is the default.
+//! - `RocksDbTrieStorage` keeps the nodes in a RocksDB database in a directory next to the database.
+//!   It requires the `rocksdb` feature, without which databases using it cannot be opened.
+//!
+//! Node indices are assigned by the implementation and are only meaningful to it. A database
+//! therefore cannot switch implementations once it contains trie nodes. The implementation in use
+//! is recorded in the `trie_backend` table.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use pathfinder_crypto::Felt;
+
+use super::trie::{self, Node, StoredNode};
+use crate::Transaction;
+
+#[cfg(feature = "rocksdb")]
+mod rocksdb;
+
+#[cfg(feature = "rocksdb")]
+pub use self::rocksdb::RocksDbTrieStorage;
+
+/// The error returned when the RocksDB trie backend is used without the `rocksdb` feature.
+pub(crate) const ROCKSDB_DISABLED: &str =
+    "The RocksDB trie backend requires pathfinder to be built with the `rocksdb` feature";
+
+/// Selects the [TrieStorage] implementation of a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrieBackend {
+    /// See [SqliteTrieStorage].
+    #[default]
+    Sqlite,
+    /// See `RocksDbTrieStorage`, which requires the `rocksdb` feature.
+    RocksDb,
+}
+
+impl TrieBackend {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TrieBackend::Sqlite => "sqlite",
+            TrieBackend::RocksDb => "rocksdb",
+        }
+    }
+
+    fn from_str(backend: &str) -> anyhow::Result<Self> {
+        match backend {
+            "sqlite" => Ok(TrieBackend::Sqlite),
+            "rocksdb" => Ok(TrieBackend::RocksDb),
+            other => anyhow::bail!("Unknown trie backend {other}"),
+        }
+    }
+}
+
+/// Identifies one of the tries stored by pathfinder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrieKind {
+    /// The global class trie.
+    Class,
+    /// The storage tries of individual contracts.
+    Contract,
+    /// The global contract state trie.
+    Storage,
+}
+
+/// Stores and retrieves trie nodes.
+///
+/// Implementations are shared between all connections of a [Storage](crate::Storage), and each
+/// call is given the database transaction it is made from.
+pub trait TrieStorage: Send + Sync {
+    /// The [TrieBackend] which selects this implementation.
+    fn backend(&self) -> TrieBackend;
+
+    /// Stores the new nodes of a trie and returns the index of its root.
+    fn insert(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        root: Felt,
+        nodes: &HashMap<Felt, Node>,
+    ) -> anyhow::Result<u64>;
+
+    /// Returns the node with the given index.
+    fn node(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<StoredNode>>;
+
+    /// Returns the hash of the node with the given index.
+    fn hash(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<Felt>>;
+
+    /// Allows the implementation to remove the nodes with an index greater than `last`, which are
+    /// no longer referenced by any root once the database transaction commits. Nodes which the
+    /// transaction inserts afterwards must be kept.
+    fn truncate(&self, tx: &Transaction<'_>, kind: TrieKind, last: u64) -> anyhow::Result<()>;
+
+    /// Called before a database transaction commits. Implementations must ensure all nodes
+    /// inserted by the transaction are durable, so that committed roots never reference nodes
+    /// which could still be lost.
+    fn before_commit(&self, tx: &Transaction<'_>) -> anyhow::Result<()>;
+
+    /// Called once a database transaction has committed, with the changes it made.
+    fn after_commit(&self, writes: PendingTrieWrites) -> anyhow::Result<()>;
+
+    /// Copies the nodes referenced by `snapshot` next to the copy of the database being made at
+    /// `database_path`. Nodes stored in the database itself are copied along with it.
+    fn backup(&self, snapshot: &rusqlite::Connection, database_path: &Path) -> anyhow::Result<()>;
+}
+
+/// Trie changes made by a database transaction which are only applied to a [TrieStorage] that
+/// does not share the transaction once it commits.
+///
+/// Changes of a transaction which is rolled back are simply dropped.
+#[derive(Default)]
+#[cfg_attr(not(feature = "rocksdb"), allow(dead_code))]
+pub struct PendingTrieWrites {
+    /// The hash and encoded data of each inserted node.
+    nodes: RefCell<HashMap<(TrieKind, u64), (Felt, Vec<u8>)>>,
+    /// The indices of the nodes removed from each truncated trie. Nodes inserted after a
+    /// truncation have greater indices, so they are not part of its range.
+    truncations: RefCell<Vec<(TrieKind, Range<u64>)>>,
+}
+
+#[cfg_attr(not(feature = "rocksdb"), allow(dead_code))]
+impl PendingTrieWrites {
+    /// Returns the hash and encoded data of a node inserted by the transaction.
+    fn get(&self, kind: TrieKind, index: u64) -> Option<(Felt, Vec<u8>)> {
+        self.nodes.borrow().get(&(kind, index)).cloned()
+    }
+
+    fn insert(&self, kind: TrieKind, index: u64, hash: Felt, data: Vec<u8>) {
+        self.nodes.borrow_mut().insert((kind, index), (hash, data));
+    }
+
+    /// Removes the nodes of a trie with an index greater than `last` and less than `next`, the
+    /// index of the next node to be inserted.
+    fn truncate(&self, kind: TrieKind, last: u64, next: u64) {
+        self.nodes
+            .borrow_mut()
+            .retain(|(k, index), _| *k != kind || *index <= last);
+
+        if last + 1 < next {
+            self.truncations.borrow_mut().push((kind, last + 1..next));
+        }
+    }
+}
+
+/// Stores trie nodes in the `trie_class`, `trie_contracts` and `trie_storage` tables.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SqliteTrieStorage;
+
+impl TrieStorage for SqliteTrieStorage {
+    fn backend(&self) -> TrieBackend {
+        TrieBackend::Sqlite
+    }
+
+    fn insert(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        root: Felt,
+        nodes: &HashMap<Felt, Node>,
+    ) -> anyhow::Result<u64> {
+        match kind {
+            TrieKind::Class => trie::trie_class::insert(tx, root, nodes),
+            TrieKind::Contract => trie::trie_contracts::insert(tx, root, nodes),
+            TrieKind::Storage => trie::trie_storage::insert(tx, root, nodes),
+        }
+    }
+
+    fn node(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<StoredNode>> {
+        match kind {
+            TrieKind::Class => trie::trie_class::node(tx, index),
+            TrieKind::Contract => trie::trie_contracts::node(tx, index),
+            TrieKind::Storage => trie::trie_storage::node(tx, index),
+        }
+    }
+
+    fn hash(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<Felt>> {
+        match kind {
+            TrieKind::Class => trie::trie_class::hash(tx, index),
+            TrieKind::Contract => trie::trie_contracts::hash(tx, index),
+            TrieKind::Storage => trie::trie_storage::hash(tx, index),
+        }
+    }
+
+    fn truncate(&self, _tx: &Transaction<'_>, _kind: TrieKind, _last: u64) -> anyhow::Result<()> {
+        // Unreferenced nodes have always been kept in the SQLite tables.
+        Ok(())
+    }
+
+    fn before_commit(&self, _tx: &Transaction<'_>) -> anyhow::Result<()> {
+        // Nodes are committed as part of the database transaction.
+        Ok(())
+    }
+
+    fn after_commit(&self, _writes: PendingTrieWrites) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn backup(
+        &self,
+        _snapshot: &rusqlite::Connection,
+        _database_path: &Path,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the directory used by `RocksDbTrieStorage` for the database at `database_path`.
+pub(crate) fn rocksdb_directory(database_path: &Path) -> PathBuf {
+    database_path.with_extension("tries")
+}
+
+/// Returns the greatest root index of each trie, or zero if a trie has no root.
+///
+/// Indices are assigned in increasing order and children are stored before their parents, so
+/// nodes with a greater index are not referenced by any root.
+pub(crate) fn last_root_indices(
+    connection: &rusqlite::Connection,
+) -> anyhow::Result<[(TrieKind, u64); 3]> {
+    let last = |table: &str| -> anyhow::Result<u64> {
+        connection
+            .query_row(
+                &format!(
+                    r"SELECT IFNULL(MAX(root_index), 0) FROM {table} WHERE block_number = (
+                        SELECT block_number FROM {table} WHERE root_index IS NOT NULL
+                        ORDER BY block_number DESC LIMIT 1
+                    )"
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Querying last root index from {table}"))
+    };
+
+    Ok([
+        (TrieKind::Class, last("class_roots")?),
+        (TrieKind::Contract, last("contract_roots")?),
+        (TrieKind::Storage, last("storage_roots")?),
+    ])
+}
+
+/// Returns the [TrieBackend] recorded in the database.
+pub(crate) fn stored_backend(connection: &rusqlite::Connection) -> anyhow::Result<TrieBackend> {
+    let backend: String = connection
+        .query_row("SELECT backend FROM trie_backend WHERE id = 1", [], |row| {
+            row.get(0)
+        })
+        .context("Querying trie backend")?;
+
+    TrieBackend::from_str(&backend)
+}
+
+/// Records the [TrieBackend] of the database.
+pub(crate) fn store_backend(
+    connection: &rusqlite::Connection,
+    backend: TrieBackend,
+) -> anyhow::Result<()> {
+    connection
+        .execute(
+            "UPDATE trie_backend SET backend = ? WHERE id = 1",
+            [backend.as_str()],
+        )
+        .context("Updating trie backend")?;
+
+    Ok(())
+}
+
+/// Opens the trie storage recorded in the database at `database_path`.
+///
+/// Fails if the database uses `RocksDbTrieStorage` but its directory is missing, as the root
+/// indices stored in the database would otherwise refer to nodes which no longer exist, or if the
+/// `rocksdb` feature is disabled.
+pub(crate) fn open(
+    connection: &rusqlite::Connection,
+    database_path: &Path,
+    read_only: bool,
+) -> anyhow::Result<Arc<dyn TrieStorage>> {
+    match stored_backend(connection)? {
+        TrieBackend::Sqlite => Ok(Arc::new(SqliteTrieStorage)),
+        TrieBackend::RocksDb => {
+            let directory = rocksdb_directory(database_path);
+            anyhow::ensure!(
+                directory.is_dir(),
+                "The database stores its trie nodes in {}, but this directory does not exist",
+                directory.display()
+            );
+
+            open_rocksdb(&directory, read_only)
+                .with_context(|| format!("Opening trie storage at {}", directory.display()))
+        }
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+fn open_rocksdb(directory: &Path, read_only: bool) -> anyhow::Result<Arc<dyn TrieStorage>> {
+    Ok(Arc::new(RocksDbTrieStorage::open(directory, read_only)?))
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn open_rocksdb(_directory: &Path, _read_only: bool) -> anyhow::Result<Arc<dyn TrieStorage>> {
+    anyhow::bail!(ROCKSDB_DISABLED)
+}
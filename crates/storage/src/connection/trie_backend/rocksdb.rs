@@ -0,0 +1,592 @@
+//! Trie nodes stored in [RocksDB](https://rocksdb.org), an embedded LSM key-value store.
+//!
+//! Each trie is stored in its own column family, with nodes keyed by their big-endian index. A
+//! node's value is its hash followed by its encoded data. The next index of each trie is kept in
+//! the default column family, so that indices are never reused once nodes have been removed.
+//!
+//! Nodes inserted by a database transaction are buffered in its [PendingTrieWrites] and written
+//! in a single synced batch just before the transaction commits, so nodes of transactions which
+//! are rolled back are never written. Nodes which are no longer referenced after a reorg are
+//! deleted once the reorg commits, and their space is reclaimed by RocksDB's compaction.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use pathfinder_crypto::Felt;
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{ColumnFamily, IteratorMode, Options, WriteBatch, WriteOptions, DB};
+
+use super::{PendingTrieWrites, TrieBackend, TrieKind, TrieStorage};
+use crate::connection::trie::{self, Node, StoredNode};
+use crate::Transaction;
+
+const KINDS: [TrieKind; 3] = [TrieKind::Class, TrieKind::Contract, TrieKind::Storage];
+
+/// The size of the node hash at the start of each value.
+const HASH_SIZE: usize = 32;
+
+/// Stores trie nodes in a RocksDB database, with one column family per trie.
+///
+/// This avoids the write amplification of maintaining SQLite's b-trees for the trie tables. A
+/// single process writes to the database, while any number of processes may read from it by
+/// opening it read-only.
+pub struct RocksDbTrieStorage {
+    db: DB,
+    /// The next index of each trie, or [None] if opened read-only.
+    next: Option<Mutex<HashMap<TrieKind, u64>>>,
+    /// The directory of the secondary instance used to read the database of another process,
+    /// which is removed when the storage is dropped.
+    secondary: Option<PathBuf>,
+}
+
+impl RocksDbTrieStorage {
+    /// Creates an empty trie storage in `directory`, which must not exist yet.
+    pub fn create(directory: &Path) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !directory.exists(),
+            "{} already exists",
+            directory.display()
+        );
+
+        Self::open_primary(directory, true)
+    }
+
+    /// Opens the existing trie storage in `directory`.
+    ///
+    /// Read-only storage is opened as a RocksDB secondary instance, which catches up with the
+    /// nodes written by the writing process whenever a node cannot be found.
+    pub fn open(directory: &Path, read_only: bool) -> anyhow::Result<Self> {
+        match read_only {
+            true => Self::open_secondary(directory),
+            false => Self::open_primary(directory, false),
+        }
+    }
+
+    fn open_primary(directory: &Path, create: bool) -> anyhow::Result<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(create);
+        options.create_missing_column_families(create);
+
+        let db = DB::open_cf(&options, directory, KINDS.map(column_family_name))
+            .context("Opening RocksDB")?;
+
+        let mut next = HashMap::new();
+        for kind in KINDS {
+            next.insert(kind, next_index(&db, kind)?);
+        }
+
+        Ok(Self {
+            db,
+            next: Some(Mutex::new(next)),
+            secondary: None,
+        })
+    }
+
+    fn open_secondary(directory: &Path) -> anyhow::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let secondary = std::env::temp_dir().join(format!(
+            "pathfinder-tries-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut options = Options::default();
+        // Secondary instances must keep all files open to follow the primary.
+        options.set_max_open_files(-1);
+
+        let db = DB::open_cf_as_secondary(
+            &options,
+            directory,
+            secondary.as_path(),
+            KINDS.map(column_family_name),
+        )
+        .context("Opening RocksDB as secondary")?;
+
+        Ok(Self {
+            db,
+            next: None,
+            secondary: Some(secondary),
+        })
+    }
+
+    fn column_family(&self, kind: TrieKind) -> anyhow::Result<&ColumnFamily> {
+        self.db
+            .cf_handle(column_family_name(kind))
+            .with_context(|| format!("Column family of the {kind:?} trie is missing"))
+    }
+
+    /// Returns the hash and encoded data of the node with the given index.
+    fn record(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<(Felt, Vec<u8>)>> {
+        if let Some(record) = tx.trie_writes().get(kind, index) {
+            return Ok(Some(record));
+        }
+
+        let column_family = self.column_family(kind)?;
+        let mut value = self
+            .db
+            .get_pinned_cf(column_family, index.to_be_bytes())
+            .context("Reading node")?;
+
+        // The node may have been written by the primary since this instance last caught up.
+        if value.is_none() && self.secondary.is_some() {
+            self.db
+                .try_catch_up_with_primary()
+                .context("Catching up with primary")?;
+            value = self
+                .db
+                .get_pinned_cf(column_family, index.to_be_bytes())
+                .context("Reading node")?;
+        }
+
+        let Some(value) = value else {
+            return Ok(None);
+        };
+
+        anyhow::ensure!(value.len() >= HASH_SIZE, "Node {index} is truncated");
+        let hash = Felt::from_be_slice(&value[..HASH_SIZE]).context("Parsing node hash")?;
+
+        Ok(Some((hash, value[HASH_SIZE..].to_vec())))
+    }
+
+    /// Copies the nodes to `directory`, which must not exist yet.
+    ///
+    /// A writable storage is copied using a RocksDB checkpoint, which hard links the existing
+    /// files. A read-only storage cannot create checkpoints, so its nodes are copied one by one.
+    fn copy(&self, directory: &Path) -> anyhow::Result<()> {
+        if self.secondary.is_none() {
+            return Checkpoint::new(&self.db)
+                .and_then(|checkpoint| checkpoint.create_checkpoint(directory))
+                .context("Creating RocksDB checkpoint");
+        }
+
+        self.db
+            .try_catch_up_with_primary()
+            .context("Catching up with primary")?;
+
+        let copy = Self::create(directory)?;
+        let mut batch = WriteBatch::default();
+        for kind in KINDS {
+            let source = self.column_family(kind)?;
+            let target = copy.column_family(kind)?;
+
+            for item in self.db.iterator_cf(source, IteratorMode::Start) {
+                let (key, value) = item.context("Reading node")?;
+                batch.put_cf(target, key, value);
+
+                if batch.len() >= 10_000 {
+                    copy.db
+                        .write(std::mem::take(&mut batch))
+                        .context("Writing nodes")?;
+                }
+            }
+        }
+        copy.db.write(batch).context("Writing nodes")?;
+
+        for kind in KINDS {
+            let name = column_family_name(kind);
+            if let Some(next) = self.db.get(name).context("Reading next index")? {
+                copy.db.put(name, next).context("Writing next index")?;
+            }
+        }
+
+        copy.db.flush().context("Flushing copied nodes")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for RocksDbTrieStorage {
+    fn drop(&mut self) {
+        if let Some(secondary) = &self.secondary {
+            let _ = std::fs::remove_dir_all(secondary);
+        }
+    }
+}
+
+impl TrieStorage for RocksDbTrieStorage {
+    fn backend(&self) -> TrieBackend {
+        TrieBackend::RocksDb
+    }
+
+    fn insert(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        root: Felt,
+        nodes: &HashMap<Felt, Node>,
+    ) -> anyhow::Result<u64> {
+        let next = self
+            .next
+            .as_ref()
+            .context("Trie storage was opened read-only")?;
+        let mut next = next.lock().unwrap();
+        let next = next.get_mut(&kind).expect("Every trie has a next index");
+
+        let writes = tx.trie_writes();
+        trie::insert_nodes(root, nodes, |hash, data| {
+            let index = *next;
+            *next += 1;
+            writes.insert(kind, index, hash, data.to_vec());
+
+            Ok(index)
+        })
+    }
+
+    fn node(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<StoredNode>> {
+        let Some((_, data)) = self.record(tx, kind, index)? else {
+            return Ok(None);
+        };
+
+        let node = StoredNode::decode(&data).context("Decoding node")?;
+
+        Ok(Some(node))
+    }
+
+    fn hash(
+        &self,
+        tx: &Transaction<'_>,
+        kind: TrieKind,
+        index: u64,
+    ) -> anyhow::Result<Option<Felt>> {
+        Ok(self.record(tx, kind, index)?.map(|(hash, _)| hash))
+    }
+
+    fn truncate(&self, tx: &Transaction<'_>, kind: TrieKind, last: u64) -> anyhow::Result<()> {
+        let next = self
+            .next
+            .as_ref()
+            .context("Trie storage was opened read-only")?;
+        let next = next.lock().unwrap()[&kind];
+
+        tx.trie_writes().truncate(kind, last, next);
+        Ok(())
+    }
+
+    fn before_commit(&self, tx: &Transaction<'_>) -> anyhow::Result<()> {
+        let writes = tx.trie_writes();
+        let nodes = writes.nodes.borrow();
+        if nodes.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = WriteBatch::default();
+        for ((kind, index), (hash, data)) in nodes.iter() {
+            let mut value = Vec::with_capacity(HASH_SIZE + data.len());
+            value.extend_from_slice(hash.as_be_bytes());
+            value.extend_from_slice(data);
+
+            batch.put_cf(self.column_family(*kind)?, index.to_be_bytes(), value);
+        }
+
+        // Persist the next indices, so that they are never reused after nodes are removed.
+        if let Some(next) = &self.next {
+            for (kind, index) in next.lock().unwrap().iter() {
+                batch.put(column_family_name(*kind), index.to_be_bytes());
+            }
+        }
+
+        let mut options = WriteOptions::default();
+        options.set_sync(true);
+        self.db
+            .write_opt(batch, &options)
+            .context("Writing nodes")?;
+
+        Ok(())
+    }
+
+    fn after_commit(&self, writes: PendingTrieWrites) -> anyhow::Result<()> {
+        let truncations = writes.truncations.into_inner();
+        if truncations.is_empty() {
+            return Ok(());
+        }
+
+        // Nodes are only removed once the transaction no longer referencing them has committed.
+        // Should this fail, the nodes are merely left in place.
+        let mut batch = WriteBatch::default();
+        for (kind, range) in truncations {
+            batch.delete_range_cf(
+                self.column_family(kind)?,
+                range.start.to_be_bytes(),
+                range.end.to_be_bytes(),
+            );
+        }
+        self.db.write(batch).context("Removing nodes")?;
+
+        Ok(())
+    }
+
+    fn backup(&self, snapshot: &rusqlite::Connection, database_path: &Path) -> anyhow::Result<()> {
+        let directory = super::rocksdb_directory(database_path);
+        self.copy(&directory)
+            .with_context(|| format!("Copying trie nodes to {}", directory.display()))?;
+
+        // Nodes removed by a reorg which committed after the snapshot was taken may be missing
+        // from the copy, while the snapshot still references them.
+        let copy = Self::open(&directory, false)?;
+        for (kind, last) in super::last_root_indices(snapshot)? {
+            if last == 0 {
+                continue;
+            }
+
+            let column_family = copy.column_family(kind)?;
+            let exists = copy
+                .db
+                .get_pinned_cf(column_family, last.to_be_bytes())
+                .context("Reading node")?
+                .is_some();
+            anyhow::ensure!(
+                exists,
+                "The {kind:?} trie changed while it was being copied, please retry the backup"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn column_family_name(kind: TrieKind) -> &'static str {
+    match kind {
+        TrieKind::Class => "class",
+        TrieKind::Contract => "contracts",
+        TrieKind::Storage => "storage",
+    }
+}
+
+/// Returns the next index of a trie, which is the greater of the persisted next index and the
+/// index following the last stored node. Indices start at one.
+fn next_index(db: &DB, kind: TrieKind) -> anyhow::Result<u64> {
+    let decode = |bytes: &[u8]| -> anyhow::Result<u64> {
+        let bytes = bytes.try_into().context("Index has an invalid length")?;
+        Ok(u64::from_be_bytes(bytes))
+    };
+
+    let stored = match db
+        .get(column_family_name(kind))
+        .context("Reading next index")?
+    {
+        Some(bytes) => decode(&bytes)?,
+        None => 1,
+    };
+
+    let column_family = db
+        .cf_handle(column_family_name(kind))
+        .with_context(|| format!("Column family of the {kind:?} trie is missing"))?;
+    let following = match db.iterator_cf(column_family, IteratorMode::End).next() {
+        Some(item) => decode(&item.context("Reading last node")?.0)? + 1,
+        None => 1,
+    };
+
+    Ok(stored.max(following))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::Child;
+    use crate::Storage;
+    use bitvec::prelude::Msb0;
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    /// A trie consisting of a binary root with an edge and a leaf as children.
+    fn nodes() -> (Felt, HashMap<Felt, Node>) {
+        let root = felt_bytes!(b"root");
+
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            root,
+            Node::Binary {
+                left: Child::Hash(felt_bytes!(b"edge")),
+                right: Child::Hash(felt_bytes!(b"leaf")),
+            },
+        );
+        nodes.insert(
+            felt_bytes!(b"edge"),
+            Node::Edge {
+                child: Child::Id(123),
+                path: bitvec::bitvec![u8, Msb0; 1,0,1,1],
+            },
+        );
+        nodes.insert(felt_bytes!(b"leaf"), Node::LeafBinary);
+
+        (root, nodes)
+    }
+
+    /// Inserts the test trie into `storage` using a transaction of an in-memory database, and
+    /// commits it.
+    fn insert(storage: &RocksDbTrieStorage, kind: TrieKind) -> u64 {
+        let db = Storage::in_memory().unwrap();
+        let mut db = db.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let (root, nodes) = nodes();
+        let root_idx = storage.insert(&tx, kind, root, &nodes).unwrap();
+        storage.before_commit(&tx).unwrap();
+        tx.commit().unwrap();
+
+        root_idx
+    }
+
+    #[test]
+    fn roundtrip() {
+        let directory = tempfile::tempdir().unwrap();
+        let storage = RocksDbTrieStorage::create(&directory.path().join("tries")).unwrap();
+        let root_idx = insert(&storage, TrieKind::Class);
+
+        let db = Storage::in_memory().unwrap();
+        let mut db = db.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let hash = |index| storage.hash(&tx, TrieKind::Class, index).unwrap();
+        let node = |index| storage.node(&tx, TrieKind::Class, index).unwrap();
+
+        assert_eq!(hash(root_idx), Some(felt_bytes!(b"root")));
+        let Some(StoredNode::Binary { left, right }) = node(root_idx) else {
+            panic!("Root should be a binary node");
+        };
+
+        assert_eq!(hash(left), Some(felt_bytes!(b"edge")));
+        assert_eq!(
+            node(left),
+            Some(StoredNode::Edge {
+                child: 123,
+                path: bitvec::bitvec![u8, Msb0; 1,0,1,1],
+            })
+        );
+
+        assert_eq!(hash(right), Some(felt_bytes!(b"leaf")));
+        assert_eq!(node(right), Some(StoredNode::LeafBinary));
+    }
+
+    #[test]
+    fn tries_are_separate() {
+        let directory = tempfile::tempdir().unwrap();
+        let storage = RocksDbTrieStorage::create(&directory.path().join("tries")).unwrap();
+        let root_idx = insert(&storage, TrieKind::Class);
+
+        let db = Storage::in_memory().unwrap();
+        let mut db = db.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        assert_eq!(
+            storage.hash(&tx, TrieKind::Contract, root_idx).unwrap(),
+            None
+        );
+        assert_eq!(
+            storage.node(&tx, TrieKind::Storage, root_idx).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn nodes_are_only_written_on_commit() {
+        let directory = tempfile::tempdir().unwrap();
+        let storage = RocksDbTrieStorage::create(&directory.path().join("tries")).unwrap();
+
+        let db = Storage::in_memory().unwrap();
+        let mut db = db.connection().unwrap();
+
+        let (root, nodes) = nodes();
+        let tx = db.transaction().unwrap();
+        let root_idx = storage
+            .insert(&tx, TrieKind::Storage, root, &nodes)
+            .unwrap();
+        // Nodes are visible to the transaction which inserted them.
+        assert_eq!(
+            storage.hash(&tx, TrieKind::Storage, root_idx).unwrap(),
+            Some(root)
+        );
+        drop(tx);
+
+        let tx = db.transaction().unwrap();
+        assert_eq!(
+            storage.hash(&tx, TrieKind::Storage, root_idx).unwrap(),
+            None
+        );
+        // Indices are not reused.
+        let second_idx = storage
+            .insert(&tx, TrieKind::Storage, root, &nodes)
+            .unwrap();
+        assert!(second_idx > root_idx);
+    }
+
+    #[test]
+    fn truncated_nodes_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tries");
+        let storage = RocksDbTrieStorage::create(&path).unwrap();
+        let first_idx = insert(&storage, TrieKind::Contract);
+        let second_idx = insert(&storage, TrieKind::Contract);
+
+        let writes = PendingTrieWrites::default();
+        writes.truncate(TrieKind::Contract, first_idx, second_idx + 1);
+        storage.after_commit(writes).unwrap();
+
+        let db = Storage::in_memory().unwrap();
+        let mut db = db.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        assert_eq!(
+            storage.hash(&tx, TrieKind::Contract, first_idx).unwrap(),
+            Some(felt_bytes!(b"root"))
+        );
+        assert_eq!(
+            storage.hash(&tx, TrieKind::Contract, second_idx).unwrap(),
+            None
+        );
+        drop(tx);
+        drop(storage);
+
+        // Indices of removed nodes are not reused after reopening.
+        let storage = RocksDbTrieStorage::open(&path, false).unwrap();
+        let third_idx = insert(&storage, TrieKind::Contract);
+        assert!(third_idx > second_idx);
+    }
+
+    #[test]
+    fn read_only() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tries");
+
+        assert!(RocksDbTrieStorage::open(&path, true).is_err());
+
+        let writer = RocksDbTrieStorage::create(&path).unwrap();
+        let reader = RocksDbTrieStorage::open(&path, true).unwrap();
+
+        let db = Storage::in_memory().unwrap();
+        let mut db = db.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        let (root, nodes) = nodes();
+        reader
+            .insert(&tx, TrieKind::Class, root, &nodes)
+            .unwrap_err();
+
+        // Nodes written by the writer are visible to the reader.
+        let root_idx = insert(&writer, TrieKind::Class);
+        assert_eq!(
+            reader.hash(&tx, TrieKind::Class, root_idx).unwrap(),
+            Some(root)
+        );
+    }
+
+    #[test]
+    fn missing_directory_is_not_created() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tries");
+
+        assert!(RocksDbTrieStorage::open(&path, false).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -5,6 +5,7 @@
 // This is intended for internal use only -- do not make public.
 mod prelude;
 
+mod backup;
 mod bloom;
 mod connection;
 pub mod fake;
@@ -16,6 +17,7 @@ use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+pub use backup::{BackupOptions, BackupReport};
 pub use connection::*;
 
 use pathfinder_common::{BlockHash, BlockNumber};
@@ -89,12 +91,18 @@ struct Inner {
     database_path: Arc<PathBuf>,
     pool: Pool<SqliteConnectionManager>,
     bloom_filter_cache: Arc<bloom::Cache>,
+    compression_dictionaries: Arc<connection::compression::DictionaryCache>,
+    trie_storage: Arc<dyn TrieStorage>,
 }
 
 pub struct StorageManager {
     database_path: PathBuf,
     journal_mode: JournalMode,
+    /// Connections are opened read-only, see [Storage::open_read_only].
+    read_only: bool,
     bloom_filter_cache: Arc<bloom::Cache>,
+    compression_dictionaries: Arc<connection::compression::DictionaryCache>,
+    trie_storage: Arc<dyn TrieStorage>,
 }
 
 impl StorageManager {
@@ -102,6 +110,10 @@ impl StorageManager {
         let journal_mode = self.journal_mode;
         let pool_manager = SqliteConnectionManager::file(&self.database_path)
             .with_init(move |connection| setup_connection(connection, journal_mode));
+        let pool_manager = match self.read_only {
+            true => pool_manager.with_flags(read_only_flags()),
+            false => pool_manager,
+        };
         let pool = Pool::builder()
             .max_size(capacity.get())
             .build(pool_manager)?;
@@ -110,8 +122,98 @@ impl StorageManager {
             database_path: Arc::new(self.database_path.clone()),
             pool,
             bloom_filter_cache: self.bloom_filter_cache.clone(),
+            compression_dictionaries: self.compression_dictionaries.clone(),
+            trie_storage: self.trie_storage.clone(),
         }))
     }
+
+    /// Selects the [TrieBackend] used to store trie nodes.
+    ///
+    /// The backend is recorded in the database, so this only has an effect on databases which do
+    /// not contain any trie nodes yet. Selecting a different backend for a database which already
+    /// contains trie nodes is an error, since the nodes would have to be synced again.
+    pub fn with_trie_backend(mut self, backend: TrieBackend) -> anyhow::Result<Self> {
+        let current = self.trie_storage.backend();
+        if current == backend {
+            return Ok(self);
+        }
+
+        match (current, backend) {
+            #[cfg(not(feature = "rocksdb"))]
+            (TrieBackend::Sqlite, TrieBackend::RocksDb) if !self.read_only => {
+                anyhow::bail!(connection::trie_backend::ROCKSDB_DISABLED)
+            }
+            #[cfg(feature = "rocksdb")]
+            (TrieBackend::Sqlite, TrieBackend::RocksDb) if !self.read_only => {
+                let connection = rusqlite::Connection::open(&self.database_path)
+                    .context("Opening DB to check for trie nodes")?;
+                let has_nodes = connection
+                    .query_row(
+                        "SELECT EXISTS(SELECT 1 FROM trie_class) \
+                            OR EXISTS(SELECT 1 FROM trie_contracts) \
+                            OR EXISTS(SELECT 1 FROM trie_storage)",
+                        [],
+                        |row| row.get::<_, bool>(0),
+                    )
+                    .context("Checking for trie nodes")?;
+                anyhow::ensure!(
+                    !has_nodes,
+                    "The database already stores trie nodes in SQLite. Using the RocksDB trie backend requires syncing a new database."
+                );
+
+                let directory = connection::trie_backend::rocksdb_directory(&self.database_path);
+                let storage = RocksDbTrieStorage::create(&directory)
+                    .with_context(|| format!("Creating trie storage at {}", directory.display()))?;
+                connection::trie_backend::store_backend(&connection, backend)?;
+                self.trie_storage = Arc::new(storage);
+
+                Ok(self)
+            }
+            (TrieBackend::Sqlite, TrieBackend::RocksDb) => {
+                anyhow::bail!("The trie backend of a read-only database cannot be changed")
+            }
+            (current, backend) => anyhow::bail!(
+                "The database stores trie nodes using the {current:?} backend. Using the {backend:?} backend requires syncing a new database."
+            ),
+        }
+    }
+
+    /// The [TrieBackend] used to store trie nodes.
+    pub fn trie_backend(&self) -> TrieBackend {
+        self.trie_storage.backend()
+    }
+
+    /// Enables or disables the index of transactions by sender address and nonce, which is
+    /// required by [Transaction::transactions_by_sender].
+    ///
+    /// The setting is stored in the database. Enabling the index on a database which already
+    /// contains transactions only schedules indexing them, which is done in small batches by
+    /// [Transaction::backfill_transaction_senders]. Disabling the index removes its entries.
+    pub fn with_transaction_sender_index(self, enabled: bool) -> anyhow::Result<Self> {
+        let storage = self
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .context("Creating connection pool")?;
+        let mut connection = storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        if self.read_only {
+            let current = tx.transaction_sender_index_enabled()?;
+            anyhow::ensure!(
+                current == enabled,
+                "The transaction sender index of a read-only database cannot be changed"
+            );
+            return Ok(self);
+        }
+
+        tx.set_transaction_sender_index(enabled)?;
+        tx.commit().context("Committing database transaction")?;
+
+        Ok(self)
+    }
 }
 
 impl Storage {
@@ -142,6 +244,8 @@ impl Storage {
         // Set the journal mode to the desired value.
         setup_journal_mode(&mut connection, journal_mode).context("Setting journal mode")?;
 
+        let trie_storage = connection::trie_backend::open(&connection, &database_path, false)?;
+
         connection
             .close()
             .map_err(|(_connection, error)| error)
@@ -150,14 +254,109 @@ impl Storage {
         Ok(StorageManager {
             database_path,
             journal_mode,
+            read_only: false,
+            bloom_filter_cache: Arc::new(bloom::Cache::with_size(bloom_filter_cache_size)),
+            compression_dictionaries: Default::default(),
+            trie_storage,
+        })
+    }
+
+    /// Opens an existing database without write access and returns a [storage manager](StorageManager)
+    /// whose connections are all read-only.
+    ///
+    /// This allows additional processes to serve data from a database which is kept up-to-date by
+    /// another pathfinder instance. No migrations are performed, so the database must already be
+    /// at the latest schema version. It must also be in WAL journal mode, as this is what allows
+    /// readers to proceed concurrently with the writing process.
+    pub fn open_read_only(
+        database_path: PathBuf,
+        bloom_filter_cache_size: usize,
+    ) -> anyhow::Result<StorageManager> {
+        let connection = rusqlite::Connection::open_with_flags(&database_path, read_only_flags())
+            .context("Opening DB in read-only mode")?;
+
+        ensure_latest_schema(&connection)?;
+
+        let journal_mode: String = connection
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .context("Querying journal mode")?;
+        anyhow::ensure!(
+            journal_mode.eq_ignore_ascii_case("wal"),
+            "Database journal mode is {journal_mode}, but read-only mode requires WAL"
+        );
+
+        let trie_storage = connection::trie_backend::open(&connection, &database_path, true)?;
+
+        connection
+            .close()
+            .map_err(|(_connection, error)| error)
+            .context("Closing DB after verification")?;
+
+        Ok(StorageManager {
+            database_path,
+            journal_mode: JournalMode::WAL,
+            read_only: true,
+            bloom_filter_cache: Arc::new(bloom::Cache::with_size(bloom_filter_cache_size)),
+            compression_dictionaries: Default::default(),
+            trie_storage,
+        })
+    }
+
+    /// Opens an existing database with write access without migrating it, and returns a
+    /// [storage manager](StorageManager).
+    ///
+    /// This is intended for maintenance commands, which must not change the database beyond the
+    /// changes they make themselves. The database must already be at the latest schema version,
+    /// and its journal mode is left as is.
+    pub fn open_without_migration(
+        database_path: PathBuf,
+        bloom_filter_cache_size: usize,
+    ) -> anyhow::Result<StorageManager> {
+        use rusqlite::OpenFlags;
+
+        // The database must already exist, so it is opened without the create flag.
+        let flags = OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let connection =
+            rusqlite::Connection::open_with_flags(&database_path, flags).context("Opening DB")?;
+
+        ensure_latest_schema(&connection)?;
+
+        let journal_mode: String = connection
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .context("Querying journal mode")?;
+        let journal_mode = match journal_mode.eq_ignore_ascii_case("wal") {
+            true => JournalMode::WAL,
+            false => JournalMode::Rollback,
+        };
+
+        let trie_storage = connection::trie_backend::open(&connection, &database_path, false)?;
+
+        connection
+            .close()
+            .map_err(|(_connection, error)| error)
+            .context("Closing DB after verification")?;
+
+        Ok(StorageManager {
+            database_path,
+            journal_mode,
+            read_only: false,
             bloom_filter_cache: Arc::new(bloom::Cache::with_size(bloom_filter_cache_size)),
+            compression_dictionaries: Default::default(),
+            trie_storage,
         })
     }
 
     /// Returns a new Sqlite [Connection] to the database.
     pub fn connection(&self) -> anyhow::Result<Connection> {
         let conn = self.0.pool.get()?;
-        Ok(Connection::new(conn, self.0.bloom_filter_cache.clone()))
+        Ok(Connection::new(
+            conn,
+            self.0.bloom_filter_cache.clone(),
+            self.0.compression_dictionaries.clone(),
+            self.0.trie_storage.clone(),
+        ))
     }
 
     /// Convenience function for tests to create an in-memory database.
@@ -191,6 +390,17 @@ impl Storage {
     pub fn path(&self) -> &Path {
         &self.0.database_path
     }
+
+    /// The [TrieBackend] used to store trie nodes.
+    pub fn trie_backend(&self) -> TrieBackend {
+        self.0.trie_storage.backend()
+    }
+}
+
+fn read_only_flags() -> rusqlite::OpenFlags {
+    use rusqlite::OpenFlags;
+
+    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX
 }
 
 fn setup_journal_mode(
@@ -321,6 +531,20 @@ fn migrate_database(connection: &mut rusqlite::Connection) -> anyhow::Result<()>
     Ok(())
 }
 
+/// Fails unless the database is at the latest schema version, for databases which are opened
+/// without being migrated.
+fn ensure_latest_schema(connection: &rusqlite::Connection) -> anyhow::Result<()> {
+    let current_revision = schema_version(connection)?;
+    let latest_revision = schema::BASE_SCHEMA_REVISION + schema::migrations().len();
+    anyhow::ensure!(
+        current_revision == latest_revision,
+        "Database version {current_revision} does not match the expected version {latest_revision}. \
+        The database must be migrated by a writing pathfinder instance first."
+    );
+
+    Ok(())
+}
+
 /// Returns the current schema version of the existing database,
 /// or `0` if database does not yet exist.
 fn schema_version(connection: &rusqlite::Connection) -> anyhow::Result<usize> {
@@ -410,6 +634,305 @@ mod tests {
             .unwrap_err();
     }
 
+    #[test]
+    fn read_only() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("read-only.sqlite");
+
+        let writer = Storage::migrate(db_path.clone(), JournalMode::WAL, 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+        let reader = Storage::open_read_only(db_path, 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let header = pathfinder_common::BlockHeader::builder()
+            .finalize_with_hash(pathfinder_common::macro_prelude::block_hash!("0x1"));
+
+        let mut reader = reader.connection().unwrap();
+        let tx = reader.transaction().unwrap();
+        assert_eq!(tx.block_id(BlockId::Latest).unwrap(), None);
+        tx.insert_block_header(&header).unwrap_err();
+        drop(tx);
+
+        // Writes by the writer are visible to the reader.
+        let mut writer = writer.connection().unwrap();
+        let tx = writer.transaction().unwrap();
+        tx.insert_block_header(&header).unwrap();
+        tx.commit().unwrap();
+
+        let tx = reader.transaction().unwrap();
+        assert_eq!(
+            tx.block_id(BlockId::Latest).unwrap(),
+            Some((header.number, header.hash))
+        );
+    }
+
+    #[test]
+    fn read_only_requires_wal() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("rollback.sqlite");
+
+        Storage::migrate(db_path.clone(), JournalMode::Rollback, 16).unwrap();
+        assert!(Storage::open_read_only(db_path, 16).is_err());
+    }
+
+    #[test]
+    fn read_only_requires_migrated_database() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("empty.sqlite");
+
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        drop(conn);
+
+        assert!(Storage::open_read_only(db_path, 16).is_err());
+    }
+
+    #[test]
+    fn open_without_migration_keeps_journal_mode() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("rollback.sqlite");
+
+        Storage::migrate(db_path.clone(), JournalMode::Rollback, 16).unwrap();
+        let storage = Storage::open_without_migration(db_path.clone(), 16)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let header = pathfinder_common::BlockHeader::builder()
+            .finalize_with_hash(pathfinder_common::macro_prelude::block_hash!("0x1"));
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_block_header(&header).unwrap();
+        tx.commit().unwrap();
+        drop(db);
+        drop(storage);
+
+        let journal_mode: String = rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "delete");
+    }
+
+    #[test]
+    fn open_without_migration_requires_migrated_database() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("empty.sqlite");
+        std::fs::File::create(&db_path).unwrap();
+
+        assert!(Storage::open_without_migration(db_path.clone(), 16).is_err());
+        assert_eq!(std::fs::metadata(&db_path).unwrap().len(), 0);
+
+        // Missing databases are not created.
+        let missing = db_dir.path().join("missing.sqlite");
+        assert!(Storage::open_without_migration(missing.clone(), 16).is_err());
+        assert!(!missing.exists());
+    }
+
+    #[cfg(not(feature = "rocksdb"))]
+    #[test]
+    fn rocksdb_trie_backend_requires_feature() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("rocksdb-tries.sqlite");
+
+        let error = Storage::migrate(db_path, JournalMode::WAL, 16)
+            .unwrap()
+            .with_trie_backend(TrieBackend::RocksDb)
+            .unwrap_err();
+        assert!(error.to_string().contains("`rocksdb` feature"));
+        assert!(!db_dir.path().join("rocksdb-tries.tries").exists());
+    }
+
+    #[cfg(feature = "rocksdb")]
+    mod rocksdb {
+        use std::collections::HashMap;
+
+        use pathfinder_crypto::Felt;
+
+        use super::*;
+
+        /// A trie consisting of only a leaf, which is enough to exercise the trie storage.
+        fn leaf_trie() -> (pathfinder_common::ClassCommitment, HashMap<Felt, Node>) {
+            let root = pathfinder_common::macro_prelude::class_commitment!("0x1");
+            let nodes = HashMap::from([(root.0, Node::LeafBinary)]);
+            (root, nodes)
+        }
+
+        #[test]
+        fn rocksdb_trie_backend() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let db_path = db_dir.path().join("rocksdb-tries.sqlite");
+            let (root, nodes) = leaf_trie();
+
+            let storage = Storage::migrate(db_path.clone(), JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::RocksDb)
+                .unwrap()
+                .create_pool(NonZeroU32::new(1).unwrap())
+                .unwrap();
+            assert_eq!(storage.trie_backend(), TrieBackend::RocksDb);
+
+            let mut db = storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            let root_idx = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.commit().unwrap();
+            drop(db);
+            drop(storage);
+
+            assert!(db_dir.path().join("rocksdb-tries.tries").is_dir());
+            let sqlite_nodes: u64 = rusqlite::Connection::open(&db_path)
+                .unwrap()
+                .query_row("SELECT count(*) FROM trie_class", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(sqlite_nodes, 0);
+
+            // The backend is read from the database when it is opened again.
+            let reader = Storage::open_read_only(db_path, 16)
+                .unwrap()
+                .create_pool(NonZeroU32::new(1).unwrap())
+                .unwrap();
+            assert_eq!(reader.trie_backend(), TrieBackend::RocksDb);
+
+            let mut db = reader.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            assert_eq!(tx.class_trie_node_hash(root_idx).unwrap(), Some(root.0));
+            assert_eq!(
+                tx.class_trie_node(root_idx).unwrap(),
+                Some(StoredNode::LeafBinary)
+            );
+        }
+
+        #[test]
+        fn missing_rocksdb_directory_is_an_error() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let db_path = db_dir.path().join("rocksdb-tries.sqlite");
+
+            Storage::migrate(db_path.clone(), JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::RocksDb)
+                .unwrap();
+
+            std::fs::rename(
+                db_dir.path().join("rocksdb-tries.tries"),
+                db_dir.path().join("moved.tries"),
+            )
+            .unwrap();
+
+            assert!(Storage::migrate(db_path.clone(), JournalMode::WAL, 16).is_err());
+            assert!(Storage::open_read_only(db_path, 16).is_err());
+        }
+
+        #[test]
+        fn unreferenced_rocksdb_nodes_are_removed() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let db_path = db_dir.path().join("rocksdb-tries.sqlite");
+            let (root, nodes) = leaf_trie();
+
+            let storage = Storage::migrate(db_path, JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::RocksDb)
+                .unwrap()
+                .create_pool(NonZeroU32::new(1).unwrap())
+                .unwrap();
+            let mut db = storage.connection().unwrap();
+
+            let tx = db.transaction().unwrap();
+            let first = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.insert_class_root(BlockNumber::GENESIS, Some(first))
+                .unwrap();
+            let second = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.insert_class_root(BlockNumber::GENESIS + 1, Some(second))
+                .unwrap();
+            tx.commit().unwrap();
+
+            // Purging the root of block 1 leaves its nodes unreferenced.
+            let tx = db.transaction().unwrap();
+            tx.purge_block(BlockNumber::GENESIS + 1).unwrap();
+            tx.truncate_tries().unwrap();
+            tx.commit().unwrap();
+
+            let tx = db.transaction().unwrap();
+            assert_eq!(tx.class_trie_node_hash(first).unwrap(), Some(root.0));
+            assert_eq!(tx.class_trie_node_hash(second).unwrap(), None);
+        }
+
+        #[test]
+        fn nodes_inserted_after_truncation_are_kept() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let db_path = db_dir.path().join("rocksdb-tries.sqlite");
+            let (root, nodes) = leaf_trie();
+
+            let storage = Storage::migrate(db_path, JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::RocksDb)
+                .unwrap()
+                .create_pool(NonZeroU32::new(1).unwrap())
+                .unwrap();
+            let mut db = storage.connection().unwrap();
+
+            let tx = db.transaction().unwrap();
+            let first = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.insert_class_root(BlockNumber::GENESIS, Some(first))
+                .unwrap();
+            let second = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.insert_class_root(BlockNumber::GENESIS + 1, Some(second))
+                .unwrap();
+            tx.commit().unwrap();
+
+            // A reorg replaces block 1 within the same transaction.
+            let tx = db.transaction().unwrap();
+            tx.purge_block(BlockNumber::GENESIS + 1).unwrap();
+            tx.truncate_tries().unwrap();
+            let third = tx.insert_class_trie(root, &nodes).unwrap();
+            tx.insert_class_root(BlockNumber::GENESIS + 1, Some(third))
+                .unwrap();
+            tx.commit().unwrap();
+
+            let tx = db.transaction().unwrap();
+            assert_eq!(tx.class_trie_node_hash(first).unwrap(), Some(root.0));
+            assert_eq!(tx.class_trie_node_hash(second).unwrap(), None);
+            assert_eq!(tx.class_trie_node_hash(third).unwrap(), Some(root.0));
+        }
+
+        #[test]
+        fn trie_backend_cannot_be_switched() {
+            let db_dir = tempfile::TempDir::new().unwrap();
+            let (root, nodes) = leaf_trie();
+
+            let sqlite_path = db_dir.path().join("sqlite.sqlite");
+            let storage = Storage::migrate(sqlite_path.clone(), JournalMode::WAL, 16)
+                .unwrap()
+                .create_pool(NonZeroU32::new(1).unwrap())
+                .unwrap();
+            let mut db = storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            tx.insert_class_trie(root, &nodes).unwrap();
+            tx.commit().unwrap();
+            drop(db);
+            drop(storage);
+
+            assert!(Storage::migrate(sqlite_path, JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::RocksDb)
+                .is_err());
+
+            let rocksdb_path = db_dir.path().join("rocksdb.sqlite");
+            Storage::migrate(rocksdb_path.clone(), JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::RocksDb)
+                .unwrap();
+
+            assert!(Storage::migrate(rocksdb_path, JournalMode::WAL, 16)
+                .unwrap()
+                .with_trie_backend(TrieBackend::Sqlite)
+                .is_err());
+        }
+    }
+
     #[test]
     fn rpc_test_db_is_migrated() {
         let mut source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
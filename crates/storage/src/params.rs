@@ -92,7 +92,8 @@ to_sql_builtin!(
     u64,
     u32,
     u16,
-    u8
+    u8,
+    bool
 );
 
 try_into_sql!(usize, u64);
@@ -279,6 +280,7 @@ pub trait RowExt {
     row_felt_wrapper!(get_contract_nonce, ContractNonce);
     row_felt_wrapper!(get_storage_value, StorageValue);
     row_felt_wrapper!(get_transaction_hash, TransactionHash);
+    row_felt_wrapper!(get_transaction_nonce, TransactionNonce);
     row_felt_wrapper!(get_contract_state_hash, ContractStateHash);
     row_felt_wrapper!(get_class_commitment_leaf, ClassCommitmentLeafHash);
     row_felt_wrapper!(
@@ -8,6 +8,13 @@ mod revision_0045;
 mod revision_0046;
 mod revision_0047;
 mod revision_0048;
+mod revision_0049;
+mod revision_0050;
+mod revision_0051;
+mod revision_0052;
+mod revision_0053;
+mod revision_0054;
+mod revision_0055;
 
 pub(crate) use base::base_schema;
 
@@ -24,6 +31,13 @@ pub fn migrations() -> &'static [MigrationFn] {
         revision_0046::migrate,
         revision_0047::migrate,
         revision_0048::migrate,
+        revision_0049::migrate,
+        revision_0050::migrate,
+        revision_0051::migrate,
+        revision_0052::migrate,
+        revision_0053::migrate,
+        revision_0054::migrate,
+        revision_0055::migrate,
     ]
 }
 
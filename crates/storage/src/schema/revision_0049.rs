@@ -0,0 +1,47 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating compression dictionary tables");
+
+    tx.execute(
+        r"CREATE TABLE compression_dictionaries (
+    id         INTEGER PRIMARY KEY,
+    kind       INTEGER NOT NULL,
+    dictionary BLOB    NOT NULL
+)",
+        [],
+    )
+    .context("Creating compression_dictionaries table")?;
+
+    // Transactions stored before this migration are re-encoded in the background, resuming from
+    // `next_rowid` until `end_rowid`. The range is restarted whenever new compression dictionaries
+    // are trained, and the row is deleted once the range is complete.
+    tx.execute_batch(
+        r"
+CREATE TABLE transaction_reencoding (
+    next_rowid INTEGER NOT NULL,
+    end_rowid  INTEGER NOT NULL
+);
+INSERT INTO transaction_reencoding (next_rowid, end_rowid)
+    SELECT 0, IFNULL(MAX(rowid), 0) FROM starknet_transactions;
+",
+    )
+    .context("Creating transaction_reencoding table")?;
+
+    // Class definitions stored before this migration are re-compressed in the background, resuming
+    // from `next_rowid` until `end_rowid`. The range is restarted whenever new compression
+    // dictionaries are trained, and the row is deleted once the range is complete.
+    tx.execute_batch(
+        r"
+CREATE TABLE class_recompression (
+    next_rowid INTEGER NOT NULL,
+    end_rowid  INTEGER NOT NULL
+);
+INSERT INTO class_recompression (next_rowid, end_rowid)
+    SELECT 0, IFNULL(MAX(rowid), 0) FROM class_definitions;
+",
+    )
+    .context("Creating class_recompression table")?;
+
+    Ok(())
+}
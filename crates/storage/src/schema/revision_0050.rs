@@ -0,0 +1,20 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating trie backend table");
+
+    // Records where trie nodes are stored, so that a database is never opened with a backend
+    // other than the one its root indices refer to. Existing databases store them in SQLite.
+    tx.execute_batch(
+        r"
+CREATE TABLE trie_backend (
+    id      INTEGER NOT NULL PRIMARY KEY,
+    backend TEXT    NOT NULL
+);
+INSERT INTO trie_backend (id, backend) VALUES (1, 'sqlite');
+",
+    )
+    .context("Creating trie_backend table")?;
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating transaction sender index table");
+
+    // The index is disabled by default and is only populated once enabled, see
+    // `StorageManager::with_transaction_sender_index`. Nonces are stored as full 32 byte
+    // big-endian values so that they sort numerically.
+    //
+    // Enabling the index records the transactions stored until then in
+    // `transaction_senders_backfill`, which are indexed in the background, resuming from
+    // `next_rowid` until `end_rowid`.
+    tx.execute_batch(
+        r"
+CREATE TABLE transaction_sender_index (
+    id      INTEGER NOT NULL PRIMARY KEY,
+    enabled INTEGER NOT NULL
+);
+INSERT INTO transaction_sender_index (id, enabled) VALUES (1, 0);
+CREATE TABLE transaction_senders (
+    block_number     INTEGER NOT NULL,
+    idx              INTEGER NOT NULL,
+    sender_address   BLOB    NOT NULL,
+    nonce            BLOB    NOT NULL,
+    transaction_hash BLOB    NOT NULL,
+    PRIMARY KEY (block_number, idx)
+);
+CREATE INDEX transaction_senders_sender_address_nonce ON transaction_senders(sender_address, nonce);
+CREATE TABLE transaction_senders_backfill (
+    next_rowid INTEGER NOT NULL,
+    end_rowid  INTEGER NOT NULL
+);
+",
+    )
+    .context("Creating transaction sender index tables")?;
+
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating L1 state update log table");
+
+    // The block hash is only included in logs since Starknet v0.11.
+    tx.execute_batch(
+        r"
+CREATE TABLE l1_state_update_logs (
+    starknet_block_number INTEGER PRIMARY KEY,
+    starknet_block_hash   BLOB,
+    starknet_state_root   BLOB    NOT NULL,
+    l1_block_number       INTEGER NOT NULL,
+    l1_transaction_hash   BLOB    NOT NULL
+);",
+    )
+    .context("Creating l1_state_update_logs table")?;
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating L1 to L2 message tables");
+
+    // L1 handler transactions stored before this migration are indexed in the background, resuming
+    // from `next_rowid` until `end_rowid`. Transactions inserted afterwards are indexed on insert.
+    tx.execute_batch(
+        r"
+CREATE TABLE l1_to_l2_messages (
+    message_hash        BLOB    PRIMARY KEY,
+    l1_block_number     INTEGER NOT NULL,
+    l1_transaction_hash BLOB    NOT NULL
+);
+CREATE INDEX l1_to_l2_messages_l1_transaction_hash ON l1_to_l2_messages(l1_transaction_hash);
+CREATE TABLE l1_handler_messages (
+    message_hash     BLOB    PRIMARY KEY,
+    transaction_hash BLOB    NOT NULL,
+    block_number     INTEGER NOT NULL
+);
+CREATE INDEX l1_handler_messages_block_number ON l1_handler_messages(block_number);
+CREATE TABLE l1_handler_messages_backfill (
+    next_rowid INTEGER NOT NULL,
+    end_rowid  INTEGER NOT NULL
+);
+INSERT INTO l1_handler_messages_backfill (next_rowid, end_rowid)
+    SELECT 0, IFNULL(MAX(rowid), 0) FROM starknet_transactions;
+",
+    )
+    .context("Creating L1 to L2 message tables")?;
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating L2 to L1 message consumption table");
+
+    // Identical messages have the same hash, so consumptions are keyed by their Ethereum log.
+    tx.execute_batch(
+        r"
+CREATE TABLE l2_to_l1_message_consumptions (
+    l1_block_number     INTEGER NOT NULL,
+    l1_log_index        INTEGER NOT NULL,
+    l1_transaction_hash BLOB    NOT NULL,
+    message_hash        BLOB    NOT NULL,
+    PRIMARY KEY (l1_block_number, l1_log_index)
+);
+CREATE INDEX l2_to_l1_message_consumptions_message_hash ON l2_to_l1_message_consumptions(message_hash);
+",
+    )
+    .context("Creating L2 to L1 message consumption table")?;
+
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use anyhow::Context;
+
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Adding block signature verification status");
+
+    // NULL if the signature was not verified, otherwise whether it is valid.
+    tx.execute_batch("ALTER TABLE block_signatures ADD COLUMN verified INTEGER;")
+        .context("Adding verified column to block_signatures table")?;
+
+    Ok(())
+}